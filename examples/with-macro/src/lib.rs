@@ -8,7 +8,7 @@ use spin_sdk_router::{router, Params};
 fn handle_example(req: Request) -> anyhow::Result<Response> {
     let router = router! {
         GET "/hello/:planet" => api::hello_planet,
-        _   "/*"             => |_req, params| {
+        ANY "/*"             => |_req, params| {
             let capture = params.wildcard().unwrap_or_default();
             Ok(http::Response::builder()
                 .status(http::StatusCode::OK)
@@ -0,0 +1,231 @@
+//! Procedural macros for `spin-sdk-router`, used through that crate.
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use validate::validate;
+
+mod validate;
+
+/// Concatenates string literals into a route pattern, failing to compile at the last literal
+/// if the pattern is invalid: `__pattern!("/api" "/users/:id")` expands to `"/api/users/:id"`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn pattern(input: TokenStream) -> TokenStream {
+    let mut literals = Vec::new();
+    if let Err((message, span)) = collect(input, &mut literals) {
+        return compile_error(&message, span);
+    }
+    let Some(&(_, span)) = literals.last() else {
+        return compile_error("expected a route pattern", Span::call_site());
+    };
+    let pattern: String = literals.iter().map(|(value, _)| value.as_str()).collect();
+    match validate(&pattern) {
+        Ok(()) => TokenTree::Literal(Literal::string(&pattern)).into(),
+        Err(reason) => compile_error(
+            &format!("invalid route pattern `{pattern}`: {reason}"),
+            span,
+        ),
+    }
+}
+
+/// Registers the function it is put on as the handler of a route, for
+/// `spin_sdk_router::collect_routes!` to add to a router: `#[route(GET, "/users/:id")]`,
+/// `#[route(GET | POST, "/form")]` or `#[route(ANY, "/*")]`.
+///
+/// The expansion names the crate `spin_sdk_router`; a crate depending on it under another
+/// name passes its path last: `#[route(GET, "/users/:id", crate = router)]`.
+#[proc_macro_attribute]
+pub fn route(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let RouteArgs {
+        methods,
+        pattern,
+        krate,
+    } = match route_args(attr) {
+        Ok(args) => args,
+        Err((message, span)) => return compile_error(&message, span),
+    };
+    let (docs, name) = match handler(item.clone()) {
+        Ok(handler) => handler,
+        Err((message, span)) => return compile_error(&message, span),
+    };
+    let registration = format!(
+        "{krate}::attributes::inventory::submit! {{
+            {krate}::attributes::RouteDef {{
+                methods: &{methods:?},
+                pattern: {pattern:?},
+                handler: {name},
+                handler_name: concat!(module_path!(), \"::{name}\"),
+                docs: &{docs:?},
+            }}
+        }}"
+    );
+    let mut expanded = item;
+    expanded.extend(registration.parse::<TokenStream>().unwrap());
+    expanded
+}
+
+/// The arguments of [`route`].
+struct RouteArgs {
+    /// The methods, or none for all of them.
+    methods: Vec<String>,
+    pattern: String,
+    /// The path of the `spin_sdk_router` crate.
+    krate: String,
+}
+
+/// Parses the arguments of [`route`]: the methods, the pattern and optionally the crate path.
+fn route_args(attr: TokenStream) -> Result<RouteArgs, (String, Span)> {
+    let mut tokens = attr.into_iter().peekable();
+    let mut methods = Vec::new();
+    loop {
+        match tokens.next() {
+            Some(TokenTree::Ident(method)) if matches!(&*method.to_string(), "ANY" | "_") => {}
+            Some(TokenTree::Ident(method)) => methods.push(method.to_string()),
+            Some(token) => return Err(("expected a method, e.g. `GET`".to_owned(), token.span())),
+            None => {
+                return Err((
+                    "expected a method and a pattern".to_owned(),
+                    Span::call_site(),
+                ))
+            }
+        }
+        match tokens.next() {
+            Some(TokenTree::Punct(p)) if p.as_char() == '|' => continue,
+            Some(TokenTree::Punct(p)) if p.as_char() == ',' => break,
+            Some(token) => return Err(("expected `|` or `,`".to_owned(), token.span())),
+            None => return Err(("expected a pattern".to_owned(), Span::call_site())),
+        }
+    }
+    let pattern_tokens = tokens
+        .by_ref()
+        .take_while(|token| !matches!(token, TokenTree::Punct(p) if p.as_char() == ','))
+        .collect();
+    let mut literals = Vec::new();
+    collect(pattern_tokens, &mut literals)?;
+    let [(pattern, span)] = &literals[..] else {
+        return Err(("expected a single pattern".to_owned(), Span::call_site()));
+    };
+    if let Err(reason) = validate(pattern) {
+        return Err((
+            format!("invalid route pattern `{pattern}`: {reason}"),
+            *span,
+        ));
+    }
+    let krate = match (tokens.next(), tokens.next()) {
+        (None, _) => "spin_sdk_router".to_owned(),
+        (Some(TokenTree::Ident(key)), Some(TokenTree::Punct(eq)))
+            if key.to_string() == "crate" && eq.as_char() == '=' && tokens.peek().is_some() =>
+        {
+            tokens.collect::<TokenStream>().to_string()
+        }
+        (Some(token), _) => {
+            return Err(("expected `crate = path`".to_owned(), token.span()));
+        }
+    };
+    Ok(RouteArgs {
+        methods,
+        pattern: pattern.clone(),
+        krate,
+    })
+}
+
+/// The documentation and the name of the function `item`.
+fn handler(item: TokenStream) -> Result<(Vec<String>, String), (String, Span)> {
+    let mut docs = Vec::new();
+    let mut tokens = item.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket => {
+                let attr: Vec<_> = group.stream().into_iter().collect();
+                if let [TokenTree::Ident(doc), TokenTree::Punct(eq), TokenTree::Literal(text)] =
+                    &attr[..]
+                {
+                    if doc.to_string() == "doc" && eq.as_char() == '=' {
+                        docs.extend(unquote(&text.to_string()));
+                    }
+                }
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "fn" => {
+                if let Some(TokenTree::Ident(name)) = tokens.next() {
+                    return Ok((docs, name.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err((
+        "`route` can only be put on functions".to_owned(),
+        Span::call_site(),
+    ))
+}
+
+/// Collects the values of the string literals of `input`, looking into the invisible groups
+/// that `macro_rules!` fragments arrive in.
+fn collect(input: TokenStream, literals: &mut Vec<(String, Span)>) -> Result<(), (String, Span)> {
+    for token in input {
+        match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::None => {
+                collect(group.stream(), literals)?
+            }
+            TokenTree::Literal(literal) => match unquote(&literal.to_string()) {
+                Some(value) => literals.push((value, literal.span())),
+                None => return Err(("expected a string literal".to_owned(), literal.span())),
+            },
+            token => return Err(("expected a string literal".to_owned(), token.span())),
+        }
+    }
+    Ok(())
+}
+
+/// The value of a string literal, given as written in the source.
+fn unquote(literal: &str) -> Option<String> {
+    if let Some(raw) = literal.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let raw = raw.get(hashes..raw.len() - hashes)?;
+        return Some(raw.strip_prefix('"')?.strip_suffix('"')?.to_owned());
+    }
+    let quoted = literal.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        value.push(match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                c @ ('\\' | '"' | '\'') => c,
+                _ => return None,
+            },
+            c => c,
+        });
+    }
+    Some(value)
+}
+
+/// `compile_error!(message)`, reported at `span`.
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = Literal::string(message);
+    message.set_span(span);
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::Literal(message).into());
+    group.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+    [
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(group),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote(r#""/a\"b""#).as_deref(), Some("/a\"b"));
+        assert_eq!(unquote(r##"r#"/a"b"#"##).as_deref(), Some("/a\"b"));
+        assert_eq!(unquote("'a'"), None);
+    }
+}
@@ -0,0 +1,85 @@
+//! Route pattern validation matching what the router accepts at runtime, so that a pattern
+//! fails to compile exactly when registering it would fail.
+
+/// Why the router would reject `pattern`, with the reason it would give.
+pub(crate) fn validate(pattern: &str) -> Result<(), String> {
+    let pattern = split_choices(pattern)?;
+    let pattern = split_defaults(&pattern)?;
+    parse(&pattern)
+}
+
+/// Removes the choices of parameters such as `:format<json|csv>`.
+fn split_choices(pattern: &str) -> Result<String, String> {
+    let mut segments = Vec::new();
+    for segment in pattern.split('/') {
+        let Some((name, rest)) = segment.strip_prefix(':').and_then(|s| s.split_once('<')) else {
+            segments.push(segment.to_owned());
+            continue;
+        };
+        let Some((set, rest)) = rest.split_once('>') else {
+            return Err("unclosed `<` in parameter choices".to_owned());
+        };
+        if !rest.is_empty() && !rest.starts_with('=') {
+            return Err("parameter choices must end the segment".to_owned());
+        }
+        if set.split('|').any(str::is_empty) {
+            return Err("parameter choices cannot be empty".to_owned());
+        }
+        if let Some(default) = rest.strip_prefix('=') {
+            if !set.split('|').any(|choice| choice == default) {
+                return Err("a parameter default must be one of its choices".to_owned());
+            }
+        }
+        segments.push(format!(":{name}{rest}"));
+    }
+    Ok(segments.join("/"))
+}
+
+/// Removes the defaults of trailing parameters such as `:format=json`.
+fn split_defaults(pattern: &str) -> Result<String, String> {
+    let mut segments = Vec::new();
+    let mut defaulted = false;
+    for segment in pattern.split('/') {
+        match segment.strip_prefix(':').and_then(|s| s.split_once('=')) {
+            Some((name, _)) => {
+                defaulted = true;
+                segments.push(&segment[..name.len() + 1]);
+            }
+            None if defaulted => {
+                return Err("only trailing parameters can have defaults".to_owned());
+            }
+            None => segments.push(segment),
+        }
+    }
+    Ok(segments.join("/"))
+}
+
+/// Checks the sections between `/` and `.` separators as routefinder parses them.
+fn parse(pattern: &str) -> Result<(), String> {
+    let trimmed = pattern.trim_start_matches('/').trim_end_matches('/');
+    let ends = trimmed.match_indices(['.', '/']).map(|(i, _)| i);
+    let mut start = 0;
+    let mut wildcard = false;
+    let mut after_wildcard = false;
+    for end in ends.chain([trimmed.len()]) {
+        let dot = start > 0 && trimmed.as_bytes()[start - 1] == b'.';
+        let section = &trimmed[start..end];
+        start = end + 1;
+        after_wildcard |= wildcard && (dot || !section.is_empty());
+        match section {
+            "*" => wildcard = true,
+            s if s.starts_with('*') => {
+                return Err(format!(
+                    "since there can only be one wildcard, it doesn't need a name. \
+                     replace `{s}` with `*`"
+                ));
+            }
+            ":" => return Err("params must be named".to_owned()),
+            _ => {}
+        }
+    }
+    if after_wildcard {
+        return Err("a wildcard must be the last segment".to_owned());
+    }
+    Ok(())
+}
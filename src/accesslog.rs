@@ -0,0 +1,252 @@
+//! Access logging in the Common, Combined or JSON log formats.
+use crate::{
+    forwarded::ClientInfo, metrics::MatchedPattern, mirror::json_string, ratelimit::client_addr,
+    sampling::Sampler, Middleware, Next, Request, Response,
+};
+use anyhow::Result;
+use std::time::{Instant, SystemTime};
+
+/// The format of access log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// The Common Log Format:
+    /// `127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /a.gif HTTP/1.1" 200 2326`.
+    #[default]
+    Common,
+    /// The Common Log Format followed by the quoted `Referer` and `User-Agent`.
+    Combined,
+    /// One JSON object per line, with the matched route pattern and the duration as well.
+    Json,
+}
+
+/// What is known about a request once its response has been produced.
+struct Entry {
+    remote: Option<String>,
+    time: SystemTime,
+    method: http::Method,
+    uri: http::Uri,
+    version: http::Version,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    status: u16,
+    bytes: usize,
+    route: Option<String>,
+    duration_ms: u128,
+}
+
+impl Entry {
+    fn format(&self, format: Format) -> String {
+        let path = self.uri.path_and_query().map_or("/", |p| p.as_str());
+        let quoted = |value: &Option<String>| match value {
+            Some(value) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+            None => "\"-\"".to_owned(),
+        };
+        let common = || {
+            format!(
+                "{} - - [{}] \"{} {path} {:?}\" {} {}",
+                self.remote.as_deref().unwrap_or("-"),
+                clf_time(self.time),
+                self.method,
+                self.version,
+                self.status,
+                self.bytes,
+            )
+        };
+        match format {
+            Format::Common => common(),
+            Format::Combined => format!(
+                "{} {} {}",
+                common(),
+                quoted(&self.referer),
+                quoted(&self.user_agent)
+            ),
+            Format::Json => {
+                let optional = |value: &Option<String>| match value {
+                    Some(value) => json_string(value),
+                    None => "null".to_owned(),
+                };
+                format!(
+                    "{{\"remote_addr\":{},\"time\":{},\"method\":{},\"path\":{},\"protocol\":{},\"status\":{},\"bytes\":{},\"referer\":{},\"user_agent\":{},\"route\":{},\"duration_ms\":{}}}",
+                    optional(&self.remote),
+                    json_string(&httpdate::fmt_http_date(self.time)),
+                    json_string(self.method.as_str()),
+                    json_string(path),
+                    json_string(&format!("{:?}", self.version)),
+                    self.status,
+                    self.bytes,
+                    optional(&self.referer),
+                    optional(&self.user_agent),
+                    optional(&self.route),
+                    self.duration_ms,
+                )
+            }
+        }
+    }
+}
+
+/// Formats `time` as in the Common Log Format, e.g. `10/Oct/2000:13:55:36 +0000`.
+fn clf_time(time: SystemTime) -> String {
+    // `Tue, 10 Oct 2000 13:55:36 GMT`
+    let date = httpdate::fmt_http_date(time);
+    match date.split(' ').collect::<Vec<_>>()[..] {
+        [_, day, month, year, clock, _] => format!("{day}/{month}/{year}:{clock} +0000"),
+        _ => date,
+    }
+}
+
+/// Middleware writing a line for each request to a sink, standard output by default:
+///
+/// ```
+/// # use spin_sdk_router::{accesslog::{AccessLog, Format}, sampling::Sampler, Router};
+/// let mut router = Router::new();
+/// router.layer(
+///     AccessLog::new(Format::Combined)
+///         .sampler(Sampler::new(0.1).status(500..=599, 1.0))
+///         .sink(|line| eprintln!("{line}")),
+/// );
+/// ```
+///
+/// The client address is that resolved by [`TrustedProxies`](crate::forwarded::TrustedProxies)
+/// if it runs before this layer, and otherwise the one Spin reports.
+pub struct AccessLog {
+    format: Format,
+    sampler: Sampler,
+    sink: Box<dyn Fn(&str)>,
+}
+
+impl AccessLog {
+    /// Log every request in `format` to standard output.
+    pub fn new(format: Format) -> Self {
+        AccessLog {
+            format,
+            sampler: Sampler::default(),
+            sink: Box::new(|line| println!("{line}")),
+        }
+    }
+
+    /// Only log the requests chosen by `sampler`.
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Write lines with `sink` instead.
+    pub fn sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&str) + 'static,
+    {
+        self.sink = Box::new(sink);
+        self
+    }
+
+    /// Write lines to the `log` crate at the info level, with the target `access`.
+    #[cfg(feature = "log")]
+    pub fn to_log(self) -> Self {
+        self.sink(|line| log::info!(target: "access", "{line}"))
+    }
+}
+
+impl Middleware for AccessLog {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let started = Instant::now();
+        let header = |name: http::header::HeaderName| {
+            let value = req.headers().get(name)?.to_str().ok()?;
+            Some(value.to_owned())
+        };
+        let mut entry = Entry {
+            remote: match ClientInfo::of(&req) {
+                Some(info) => info.ip.map(|ip| ip.to_string()),
+                None => client_addr(&req),
+            },
+            time: SystemTime::now(),
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            version: req.version(),
+            referer: header(http::header::REFERER),
+            user_agent: header(http::header::USER_AGENT),
+            status: 0,
+            bytes: 0,
+            route: None,
+            duration_ms: 0,
+        };
+        let res = next.run(req)?;
+        if self.sampler.sample(&res) {
+            entry.status = res.status().as_u16();
+            entry.bytes = res.body().as_ref().map_or(0, |body| body.len());
+            entry.route = res
+                .extensions()
+                .get::<MatchedPattern>()
+                .map(|MatchedPattern(pattern)| pattern.clone());
+            entry.duration_ms = started.elapsed().as_millis();
+            (self.sink)(&entry.format(self.format));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn entry() -> Entry {
+        Entry {
+            remote: Some("127.0.0.1".to_owned()),
+            time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+            method: http::Method::GET,
+            uri: "/apache_pb.gif?x=1".parse().unwrap(),
+            version: http::Version::HTTP_11,
+            referer: None,
+            user_agent: Some("curl/8.0 \"quoted\"".to_owned()),
+            status: 200,
+            bytes: 2326,
+            route: Some("/:file".to_owned()),
+            duration_ms: 3,
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        let common = "127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /apache_pb.gif?x=1 HTTP/1.1\" 200 2326";
+        assert_eq!(entry().format(Format::Common), common);
+        assert_eq!(
+            entry().format(Format::Combined),
+            format!("{common} \"-\" \"curl/8.0 \\\"quoted\\\"\"")
+        );
+        assert_eq!(
+            entry().format(Format::Json),
+            "{\"remote_addr\":\"127.0.0.1\",\"time\":\"Tue, 10 Oct 2000 13:55:36 GMT\",\"method\":\"GET\",\"path\":\"/apache_pb.gif?x=1\",\"protocol\":\"HTTP/1.1\",\"status\":200,\"bytes\":2326,\"referer\":null,\"user_agent\":\"curl/8.0 \\\"quoted\\\"\",\"route\":\"/:file\",\"duration_ms\":3}"
+        );
+    }
+
+    #[test]
+    fn test_access_log() {
+        use crate::{Params, Router};
+        use std::{cell::RefCell, rc::Rc};
+
+        let lines = Rc::new(RefCell::new(Vec::new()));
+        let sink = lines.clone();
+        let mut router = Router::new();
+        router.get("/", |_req, _params: Params| {
+            Ok(http::Response::new(Some("hello".into())))
+        });
+        router.layer(
+            AccessLog::new(Format::Common)
+                .sink(move |line| sink.borrow_mut().push(line.to_owned())),
+        );
+        let req = http::Request::builder()
+            .uri("/")
+            .header(crate::ratelimit::CLIENT_ADDR_HEADER, "10.0.0.1:5000")
+            .body(None)
+            .unwrap();
+        router.handle(req).unwrap();
+
+        let lines = lines.borrow();
+        assert!(lines[0].starts_with("10.0.0.1 - - ["), "{}", lines[0]);
+        assert!(
+            lines[0].ends_with("] \"GET / HTTP/1.1\" 200 5"),
+            "{}",
+            lines[0]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Aliases of route paths, for moving routes without breaking the links to their old paths.
+use crate::{error, redirect, Request, Response, RouteError, Router};
+use anyhow::Result;
+use routefinder::{Captures, RouteSpec, Router as MethodRouter};
+
+/// How a request to an alias reaches the route of its target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AliasMode {
+    /// Redirect the client to the target with 308 Permanent Redirect.
+    #[default]
+    Redirect,
+    /// Dispatch the request as if it had been made to the target, without telling the client.
+    Rewrite,
+}
+
+/// An alias registered with [`Router::alias`].
+#[derive(Clone, Debug)]
+pub struct Alias {
+    from: String,
+    to: String,
+    mode: AliasMode,
+}
+
+impl Alias {
+    /// The path pattern of the alias, e.g. `/old/:id`.
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    /// The path pattern the alias leads to, e.g. `/new/:id`.
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    /// How requests reach the target.
+    pub fn mode(&self) -> AliasMode {
+        self.mode
+    }
+
+    /// Reach the target by rewriting the request instead of redirecting the client.
+    pub fn rewrite(&mut self) -> &mut Self {
+        self.mode = AliasMode::Rewrite;
+        self
+    }
+}
+
+/// Substitutes the parameters and the wildcard captured from a path into the pattern `to`.
+pub(crate) fn substitute(to: &str, captures: &Captures<'_, '_>) -> String {
+    to.split('/')
+        .map(|segment| match segment {
+            "*" => captures.wildcard().unwrap_or_default(),
+            _ => match segment.strip_prefix(':') {
+                Some(name) => captures.get(name).unwrap_or_default(),
+                None => segment,
+            },
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Parses the pattern `from`, checking that it captures the parameters and the wildcard of
+/// the pattern `to` it leads to.
+pub(crate) fn parse_source(from: &str, to: &str) -> Result<RouteSpec, RouteError> {
+    let spec = error::parse_pattern(from)?;
+    error::parse_pattern(to)?;
+    let captured = |name: &str| {
+        from.split('/')
+            .any(|segment| segment == name || (name == "*" && segment.starts_with('*')))
+    };
+    if let Some(missing) = to
+        .split('/')
+        .find(|segment| (segment.starts_with(':') || *segment == "*") && !captured(segment))
+    {
+        return Err(RouteError::InvalidPattern {
+            pattern: to.to_owned(),
+            reason: format!("`{missing}` is not captured by `{from}`"),
+        });
+    }
+    Ok(spec)
+}
+
+/// The aliases of a router.
+#[derive(Default)]
+pub(crate) struct Aliases {
+    router: MethodRouter<usize>,
+    aliases: Vec<Alias>,
+}
+
+impl Router {
+    /// Answer requests to `from` with the route at `to`, redirecting the client there with
+    /// 308 Permanent Redirect unless the alias is set to [rewrite](Alias::rewrite):
+    ///
+    /// ```
+    /// # use spin_sdk_router::{Params, Router};
+    /// let mut router = Router::new();
+    /// router.get("/users/:id", |_req, _params: Params| Ok(http::Response::new(None)));
+    /// router.alias("/members/:id", "/users/:id");
+    /// router.alias("/u/:id", "/users/:id").rewrite();
+    /// ```
+    ///
+    /// Parameters and a wildcard captured by `from` are substituted into `to`, and the query
+    /// is kept. An alias applies whether or not a route matches `from`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either path is not a valid route pattern, or if `to` has a parameter that
+    /// `from` does not capture; see [`Router::try_alias`].
+    pub fn alias(&mut self, from: &str, to: &str) -> &mut Alias {
+        self.try_alias(from, to).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Answer requests to `from` with the route at `to`, failing if either path is not a
+    /// valid route pattern or if `to` has a parameter that `from` does not capture.
+    pub fn try_alias(&mut self, from: &str, to: &str) -> Result<&mut Alias, RouteError> {
+        let spec = parse_source(from, to)?;
+        let aliases = &mut self.aliases;
+        aliases.router.add(spec, aliases.aliases.len()).unwrap();
+        aliases.aliases.push(Alias {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            mode: AliasMode::default(),
+        });
+        Ok(aliases.aliases.last_mut().unwrap())
+    }
+
+    /// The aliases of the router, in registration order.
+    pub fn aliases(&self) -> impl Iterator<Item = &Alias> {
+        self.aliases.aliases.iter()
+    }
+
+    /// Redirects a request whose path matches an alias, or rewrites its path to the target.
+    pub(crate) fn resolve_alias(&self, request: &mut Request) -> Result<Option<Response>> {
+        let Some(m) = self.aliases.router.best_match(request.uri().path()) else {
+            return Ok(None);
+        };
+        let alias = &self.aliases.aliases[*m.handler()];
+        let mut target = substitute(&alias.to, &m.captures());
+        if let Some(query) = request.uri().query() {
+            target = format!("{target}?{query}");
+        }
+        if alias.mode == AliasMode::Redirect {
+            return redirect::permanent(&target).map(Some);
+        }
+        let mut parts = request.uri().clone().into_parts();
+        parts.path_and_query = Some(target.parse()?);
+        *request.uri_mut() = http::Uri::from_parts(parts)?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+
+    fn get(router: &Router, uri: &str) -> Response {
+        let req = http::Request::get(uri).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut router = Router::new();
+        router.get("/users/:id", |req: Request, params: Params| {
+            let body = format!("{} {}", req.uri(), params.get("id").unwrap());
+            Ok(http::Response::new(Some(body.into())))
+        });
+        router.get("/docs/*", |req: Request, _params: Params| {
+            Ok(http::Response::new(Some(req.uri().to_string().into())))
+        });
+        router.alias("/members/:id", "/users/:id");
+        router.alias("/u/:id", "/users/:id").rewrite();
+        router.alias("/guide/*", "/docs/*");
+
+        let res = get(&router, "/members/7?tab=posts");
+        assert_eq!(res.status(), http::StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[http::header::LOCATION], "/users/7?tab=posts");
+        let res = get(&router, "/guide/intro/setup");
+        assert_eq!(res.headers()[http::header::LOCATION], "/docs/intro/setup");
+        let res = get(&router, "/u/7?tab=posts");
+        assert_eq!(res.into_body().unwrap(), "/users/7?tab=posts 7");
+        assert_eq!(get(&router, "/users/8").into_body().unwrap(), "/users/8 8");
+
+        let err = router.try_alias("/old", "/users/:id").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "invalid route pattern `/users/:id`: `:id` is not captured by `/old`"
+        );
+        assert_eq!(router.aliases().count(), 3);
+    }
+}
@@ -0,0 +1,113 @@
+//! Routes declared with attributes on their handlers, next to them in any module:
+//!
+//! ```
+//! use spin_sdk_router::{attributes::route, collect_routes, Params, Request, Response};
+//!
+//! /// Shows a user.
+//! #[route(GET, "/users/:id")]
+//! fn show_user(_req: Request, params: Params) -> anyhow::Result<Response> {
+//!     let id = params.get("id").unwrap_or_default().to_owned();
+//!     Ok(http::Response::new(Some(id.into())))
+//! }
+//!
+//! let router = collect_routes!();
+//! ```
+//!
+//! The attribute takes the methods, e.g. `GET`, `GET | POST` or `ANY`, and the pattern, which
+//! is checked at compile time as in [`router!`](crate::router). Handlers are plain functions,
+//! whose documentation becomes that of their route. A crate that renames its dependency on
+//! `spin_sdk_router` passes the new name last, e.g. `#[route(GET, "/", crate = router)]`.
+use crate::{Params, Request, Response, Route, Router};
+use anyhow::Result;
+
+#[doc(hidden)]
+pub use inventory;
+pub use spin_sdk_router_macros::route;
+
+/// A route declared with [`route`], registered when the program starts.
+#[doc(hidden)]
+pub struct RouteDef {
+    pub methods: &'static [&'static str],
+    pub pattern: &'static str,
+    pub handler: fn(Request, Params) -> Result<Response>,
+    pub handler_name: &'static str,
+    pub docs: &'static [&'static str],
+}
+
+inventory::collect!(RouteDef);
+
+/// A router with the routes declared with [`route`] in the program.
+#[macro_export]
+macro_rules! collect_routes {
+    () => {{
+        let mut router = $crate::Router::new();
+        router.collect_routes();
+        router
+    }};
+}
+
+impl Router {
+    /// Register the routes declared with [`route`] in the program, ordered by pattern and
+    /// method so that the router does not depend on the link order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a route declares a method that is not valid.
+    pub fn collect_routes(&mut self) {
+        let mut defs: Vec<_> = inventory::iter::<RouteDef>().collect();
+        defs.sort_by_key(|def| (def.pattern, def.methods));
+        for def in defs {
+            let methods = def.methods.iter().map(|method| {
+                Some(http::Method::from_bytes(method.as_bytes()).expect("invalid method"))
+            });
+            let methods: Vec<_> = match def.methods {
+                [] => vec![None],
+                _ => methods.collect(),
+            };
+            for method in methods {
+                let route = Route::new(method, def.pattern, def.handler)
+                    .with_handler_name(def.handler_name);
+                // Patterns were checked when the attributes were expanded.
+                let route = self.insert(route).unwrap_or_else(|e| panic!("{e}"));
+                route.doc_lines(def.docs);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Greets someone.
+    #[route(GET | POST, "/hello/:name")]
+    fn hello(_req: Request, params: Params) -> Result<Response> {
+        let body = format!("hello {}", params.get("name").unwrap_or_default());
+        Ok(http::Response::new(Some(body.into())))
+    }
+
+    #[route(ANY, "/echo/*", crate = crate)]
+    fn echo(req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::new(Some(req.method().to_string().into())))
+    }
+
+    #[test]
+    fn test_collect_routes() {
+        let router = crate::collect_routes!();
+        let req = http::Request::post("/hello/ada").body(None).unwrap();
+        assert_eq!(
+            router.handle(req).unwrap().into_body().unwrap(),
+            "hello ada"
+        );
+        let req = http::Request::delete("/echo/a/b").body(None).unwrap();
+        assert_eq!(router.handle(req).unwrap().into_body().unwrap(), "DELETE");
+
+        let routes: Vec<_> = router.routes().collect();
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[1].docs(), Some("Greets someone."));
+        assert_eq!(
+            routes[1].handler_name(),
+            Some("spin_sdk_router::attributes::tests::hello")
+        );
+    }
+}
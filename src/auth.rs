@@ -0,0 +1,255 @@
+//! Authentication guards.
+use crate::{Guard, Rejection, Request};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// The user authenticated by [`basic_auth`], inserted into the request extensions so that
+/// handlers can tell who made the request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal(pub String);
+
+/// A guard requiring HTTP Basic authentication, see [`basic_auth`].
+pub struct BasicAuth<F> {
+    realm: String,
+    verify: F,
+}
+
+/// A guard requiring HTTP Basic authentication for `realm`, accepting the credentials for
+/// which `verify(user, password)` returns true:
+///
+/// ```
+/// # use spin_sdk_router::{auth::basic_auth, Router};
+/// let mut router = Router::new();
+/// router.guard(basic_auth("admin", |user, password| {
+///     user == "admin" && password == "hunter2"
+/// }));
+/// ```
+///
+/// Requests without valid credentials are rejected with 401 Unauthorized and a
+/// `WWW-Authenticate` challenge. Accepted requests carry the user name as a [`Principal`]
+/// in their extensions.
+pub fn basic_auth<F>(realm: &str, verify: F) -> BasicAuth<F>
+where
+    F: Fn(&str, &str) -> bool + 'static,
+{
+    BasicAuth {
+        realm: realm.to_owned(),
+        verify,
+    }
+}
+
+/// Decodes the user and password of a `Basic` `Authorization` header.
+fn basic_credentials(req: &Request) -> Option<(String, String)> {
+    let value = req
+        .headers()
+        .get(http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let (scheme, token) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(token.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_owned(), password.to_owned()))
+}
+
+impl<F> BasicAuth<F> {
+    fn challenge(&self, reason: &str) -> Rejection {
+        let realm = self.realm.replace('\\', "\\\\").replace('"', "\\\"");
+        let response = http::Response::builder()
+            .status(http::StatusCode::UNAUTHORIZED)
+            .header(
+                http::header::WWW_AUTHENTICATE,
+                format!("Basic realm=\"{realm}\", charset=\"UTF-8\""),
+            )
+            .body(None)
+            .unwrap();
+        Rejection::with_response(response, reason)
+    }
+}
+
+impl<F> Guard for BasicAuth<F>
+where
+    F: Fn(&str, &str) -> bool + 'static,
+{
+    fn check(&self, req: &mut Request) -> Result<(), Rejection> {
+        let Some((user, password)) = basic_credentials(req) else {
+            return Err(self.challenge("missing basic credentials"));
+        };
+        if !(self.verify)(&user, &password) {
+            return Err(self.challenge(&format!("invalid credentials for {user}")));
+        }
+        req.extensions_mut().insert(Principal(user));
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "basic_auth"
+    }
+}
+
+/// A guard requiring a bearer token or API key, see [`token_auth`].
+pub struct TokenAuth<F> {
+    validate: F,
+    api_key_header: http::header::HeaderName,
+}
+
+/// A guard accepting requests that carry a token in an `Authorization: Bearer` header or an
+/// `X-Api-Key` header, for which `validate` resolves a principal:
+///
+/// ```
+/// # use spin_sdk_router::{auth::token_auth, Router};
+/// #[derive(Clone)]
+/// struct Client { name: String }
+///
+/// let mut router = Router::new();
+/// router.guard(token_auth(|token| {
+///     (token == "s3cret").then(|| Client { name: "billing".to_owned() })
+/// }));
+/// ```
+///
+/// The principal is inserted into the request extensions for handlers to read. Requests
+/// without a token, or whose token does not resolve, are rejected with 401 Unauthorized and a
+/// `WWW-Authenticate: Bearer` challenge.
+///
+/// Validation runs synchronously, like the rest of dispatch, so lookups must use blocking
+/// calls such as Spin's key-value store. Async validators are not accepted: guards have no
+/// executor to drive a future on, and Spin's blocking APIs cover the lookups a validator
+/// needs.
+pub fn token_auth<F, P>(validate: F) -> TokenAuth<F>
+where
+    F: Fn(&str) -> Option<P> + 'static,
+    P: Clone + Send + Sync + 'static,
+{
+    TokenAuth {
+        validate,
+        api_key_header: http::header::HeaderName::from_static("x-api-key"),
+    }
+}
+
+impl<F> TokenAuth<F> {
+    /// Read API keys from the header `name` instead of `X-Api-Key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn api_key_header(mut self, name: &str) -> Self {
+        self.api_key_header = name.parse().expect("invalid header name");
+        self
+    }
+
+    /// The bearer token or API key the request carries.
+    fn token<'a>(&self, req: &'a Request) -> Option<&'a str> {
+        let bearer = req
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim());
+        let api_key = || {
+            let value = req.headers().get(&self.api_key_header)?;
+            Some(value.to_str().ok()?.trim())
+        };
+        bearer.or_else(api_key).filter(|token| !token.is_empty())
+    }
+}
+
+fn bearer_challenge(reason: &str) -> Rejection {
+    let response = http::Response::builder()
+        .status(http::StatusCode::UNAUTHORIZED)
+        .header(http::header::WWW_AUTHENTICATE, "Bearer")
+        .body(None)
+        .unwrap();
+    Rejection::with_response(response, reason)
+}
+
+impl<F, P> Guard for TokenAuth<F>
+where
+    F: Fn(&str) -> Option<P> + 'static,
+    P: Clone + Send + Sync + 'static,
+{
+    fn check(&self, req: &mut Request) -> Result<(), Rejection> {
+        let Some(token) = self.token(req) else {
+            return Err(bearer_challenge("missing bearer token or API key"));
+        };
+        let Some(principal) = (self.validate)(token) else {
+            return Err(bearer_challenge("invalid bearer token or API key"));
+        };
+        req.extensions_mut().insert(principal);
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "token_auth"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Response, Router};
+
+    fn whoami(req: Request, _params: Params) -> anyhow::Result<Response> {
+        let Principal(user) = req.extensions().get().unwrap();
+        Ok(http::Response::new(Some(user.clone().into())))
+    }
+
+    fn get(router: &Router, authorization: Option<&str>) -> Response {
+        let mut req = http::Request::builder().uri("/whoami");
+        if let Some(authorization) = authorization {
+            req = req.header(http::header::AUTHORIZATION, authorization);
+        }
+        router.handle(req.body(None).unwrap()).unwrap()
+    }
+
+    fn token_router() -> Router {
+        let mut router = Router::new();
+        router.get("/whoami", whoami);
+        router.guard(token_auth(|token| {
+            (token == "t0ken").then(|| Principal("service".to_owned()))
+        }));
+        router
+    }
+
+    #[test]
+    fn test_basic_auth() {
+        let mut router = Router::new();
+        router.get("/whoami", whoami);
+        router.guard(basic_auth("staff", |user, password| {
+            user == "ada" && password == "lovelace"
+        }));
+
+        let res = get(&router, Some("Basic YWRhOmxvdmVsYWNl"));
+        assert_eq!(res.into_body().unwrap(), "ada");
+
+        for authorization in [None, Some("Basic YWRhOndyb25n"), Some("Bearer token")] {
+            let res = get(&router, authorization);
+            assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                res.headers()[http::header::WWW_AUTHENTICATE],
+                "Basic realm=\"staff\", charset=\"UTF-8\""
+            );
+        }
+    }
+
+    #[test]
+    fn test_token_auth() {
+        let router = token_router();
+        let res = get(&router, Some("Bearer t0ken"));
+        assert_eq!(res.into_body().unwrap(), "service");
+
+        let req = http::Request::builder()
+            .uri("/whoami")
+            .header("x-api-key", "t0ken")
+            .body(None)
+            .unwrap();
+        assert_eq!(router.handle(req).unwrap().status(), http::StatusCode::OK);
+
+        for authorization in [None, Some("Bearer wrong"), Some("Basic t0ken")] {
+            let res = get(&router, authorization);
+            assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+            assert_eq!(res.headers()[http::header::WWW_AUTHENTICATE], "Bearer");
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Inspects the route manifest of a Spin component without running it.
+//!
+//! ```text
+//! spin-routes print <manifest.json>
+//! spin-routes validate <manifest.json>
+//! spin-routes diff <old.json> <new.json>
+//! spin-routes export nginx <manifest.json> <upstream>
+//! spin-routes export envoy <manifest.json> <cluster>
+//! ```
+//!
+//! Each manifest can also be given as a Wasm binary embedding one with
+//! `spin_sdk_router::embed_manifest!`.
+//!
+//! `validate` exits with status 1 when the route table has issues, and `diff` when the
+//! manifests differ, so that both can guard a snapshot of the route table in CI.
+use anyhow::{bail, Context, Result};
+use spin_sdk_router::manifest::Manifest;
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: spin-routes print <manifest>
+       spin-routes validate <manifest>
+       spin-routes diff <old> <new>
+       spin-routes export nginx <manifest> <upstream>
+       spin-routes export envoy <manifest> <cluster>";
+
+fn load(path: &str) -> Result<Manifest> {
+    let bytes = std::fs::read(path).with_context(|| format!("cannot read {path}"))?;
+    let manifest = if bytes.starts_with(b"\0asm") {
+        Manifest::from_wasm(&bytes)
+    } else {
+        Manifest::from_json(std::str::from_utf8(&bytes)?)
+    };
+    manifest.with_context(|| format!("cannot load {path}"))
+}
+
+fn run(args: &[String]) -> Result<bool> {
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    match args[..] {
+        ["print", path] => {
+            print!("{}", load(path)?);
+            Ok(true)
+        }
+        ["validate", path] => {
+            let report = load(path)?.validate()?;
+            print!("{report}");
+            Ok(report.is_ok())
+        }
+        ["diff", old, new] => {
+            let changes = load(old)?.diff(&load(new)?);
+            for change in &changes {
+                println!("{change}");
+            }
+            Ok(changes.is_empty())
+        }
+        ["export", "nginx", path, upstream] => {
+            print!("{}", load(path)?.to_nginx(upstream));
+            Ok(true)
+        }
+        ["export", "envoy", path, cluster] => {
+            println!("{}", load(path)?.to_envoy(cluster));
+            Ok(true)
+        }
+        _ => bail!("{USAGE}"),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! A router builder reporting every invalid or conflicting route at once.
+use crate::{DuplicatePolicy, Params, Request, Response, Route, RouteError, RouteIssue, Router};
+use anyhow::Result;
+
+/// Accumulates routes, then builds a router or reports all the routes that could not be
+/// registered:
+///
+/// ```
+/// # use spin_sdk_router::{Params, Request, Response, Router};
+/// # fn handler(_req: Request, _params: Params) -> anyhow::Result<Response> {
+/// #     Ok(http::Response::new(None))
+/// # }
+/// let errors = Router::builder()
+///     .get("/users/:id", handler)
+///     .get("/users/:id", handler)
+///     .post("/*/edit", handler)
+///     .build()
+///     .err()
+///     .unwrap();
+/// assert_eq!(errors.len(), 2);
+/// ```
+///
+/// Routes registered twice are errors unless another [`DuplicatePolicy`] is chosen.
+pub struct RouterBuilder {
+    router: Router,
+    errors: Vec<RouteError>,
+    duplicate_policy: Option<DuplicatePolicy>,
+}
+
+impl Router {
+    /// A builder for a router, collecting registration errors instead of panicking.
+    pub fn builder() -> RouterBuilder {
+        let mut router = Router::new();
+        router.on_duplicate(DuplicatePolicy::Error);
+        RouterBuilder {
+            router,
+            errors: Vec::new(),
+            duplicate_policy: None,
+        }
+    }
+}
+
+impl RouterBuilder {
+    /// Handle routes registered twice with `policy` instead of reporting them.
+    pub fn on_duplicate(mut self, policy: DuplicatePolicy) -> Self {
+        self.router.on_duplicate(policy);
+        self.duplicate_policy = Some(policy);
+        self
+    }
+
+    /// Register `handler` at `path` for `method`, or for all methods if `None`, attaching
+    /// metadata to the route with `configure` if it is registered.
+    pub fn route_with<F, C>(
+        mut self,
+        method: Option<http::Method>,
+        path: &str,
+        handler: F,
+        configure: C,
+    ) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+        C: FnOnce(&mut Route),
+    {
+        let route = match method {
+            Some(method) => self.router.try_add(path, method, handler),
+            None => self.router.try_all(path, handler),
+        };
+        match route {
+            Ok(route) => configure(route),
+            Err(e) => self.errors.push(e),
+        }
+        self
+    }
+
+    /// Register `handler` at `path` for `method`.
+    pub fn add<F>(self, path: &str, method: http::Method, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.route_with(Some(method), path, handler, |_| {})
+    }
+
+    /// Register `handler` at `path` for all methods.
+    pub fn all<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.route_with(None, path, handler, |_| {})
+    }
+
+    /// Register one handler at `path` for each of `methods`.
+    pub fn any_of<F>(mut self, methods: &[http::Method], path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        if let Err(e) = self.router.try_any_of(methods, path, handler) {
+            self.errors.push(e);
+        }
+        self
+    }
+
+    /// Register `handler` at `path` for GET.
+    pub fn get<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::GET, handler)
+    }
+
+    /// Register `handler` at `path` for HEAD.
+    pub fn head<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::HEAD, handler)
+    }
+
+    /// Register `handler` at `path` for POST.
+    pub fn post<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::POST, handler)
+    }
+
+    /// Register `handler` at `path` for PUT.
+    pub fn put<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::PUT, handler)
+    }
+
+    /// Register `handler` at `path` for PATCH.
+    pub fn patch<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::PATCH, handler)
+    }
+
+    /// Register `handler` at `path` for DELETE.
+    pub fn delete<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::DELETE, handler)
+    }
+
+    /// Register `handler` at `path` for OPTIONS.
+    pub fn options<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::OPTIONS, handler)
+    }
+
+    /// Register `handler` at `path` for TRACE.
+    pub fn trace<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::TRACE, handler)
+    }
+
+    /// Register `handler` at `path` for CONNECT.
+    pub fn connect<F>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::CONNECT, handler)
+    }
+
+    /// Configure the router being built with `configure`, e.g. to add middleware or guards,
+    /// or to register routes with the `try_` methods, whose first error is reported by
+    /// [`RouterBuilder::build`].
+    pub fn configure<C>(mut self, configure: C) -> Self
+    where
+        C: FnOnce(&mut Router) -> Result<(), RouteError>,
+    {
+        if let Err(e) = configure(&mut self.router) {
+            self.errors.push(e);
+        }
+        self
+    }
+
+    /// The router, or the errors of all the routes that could not be registered: invalid
+    /// patterns in registration order, then routes registered twice, then the unreachable
+    /// and ambiguous routes [`Router::check`] reports.
+    pub fn build(mut self) -> Result<Router, Vec<RouteError>> {
+        if let Err(duplicates) = self.router.check_duplicates() {
+            self.errors.extend(duplicates);
+        }
+        let conflicts = self.router.check().issues.into_iter().filter(|issue| {
+            // Duplicates are handled as the duplicate policy says.
+            !matches!(issue, RouteIssue::Duplicate { .. })
+        });
+        self.errors.extend(conflicts.map(RouteError::Conflict));
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+        if self.duplicate_policy.is_none() {
+            self.router.on_duplicate(DuplicatePolicy::default());
+        }
+        Ok(self.router)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ok(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    #[test]
+    fn test_builder() {
+        let errors = Router::builder()
+            .get("/users/:id", ok)
+            .post("/*/edit", ok)
+            .get("/users/:id", ok)
+            .all("/:", ok)
+            .configure(|router| {
+                router.try_get("/users/:name", ok)?;
+                router.try_get("/files/*name", ok)?;
+                Ok(())
+            })
+            .build()
+            .err()
+            .unwrap();
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "invalid route pattern `/*/edit`: a wildcard must be the last segment",
+                "invalid route pattern `/:`: params must be named",
+                "invalid route pattern `/files/*name`: since there can only be one wildcard, \
+                 it doesn't need a name. replace `*name` with `*`",
+                "duplicate route GET /users/:id",
+                "conflicting route: GET /users/:name is unreachable, shadowed by /users/:id",
+            ]
+        );
+
+        let router = Router::builder()
+            .get("/users/:id", ok)
+            .route_with(Some(http::Method::PUT), "/users/:id", ok, |route| {
+                route.doc("Replaces a user.");
+            })
+            .configure(|router| {
+                router.expect_continue(crate::ExpectContinue::Reject);
+                Ok(())
+            })
+            .build()
+            .unwrap();
+        assert_eq!(router.duplicate_policy(), DuplicatePolicy::FirstWins);
+        assert_eq!(router.routes().count(), 2);
+
+        let router = Router::builder()
+            .trace("/echo", ok)
+            .connect("/tunnel", ok)
+            .any_of(&[http::Method::GET, http::Method::POST], "/form", ok)
+            .build()
+            .unwrap();
+        let routes: Vec<_> = router
+            .routes()
+            .map(|route| format!("{} {}", route.method().unwrap(), route.pattern()))
+            .collect();
+        assert_eq!(
+            routes,
+            ["TRACE /echo", "CONNECT /tunnel", "GET /form", "POST /form"]
+        );
+        let errors = Router::builder()
+            .any_of(&[http::Method::GET], "/:", ok)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+
+        let router = Router::builder()
+            .on_duplicate(DuplicatePolicy::LastWins)
+            .get("/a", ok)
+            .get("/a", ok)
+            .build()
+            .unwrap();
+        assert_eq!(router.duplicate_policy(), DuplicatePolicy::LastWins);
+
+        // A variant registered after the route serving everyone else is not a duplicate.
+        let router = Router::builder()
+            .get("/a", ok)
+            .route_with(Some(http::Method::GET), "/a", ok, |route| {
+                route.query("page", "2");
+            })
+            .build();
+        assert!(router.is_ok());
+    }
+}
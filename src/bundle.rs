@@ -0,0 +1,367 @@
+//! Bundles of routes that library crates export for applications to merge into their router.
+use crate::{Capability, RouteError, Router};
+use std::{collections::BTreeMap, fmt};
+
+/// The version of a bundle, as `major.minor.patch`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// Incremented for incompatible changes.
+    pub major: u64,
+    /// Incremented for compatible additions.
+    pub minor: u64,
+    /// Incremented for compatible fixes.
+    pub patch: u64,
+}
+
+impl Version {
+    /// Parses a version such as `1.4.2`, `1.4` or `1`, ignoring any pre-release or build
+    /// suffix, e.g. `-beta.1`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.split(['-', '+']).next()?;
+        let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+        let major = parts.next()??;
+        let minor = parts.next().unwrap_or(Some(0))?;
+        let patch = parts.next().unwrap_or(Some(0))?;
+        parts.next().is_none().then_some(Version {
+            major,
+            minor,
+            patch,
+        })
+    }
+
+    /// Whether this version can stand in for `required`, as Cargo's default (caret)
+    /// requirements decide: it is at least `required` and has the same leftmost non-zero
+    /// component.
+    pub fn satisfies(&self, required: &Version) -> bool {
+        let same_series = match (required.major, required.minor) {
+            (0, 0) => self.minor == 0 && self.patch == required.patch,
+            (0, minor) => self.major == 0 && self.minor == minor,
+            (major, _) => self.major == major,
+        };
+        same_series && self >= required
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A named, versioned set of routes with the capabilities they require and free-form
+/// metadata, merged into an application's router with [`Router::merge`]:
+///
+/// ```
+/// # use spin_sdk_router::{bundle::RoutesBundle, Capability, Params, Router};
+/// // In the library crate:
+/// pub fn auth_routes() -> RoutesBundle {
+///     let mut bundle = RoutesBundle::new("auth", env!("CARGO_PKG_VERSION"))
+///         .requires(Capability::KeyValue("sessions".to_owned()))
+///         .metadata("docs", "https://example.com/auth");
+///     bundle.router().post("/login", |_req, _params: Params| Ok(http::Response::new(None)));
+///     bundle
+/// }
+///
+/// // In the application:
+/// let mut router = Router::new();
+/// router.merge(auth_routes())?;
+/// # Ok::<(), spin_sdk_router::bundle::BundleError>(())
+/// ```
+pub struct RoutesBundle {
+    name: String,
+    version: Version,
+    router: Router,
+    capabilities: Vec<Capability>,
+    dependencies: Vec<(String, Version)>,
+    metadata: BTreeMap<String, String>,
+}
+
+impl RoutesBundle {
+    /// An empty bundle named `name`, at `version`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is not a valid version; see [`Version::parse`].
+    pub fn new(name: &str, version: &str) -> Self {
+        RoutesBundle {
+            name: name.to_owned(),
+            version: Version::parse(version).expect("invalid bundle version"),
+            router: Router::new(),
+            capabilities: Vec::new(),
+            dependencies: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// The router to register the routes of the bundle on.
+    pub fn router(&mut self) -> &mut Router {
+        &mut self.router
+    }
+
+    /// Declare that every route of the bundle needs `capability`, as
+    /// [`Route::requires`](crate::Route::requires) does.
+    pub fn requires(mut self, capability: Capability) -> Self {
+        self.capabilities.push(capability);
+        self
+    }
+
+    /// Declare that the bundle needs the bundle `name` at a version compatible with
+    /// `version` to have been merged before it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is not a valid version; see [`Version::parse`].
+    pub fn depends_on(mut self, name: &str, version: &str) -> Self {
+        let version = Version::parse(version).expect("invalid bundle version");
+        self.dependencies.push((name.to_owned(), version));
+        self
+    }
+
+    /// Attach `value` to the bundle under `key`, e.g. its documentation or license.
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_owned(), value.to_owned());
+        self
+    }
+}
+
+/// What is kept of a bundle once merged, listed by [`Router::bundles`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BundleInfo {
+    /// The name of the bundle.
+    pub name: String,
+    /// The version of the bundle.
+    pub version: Version,
+    /// The number of routes the bundle added.
+    pub routes: usize,
+    /// The metadata of the bundle.
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Why a bundle could not be merged. Nothing of a bundle is merged when merging it fails.
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BundleError {
+    /// A bundle of the same name was merged already.
+    AlreadyMerged {
+        /// The name of the bundle.
+        name: String,
+        /// The version merged already.
+        version: Version,
+    },
+    /// A bundle the bundle depends on was not merged before it.
+    MissingDependency {
+        /// The name of the bundle.
+        name: String,
+        /// The name of the dependency.
+        dependency: String,
+        /// The version of the dependency required.
+        required: Version,
+    },
+    /// A bundle the bundle depends on was merged at an incompatible version.
+    IncompatibleDependency {
+        /// The name of the bundle.
+        name: String,
+        /// The name of the dependency.
+        dependency: String,
+        /// The version of the dependency required.
+        required: Version,
+        /// The version of the dependency merged.
+        found: Version,
+    },
+    /// Routes of the bundle are registered on the router already, as `METHOD pattern`.
+    Conflicts {
+        /// The name of the bundle.
+        name: String,
+        /// The conflicting routes.
+        routes: Vec<String>,
+    },
+    /// A route of the bundle could not be registered.
+    Route(RouteError),
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::AlreadyMerged { name, version } => {
+                write!(f, "bundle `{name}` is merged already, at version {version}")
+            }
+            BundleError::MissingDependency {
+                name,
+                dependency,
+                required,
+            } => write!(
+                f,
+                "bundle `{name}` needs bundle `{dependency}` {required}, which is not merged"
+            ),
+            BundleError::IncompatibleDependency {
+                name,
+                dependency,
+                required,
+                found,
+            } => write!(
+                f,
+                "bundle `{name}` needs bundle `{dependency}` {required}, but {found} is merged"
+            ),
+            BundleError::Conflicts { name, routes } => write!(
+                f,
+                "bundle `{name}` registers routes that exist already: {}",
+                routes.join(", ")
+            ),
+            BundleError::Route(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl Router {
+    /// Merge the routes of `bundle` into the router, failing without merging anything if a
+    /// bundle of the same name was merged already, if a dependency of the bundle was not
+    /// merged at a compatible version, or if a route of the bundle is registered already,
+    /// whatever the [duplicate policy](Router::on_duplicate).
+    ///
+    /// Only the routes of the bundle's router are merged, not its middleware, guards or
+    /// other settings.
+    pub fn merge(&mut self, bundle: RoutesBundle) -> Result<(), BundleError> {
+        let RoutesBundle {
+            name,
+            version,
+            router,
+            capabilities,
+            dependencies,
+            metadata,
+        } = bundle;
+        if let Some(merged) = self.bundles.iter().find(|b| b.name == name) {
+            return Err(BundleError::AlreadyMerged {
+                name,
+                version: merged.version,
+            });
+        }
+        for (dependency, required) in dependencies {
+            match self.bundles.iter().find(|b| b.name == dependency) {
+                None => {
+                    return Err(BundleError::MissingDependency {
+                        name,
+                        dependency,
+                        required,
+                    })
+                }
+                Some(merged) if !merged.version.satisfies(&required) => {
+                    return Err(BundleError::IncompatibleDependency {
+                        name,
+                        dependency,
+                        required,
+                        found: merged.version,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        let conflicts: Vec<_> = router
+            .routes
+            .iter()
+            .filter(|route| self.duplicate_of(route).is_some())
+            .map(|route| {
+                let method = route.method().map_or("*", http::Method::as_str);
+                format!("{method} {}", route.pattern)
+            })
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(BundleError::Conflicts {
+                name,
+                routes: conflicts,
+            });
+        }
+        let routes = router.routes.len();
+        for mut route in router.routes {
+            route.capabilities.extend(capabilities.iter().cloned());
+            self.insert(route).map_err(BundleError::Route)?;
+        }
+        self.bundles.push(BundleInfo {
+            name,
+            version,
+            routes,
+            metadata,
+        });
+        Ok(())
+    }
+
+    /// The bundles merged into the router, in the order they were merged.
+    pub fn bundles(&self) -> &[BundleInfo] {
+        &self.bundles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{capabilities::Environment, Params};
+
+    fn bundle(name: &str, version: &str, paths: &[&str]) -> RoutesBundle {
+        let mut bundle = RoutesBundle::new(name, version);
+        for path in paths {
+            bundle
+                .router()
+                .get(path, |_req, _params: Params| Ok(http::Response::new(None)));
+        }
+        bundle
+    }
+
+    #[test]
+    fn test_versions() {
+        let v = |s| Version::parse(s).unwrap();
+        assert_eq!(v("1.4.2-beta.1"), v("1.4.2"));
+        assert_eq!(v("2"), v("2.0.0"));
+        assert_eq!(Version::parse("1.x"), None);
+        assert!(v("1.5.0").satisfies(&v("1.4.2")));
+        assert!(!v("1.4.1").satisfies(&v("1.4.2")));
+        assert!(!v("2.0.0").satisfies(&v("1.4.2")));
+        assert!(v("0.3.9").satisfies(&v("0.3.1")));
+        assert!(!v("0.4.0").satisfies(&v("0.3.1")));
+        assert!(!v("0.0.4").satisfies(&v("0.0.3")));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut router = Router::new();
+        router.get("/health", |_req, _params: Params| {
+            Ok(http::Response::new(None))
+        });
+        let auth = bundle("auth", "1.2.0", &["/login", "/logout"])
+            .requires(Capability::KeyValue("sessions".to_owned()))
+            .metadata("license", "MIT");
+        router.merge(auth).unwrap();
+        let req = http::Request::get("/login").body(None).unwrap();
+        assert_eq!(router.handle(req).unwrap().status(), 200);
+        assert_eq!(router.bundles()[0].routes, 2);
+        assert_eq!(router.bundles()[0].metadata["license"], "MIT");
+        assert!(router.check_capabilities(&Environment::new()).is_err());
+
+        let err = router.merge(bundle("auth", "1.3.0", &[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "bundle `auth` is merged already, at version 1.2.0"
+        );
+        let admin = bundle("admin", "0.1.0", &["/admin"]).depends_on("auth", "1.3");
+        assert_eq!(
+            router.merge(admin).unwrap_err().to_string(),
+            "bundle `admin` needs bundle `auth` 1.3.0, but 1.2.0 is merged"
+        );
+        let admin = bundle("admin", "0.1.0", &["/admin"]).depends_on("billing", "1");
+        assert!(matches!(
+            router.merge(admin),
+            Err(BundleError::MissingDependency { .. })
+        ));
+        let admin = bundle("admin", "0.1.0", &["/admin", "/health", "/login"]);
+        assert_eq!(
+            router.merge(admin).unwrap_err().to_string(),
+            "bundle `admin` registers routes that exist already: GET /health, GET /login"
+        );
+        let req = http::Request::get("/admin").body(None).unwrap();
+        assert_eq!(router.handle(req).unwrap().status(), 404);
+
+        let admin = bundle("admin", "0.1.0", &["/admin"]).depends_on("auth", "1.1");
+        router.merge(admin).unwrap();
+        assert_eq!(router.bundles().len(), 2);
+    }
+}
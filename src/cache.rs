@@ -0,0 +1,175 @@
+//! Declarative `Cache-Control` policies attached to routes.
+use crate::Route;
+use std::fmt;
+
+/// A `Cache-Control` policy, e.g. `CachePolicy::public().max_age(3600).immutable()`.
+///
+/// Attached to a route with [`Route::cache`], it is added to the route's successful
+/// responses unless the handler sets `Cache-Control` itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CachePolicy {
+    visibility: Option<&'static str>,
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+    stale_if_error: Option<u64>,
+    must_revalidate: bool,
+    immutable: bool,
+}
+
+impl CachePolicy {
+    /// A response any cache may store, including shared caches such as CDNs.
+    pub fn public() -> Self {
+        CachePolicy {
+            visibility: Some("public"),
+            ..Default::default()
+        }
+    }
+
+    /// A response only the user's own browser may store.
+    pub fn private() -> Self {
+        CachePolicy {
+            visibility: Some("private"),
+            ..Default::default()
+        }
+    }
+
+    /// A response no cache may store.
+    pub fn no_store() -> Self {
+        CachePolicy {
+            no_store: true,
+            ..Default::default()
+        }
+    }
+
+    /// A response caches may store but must revalidate before every use.
+    pub fn no_cache() -> Self {
+        CachePolicy {
+            no_cache: true,
+            ..Default::default()
+        }
+    }
+
+    /// Consider the response fresh for `seconds`.
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Consider the response fresh for `seconds` in shared caches, overriding `max-age`.
+    pub fn s_maxage(mut self, seconds: u64) -> Self {
+        self.s_maxage = Some(seconds);
+        self
+    }
+
+    /// Allow a stale response to be served for `seconds` while it is revalidated.
+    pub fn stale_while_revalidate(mut self, seconds: u64) -> Self {
+        self.stale_while_revalidate = Some(seconds);
+        self
+    }
+
+    /// Allow a stale response to be served for `seconds` when revalidation fails.
+    pub fn stale_if_error(mut self, seconds: u64) -> Self {
+        self.stale_if_error = Some(seconds);
+        self
+    }
+
+    /// Forbid serving the response once stale without revalidating it.
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    /// Declare that the response never changes while fresh, e.g. for fingerprinted assets.
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+}
+
+/// Renders the policy as a `Cache-Control` header value.
+impl fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = |name: &str, value: Option<u64>| value.map(|v| format!("{name}={v}"));
+        let flag = |name: &str, set: bool| set.then(|| name.to_owned());
+        let directives = [
+            self.visibility.map(str::to_owned),
+            flag("no-store", self.no_store),
+            flag("no-cache", self.no_cache),
+            seconds("max-age", self.max_age),
+            seconds("s-maxage", self.s_maxage),
+            seconds("stale-while-revalidate", self.stale_while_revalidate),
+            seconds("stale-if-error", self.stale_if_error),
+            flag("must-revalidate", self.must_revalidate),
+            flag("immutable", self.immutable),
+        ];
+        let directives: Vec<_> = directives.into_iter().flatten().collect();
+        f.write_str(&directives.join(", "))
+    }
+}
+
+impl Route {
+    /// Add the caching policy `policy` to the route's successful responses that do not set
+    /// `Cache-Control` themselves.
+    pub fn cache(&mut self, policy: CachePolicy) -> &mut Self {
+        self.cache = Some(policy);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+
+    #[test]
+    fn test_cache_policy() {
+        let policy = CachePolicy::public().max_age(3600).immutable();
+        assert_eq!(policy.to_string(), "public, max-age=3600, immutable");
+        let policy = CachePolicy::private()
+            .max_age(60)
+            .stale_while_revalidate(30)
+            .must_revalidate();
+        assert_eq!(
+            policy.to_string(),
+            "private, max-age=60, stale-while-revalidate=30, must-revalidate"
+        );
+        assert_eq!(CachePolicy::no_store().to_string(), "no-store");
+    }
+
+    #[test]
+    fn test_route_cache_policy() {
+        let mut router = Router::new();
+        router
+            .get("/logo.png", |_req, _params| Ok(http::Response::new(None)))
+            .cache(CachePolicy::public().max_age(86400));
+        router
+            .get("/me", |_req, _params| {
+                Ok(http::Response::builder()
+                    .header(http::header::CACHE_CONTROL, "no-store")
+                    .body(None)?)
+            })
+            .cache(CachePolicy::private().max_age(60));
+        router
+            .get("/missing", |_req, _params| {
+                Ok(http::Response::builder().status(404).body(None)?)
+            })
+            .cache(CachePolicy::public().max_age(60));
+
+        let get = |path: &str| {
+            let req = http::Request::builder().uri(path).body(None).unwrap();
+            router.handle(req).unwrap()
+        };
+        let res = get("/logo.png");
+        assert_eq!(
+            res.headers()[http::header::CACHE_CONTROL],
+            "public, max-age=86400"
+        );
+        let res = get("/me");
+        assert_eq!(res.headers()[http::header::CACHE_CONTROL], "no-store");
+        let res = get("/missing");
+        assert!(!res.headers().contains_key(http::header::CACHE_CONTROL));
+    }
+}
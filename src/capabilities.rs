@@ -0,0 +1,249 @@
+//! Spin capabilities required by routes, checked against the component's configuration.
+use crate::{Route, Router};
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// A capability a route needs the component to be granted in `spin.toml`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Capability {
+    /// Access to the key-value store with the given label.
+    KeyValue(String),
+    /// Access to the SQLite database with the given label.
+    Sqlite(String),
+    /// Outbound connections to the given URL or host, e.g. `https://api.example.com`.
+    OutboundHost(String),
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::KeyValue(store) => write!(f, "key-value store `{store}`"),
+            Capability::Sqlite(database) => write!(f, "SQLite database `{database}`"),
+            Capability::OutboundHost(host) => write!(f, "outbound host `{host}`"),
+        }
+    }
+}
+
+/// The capabilities granted to the component, as configured in `spin.toml`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Environment {
+    key_value_stores: Vec<String>,
+    sqlite_databases: Vec<String>,
+    allowed_outbound_hosts: Vec<String>,
+}
+
+impl Environment {
+    /// An environment granting nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grant access to the key-value store `label`.
+    pub fn key_value_store(mut self, label: &str) -> Self {
+        self.key_value_stores.push(label.to_owned());
+        self
+    }
+
+    /// Grant access to the SQLite database `label`.
+    pub fn sqlite_database(mut self, label: &str) -> Self {
+        self.sqlite_databases.push(label.to_owned());
+        self
+    }
+
+    /// Allow outbound connections matching `host`, in the format of `allowed_outbound_hosts`,
+    /// e.g. `https://*.example.com` or `*://localhost:*`.
+    pub fn allowed_outbound_host(mut self, host: &str) -> Self {
+        self.allowed_outbound_hosts.push(host.to_owned());
+        self
+    }
+
+    /// The environment of `component` in a version 2 `spin.toml` manifest.
+    #[cfg(feature = "preflight")]
+    pub fn from_spin_toml(manifest: &str, component: &str) -> Result<Self> {
+        use anyhow::Context;
+
+        let manifest: toml::Table = manifest.parse().context("invalid spin.toml")?;
+        let Some(config) = manifest
+            .get("component")
+            .and_then(|components| components.get(component))
+        else {
+            bail!("spin.toml has no component `{component}`");
+        };
+        let list = |key: &str| -> Vec<String> {
+            let values = config.get(key).and_then(toml::Value::as_array);
+            let values = values.into_iter().flatten().filter_map(toml::Value::as_str);
+            values.map(str::to_owned).collect()
+        };
+        Ok(Environment {
+            key_value_stores: list("key_value_stores"),
+            sqlite_databases: list("sqlite_databases"),
+            allowed_outbound_hosts: list("allowed_outbound_hosts"),
+        })
+    }
+
+    /// Whether the capability is granted.
+    fn grants(&self, capability: &Capability) -> bool {
+        match capability {
+            Capability::KeyValue(store) => self.key_value_stores.contains(store),
+            Capability::Sqlite(database) => self.sqlite_databases.contains(database),
+            Capability::OutboundHost(host) => {
+                let wanted = HostPattern::parse(host);
+                self.allowed_outbound_hosts
+                    .iter()
+                    .any(|allowed| HostPattern::parse(allowed).allows(&wanted))
+            }
+        }
+    }
+}
+
+/// A URL or host in the format of `allowed_outbound_hosts`: `scheme://host:port`, where each
+/// part may be `*` and the host may be `*.domain`.
+struct HostPattern<'a> {
+    scheme: &'a str,
+    host: &'a str,
+    port: Option<&'a str>,
+}
+
+impl<'a> HostPattern<'a> {
+    fn parse(url: &'a str) -> Self {
+        let (scheme, rest) = url.split_once("://").unwrap_or(("*", url));
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        };
+        HostPattern { scheme, host, port }
+    }
+
+    /// The port, defaulting to that of the scheme.
+    fn port(&self) -> Option<&'a str> {
+        self.port.or(match self.scheme {
+            "http" => Some("80"),
+            "https" => Some("443"),
+            _ => None,
+        })
+    }
+
+    /// Whether connections to `wanted` are allowed by this pattern.
+    fn allows(&self, wanted: &HostPattern<'_>) -> bool {
+        let scheme = self.scheme == "*" || self.scheme.eq_ignore_ascii_case(wanted.scheme);
+        let host = match self.host.strip_prefix("*.") {
+            _ if self.host == "*" => true,
+            Some(domain) => wanted
+                .host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", domain.to_ascii_lowercase())),
+            None => self.host.eq_ignore_ascii_case(wanted.host),
+        };
+        let port = self.port == Some("*") || self.port() == wanted.port();
+        scheme && host && port
+    }
+}
+
+impl Route {
+    /// Declare that the route needs `capability`, so that [`Router::check_capabilities`] can
+    /// catch a `spin.toml` that does not grant it before the route fails at runtime.
+    pub fn requires(&mut self, capability: Capability) -> &mut Self {
+        self.capabilities.push(capability);
+        self
+    }
+}
+
+impl Router {
+    /// Check that `environment` grants every capability the routes require, failing with a
+    /// list of those it does not. Run it as a preflight check, e.g. in a test loading the
+    /// component's `spin.toml`:
+    ///
+    /// ```ignore
+    /// let environment = Environment::from_spin_toml(include_str!("../spin.toml"), "api")?;
+    /// router().check_capabilities(&environment)?;
+    /// ```
+    pub fn check_capabilities(&self, environment: &Environment) -> Result<()> {
+        let mut missing = Vec::new();
+        let mut routers = vec![self];
+        while let Some(router) = routers.pop() {
+            routers.extend(router.hosts.values());
+            for route in &router.routes {
+                for capability in &route.capabilities {
+                    if !environment.grants(capability) {
+                        let method = route.method().map_or("*", http::Method::as_str);
+                        missing.push(format!("{method} {} needs {capability}", route.pattern));
+                    }
+                }
+            }
+        }
+        if !missing.is_empty() {
+            bail!("missing capabilities:\n{}", missing.join("\n"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outbound_hosts() {
+        let allows = |allowed: &str, wanted: &str| {
+            HostPattern::parse(allowed).allows(&HostPattern::parse(wanted))
+        };
+        assert!(allows("https://api.example.com", "https://api.example.com"));
+        assert!(allows(
+            "https://api.example.com",
+            "https://api.example.com:443"
+        ));
+        assert!(!allows("https://api.example.com", "http://api.example.com"));
+        assert!(allows("https://*.example.com", "https://api.example.com"));
+        assert!(!allows("https://*.example.com", "https://example.com"));
+        assert!(allows("*://localhost:*", "http://localhost:3000"));
+        assert!(!allows("http://localhost:8080", "http://localhost:3000"));
+    }
+
+    #[test]
+    fn test_check_capabilities() {
+        let mut router = Router::new();
+        router
+            .get("/users", |_req, _params| Ok(http::Response::new(None)))
+            .requires(Capability::Sqlite("default".to_owned()))
+            .requires(Capability::OutboundHost(
+                "https://api.example.com".to_owned(),
+            ));
+        router
+            .host("admin.example.com")
+            .get("/sessions", |_req, _params| Ok(http::Response::new(None)))
+            .requires(Capability::KeyValue("sessions".to_owned()));
+
+        let environment = Environment::new()
+            .sqlite_database("default")
+            .allowed_outbound_host("https://*.example.com");
+        let err = router.check_capabilities(&environment).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "missing capabilities:\nGET /sessions needs key-value store `sessions`"
+        );
+        let environment = environment.key_value_store("sessions");
+        assert!(router.check_capabilities(&environment).is_ok());
+    }
+
+    #[cfg(feature = "preflight")]
+    #[test]
+    fn test_from_spin_toml() {
+        let manifest = r#"
+            spin_manifest_version = 2
+
+            [component.api]
+            source = "api.wasm"
+            key_value_stores = ["default"]
+            allowed_outbound_hosts = ["https://api.example.com"]
+        "#;
+        let environment = Environment::from_spin_toml(manifest, "api").unwrap();
+        assert_eq!(
+            environment,
+            Environment::new()
+                .key_value_store("default")
+                .allowed_outbound_host("https://api.example.com")
+        );
+        assert!(Environment::from_spin_toml(manifest, "web").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Static analysis of the route table.
+use crate::{Route, Router};
+use std::fmt;
+
+/// A problem found in the route table by [`Router::check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouteIssue {
+    /// The same pattern was registered more than once; which registration is dispatched to
+    /// depends on the [`DuplicatePolicy`](crate::DuplicatePolicy) in force.
+    Duplicate {
+        /// The method of the routes, or `None` for routes registered for all methods.
+        method: Option<http::Method>,
+        /// The duplicated pattern.
+        pattern: String,
+    },
+    /// The route matches exactly the same paths as an earlier route (differing only in
+    /// parameter names), so it is never dispatched to.
+    Shadowed {
+        /// The method of the routes, or `None` for routes registered for all methods.
+        method: Option<http::Method>,
+        /// The unreachable pattern.
+        pattern: String,
+        /// The earlier pattern that shadows it.
+        by: String,
+    },
+    /// Both routes match some paths but neither is more specific than the other, so which
+    /// one handles an overlapping path depends on segment precedence.
+    Ambiguous {
+        /// The method of the routes, or `None` for routes registered for all methods.
+        method: Option<http::Method>,
+        /// The pattern registered first.
+        first: String,
+        /// The pattern registered second.
+        second: String,
+    },
+}
+
+impl fmt::Display for RouteIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = |m: &Option<http::Method>| m.as_ref().map_or("*", |m| m.as_str()).to_owned();
+        match self {
+            RouteIssue::Duplicate { method: m, pattern } => {
+                write!(f, "{} {pattern} is registered more than once", method(m))
+            }
+            RouteIssue::Shadowed {
+                method: m,
+                pattern,
+                by,
+            } => write!(
+                f,
+                "{} {pattern} is unreachable, shadowed by {by}",
+                method(m)
+            ),
+            RouteIssue::Ambiguous {
+                method: m,
+                first,
+                second,
+            } => write!(f, "{} {first} and {second} are ambiguous", method(m)),
+        }
+    }
+}
+
+/// The result of [`Router::check`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RouteReport {
+    /// The issues found, in registration order.
+    pub issues: Vec<RouteIssue>,
+}
+
+impl RouteReport {
+    /// Whether no issues were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for RouteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A `/`-separated piece of a route pattern.
+#[derive(PartialEq, Eq)]
+enum Piece<'a> {
+    Literal(&'a str),
+    /// A piece consisting of a single `:param`, with the values it is limited to if any.
+    Param(Option<&'a [String]>),
+    /// A piece mixing parameters with literal text, e.g. `:name.json`.
+    Mixed(&'a str),
+    Wildcard,
+}
+
+/// The patterns `route` matches: its own, and the shorter ones omitting its defaulted
+/// segments, e.g. `/feed` for `/feed/:format=json`.
+fn forms(route: &Route) -> Vec<String> {
+    let segments: Vec<_> = route.pattern.split('/').collect();
+    (0..=route.defaults.len())
+        .map(|omitted| segments[..segments.len() - omitted].join("/"))
+        .collect()
+}
+
+/// The pieces of `form`, one of the patterns `route` matches.
+fn pieces<'a>(route: &'a Route, form: &'a str) -> Vec<Piece<'a>> {
+    let choices = |name: &str| {
+        let choices = route.choices.iter().find(|(param, _)| param == name);
+        choices.map(|(_, values)| values.as_slice())
+    };
+    form.split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| match s {
+            "*" => Piece::Wildcard,
+            s if s.starts_with(':') && !s.contains('.') => Piece::Param(choices(&s[1..])),
+            s if s.contains(':') => Piece::Mixed(s),
+            s => Piece::Literal(s),
+        })
+        .collect()
+}
+
+/// Whether some path is matched by both patterns.
+fn overlaps(a: &[Piece<'_>], b: &[Piece<'_>]) -> bool {
+    match (a.split_first(), b.split_first()) {
+        (None, None) => true,
+        (Some((Piece::Wildcard, _)), _) | (_, Some((Piece::Wildcard, _))) => true,
+        (None, _) | (_, None) => false,
+        (Some((x, a)), Some((y, b))) => {
+            // Pieces capturing a parameter are assumed to be able to match any literal, unless
+            // limited to a set of values.
+            let disjoint = match (x, y) {
+                (Piece::Literal(x), Piece::Literal(y)) => x != y,
+                (Piece::Literal(x), Piece::Param(Some(values)))
+                | (Piece::Param(Some(values)), Piece::Literal(x)) => !values.iter().any(|v| v == x),
+                (Piece::Param(Some(a)), Piece::Param(Some(b))) => !a.iter().any(|v| b.contains(v)),
+                _ => false,
+            };
+            !disjoint && overlaps(a, b)
+        }
+    }
+}
+
+/// Whether every path matched by `a` is also matched by `b`.
+fn is_subset(a: &[Piece<'_>], b: &[Piece<'_>]) -> bool {
+    match (a.split_first(), b.split_first()) {
+        (None, None) => true,
+        (_, Some((Piece::Wildcard, _))) => true,
+        (None, _) | (_, None) => false,
+        (Some((x, a)), Some((y, b))) => {
+            let piece_subset = match (x, y) {
+                (Piece::Literal(x), Piece::Literal(y)) => x == y,
+                (Piece::Mixed(x), Piece::Mixed(y)) => x == y,
+                (Piece::Wildcard, _) => false,
+                (Piece::Literal(x), Piece::Param(Some(values))) => values.iter().any(|v| v == x),
+                (Piece::Param(Some(a)), Piece::Param(Some(b))) => a.iter().all(|v| b.contains(v)),
+                (Piece::Param(Some(a)), Piece::Literal(y)) => a.iter().all(|v| v == y),
+                (_, Piece::Param(None)) => true,
+                _ => false,
+            };
+            piece_subset && is_subset(a, b)
+        }
+    }
+}
+
+fn normalize(pattern: &str) -> String {
+    format!("/{}", pattern.trim_matches('/'))
+}
+
+impl Router {
+    /// Analyze the route table for duplicate, unreachable and ambiguous routes.
+    ///
+    /// Routes are only compared with other routes of the same method, and routes registered
+    /// for all methods with each other. A more specific route (e.g. `/users/me`) taking
+    /// precedence over a more general one (e.g. `/users/:id` or `/users/*`) is not an issue.
+    pub fn check(&self) -> RouteReport {
+        let mut report = RouteReport::default();
+        let routes: Vec<_> = self.routes().collect();
+        for (i, later) in routes.iter().enumerate() {
+            for earlier in &routes[..i] {
+                // A conditional route lets non-matching requests through to later routes.
+                if earlier.method != later.method || earlier.is_conditional() {
+                    continue;
+                }
+                // A route constraining more query parameters is tried first, as is a conditional
+                // variant of the same pattern.
+                if later.queries.len() > earlier.queries.len()
+                    || (later.pattern == earlier.pattern && later.is_conditional())
+                {
+                    continue;
+                }
+                if let Some(issue) = compare(earlier, later) {
+                    report.issues.push(issue);
+                    break;
+                }
+            }
+        }
+        report
+    }
+}
+
+fn compare(earlier: &Route, later: &Route) -> Option<RouteIssue> {
+    let method = later.method.clone();
+    let (earlier_forms, later_forms) = (forms(earlier), forms(later));
+    let mut ambiguous = None;
+    for later_form in &later_forms {
+        let later_pieces = pieces(later, later_form);
+        for earlier_form in &earlier_forms {
+            let earlier_pieces = pieces(earlier, earlier_form);
+            let forward = is_subset(&later_pieces, &earlier_pieces);
+            let backward = is_subset(&earlier_pieces, &later_pieces);
+
+            if forward && backward {
+                let pattern = normalize(later_form);
+                let by = normalize(earlier_form);
+                return Some(if pattern == by {
+                    RouteIssue::Duplicate { method, pattern }
+                } else {
+                    RouteIssue::Shadowed {
+                        method,
+                        pattern,
+                        by,
+                    }
+                });
+            }
+            if !forward && !backward && overlaps(&earlier_pieces, &later_pieces) {
+                ambiguous.get_or_insert_with(|| RouteIssue::Ambiguous {
+                    method: method.clone(),
+                    first: normalize(earlier_form),
+                    second: normalize(later_form),
+                });
+            }
+        }
+    }
+    ambiguous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Request, Response};
+    use anyhow::Result;
+
+    fn h(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::builder().status(200).body(None)?)
+    }
+
+    #[test]
+    fn test_check_clean_router() {
+        let mut router = Router::default();
+        router.get("/users", h);
+        router.get("/users/me", h);
+        router.get("/users/:id", h);
+        router.get("/users/*", h);
+        router.post("/users/:id", h);
+        router.all("/*", h);
+        router.get("/beta/:id", h).when(|_| false);
+        router.get("/beta/:name", h);
+        router.get("/export/:format<json|csv>", h);
+        router.get("/export/:name", h);
+        router.get("/report/:format<pdf|csv>", h);
+        router.get("/report/:kind<html|txt>", h);
+        router.get("/news/:format=json", h);
+        router.get("/news/all", h);
+        assert!(router.check().is_ok());
+    }
+
+    #[test]
+    fn test_check_reports_issues() {
+        let mut router = Router::default();
+        router.get("/users/:id", h);
+        router.get("users/:id/", h);
+        router.get("/users/:name", h);
+        router.get("/:org/repos/:repo", h);
+        router.get("/acme/:kind/readme", h);
+        router.get("/feed/:format<atom|rss>", h);
+        router.get("/feed/:kind<rss|json>", h);
+        router.get("/posts/:format=json", h);
+        router.get("/posts", h);
+
+        let report = router.check();
+        assert_eq!(
+            report.issues,
+            [
+                RouteIssue::Duplicate {
+                    method: Some(http::Method::GET),
+                    pattern: "/users/:id".to_owned(),
+                },
+                RouteIssue::Shadowed {
+                    method: Some(http::Method::GET),
+                    pattern: "/users/:name".to_owned(),
+                    by: "/users/:id".to_owned(),
+                },
+                RouteIssue::Ambiguous {
+                    method: Some(http::Method::GET),
+                    first: "/:org/repos/:repo".to_owned(),
+                    second: "/acme/:kind/readme".to_owned(),
+                },
+                RouteIssue::Ambiguous {
+                    method: Some(http::Method::GET),
+                    first: "/feed/:format".to_owned(),
+                    second: "/feed/:kind".to_owned(),
+                },
+                RouteIssue::Duplicate {
+                    method: Some(http::Method::GET),
+                    pattern: "/posts".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string().lines().next(),
+            Some("GET /users/:id is registered more than once")
+        );
+    }
+}
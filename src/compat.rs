@@ -0,0 +1,174 @@
+//! Adapters for handlers written against the Spin SDK's built-in router.
+//!
+//! The SDK's router accepts handlers taking any request type it can convert the incoming
+//! request into and returning anything it can convert into a response. [`handler`] wraps such
+//! a handler so that it can be registered on a [`Router`](crate::Router) unchanged:
+//!
+//! ```
+//! # use spin_sdk_router::{compat, Params, Router};
+//! fn hello(req: http::Request<String>, params: Params) -> anyhow::Result<http::Response<String>> {
+//!     let name = params.get("name").unwrap_or("world");
+//!     Ok(http::Response::new(format!("hello {name}, you sent {}", req.body())))
+//! }
+//!
+//! let mut router = Router::new();
+//! router.post("/hello/:name", compat::handler(hello));
+//! ```
+use crate::{Params, Request, Response};
+use anyhow::{Context, Result};
+use bytes::Bytes;
+
+/// A request type a handler can take, converted from the incoming [`Request`].
+pub trait FromRequest: Sized {
+    /// Converts the incoming request.
+    fn from_request(req: Request) -> Result<Self>;
+}
+
+impl FromRequest for Request {
+    fn from_request(req: Request) -> Result<Self> {
+        Ok(req)
+    }
+}
+
+impl FromRequest for http::Request<Bytes> {
+    fn from_request(req: Request) -> Result<Self> {
+        Ok(req.map(Option::unwrap_or_default))
+    }
+}
+
+impl FromRequest for http::Request<Vec<u8>> {
+    fn from_request(req: Request) -> Result<Self> {
+        Ok(req.map(|body| body.map(Vec::from).unwrap_or_default()))
+    }
+}
+
+impl FromRequest for http::Request<String> {
+    fn from_request(req: Request) -> Result<Self> {
+        let (parts, body) = req.into_parts();
+        let body = String::from_utf8(body.map(Vec::from).unwrap_or_default())
+            .context("request body is not valid UTF-8")?;
+        Ok(http::Request::from_parts(parts, body))
+    }
+}
+
+impl FromRequest for http::Request<()> {
+    fn from_request(req: Request) -> Result<Self> {
+        Ok(req.map(|_| ()))
+    }
+}
+
+/// A value a handler can return, converted into a [`Response`].
+pub trait IntoResponse {
+    /// Converts the value into the response to send.
+    fn into_response(self) -> Result<Response>;
+}
+
+impl<B: Into<Bytes>> IntoResponse for http::Response<Option<B>> {
+    fn into_response(self) -> Result<Response> {
+        Ok(self.map(|body| body.map(Into::into)))
+    }
+}
+
+impl IntoResponse for http::Response<Bytes> {
+    fn into_response(self) -> Result<Response> {
+        Ok(self.map(Some))
+    }
+}
+
+impl IntoResponse for http::Response<Vec<u8>> {
+    fn into_response(self) -> Result<Response> {
+        Ok(self.map(|body| Some(body.into())))
+    }
+}
+
+impl IntoResponse for http::Response<String> {
+    fn into_response(self) -> Result<Response> {
+        Ok(self.map(|body| Some(body.into())))
+    }
+}
+
+impl IntoResponse for http::Response<()> {
+    fn into_response(self) -> Result<Response> {
+        Ok(self.map(|_| None))
+    }
+}
+
+impl IntoResponse for http::StatusCode {
+    fn into_response(self) -> Result<Response> {
+        Ok(http::Response::builder().status(self).body(None)?)
+    }
+}
+
+impl<T, E> IntoResponse for std::result::Result<T, E>
+where
+    T: IntoResponse,
+    anyhow::Error: From<E>,
+{
+    fn into_response(self) -> Result<Response> {
+        self?.into_response()
+    }
+}
+
+/// Adapts a handler written for the Spin SDK's router so it can be registered on a
+/// [`Router`](crate::Router).
+pub fn handler<F, I, O>(handler: F) -> impl Fn(Request, Params) -> Result<Response>
+where
+    F: Fn(I, Params) -> O,
+    I: FromRequest,
+    O: IntoResponse,
+{
+    move |req, params| handler(I::from_request(req)?, params).into_response()
+}
+
+/// Converts route parameters borrowed from a matched path, as the Spin SDK's router passes
+/// them, into the owned [`Params`] handlers receive.
+pub fn params(captures: routefinder::Captures<'_, '_>) -> Params {
+    captures.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+
+    fn greet(req: http::Request<String>, params: Params) -> Result<http::Response<String>> {
+        let name = params.get("name").unwrap_or_default();
+        Ok(http::Response::new(format!("{} {name}", req.body())))
+    }
+
+    fn teapot(_req: http::Request<()>, _params: Params) -> http::StatusCode {
+        http::StatusCode::IM_A_TEAPOT
+    }
+
+    fn post(router: &Router, path: &str, body: &'static [u8]) -> Result<Response> {
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(path)
+            .body(Some(Bytes::from_static(body)))
+            .unwrap();
+        router.handle(req)
+    }
+
+    #[test]
+    fn test_handler() {
+        let mut router = Router::new();
+        router.post("/greet/:name", handler(greet));
+        router.post("/teapot", handler(teapot));
+
+        let res = post(&router, "/greet/spin", b"hello").unwrap();
+        assert_eq!(res.into_body().unwrap(), "hello spin");
+        let res = post(&router, "/teapot", b"").unwrap();
+        assert_eq!(res.status(), http::StatusCode::IM_A_TEAPOT);
+        assert!(post(&router, "/greet/spin", b"\xff").is_err());
+    }
+
+    #[test]
+    fn test_params() {
+        let mut captures = routefinder::Captures::new();
+        let name = String::from("id");
+        captures.push(routefinder::Capture::new(name.as_str(), "42"));
+        let params = params(captures);
+        drop(name);
+        assert_eq!(params.get("id"), Some("42"));
+    }
+}
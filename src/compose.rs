@@ -0,0 +1,103 @@
+//! Combinators wrapping a single handler, for reuse that does not warrant middleware.
+//!
+//! ```
+//! # use spin_sdk_router::{compose::{map_response, wrap}, Params, Rejection, Request, Response, Router};
+//! fn report(_req: Request, _params: Params) -> anyhow::Result<Response> {
+//!     Ok(http::Response::new(Some("ok".into())))
+//! }
+//!
+//! fn no_store(mut res: Response) -> anyhow::Result<Response> {
+//!     res.headers_mut()
+//!         .insert(http::header::CACHE_CONTROL, "no-store".parse()?);
+//!     Ok(res)
+//! }
+//!
+//! let mut router = Router::new();
+//! let internal = |req: &mut Request| match req.headers().contains_key("x-internal") {
+//!     true => Ok(()),
+//!     false => Err(Rejection::new(http::StatusCode::FORBIDDEN, "external request")),
+//! };
+//! router.get("/report", wrap(internal, map_response(report, no_store)));
+//! ```
+use crate::{Guard, Params, Request, Response};
+use anyhow::Result;
+
+/// Check requests with `guard` before passing them to `handler`, answering rejected requests
+/// with the guard's response.
+pub fn wrap<G, H>(guard: G, handler: H) -> impl Fn(Request, Params) -> Result<Response>
+where
+    G: Guard,
+    H: Fn(Request, Params) -> Result<Response>,
+{
+    move |mut req, params| match guard.check(&mut req) {
+        Ok(()) => handler(req, params),
+        Err(rejection) => Ok(rejection.into_response()),
+    }
+}
+
+/// Transform requests with `f` before passing them to `handler`.
+pub fn map_request<H, F>(handler: H, f: F) -> impl Fn(Request, Params) -> Result<Response>
+where
+    H: Fn(Request, Params) -> Result<Response>,
+    F: Fn(Request) -> Result<Request>,
+{
+    move |req, params| handler(f(req)?, params)
+}
+
+/// Transform the responses of `handler` with `f`.
+pub fn map_response<H, F>(handler: H, f: F) -> impl Fn(Request, Params) -> Result<Response>
+where
+    H: Fn(Request, Params) -> Result<Response>,
+    F: Fn(Response) -> Result<Response>,
+{
+    move |req, params| f(handler(req, params)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Rejection, Router};
+
+    fn echo(req: Request, _params: Params) -> Result<Response> {
+        let mut res = http::Response::new(None);
+        if let Some(user) = req.headers().get("x-user") {
+            res.headers_mut().insert("x-user", user.clone());
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn test_compose() {
+        let guard = |req: &mut Request| {
+            if req.headers().contains_key(http::header::AUTHORIZATION) {
+                Ok(())
+            } else {
+                Err(Rejection::new(http::StatusCode::UNAUTHORIZED, "anonymous"))
+            }
+        };
+        let handler = map_request(echo, |mut req| {
+            req.headers_mut().insert("x-user", "ada".parse()?);
+            Ok(req)
+        });
+        let handler = map_response(handler, |mut res| {
+            res.headers_mut().insert("x-mapped", "1".parse()?);
+            Ok(res)
+        });
+        let mut router = Router::new();
+        router.get("/", wrap(guard, handler));
+
+        let req = http::Request::builder()
+            .uri("/")
+            .header(http::header::AUTHORIZATION, "yes")
+            .body(None)
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.headers()["x-user"], "ada");
+        assert_eq!(res.headers()["x-mapped"], "1");
+
+        let req = http::Request::builder().uri("/").body(None).unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+        assert!(!res.headers().contains_key("x-mapped"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Response compression negotiated with `Accept-Encoding`, and request body decompression.
+use crate::{negotiate, Middleware, Next, Rejection, Request, Response};
+use anyhow::Result;
+use std::io::{Read, Write};
+
+/// The content types compressed by default: text formats that shrink well.
+const COMPRESSIBLE: &[&str] = &[
+    "text/",
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "application/wasm",
+    "image/svg+xml",
+];
+
+/// Middleware compressing response bodies with gzip, or with brotli when the `brotli`
+/// feature is enabled and the client prefers it.
+///
+/// Only successful responses at least [`Compression::min_size`] bytes long with a
+/// compressible `Content-Type` are compressed. Responses that already have a
+/// `Content-Encoding` or carry `Cache-Control: no-transform` are left alone.
+pub struct Compression {
+    min_size: usize,
+    content_types: Vec<String>,
+    level: u32,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression {
+            min_size: 1024,
+            content_types: COMPRESSIBLE.iter().map(|t| t.to_string()).collect(),
+            level: 6,
+        }
+    }
+}
+
+impl Compression {
+    /// Compress text, JSON, JavaScript, XML, SVG and Wasm responses of at least 1 KiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only compress bodies of at least `bytes` bytes, as compressing small bodies costs
+    /// more time than it saves.
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Only compress responses whose `Content-Type` starts with one of `content_types`,
+    /// e.g. `text/` or `application/json`.
+    pub fn content_types(mut self, content_types: &[&str]) -> Self {
+        self.content_types = content_types.iter().map(|t| t.to_string()).collect();
+        self
+    }
+
+    /// The compression level, from 0 (fastest) to 9 (smallest); 6 by default.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    fn compressible(&self, res: &Response) -> bool {
+        let headers = res.headers();
+        let content_type = headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        let no_transform = headers
+            .get_all(http::header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.to_ascii_lowercase().contains("no-transform"));
+        res.status() == http::StatusCode::OK
+            && !headers.contains_key(http::header::CONTENT_ENCODING)
+            && !no_transform
+            && res
+                .body()
+                .as_ref()
+                .is_some_and(|b| b.len() >= self.min_size)
+            && self
+                .content_types
+                .iter()
+                .any(|t| content_type.starts_with(t.as_str()))
+    }
+
+    fn compress(&self, coding: &str, body: &[u8]) -> Result<Vec<u8>> {
+        #[cfg(feature = "brotli")]
+        if coding == "br" {
+            let mut compressed = Vec::new();
+            let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, self.level, 22);
+            encoder.write_all(body)?;
+            drop(encoder);
+            return Ok(compressed);
+        }
+        debug_assert_eq!(coding, "gzip");
+        let level = flate2::Compression::new(self.level);
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+        encoder.write_all(body)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+/// The content coding the request prefers among those supported, if it accepts any.
+fn preferred_coding(req: &Request) -> Option<&'static str> {
+    let codings: &[&str] = if cfg!(feature = "brotli") {
+        &["br", "gzip"]
+    } else {
+        &["gzip"]
+    };
+    let mut best = None;
+    for coding in codings {
+        let q = negotiate::encoding_quality(req, coding);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((*coding, q));
+        }
+    }
+    best.map(|(coding, _)| coding)
+}
+
+impl Middleware for Compression {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let coding = preferred_coding(&req);
+        let mut res = next.run(req)?;
+        if !self.compressible(&res) {
+            return Ok(res);
+        }
+        res.headers_mut()
+            .append(http::header::VARY, "accept-encoding".parse()?);
+        let Some(coding) = coding else {
+            return Ok(res);
+        };
+        let compressed = self.compress(coding, res.body().as_ref().unwrap())?;
+        *res.body_mut() = Some(compressed.into());
+        let headers = res.headers_mut();
+        headers.insert(http::header::CONTENT_ENCODING, coding.parse()?);
+        headers.remove(http::header::CONTENT_LENGTH);
+        // The compressed bytes differ, so a strong validator no longer applies.
+        if let Some(etag) = headers.get(http::header::ETAG) {
+            if etag.as_bytes().starts_with(b"\"") {
+                let weak = format!("W/{}", etag.to_str()?);
+                headers.insert(http::header::ETAG, weak.parse()?);
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Middleware decompressing request bodies sent with `Content-Encoding: gzip`, or `br` when
+/// the `brotli` feature is enabled, before they reach handlers.
+///
+/// Bodies decompressing to more than [`Decompression::max_size`] bytes are rejected with 413
+/// Payload Too Large, which protects against decompression bombs. Malformed bodies are
+/// rejected with 400 Bad Request, and unsupported codings with 415 Unsupported Media Type.
+pub struct Decompression {
+    max_size: u64,
+}
+
+impl Default for Decompression {
+    fn default() -> Self {
+        Decompression { max_size: 1 << 20 }
+    }
+}
+
+impl Decompression {
+    /// Decompress request bodies up to 1 MiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reject bodies decompressing to more than `bytes` bytes.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    fn decompress(&self, coding: &str, body: &[u8]) -> Result<Vec<u8>, Rejection> {
+        let decoder: Box<dyn Read + '_> = match coding {
+            "gzip" | "x-gzip" => Box::new(flate2::read::GzDecoder::new(body)),
+            #[cfg(feature = "brotli")]
+            "br" => Box::new(brotli::Decompressor::new(body, 4096)),
+            _ => {
+                return Err(Rejection::new(
+                    http::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    format!("unsupported content coding {coding}"),
+                ))
+            }
+        };
+        // Reading one byte past the limit tells a body at the limit from a larger one.
+        let mut decoded = Vec::new();
+        decoder
+            .take(self.max_size + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|e| {
+                Rejection::new(
+                    http::StatusCode::BAD_REQUEST,
+                    format!("malformed {coding} body: {e}"),
+                )
+            })?;
+        if decoded.len() as u64 > self.max_size {
+            return Err(Rejection::new(
+                http::StatusCode::PAYLOAD_TOO_LARGE,
+                format!("body decompresses to more than {} bytes", self.max_size),
+            ));
+        }
+        Ok(decoded)
+    }
+}
+
+impl Middleware for Decompression {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        let Some(coding) = req.headers().get(http::header::CONTENT_ENCODING) else {
+            return next.run(req);
+        };
+        let coding = coding
+            .to_str()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if coding == "identity" {
+            return next.run(req);
+        }
+        let body = req.body().as_deref().unwrap_or_default();
+        match self.decompress(&coding, body) {
+            Ok(decoded) => {
+                let headers = req.headers_mut();
+                headers.remove(http::header::CONTENT_ENCODING);
+                headers.insert(http::header::CONTENT_LENGTH, decoded.len().into());
+                *req.body_mut() = Some(decoded.into());
+                next.run(req)
+            }
+            Err(rejection) => Ok(rejection.into_response()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+
+    fn router(compression: Compression) -> Router {
+        let mut router = Router::new();
+        router.get("/json", |_req, _params| {
+            Ok(http::Response::builder()
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .header(http::header::ETAG, "\"v1\"")
+                .body(Some(format!("[{}0]", "0,".repeat(1000)).into()))?)
+        });
+        router.get("/png", |_req, _params| {
+            Ok(http::Response::builder()
+                .header(http::header::CONTENT_TYPE, "image/png")
+                .body(Some(vec![0; 2000].into()))?)
+        });
+        router.layer(compression);
+        router
+    }
+
+    fn get(router: &Router, path: &str, accept_encoding: &str) -> Response {
+        let req = http::Request::builder()
+            .uri(path)
+            .header(http::header::ACCEPT_ENCODING, accept_encoding)
+            .body(None)
+            .unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_gzip() {
+        let router = router(Compression::new());
+        let res = get(&router, "/json", "gzip");
+        assert_eq!(res.headers()[http::header::CONTENT_ENCODING], "gzip");
+        assert_eq!(res.headers()[http::header::VARY], "accept-encoding");
+        assert_eq!(res.headers()[http::header::ETAG], "W/\"v1\"");
+
+        let body = res.into_body().unwrap();
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&body[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert!(decoded.starts_with("[0,0,"));
+        assert!(body.len() < decoded.len());
+    }
+
+    #[test]
+    fn test_skipped_responses() {
+        let router = router(Compression::new());
+        let res = get(&router, "/png", "gzip");
+        assert!(!res.headers().contains_key(http::header::CONTENT_ENCODING));
+        let res = get(&router, "/json", "identity");
+        assert!(!res.headers().contains_key(http::header::CONTENT_ENCODING));
+        assert_eq!(res.headers()[http::header::VARY], "accept-encoding");
+
+        let router = self::router(Compression::new().min_size(1 << 20));
+        let res = get(&router, "/json", "gzip");
+        assert!(!res.headers().contains_key(http::header::CONTENT_ENCODING));
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn test_brotli() {
+        let router = router(Compression::new());
+        let res = get(&router, "/json", "gzip, br");
+        assert_eq!(res.headers()[http::header::CONTENT_ENCODING], "br");
+        let body = res.into_body().unwrap();
+        let mut decoded = String::new();
+        brotli::Decompressor::new(&body[..], 4096)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert!(decoded.starts_with("[0,0,"));
+    }
+
+    fn post(router: &Router, encoding: &str, body: Vec<u8>) -> Response {
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri("/echo")
+            .header(http::header::CONTENT_ENCODING, encoding)
+            .body(Some(body.into()))
+            .unwrap();
+        router.handle(req).unwrap()
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_decompression() {
+        let mut router = Router::new();
+        router.post("/echo", |req, _params| {
+            assert!(!req.headers().contains_key(http::header::CONTENT_ENCODING));
+            Ok(http::Response::builder().body(req.into_body())?)
+        });
+        router.layer(Decompression::new().max_size(1000));
+
+        let res = post(&router, "gzip", gzip(b"hello"));
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.into_body().unwrap(), "hello");
+
+        let res = post(&router, "gzip", gzip(&[0; 1000]));
+        assert_eq!(res.status(), http::StatusCode::OK);
+        let res = post(&router, "gzip", gzip(&[0; 1001]));
+        assert_eq!(res.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = post(&router, "gzip", b"hello".to_vec());
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+        let res = post(&router, "compress", b"hello".to_vec());
+        assert_eq!(res.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
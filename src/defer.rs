@@ -0,0 +1,175 @@
+//! Work deferred until the response has been produced.
+use crate::diagnostic::{self, DiagnosticSink};
+use crate::{Diagnostic, Request, Response, Router};
+use anyhow::Result;
+use std::{cell::RefCell, rc::Rc};
+
+type Task = Box<dyn FnOnce() -> Result<()>>;
+
+thread_local! {
+    /// The tasks deferred by the request being handled, if any.
+    static QUEUE: RefCell<Option<Vec<Task>>> = const { RefCell::new(None) };
+}
+
+/// Schedule `task` to run once the response has been produced, for non-critical work such as
+/// refreshing a cache or sending a notification:
+///
+/// ```
+/// # use spin_sdk_router::{defer::defer, Params, Request, Response};
+/// fn create_order(req: Request, _params: Params) -> anyhow::Result<Response> {
+///     defer(|| {
+///         // Notify the warehouse without holding up the client.
+///         Ok(())
+///     });
+///     Ok(http::Response::builder().status(201).body(None)?)
+/// }
+/// ```
+///
+/// Tasks run in the order they were deferred, when [`Router::handle`] has produced the
+/// response, or when the caller of [`Router::handle_deferred`] runs them after sending it.
+/// Failing tasks are reported to the router's diagnostic sink, set with
+/// [`Router::on_diagnostic`].
+///
+/// Outside of request handling there is no response to wait for, so the task runs at once
+/// and its failure goes to the default diagnostic sink. Tasks are closures, which cannot be
+/// handed to a job queue to run in another instance; a task whose work must outlive the
+/// instance should enqueue that work itself.
+pub fn defer<F>(task: F)
+where
+    F: FnOnce() -> Result<()> + 'static,
+{
+    let task = QUEUE.with(|queue| match &mut *queue.borrow_mut() {
+        Some(tasks) => {
+            tasks.push(Box::new(task));
+            None
+        }
+        None => Some(task),
+    });
+    if let Some(task) = task {
+        report(None, task());
+    }
+}
+
+fn report(sink: Option<&DiagnosticSink>, result: Result<()>) {
+    if let Err(e) = result {
+        let message = format!("deferred task failed: {e:#}");
+        let diagnostic = Diagnostic {
+            source: "defer",
+            message: &message,
+        };
+        diagnostic::report(sink, &diagnostic);
+    }
+}
+
+/// The tasks deferred while handling a request, returned by [`Router::handle_deferred`].
+#[must_use = "deferred tasks do nothing unless run"]
+#[derive(Default)]
+pub struct Deferred {
+    tasks: Vec<Task>,
+    sink: Option<Rc<DiagnosticSink>>,
+}
+
+impl Deferred {
+    /// The number of tasks.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether no task was deferred.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Run the tasks in the order they were deferred.
+    pub fn run(self) {
+        for task in self.tasks {
+            report(self.sink.as_deref(), task());
+        }
+    }
+
+    /// Report failing tasks to `sink`, the diagnostic sink of the router they were deferred in.
+    pub(crate) fn reporting_to(mut self, sink: Option<Rc<DiagnosticSink>>) -> Self {
+        self.sink = sink;
+        self
+    }
+}
+
+/// Runs `f`, collecting the tasks it defers. Tasks deferred within a nested call, e.g. a
+/// virtual host handling the request, are left to the outermost one.
+pub(crate) fn collect<T>(f: impl FnOnce() -> T) -> (T, Deferred) {
+    let outermost = QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        let outermost = queue.is_none();
+        queue.get_or_insert_with(Vec::new);
+        outermost
+    });
+    let value = f();
+    let tasks = match outermost {
+        true => QUEUE
+            .with(|queue| queue.borrow_mut().take())
+            .unwrap_or_default(),
+        false => Vec::new(),
+    };
+    let deferred = Deferred { tasks, sink: None };
+    (value, deferred)
+}
+
+impl Router {
+    /// Dispatch a request, returning the tasks [deferred](defer) while handling it alongside
+    /// the response, so that they can run after the response has been sent:
+    ///
+    /// ```ignore
+    /// let (response, deferred) = router.handle_deferred(request)?;
+    /// response_out.set(response);
+    /// deferred.run();
+    /// ```
+    pub fn handle_deferred(&self, request: Request) -> Result<(Response, Deferred)> {
+        let (response, deferred) = collect(|| self.dispatch_all(request));
+        Ok((
+            response?,
+            deferred.reporting_to(self.diagnostic_sink.clone()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_defer() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut router = Router::new();
+        let sink_log = log.clone();
+        router.on_diagnostic(move |diagnostic| {
+            assert_eq!(diagnostic.source, "defer");
+            sink_log.borrow_mut().push("reported");
+        });
+        let handler_log = log.clone();
+        router.get("/", move |_req: Request, _params: Params| {
+            let log = handler_log.clone();
+            defer(move || {
+                log.borrow_mut().push("deferred");
+                Ok(())
+            });
+            defer(|| anyhow::bail!("failing tasks do not stop the others"));
+            handler_log.borrow_mut().push("handled");
+            Ok(http::Response::new(None))
+        });
+        let req = || http::Request::builder().uri("/").body(None).unwrap();
+
+        let (_res, deferred) = router.handle_deferred(req()).unwrap();
+        assert_eq!(deferred.len(), 2);
+        assert_eq!(*log.borrow(), ["handled"]);
+        deferred.run();
+        assert_eq!(*log.borrow(), ["handled", "deferred", "reported"]);
+
+        router.handle(req()).unwrap();
+        assert_eq!(
+            *log.borrow(),
+            ["handled", "deferred", "reported", "handled", "deferred", "reported"]
+        );
+    }
+}
@@ -0,0 +1,38 @@
+//! Problems the router works around rather than failing the request, such as a failing
+//! deferred task or a panicking handler.
+use std::fmt;
+
+/// A problem reported to the router's diagnostic sink, set with
+/// [`Router::on_diagnostic`](crate::Router::on_diagnostic).
+#[derive(Debug)]
+pub struct Diagnostic<'a> {
+    /// What reported the problem, e.g. `defer` or `memory guard`.
+    pub source: &'a str,
+    /// What happened.
+    pub message: &'a str,
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+pub(crate) type DiagnosticSink = dyn Fn(&Diagnostic<'_>);
+
+/// The default diagnostic sink, writing to the `log` crate at the warn level when the `log`
+/// feature is enabled, and otherwise to stderr where Spin captures component logs.
+pub(crate) fn log_diagnostic(diagnostic: &Diagnostic<'_>) {
+    #[cfg(feature = "log")]
+    log::warn!(target: "spin_sdk_router", "{diagnostic}");
+    #[cfg(not(feature = "log"))]
+    eprintln!("{diagnostic}");
+}
+
+/// Reports `diagnostic` to `sink`, or to the default sink if none is set.
+pub(crate) fn report(sink: Option<&DiagnosticSink>, diagnostic: &Diagnostic<'_>) {
+    match sink {
+        Some(sink) => sink(diagnostic),
+        None => log_diagnostic(diagnostic),
+    }
+}
@@ -0,0 +1,234 @@
+//! Verification and emission of body digests (`Content-MD5`, `Digest` and `Repr-Digest`).
+use crate::sfv::{self, BareItem, Item, ListEntry};
+use crate::{Guard, Middleware, Next, Rejection, Request, Response};
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+
+/// A digest algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// MD5, only accepted for compatibility with `Content-MD5` and legacy `Digest` headers.
+    Md5,
+    /// SHA-256.
+    Sha256,
+    /// SHA-512.
+    Sha512,
+}
+
+impl Algorithm {
+    /// Looks up an algorithm by its (case-insensitive) registered name.
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Some(Algorithm::Md5),
+            "sha-256" => Some(Algorithm::Sha256),
+            "sha-512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha256 => "sha-256",
+            Algorithm::Sha512 => "sha-512",
+        }
+    }
+
+    fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Algorithm::Md5 => Md5::digest(data).to_vec(),
+            Algorithm::Sha256 => Sha256::digest(data).to_vec(),
+            Algorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+}
+
+/// A guard rejecting requests with 400 Bad Request when a `Content-MD5`, `Digest` or
+/// `Repr-Digest` header does not match the request body.
+///
+/// Digests using unknown algorithms are ignored.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VerifyDigest {
+    required: bool,
+}
+
+impl VerifyDigest {
+    /// Construct a guard that verifies digests when they are present.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also reject requests that carry no digest the guard can verify.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+/// Collects the `(algorithm, expected digest)` pairs declared by the request headers.
+fn declared_digests(headers: &http::HeaderMap) -> Vec<(Algorithm, Option<Vec<u8>>)> {
+    let decode = |value: &str| STANDARD.decode(value.trim()).ok();
+    let mut digests = Vec::new();
+
+    for value in headers.get_all("content-md5") {
+        let value = value.to_str().ok();
+        digests.push((Algorithm::Md5, value.and_then(decode)));
+    }
+    for value in headers.get_all("digest") {
+        for member in value.to_str().unwrap_or_default().split(',') {
+            let Some((name, value)) = member.split_once('=') else {
+                continue;
+            };
+            if let Some(algorithm) = Algorithm::from_name(name.trim()) {
+                digests.push((algorithm, decode(value)));
+            }
+        }
+    }
+    for value in headers.get_all("repr-digest") {
+        let dictionary = value.to_str().ok().map(sfv::parse_dictionary);
+        for (name, entry) in dictionary.and_then(Result::ok).into_iter().flatten() {
+            if let Some(algorithm) = Algorithm::from_name(&name) {
+                let expected = match entry {
+                    ListEntry::Item(Item {
+                        bare_item: BareItem::ByteSequence(bytes),
+                        ..
+                    }) => Some(bytes),
+                    _ => None,
+                };
+                digests.push((algorithm, expected));
+            }
+        }
+    }
+    digests
+}
+
+impl Guard for VerifyDigest {
+    fn check(&self, req: &mut Request) -> Result<(), Rejection> {
+        let digests = declared_digests(req.headers());
+        if digests.is_empty() && self.required {
+            return Err(Rejection::new(
+                http::StatusCode::BAD_REQUEST,
+                "missing body digest",
+            ));
+        }
+
+        let body = req.body().as_deref().unwrap_or_default();
+        for (algorithm, expected) in digests {
+            if expected.as_deref() != Some(&*algorithm.digest(body)) {
+                return Err(Rejection::new(
+                    http::StatusCode::BAD_REQUEST,
+                    format!("{} digest does not match the body", algorithm.name()),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Middleware adding a `Repr-Digest` header to responses that do not already have one.
+#[derive(Clone, Copy, Debug)]
+pub struct EmitDigest {
+    algorithm: Algorithm,
+}
+
+impl EmitDigest {
+    /// Emit digests computed with the given algorithm.
+    pub fn new(algorithm: Algorithm) -> Self {
+        EmitDigest { algorithm }
+    }
+}
+
+impl Default for EmitDigest {
+    fn default() -> Self {
+        Self::new(Algorithm::Sha256)
+    }
+}
+
+impl Middleware for EmitDigest {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let mut res = next.run(req)?;
+        if !res.headers().contains_key("repr-digest") {
+            let body = res.body().as_deref().unwrap_or_default();
+            let digest = BareItem::ByteSequence(self.algorithm.digest(body));
+            let value = sfv::serialize_dictionary(&vec![(
+                self.algorithm.name().to_owned(),
+                ListEntry::Item(Item::new(digest)),
+            )]);
+            res.headers_mut().insert("repr-digest", value.parse()?);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    fn echo(req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::builder()
+            .status(200)
+            .body(req.into_body())?)
+    }
+
+    fn post(headers: &[(&str, &str)], body: &'static str) -> Request {
+        let mut builder = http::Request::builder().method("POST").uri("/echo");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Some(body.into())).unwrap()
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.post("/echo", echo);
+        router.guard(VerifyDigest::new());
+        router
+    }
+
+    // Digests of the body `hello`.
+    const MD5: &str = "XUFAKrxLKna5cZ2REBfFkg==";
+    const SHA256: &str = "LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=";
+
+    #[test]
+    fn test_verify_digest_headers() {
+        let router = router();
+        let sha256 = format!("sha-256=:{SHA256}:");
+        let legacy = format!("SHA-256={SHA256}, unknown=abc");
+        for header in [
+            ("content-md5", MD5),
+            ("digest", &legacy),
+            ("repr-digest", &sha256),
+        ] {
+            let res = router.handle(post(&[header], "hello")).unwrap();
+            assert_eq!(res.status(), http::StatusCode::OK, "{header:?}");
+
+            let res = router.handle(post(&[header], "goodbye")).unwrap();
+            assert_eq!(res.status(), http::StatusCode::BAD_REQUEST, "{header:?}");
+        }
+    }
+
+    #[test]
+    fn test_verify_digest_required() {
+        let router = router();
+        let res = router.handle(post(&[], "hello")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        let mut router = Router::new();
+        router.post("/echo", echo);
+        router.guard(VerifyDigest::new().required());
+        let res = router.handle(post(&[], "hello")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_emit_digest() {
+        let mut router = router();
+        router.layer(EmitDigest::default());
+
+        let res = router.handle(post(&[], "hello")).unwrap();
+        assert_eq!(res.headers()["repr-digest"], format!("sha-256=:{SHA256}:"));
+    }
+}
@@ -0,0 +1,113 @@
+//! Graphviz export of the routing trie.
+use crate::Router;
+use std::fmt::Write;
+
+#[derive(Default)]
+struct Node {
+    label: String,
+    children: Vec<usize>,
+    routes: Vec<String>,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Router {
+    /// Render the routing trie in Graphviz DOT format.
+    ///
+    /// Each node is a path segment; segments that capture a parameter or wildcard are drawn
+    /// as ellipses, and nodes that terminate a route list its method and handler.
+    pub fn to_dot(&self) -> String {
+        let mut nodes = vec![Node {
+            label: "/".to_owned(),
+            ..Node::default()
+        }];
+
+        for route in self.routes() {
+            let mut current = 0;
+            for segment in route.pattern().split('/').filter(|s| !s.is_empty()) {
+                let existing = nodes[current]
+                    .children
+                    .iter()
+                    .copied()
+                    .find(|&child| nodes[child].label == segment);
+                current = match existing {
+                    Some(child) => child,
+                    None => {
+                        nodes.push(Node {
+                            label: segment.to_owned(),
+                            ..Node::default()
+                        });
+                        let child = nodes.len() - 1;
+                        nodes[current].children.push(child);
+                        child
+                    }
+                };
+            }
+            let method = route.method().map_or("*", |m| m.as_str());
+            let handler = route.handler_name().unwrap_or("<closure>");
+            nodes[current].routes.push(format!("{method} {handler}"));
+        }
+
+        let mut dot = String::from("digraph routes {\n    node [shape=box];\n");
+        for (id, node) in nodes.iter().enumerate() {
+            let mut label = escape(&node.label);
+            for route in &node.routes {
+                label.push_str("\\n");
+                label.push_str(&escape(route));
+            }
+            let capture = node.label.contains([':', '*']);
+            let shape = if capture { ", shape=ellipse" } else { "" };
+            let style = if node.routes.is_empty() {
+                ""
+            } else {
+                ", style=bold"
+            };
+            writeln!(dot, "    n{id} [label=\"{label}\"{shape}{style}];").unwrap();
+        }
+        for (id, node) in nodes.iter().enumerate() {
+            for child in &node.children {
+                writeln!(dot, "    n{id} -> n{child};").unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Params, Request, Response, Router};
+    use anyhow::Result;
+
+    fn show(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::builder().status(200).body(None)?)
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut router = Router::default();
+        router.get("/users/:id", show);
+        router.delete("/users/:id", show);
+        router.get("/users", show);
+        router.all("/*", |_req, _params| Ok(http::Response::new(None)));
+
+        assert_eq!(
+            router.to_dot(),
+            concat!(
+                "digraph routes {\n",
+                "    node [shape=box];\n",
+                "    n0 [label=\"/\"];\n",
+                "    n1 [label=\"users\\nGET spin_sdk_router::dot::tests::show\", style=bold];\n",
+                "    n2 [label=\":id\\nGET spin_sdk_router::dot::tests::show",
+                "\\nDELETE spin_sdk_router::dot::tests::show\", shape=ellipse, style=bold];\n",
+                "    n3 [label=\"*\\n* <closure>\", shape=ellipse, style=bold];\n",
+                "    n0 -> n1;\n",
+                "    n0 -> n3;\n",
+                "    n1 -> n2;\n",
+                "}\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Draining the component ahead of a deploy, so that a load balancer moves traffic away.
+use crate::{Diagnostic, Params, Request, Response, Route, Router};
+use anyhow::Result;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+/// Storage for whether the component is draining.
+///
+/// Each Spin instance may only serve a single request, so for draining to hold across
+/// requests the flag must live in shared state such as a key-value store.
+pub trait DrainStore: 'static {
+    /// Whether the component is draining.
+    fn is_draining(&self) -> Result<bool>;
+
+    /// Start or stop draining.
+    fn set_draining(&self, draining: bool) -> Result<()>;
+}
+
+/// A [`DrainStore`] keeping the flag in the memory of the current instance.
+#[derive(Debug, Default)]
+pub struct MemoryDrainStore {
+    draining: Cell<bool>,
+}
+
+impl MemoryDrainStore {
+    /// Construct a store for a component that is not draining.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DrainStore for MemoryDrainStore {
+    fn is_draining(&self) -> Result<bool> {
+        Ok(self.draining.get())
+    }
+
+    fn set_draining(&self, draining: bool) -> Result<()> {
+        self.draining.set(draining);
+        Ok(())
+    }
+}
+
+/// The draining flag, shared with the handlers of the admin endpoint.
+type SharedStore = Rc<RefCell<Box<dyn DrainStore>>>;
+
+pub(crate) struct Drain {
+    store: SharedStore,
+    retry_after: u64,
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Drain {
+            store: Rc::new(RefCell::new(Box::new(MemoryDrainStore::new()))),
+            retry_after: 30,
+        }
+    }
+}
+
+/// The handler of the admin endpoint, setting the flag to `draining` if given and
+/// reporting it.
+fn drain_endpoint(
+    store: &SharedStore,
+    draining: Option<bool>,
+) -> impl Fn(Request, Params) -> Result<Response> {
+    let store = store.clone();
+    move |_req, _params| {
+        if let Some(draining) = draining {
+            store.borrow().set_draining(draining)?;
+        }
+        let body = if store.borrow().is_draining()? {
+            "draining\n"
+        } else {
+            "serving\n"
+        };
+        Ok(http::Response::builder()
+            .header(http::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Some(body.into()))?)
+    }
+}
+
+impl Route {
+    /// Keep serving the route while the component is draining, e.g. for health checks.
+    /// Routes with a [`CachePolicy`](crate::CachePolicy) keep serving as well.
+    pub fn serve_while_draining(&mut self) -> &mut Self {
+        self.serve_while_draining = true;
+        self
+    }
+}
+
+impl Router {
+    /// Keep the draining flag in `store` instead of in the memory of the instance.
+    pub fn drain_store<S: DrainStore>(&mut self, store: S) {
+        *self.drain.store.borrow_mut() = Box::new(store);
+    }
+
+    /// Ask clients to retry requests turned away while draining after `seconds`; 30 by
+    /// default.
+    pub fn drain_retry_after(&mut self, seconds: u64) {
+        self.drain.retry_after = seconds;
+    }
+
+    /// Start or stop draining. While draining, requests are answered with 503 Service
+    /// Unavailable and a `Retry-After` header, except for builtin endpoints and routes
+    /// that [serve while draining](Route::serve_while_draining).
+    pub fn set_draining(&self, draining: bool) -> Result<()> {
+        self.drain.store.borrow().set_draining(draining)
+    }
+
+    /// Whether the component is draining. A store that cannot be read is reported to the
+    /// [diagnostic sink](Router::on_diagnostic) and treated as not draining.
+    pub fn is_draining(&self) -> bool {
+        self.drain.store.borrow().is_draining().unwrap_or_else(|e| {
+            self.report(&Diagnostic {
+                source: "drain",
+                message: &format!("cannot read the draining flag: {e:#}"),
+            });
+            false
+        })
+    }
+
+    /// Serve an admin endpoint at `/__drain`: `POST` starts draining, `DELETE` stops it, and
+    /// `GET` reports whether the component is draining. Protect it with a guard.
+    pub fn serve_drain(&mut self) {
+        let store = self.drain.store.clone();
+        self.get("/__drain", drain_endpoint(&store, None))
+            .serve_while_draining();
+        self.post("/__drain", drain_endpoint(&store, Some(true)))
+            .serve_while_draining();
+        self.delete("/__drain", drain_endpoint(&store, Some(false)))
+            .serve_while_draining();
+    }
+
+    /// The response turning the request away if the component is draining and the matched
+    /// route does not keep serving.
+    pub(crate) fn drained(&self, route: Option<&Route>) -> Result<Option<Response>> {
+        if route.is_some_and(|r| r.serve_while_draining || r.cache.is_some()) {
+            return Ok(None);
+        }
+        if !self.is_draining() {
+            return Ok(None);
+        }
+        let res = http::Response::builder()
+            .status(http::StatusCode::SERVICE_UNAVAILABLE)
+            .header(http::header::RETRY_AFTER, self.drain.retry_after)
+            .body(None)?;
+        Ok(Some(res))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CachePolicy;
+
+    fn ok(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    fn send(router: &Router, method: http::Method, path: &str) -> Response {
+        let req = http::Request::builder()
+            .method(method)
+            .uri(path)
+            .body(None)
+            .unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_draining() {
+        let mut router = Router::new();
+        router.get("/work", ok);
+        router.get("/health", ok).serve_while_draining();
+        router
+            .get("/logo", ok)
+            .cache(CachePolicy::public().max_age(60));
+        router.serve_drain();
+
+        assert_eq!(send(&router, http::Method::GET, "/work").status(), 200);
+        let res = send(&router, http::Method::POST, "/__drain");
+        assert_eq!(res.into_body().unwrap(), "draining\n");
+
+        let res = send(&router, http::Method::GET, "/work");
+        assert_eq!(res.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers()[http::header::RETRY_AFTER], "30");
+        assert_eq!(send(&router, http::Method::GET, "/health").status(), 200);
+        assert_eq!(send(&router, http::Method::GET, "/logo").status(), 200);
+
+        let res = send(&router, http::Method::DELETE, "/__drain");
+        assert_eq!(res.into_body().unwrap(), "serving\n");
+        assert_eq!(send(&router, http::Method::GET, "/work").status(), 200);
+    }
+}
@@ -0,0 +1,399 @@
+//! What happens when a route is registered twice.
+use crate::{
+    Diagnostic, HandlerFn, Param, Params, Request, Response, Route, RouteError, Router, RouterError,
+};
+use anyhow::Result;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// How the router handles a route registered for the same method and pattern as an earlier
+/// route that matches every request for it.
+///
+/// Routes limited by conditions, media types, device classes or query parameters are
+/// variants rather than duplicates, wherever they are registered. Duplicates are resolved
+/// once the routes' conditions are final, when the router is built or first handles a
+/// request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Fail [`RouterBuilder::build`](crate::RouterBuilder::build) with
+    /// [`RouteError::Duplicate`](crate::RouteError). A [`Router`] reports the later route
+    /// from [`Router::check_duplicates`] and to its diagnostic sink, and neither lists nor
+    /// dispatches to it.
+    Error,
+    /// Keep dispatching to the earlier route; the later one is listed but never matched.
+    #[default]
+    FirstWins,
+    /// Replace the earlier route with the later one.
+    LastWins,
+    /// Dispatch to the earlier route's handler, then to the later one's if it returns
+    /// [`RouterError::NotFound`]. The later handler sees the extensions inserted by the router
+    /// and its guards and middleware, and those registered with [`Router::chain_extension`].
+    Chain,
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicatePolicy::Error => "error",
+            DuplicatePolicy::FirstWins => "first wins",
+            DuplicatePolicy::LastWins => "last wins",
+            DuplicatePolicy::Chain => "chain",
+        })
+    }
+}
+
+/// The routes registered twice, by index, as the policies in force resolve them.
+#[derive(Default)]
+pub(crate) struct Duplicates {
+    /// Routes never dispatched to.
+    skipped: HashSet<usize>,
+    /// Routes replaced or merged into another, which are not listed either.
+    unlisted: HashSet<usize>,
+    /// The routes tried in turn after a route returning [`RouterError::NotFound`].
+    chains: HashMap<usize, Vec<usize>>,
+    errors: Vec<RouteError>,
+    /// Whether the errors have been reported to the diagnostic sink.
+    reported: Cell<bool>,
+}
+
+impl Duplicates {
+    pub(crate) fn skips(&self, index: usize) -> bool {
+        self.skipped.contains(&index)
+    }
+
+    pub(crate) fn lists(&self, index: usize) -> bool {
+        !self.unlisted.contains(&index)
+    }
+
+    pub(crate) fn chain(&self, index: usize) -> &[usize] {
+        self.chains.get(&index).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// Copies an extension, if the request has one, from one request's extensions to another's.
+pub(crate) type CopyExtension = fn(&http::Extensions, &mut http::Extensions);
+
+fn copy_extension<T: Clone + Send + Sync + 'static>(
+    from: &http::Extensions,
+    to: &mut http::Extensions,
+) {
+    if let Some(value) = from.get::<T>() {
+        to.insert(value.clone());
+    }
+}
+
+/// The extensions inserted by the router and its guards and middleware, which the later
+/// handlers of a chain see.
+pub(crate) fn chained_extensions() -> Vec<CopyExtension> {
+    vec![
+        #[cfg(feature = "auth")]
+        copy_extension::<crate::auth::Principal>,
+        #[cfg(feature = "jwt")]
+        copy_extension::<crate::jwt::Claims>,
+        copy_extension::<crate::forwarded::ClientInfo>,
+        copy_extension::<crate::limits::BodyLimit>,
+        copy_extension::<crate::matrix::Matrix>,
+        copy_extension::<crate::metrics::PatternSlot>,
+        copy_extension::<crate::mounts::PathSplit>,
+        copy_extension::<crate::requestid::RequestId>,
+        copy_extension::<crate::services::Container>,
+        copy_extension::<crate::state::StateMap>,
+        copy_extension::<crate::tracecontext::TraceContext>,
+    ]
+}
+
+/// A copy of `params`, which cannot be cloned.
+fn copy_params(params: &Params) -> Params {
+    let mut copy = Params::default();
+    for (name, value) in params.iter() {
+        copy.push(Param::new(name.to_owned(), value.to_owned()));
+    }
+    if let Some(wildcard) = params.wildcard() {
+        copy.set_wildcard(wildcard.to_owned());
+    }
+    copy
+}
+
+/// A copy of `req` with the extensions `extensions` copies, as extensions cannot be cloned.
+fn copy(req: &Request, extensions: &[CopyExtension]) -> Request {
+    let mut copy = http::Request::new(req.body().clone());
+    *copy.method_mut() = req.method().clone();
+    *copy.uri_mut() = req.uri().clone();
+    *copy.version_mut() = req.version();
+    *copy.headers_mut() = req.headers().clone();
+    for copy_extension in extensions {
+        copy_extension(req.extensions(), copy.extensions_mut());
+    }
+    copy
+}
+
+/// Whether `later` duplicates `earlier`, which matches every request for its pattern.
+fn duplicates(earlier: &Route, later: &Route) -> bool {
+    earlier.method == later.method
+        && earlier.pattern == later.pattern
+        && earlier.choices == later.choices
+        && earlier.defaults == later.defaults
+        && !earlier.is_conditional()
+}
+
+impl Router {
+    /// Handle routes registered twice according to `policy`, for the routes registered from
+    /// now on.
+    pub fn on_duplicate(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// How routes registered twice are handled.
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    /// Fail with the routes registered twice under [`DuplicatePolicy::Error`], e.g. before
+    /// serving requests with the router.
+    pub fn check_duplicates(&self) -> Result<(), Vec<RouteError>> {
+        match &self.duplicates().errors {
+            errors if errors.is_empty() => Ok(()),
+            errors => Err(errors.clone()),
+        }
+    }
+
+    /// Reports the routes registered twice under [`DuplicatePolicy::Error`] to the diagnostic
+    /// sink, once after the last registration.
+    pub(crate) fn report_duplicates(&self) {
+        let resolved = self.duplicates();
+        if resolved.reported.replace(true) {
+            return;
+        }
+        for error in &resolved.errors {
+            self.report(&Diagnostic {
+                source: "duplicate routes",
+                message: &error.to_string(),
+            });
+        }
+    }
+
+    /// Let the later handlers of a [`DuplicatePolicy::Chain`] see the request's extension of
+    /// type `T`, e.g. one inserted by an application's own middleware.
+    pub fn chain_extension<T: Clone + Send + Sync + 'static>(&mut self) {
+        self.chained_extensions.push(copy_extension::<T>);
+    }
+
+    /// The index of the earlier route that `route` duplicates.
+    pub(crate) fn duplicate_of(&self, route: &Route) -> Option<usize> {
+        let resolved = self.duplicates();
+        self.routes
+            .iter()
+            .enumerate()
+            .position(|(index, earlier)| resolved.lists(index) && duplicates(earlier, route))
+    }
+
+    /// The routes registered twice, resolved on first use after the last registration.
+    pub(crate) fn duplicates(&self) -> &Duplicates {
+        self.duplicates.get_or_init(|| {
+            let mut resolved = Duplicates::default();
+            // The route dispatched to for each set of duplicates so far.
+            let mut heads: Vec<usize> = Vec::new();
+            for (index, route) in self.routes.iter().enumerate() {
+                if route.is_conditional() {
+                    continue;
+                }
+                let Some(head) = heads
+                    .iter_mut()
+                    .find(|head| duplicates(&self.routes[**head], route))
+                else {
+                    heads.push(index);
+                    continue;
+                };
+                match route.duplicate_policy.unwrap_or_default() {
+                    DuplicatePolicy::Error => {
+                        resolved.skipped.insert(index);
+                        resolved.unlisted.insert(index);
+                        resolved.errors.push(RouteError::Duplicate {
+                            method: route.method.clone(),
+                            pattern: route.pattern.clone(),
+                        });
+                    }
+                    DuplicatePolicy::FirstWins => {
+                        resolved.skipped.insert(index);
+                    }
+                    DuplicatePolicy::LastWins => {
+                        resolved.skipped.insert(*head);
+                        resolved.unlisted.insert(*head);
+                        *head = index;
+                    }
+                    DuplicatePolicy::Chain => {
+                        resolved.skipped.insert(index);
+                        resolved.unlisted.insert(index);
+                        resolved.chains.entry(*head).or_default().push(index);
+                    }
+                }
+            }
+            resolved
+        })
+    }
+
+    /// Calls `handler`, then the routes chained after it in turn while they return
+    /// [`RouterError::NotFound`], as [`DuplicatePolicy::Chain`] does.
+    pub(crate) fn call_chain(
+        &self,
+        handler: &HandlerFn,
+        chain: &[usize],
+        req: Request,
+        params: Params,
+    ) -> Result<Response> {
+        let extensions = &self.chained_extensions;
+        let mut retry = chain
+            .first()
+            .map(|_| (copy(&req, extensions), copy_params(&params)));
+        let mut result = handler(req, params);
+        for (i, index) in chain.iter().enumerate() {
+            let not_found =
+                matches!(&result, Err(e) if e.downcast_ref() == Some(&RouterError::NotFound));
+            let Some((req, params)) = retry.take().filter(|_| not_found) else {
+                break;
+            };
+            if i + 1 < chain.len() {
+                retry = Some((copy(&req, extensions), copy_params(&params)));
+            }
+            result = (self.routes[*index].handler)(req, params);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RouteError;
+
+    fn reply(body: &'static str) -> impl Fn(Request, Params) -> anyhow::Result<crate::Response> {
+        move |_req, _params| Ok(http::Response::new(Some(body.into())))
+    }
+
+    fn get(router: &Router, path: &str) -> bytes::Bytes {
+        let req = http::Request::get(path).body(None).unwrap();
+        router.handle(req).unwrap().into_body().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_policies() {
+        let mut router = Router::new();
+        router.get("/a", reply("first"));
+        router.get("/a", reply("second"));
+        assert_eq!(get(&router, "/a"), "first");
+
+        router.on_duplicate(DuplicatePolicy::LastWins);
+        router.get("/b", reply("first"));
+        router.get("/b", reply("second"));
+        assert_eq!(get(&router, "/b"), "second");
+        assert_eq!(router.routes().filter(|r| r.pattern() == "/b").count(), 1);
+
+        router.on_duplicate(DuplicatePolicy::Chain);
+        router.get("/c/:id", |_req, params: Params| match params.get("id") {
+            Some("1") => Ok(http::Response::new(Some("one".into()))),
+            _ => Err(RouterError::NotFound.into()),
+        });
+        router.get("/c/:id", reply("any"));
+        assert_eq!(get(&router, "/c/1"), "one");
+        assert_eq!(get(&router, "/c/2"), "any");
+
+        let mut router = Router::new();
+        router.on_duplicate(DuplicatePolicy::Error);
+        router.get("/d", reply("mobile")).when(|_req| false);
+        router.get("/d", reply("everyone"));
+        router.get("/d", reply("again"));
+        router.post("/d", reply("post"));
+        let reported = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = reported.clone();
+        router.on_diagnostic(move |diagnostic| sink.borrow_mut().push(diagnostic.to_string()));
+        let errors = router.check_duplicates().unwrap_err();
+        assert_eq!(
+            errors,
+            [RouteError::Duplicate {
+                method: Some(http::Method::GET),
+                pattern: "/d".to_owned()
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "duplicate route GET /d");
+        assert_eq!(get(&router, "/d"), "everyone");
+        assert_eq!(get(&router, "/d"), "everyone");
+        assert_eq!(
+            *reported.borrow(),
+            ["duplicate routes: duplicate route GET /d"]
+        );
+        let req = http::Request::post("/d").body(None).unwrap();
+        assert_eq!(router.handle(req).unwrap().into_body().unwrap(), "post");
+        assert_eq!(router.routes().filter(|r| r.pattern() == "/d").count(), 3);
+    }
+
+    #[derive(Clone)]
+    struct Tenant(&'static str);
+
+    #[test]
+    fn test_chain_keeps_extensions() {
+        let mut router = Router::new();
+        router.on_duplicate(DuplicatePolicy::Chain);
+        router.chain_extension::<Tenant>();
+        router.get("/e", |_req, _params| Err(RouterError::NotFound.into()));
+        router.get("/e", |req: Request, _params| {
+            let id = crate::requestid::RequestId::of(&req).unwrap_or_default();
+            let tenant = req.extensions().get::<Tenant>().map_or("", |t| t.0);
+            Ok(http::Response::new(Some(format!("{id} {tenant}").into())))
+        });
+        router.guard(|req: &mut Request| -> Result<(), crate::Rejection> {
+            req.extensions_mut().insert(Tenant("acme"));
+            Ok(())
+        });
+        router.layer(crate::requestid::SetRequestId::new().generator(|| "abc".to_owned()));
+        assert_eq!(get(&router, "/e"), "abc acme");
+    }
+
+    #[test]
+    fn test_variant_after_catch_all() {
+        let policies = [
+            DuplicatePolicy::Error,
+            DuplicatePolicy::FirstWins,
+            DuplicatePolicy::LastWins,
+            DuplicatePolicy::Chain,
+        ];
+        for policy in policies {
+            let mut router = Router::new();
+            router.on_duplicate(policy);
+            router.get("/feed", reply("everyone"));
+            router.get("/feed", reply("page two")).query("page", "2");
+            router
+                .get("/feed", reply("beta"))
+                .when(|req| req.headers().contains_key("x-beta"));
+            router
+                .get("/feed", reply("json"))
+                .produces("application/json");
+            router.get("/feed", reply("upload")).consumes("text/csv");
+            router
+                .get("/feed", reply("mobile"))
+                .device(crate::negotiate::DeviceClass::Mobile);
+
+            assert_eq!(router.check_duplicates(), Ok(()), "{policy}");
+            assert_eq!(router.routes().count(), 6, "{policy}");
+            // Every request but the one for JSON accepts only HTML.
+            let fetch = |path: &str, header: (&str, &str)| {
+                let mut req = http::Request::get(path).header(header.0, header.1);
+                if header.0 != "accept" {
+                    req = req.header("accept", "text/html");
+                }
+                let res = router.handle(req.body(None).unwrap()).unwrap();
+                res.into_body().unwrap_or_default()
+            };
+            let html = ("accept", "text/html");
+            assert_eq!(fetch("/feed", html), "everyone", "{policy}");
+            assert_eq!(fetch("/feed?page=2", html), "page two", "{policy}");
+            assert_eq!(fetch("/feed", ("x-beta", "1")), "beta", "{policy}");
+            let json = ("accept", "application/json");
+            assert_eq!(fetch("/feed", json), "json", "{policy}");
+            let csv = ("content-type", "text/csv");
+            assert_eq!(fetch("/feed", csv), "upload", "{policy}");
+            let mobile = ("sec-ch-ua-mobile", "?1");
+            assert_eq!(fetch("/feed", mobile), "mobile", "{policy}");
+        }
+    }
+}
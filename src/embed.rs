@@ -0,0 +1,116 @@
+//! Serving static assets embedded in the Wasm binary with [`include_dir`].
+use crate::files::{precompressed_variants, relative_path, status, Validators};
+use crate::fingerprint::fnv1a;
+use crate::mime::content_type_for_path;
+use crate::range::ranged_response;
+use crate::{Route, Router};
+use include_dir::Dir;
+
+impl Router {
+    /// Serve the files of an embedded directory at `pattern`, which must end in a wildcard,
+    /// just like [`Router::serve_dir`] but without a filesystem mount:
+    ///
+    /// ```ignore
+    /// static ASSETS: include_dir::Dir<'static> = include_dir::include_dir!("$CARGO_MANIFEST_DIR/static");
+    ///
+    /// router.serve_embedded("/assets/*", &ASSETS);
+    /// ```
+    ///
+    /// Responses carry a weak `ETag` derived from the file contents, and requests whose
+    /// `If-None-Match` matches it are answered with 304 Not Modified; `Range` requests are
+    /// supported as well. Embedded `.br` and `.gz` siblings are served to requests accepting
+    /// those encodings. Missing files are answered with 404 Not Found, and paths escaping the
+    /// directory and directories with 403 Forbidden.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is not a valid route pattern.
+    pub fn serve_embedded(&mut self, pattern: &str, dir: &'static Dir<'static>) -> &mut Route {
+        self.get(pattern, move |req, params| {
+            let Some(path) = relative_path(params.wildcard().unwrap_or_default()) else {
+                return status(http::StatusCode::FORBIDDEN);
+            };
+            // Entries are looked up by their path from the root of the embedded tree.
+            let path = dir.path().join(path);
+            let Some(file) = dir.get_file(&path) else {
+                return match dir.get_dir(&path) {
+                    Some(_) => status(http::StatusCode::FORBIDDEN),
+                    None => status(http::StatusCode::NOT_FOUND),
+                };
+            };
+            let variant = precompressed_variants(&req)
+                .into_iter()
+                .find_map(|(coding, ext)| {
+                    let mut compressed = path.clone().into_os_string();
+                    compressed.push(format!(".{ext}"));
+                    Some((coding, dir.get_file(compressed)?))
+                });
+            let (coding, file) = match variant {
+                Some((coding, compressed)) => (Some(coding), compressed),
+                None => (None, file),
+            };
+            let validators = Validators {
+                etag: format!("W/\"{:016x}\"", fnv1a(file.contents())),
+                last_modified: None,
+            };
+            let mut res = if validators.not_modified(&req) {
+                validators.not_modified_response()?
+            } else {
+                let mut res = http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, content_type_for_path(&path))
+                    .body(Some(file.contents().to_vec().into()))?;
+                validators.apply(&mut res)?;
+                ranged_response(&req, res)?
+            };
+            let headers = res.headers_mut();
+            headers.append(http::header::VARY, "accept-encoding".parse()?);
+            if let Some(coding) = coding {
+                headers.insert(http::header::CONTENT_ENCODING, coding.parse()?);
+            }
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Response;
+
+    static SRC: Dir<'static> = include_dir::include_dir!("$CARGO_MANIFEST_DIR/src");
+
+    fn get(router: &Router, path: &str) -> Response {
+        let req = http::Request::builder().uri(path).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_serve_embedded() {
+        let mut router = Router::new();
+        router.serve_embedded("/src/*", &SRC);
+
+        let res = get(&router, "/src/embed.rs");
+        assert_eq!(res.status(), http::StatusCode::OK);
+        let etag = res.headers()[http::header::ETAG].clone();
+        let req = http::Request::builder()
+            .uri("/src/embed.rs")
+            .header(http::header::IF_NONE_MATCH, etag)
+            .body(None)
+            .unwrap();
+        let not_modified = router.handle(req).unwrap();
+        assert_eq!(not_modified.status(), http::StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            res.into_body().unwrap(),
+            include_str!("embed.rs").as_bytes()
+        );
+        assert_eq!(
+            get(&router, "/src/missing.rs").status(),
+            http::StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get(&router, "/src/../Cargo.toml").status(),
+            http::StatusCode::FORBIDDEN
+        );
+    }
+}
@@ -0,0 +1,254 @@
+//! Errors raised while registering routes.
+use routefinder::{RouteSpec, Segment};
+use std::fmt;
+
+/// An error registering a route.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RouteError {
+    /// The route pattern could not be parsed.
+    InvalidPattern {
+        /// The pattern that was registered.
+        pattern: String,
+        /// Why the pattern is invalid.
+        reason: String,
+    },
+    /// The route duplicates an earlier one, and the router's
+    /// [duplicate policy](crate::Router::on_duplicate) is to fail.
+    Duplicate {
+        /// The method of the route, or `None` for a route for all methods.
+        method: Option<http::Method>,
+        /// The pattern of the route.
+        pattern: String,
+    },
+    /// The route is unreachable or ambiguous, as [`Router::check`](crate::Router::check)
+    /// reports.
+    Conflict(crate::RouteIssue),
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::InvalidPattern { pattern, reason } => {
+                write!(f, "invalid route pattern `{pattern}`: {reason}")
+            }
+            RouteError::Duplicate { method, pattern } => {
+                let method = method.as_ref().map_or("*", http::Method::as_str);
+                write!(f, "duplicate route {method} {pattern}")
+            }
+            RouteError::Conflict(issue) => write!(f, "conflicting route: {issue}"),
+        }
+    }
+}
+
+impl std::error::Error for RouteError {}
+
+/// An error a handler can return to have the router answer the request as if it had matched
+/// no route, through the same handlers:
+///
+/// ```
+/// # use spin_sdk_router::{Params, Request, Router, RouterError};
+/// let mut router = Router::new();
+/// router.get("/users/:id", |_req: Request, params: Params| {
+///     match params.get("id") {
+///         Some("1") => Ok(http::Response::new(Some("alice".into()))),
+///         _ => Err(RouterError::NotFound.into()),
+///     }
+/// });
+/// router.fallback(|_req, _params| {
+///     Ok(http::Response::builder().status(404).body(Some("no such page".into()))?)
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RouterError {
+    /// Answer with the [fallback](crate::Router::fallback), 404 Not Found by default.
+    NotFound,
+    /// Answer with the [405 handler](crate::Router::method_not_allowed), 405 Method Not
+    /// Allowed by default.
+    MethodNotAllowed,
+}
+
+impl fmt::Display for RouterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouterError::NotFound => f.write_str("not found"),
+            RouterError::MethodNotAllowed => f.write_str("method not allowed"),
+        }
+    }
+}
+
+impl std::error::Error for RouterError {}
+
+/// A handler failure, as reported to the hooks registered with
+/// [`Router::on_error`](crate::Router::on_error).
+#[derive(Debug)]
+pub struct ErrorEvent<'a> {
+    /// The error the handler returned.
+    pub error: &'a anyhow::Error,
+    /// The method of the request.
+    pub method: &'a http::Method,
+    /// The URI of the request.
+    pub uri: &'a http::Uri,
+    /// The headers of the request.
+    pub headers: &'a http::HeaderMap,
+    /// The pattern of the matched route, if the failing handler was a route's.
+    pub route: Option<&'a str>,
+}
+
+impl fmt::Display for ErrorEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({}) failed: {:#}",
+            self.method,
+            self.uri.path(),
+            self.route.unwrap_or("no route"),
+            self.error
+        )
+    }
+}
+
+pub(crate) type ErrorHook = dyn Fn(&ErrorEvent<'_>);
+
+/// Parses a route pattern, rejecting patterns that routefinder accepts but would panic on
+/// when matching a request.
+pub(crate) fn parse_pattern(pattern: &str) -> Result<RouteSpec, RouteError> {
+    let invalid = |reason: &str| RouteError::InvalidPattern {
+        pattern: pattern.to_owned(),
+        reason: reason.to_owned(),
+    };
+    let spec: RouteSpec = pattern.parse().map_err(|e: String| invalid(&e))?;
+    let segments = spec.segments();
+    if let Some(i) = segments.iter().position(|s| *s == Segment::Wildcard) {
+        if i != segments.len() - 1 {
+            return Err(invalid("a wildcard must be the last segment"));
+        }
+    }
+    Ok(spec)
+}
+
+/// Checks `pattern` as registering a route does, without registering it.
+pub(crate) fn check_pattern(pattern: &str) -> Result<(), RouteError> {
+    let (pattern, _) = split_choices(pattern)?;
+    let (pattern, _) = split_defaults(&pattern)?;
+    parse_pattern(&pattern).map(|_| ())
+}
+
+/// Splits the defaults off the trailing parameters of a pattern such as `/feed/:format=json`,
+/// returning the pattern without them and the `(name, default)` pairs.
+pub(crate) fn split_defaults(pattern: &str) -> Result<(String, Vec<(String, String)>), RouteError> {
+    let mut segments = Vec::new();
+    let mut defaults = Vec::new();
+    for segment in pattern.split('/') {
+        match segment.strip_prefix(':').and_then(|s| s.split_once('=')) {
+            Some((name, default)) => {
+                defaults.push((name.to_owned(), default.to_owned()));
+                segments.push(&segment[..name.len() + 1]);
+            }
+            None if !defaults.is_empty() => {
+                return Err(RouteError::InvalidPattern {
+                    pattern: pattern.to_owned(),
+                    reason: "only trailing parameters can have defaults".to_owned(),
+                });
+            }
+            None => segments.push(segment),
+        }
+    }
+    Ok((segments.join("/"), defaults))
+}
+
+/// The values a route parameter is constrained to, as `(name, choices)`.
+pub(crate) type Choices = (String, Vec<String>);
+
+/// Splits the choices off parameters constrained to a set of values, such as
+/// `/export/:format<json|xml|csv>`, returning the pattern without them and the
+/// `(name, choices)` pairs.
+pub(crate) fn split_choices(pattern: &str) -> Result<(String, Vec<Choices>), RouteError> {
+    let invalid = |reason: &str| RouteError::InvalidPattern {
+        pattern: pattern.to_owned(),
+        reason: reason.to_owned(),
+    };
+    let mut segments = Vec::new();
+    let mut choices = Vec::new();
+    for segment in pattern.split('/') {
+        let Some((name, rest)) = segment.strip_prefix(':').and_then(|s| s.split_once('<')) else {
+            segments.push(segment.to_owned());
+            continue;
+        };
+        let Some((set, rest)) = rest.split_once('>') else {
+            return Err(invalid("unclosed `<` in parameter choices"));
+        };
+        if !rest.is_empty() && !rest.starts_with('=') {
+            return Err(invalid("parameter choices must end the segment"));
+        }
+        let set: Vec<_> = set.split('|').map(str::to_owned).collect();
+        if set.iter().any(String::is_empty) {
+            return Err(invalid("parameter choices cannot be empty"));
+        }
+        if let Some(default) = rest.strip_prefix('=') {
+            if !set.iter().any(|choice| choice == default) {
+                return Err(invalid("a parameter default must be one of its choices"));
+            }
+        }
+        choices.push((name.to_owned(), set));
+        segments.push(format!(":{name}{rest}"));
+    }
+    Ok((segments.join("/"), choices))
+}
+
+// The compile-time checks of the `router!` macro, which must accept exactly the patterns
+// the router does.
+#[cfg(test)]
+#[path = "../macros/src/validate.rs"]
+mod macro_validate;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Request, Response, Router};
+
+    fn h(_req: Request, _params: Params) -> anyhow::Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    #[test]
+    fn test_macro_validation_matches_router() {
+        for pattern in [
+            "",
+            "/",
+            "users",
+            "users/:id/",
+            "//users//:id",
+            "/users/:id",
+            "/users/:",
+            "/:id/x/:id",
+            "/a b",
+            "/a?b#c",
+            "/files/*",
+            "/files/*/",
+            "/files/*/edit",
+            "/files/*.json",
+            "/files/*.",
+            "/files/*name",
+            "/:name.json",
+            "/a.:",
+            "/feed/:format=json",
+            "/feed/:format=json/:page=1",
+            "/feed/:format=json/latest",
+            "/:lang<en|fr>/docs",
+            "/:lang<en|fr>=fr",
+            "/:lang<en|fr>=de",
+            "/:lang<en|",
+            "/:lang<en||fr>",
+            "/:lang<en|fr>.json",
+        ] {
+            let registered = Router::new().try_get(pattern, h).map(|_| ());
+            let registered = registered.map_err(|e| match e {
+                RouteError::InvalidPattern { reason, .. } => reason,
+                e => e.to_string(),
+            });
+            assert_eq!(macro_validate::validate(pattern), registered, "{pattern}");
+        }
+    }
+}
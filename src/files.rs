@@ -0,0 +1,545 @@
+//! Serving static files from the filesystem.
+use crate::mime::content_type_for_path;
+use crate::range::ranged_response;
+use crate::sitemap::escape_xml;
+use crate::{negotiate, Params, Request, Response, Route, Router};
+use anyhow::Result;
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+impl Router {
+    /// Serve the files under `dir` at `pattern`, which must end in a wildcard, e.g.
+    /// `router.serve_dir("/assets/*", "static/")`.
+    ///
+    /// In a Spin component `dir` is a path in the WASI filesystem, so the directory has to be
+    /// mapped into the component with the `files` setting of the manifest. Responses carry a
+    /// `Content-Type` guessed from the file extension, a weak `ETag` derived from the size and
+    /// modification time, and `Last-Modified`; conditional requests for an unchanged file are
+    /// answered with 304 Not Modified, and `Range` requests as described for
+    /// [`ranged_response`]. When a `.br` or `.gz` sibling of a file exists and the request
+    /// accepts that encoding, the precompressed file is served with `Content-Encoding`.
+    /// Missing files are answered with 404 Not Found, and paths escaping `dir`, directories
+    /// and unreadable files with 403 Forbidden; see [`Router::serve_dir_with`] to serve
+    /// directories.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is not a valid route pattern.
+    pub fn serve_dir(&mut self, pattern: &str, dir: impl Into<PathBuf>) -> &mut Route {
+        self.serve_dir_with(pattern, ServeDir::new(dir))
+    }
+
+    /// Serve files like [`Router::serve_dir`], with the directory handling configured by
+    /// `options`, e.g. `ServeDir::new("static/").index("index.html").listing(true)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is not a valid route pattern.
+    pub fn serve_dir_with(&mut self, pattern: &str, options: ServeDir) -> &mut Route {
+        self.get(pattern, move |req, params| {
+            serve_file(&options, req, params)
+        })
+    }
+
+    /// Host a single-page app: answer GET requests that match no route and accept
+    /// `text/html` with the file at `index`, so that client-side routes load the app.
+    ///
+    /// Other unmatched requests, such as API calls, get a JSON 404 Not Found. This replaces
+    /// any fallback set with [`Router::fallback_to`].
+    pub fn spa_fallback(&mut self, index: impl Into<PathBuf>) {
+        let index = index.into();
+        self.fallback = Some(Box::new(move |req, _params| {
+            let is_get = matches!(*req.method(), http::Method::GET | http::Method::HEAD);
+            if !is_get || !accepts_html(&req) {
+                return Ok(http::Response::builder()
+                    .status(http::StatusCode::NOT_FOUND)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(Some(r#"{"error":"not found"}"#.into()))?);
+            }
+            match std::fs::read(&index) {
+                Ok(contents) => Ok(http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, content_type_for_path(&index))
+                    .body(Some(contents.into()))?),
+                Err(e) if e.kind() == ErrorKind::NotFound => status(http::StatusCode::NOT_FOUND),
+                Err(_) => status(http::StatusCode::FORBIDDEN),
+            }
+        }));
+    }
+}
+
+/// The settings of a directory served with [`Router::serve_dir_with`].
+#[derive(Clone, Debug)]
+pub struct ServeDir {
+    dir: PathBuf,
+    index: Option<String>,
+    listing: bool,
+}
+
+impl ServeDir {
+    /// Serve the files under `dir`, answering requests for directories with 403 Forbidden.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        ServeDir {
+            dir: dir.into(),
+            index: None,
+            listing: false,
+        }
+    }
+
+    /// Answer requests for a directory with the file named `index` in it, if there is one.
+    pub fn index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_owned());
+        self
+    }
+
+    /// Answer requests for a directory without an index file with a generated HTML page
+    /// listing its entries.
+    pub fn listing(mut self, listing: bool) -> Self {
+        self.listing = listing;
+        self
+    }
+}
+
+/// Whether the request's `Accept` header explicitly asks for HTML, as browser navigations
+/// do, rather than accepting it through a wildcard.
+fn accepts_html(req: &Request) -> bool {
+    let mentions_html = req
+        .headers()
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.to_ascii_lowercase().contains("text/html"));
+    mentions_html && negotiate::quality(req, "text/html") > 0.0
+}
+
+pub(crate) fn status(status: http::StatusCode) -> Result<Response> {
+    Ok(http::Response::builder().status(status).body(None)?)
+}
+
+fn serve_file(options: &ServeDir, req: Request, params: Params) -> Result<Response> {
+    let Some(path) = relative_path(params.wildcard().unwrap_or_default()) else {
+        return status(http::StatusCode::FORBIDDEN);
+    };
+    let mut path = options.dir.join(path);
+    match std::fs::metadata(&path) {
+        Ok(metadata) if metadata.is_file() => {}
+        Ok(metadata) if metadata.is_dir() => {
+            let index = options.index.as_ref().map(|index| path.join(index));
+            match index {
+                Some(index) if index.is_file() => path = index,
+                _ if options.listing => return listing(&req, &path),
+                _ => return status(http::StatusCode::FORBIDDEN),
+            }
+        }
+        Ok(_) => return status(http::StatusCode::FORBIDDEN),
+        Err(e) if e.kind() == ErrorKind::NotFound => return status(http::StatusCode::NOT_FOUND),
+        Err(_) => return status(http::StatusCode::FORBIDDEN),
+    }
+    let variant = precompressed_variants(&req)
+        .into_iter()
+        .find_map(|(coding, ext)| {
+            let mut compressed = path.clone().into_os_string();
+            compressed.push(format!(".{ext}"));
+            let compressed = PathBuf::from(compressed);
+            compressed.is_file().then_some((coding, compressed))
+        });
+    let (coding, file) = match variant {
+        Some((coding, compressed)) => (Some(coding), compressed),
+        None => (None, path.clone()),
+    };
+    let mut res = serve_variant(&req, &path, &file)?;
+    if res.status().is_success() || res.status() == http::StatusCode::NOT_MODIFIED {
+        let headers = res.headers_mut();
+        headers.append(http::header::VARY, "accept-encoding".parse()?);
+        if let Some(coding) = coding {
+            headers.insert(http::header::CONTENT_ENCODING, coding.parse()?);
+        }
+    }
+    Ok(res)
+}
+
+/// Renders an HTML page listing the entries of the directory at `path`, with links relative
+/// to the request path.
+fn listing(req: &Request, path: &Path) -> Result<Response> {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return status(http::StatusCode::FORBIDDEN);
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let is_dir = entry.file_type().ok()?.is_dir();
+            Some(if is_dir { format!("{name}/") } else { name })
+        })
+        .collect();
+    names.sort();
+
+    let base = req.uri().path().trim_end_matches('/');
+    let title = escape_xml(&format!("{base}/"));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head><title>Index of {title}</title></head>\n<body>\n\
+         <h1>Index of {title}</h1>\n<ul>\n"
+    );
+    for name in names {
+        let href = escape_xml(&format!("{base}/{}", percent_encode(&name)));
+        html.push_str(&format!(
+            "<li><a href=\"{href}\">{}</a></li>\n",
+            escape_xml(&name)
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    Ok(http::Response::builder()
+        .status(http::StatusCode::OK)
+        .header(http::header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Some(html.into()))?)
+}
+
+pub(crate) fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(b as char)
+            }
+            b => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+/// The precompressed variants the request accepts as (content coding, file extension), most
+/// preferred first.
+pub(crate) fn precompressed_variants(req: &Request) -> Vec<(&'static str, &'static str)> {
+    let mut variants: Vec<_> = [("br", "br"), ("gzip", "gz")]
+        .into_iter()
+        .map(|(coding, ext)| (negotiate::encoding_quality(req, coding), coding, ext))
+        .filter(|(q, _, _)| *q > 0.0)
+        .collect();
+    variants.sort_by(|a, b| b.0.total_cmp(&a.0));
+    variants
+        .into_iter()
+        .map(|(_, coding, ext)| (coding, ext))
+        .collect()
+}
+
+/// Serves `file`, which holds the contents of `path` in some content coding.
+fn serve_variant(req: &Request, path: &Path, file: &Path) -> Result<Response> {
+    let metadata = match std::fs::metadata(file) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return status(http::StatusCode::NOT_FOUND),
+        Err(_) => return status(http::StatusCode::FORBIDDEN),
+    };
+    let modified = metadata.modified().ok();
+    let mtime = modified
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    let validators = Validators {
+        etag: format!("W/\"{:x}-{:x}\"", metadata.len(), mtime),
+        last_modified: modified,
+    };
+    if validators.not_modified(req) {
+        return validators.not_modified_response();
+    }
+    let contents = match std::fs::read(file) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return status(http::StatusCode::NOT_FOUND),
+        Err(_) => return status(http::StatusCode::FORBIDDEN),
+    };
+    let mut res = http::Response::builder()
+        .status(http::StatusCode::OK)
+        .header(http::header::CONTENT_TYPE, content_type_for_path(path))
+        .body(Some(contents.into()))?;
+    validators.apply(&mut res)?;
+    ranged_response(req, res)
+}
+
+/// The validators of a static file, used to answer conditional requests with 304 Not
+/// Modified.
+pub(crate) struct Validators {
+    /// A weak entity tag such as `W/"1f-65a1b2c3"`.
+    pub(crate) etag: String,
+    pub(crate) last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Whether the client's cached copy is current. `If-None-Match` takes precedence over
+    /// `If-Modified-Since`, as RFC 9110 requires.
+    pub(crate) fn not_modified(&self, req: &Request) -> bool {
+        if !matches!(*req.method(), http::Method::GET | http::Method::HEAD) {
+            return false;
+        }
+        let headers = req.headers();
+        if headers.contains_key(http::header::IF_NONE_MATCH) {
+            let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_owned();
+            return headers
+                .get_all(http::header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(&self.etag));
+        }
+        let since = headers
+            .get(http::header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| httpdate::parse_http_date(v).ok());
+        match (since, self.last_modified) {
+            // HTTP dates have a resolution of one second.
+            (Some(since), Some(modified)) => {
+                httpdate::HttpDate::from(modified) <= httpdate::HttpDate::from(since)
+            }
+            _ => false,
+        }
+    }
+
+    pub(crate) fn not_modified_response(&self) -> Result<Response> {
+        let mut res = status(http::StatusCode::NOT_MODIFIED)?;
+        self.apply(&mut res)?;
+        Ok(res)
+    }
+
+    pub(crate) fn apply(&self, res: &mut Response) -> Result<()> {
+        let headers = res.headers_mut();
+        headers.insert(http::header::ETAG, self.etag.parse()?);
+        if let Some(modified) = self.last_modified {
+            let date = httpdate::fmt_http_date(modified);
+            headers.insert(http::header::LAST_MODIFIED, date.parse()?);
+        }
+        Ok(())
+    }
+}
+
+/// Converts the percent-encoded request path into a relative filesystem path, or returns
+/// `None` if the path would escape the directory it is joined onto.
+pub(crate) fn relative_path(path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(path)?;
+    let mut resolved = PathBuf::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            s if s.contains(['\\', '\0']) || s.contains(':') => return None,
+            s => resolved.push(s),
+        }
+    }
+    Some(resolved)
+}
+
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+    while let Some(b) = input.next() {
+        if b == b'%' {
+            let hex = [input.next()?, input.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(router: &Router, path: &str) -> Response {
+        let req = http::Request::builder().uri(path).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_serve_dir() {
+        let dir = std::env::temp_dir().join(format!("serve-dir-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("css")).unwrap();
+        std::fs::write(dir.join("css/site.css"), "body {}").unwrap();
+        std::fs::write(dir.join("hello world.txt"), "hi").unwrap();
+
+        let mut router = Router::new();
+        router.serve_dir("/assets/*", &dir);
+
+        let res = get(&router, "/assets/css/site.css");
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(
+            res.headers()[http::header::CONTENT_TYPE],
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(res.into_body().unwrap(), "body {}");
+
+        let res = get(&router, "/assets/hello%20world.txt");
+        assert_eq!(res.into_body().unwrap(), "hi");
+
+        assert_eq!(
+            get(&router, "/assets/missing.js").status(),
+            http::StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            get(&router, "/assets/css").status(),
+            http::StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            get(&router, "/assets/css/%2e%2e/%2e%2e/etc/passwd").status(),
+            http::StatusCode::FORBIDDEN
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_conditional_requests() {
+        let dir = std::env::temp_dir().join(format!("conditional-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "let x;").unwrap();
+
+        let mut router = Router::new();
+        router.serve_dir("/assets/*", &dir);
+        let get_with = |name: &str, value: &str| {
+            let req = http::Request::builder()
+                .uri("/assets/app.js")
+                .header(name, value)
+                .body(None)
+                .unwrap();
+            router.handle(req).unwrap()
+        };
+
+        let res = get(&router, "/assets/app.js");
+        let etag = res.headers()[http::header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let modified = res.headers()[http::header::LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert!(etag.starts_with("W/\"6-"));
+
+        let res = get_with("if-none-match", &format!("\"other\", {etag}"));
+        assert_eq!(res.status(), http::StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[http::header::ETAG], etag.as_str());
+        assert!(res.into_body().is_none());
+
+        let res = get_with("if-none-match", "\"other\"");
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        let res = get_with("if-modified-since", &modified);
+        assert_eq!(res.status(), http::StatusCode::NOT_MODIFIED);
+        let res = get_with("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        let res = get_with("range", "bytes=4-");
+        assert_eq!(res.status(), http::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.into_body().unwrap(), "x;");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_precompressed_variants() {
+        let dir = std::env::temp_dir().join(format!("precompressed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "plain").unwrap();
+        std::fs::write(dir.join("app.js.gz"), "gzipped").unwrap();
+        std::fs::write(dir.join("app.js.br"), "brotli").unwrap();
+
+        let mut router = Router::new();
+        router.serve_dir("/assets/*", &dir);
+        let get_encoded = |accept_encoding: &str| {
+            let req = http::Request::builder()
+                .uri("/assets/app.js")
+                .header(http::header::ACCEPT_ENCODING, accept_encoding)
+                .body(None)
+                .unwrap();
+            router.handle(req).unwrap()
+        };
+
+        let res = get_encoded("gzip, deflate, br");
+        assert_eq!(res.headers()[http::header::CONTENT_ENCODING], "br");
+        assert_eq!(
+            res.headers()[http::header::CONTENT_TYPE],
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(res.headers()[http::header::VARY], "accept-encoding");
+        assert_eq!(res.into_body().unwrap(), "brotli");
+
+        let res = get_encoded("br;q=0.5, gzip");
+        assert_eq!(res.into_body().unwrap(), "gzipped");
+
+        let res = get(&router, "/assets/app.js");
+        assert!(!res.headers().contains_key(http::header::CONTENT_ENCODING));
+        assert_eq!(res.into_body().unwrap(), "plain");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_directory_index_and_listing() {
+        let dir = std::env::temp_dir().join(format!("listing-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("docs/guides")).unwrap();
+        std::fs::write(dir.join("docs/a <b>.txt"), "a").unwrap();
+        std::fs::create_dir_all(dir.join("site")).unwrap();
+        std::fs::write(dir.join("site/index.html"), "<home>").unwrap();
+
+        let mut router = Router::new();
+        router.serve_dir_with(
+            "/files/*",
+            ServeDir::new(&dir).index("index.html").listing(true),
+        );
+
+        let res = get(&router, "/files/site");
+        assert_eq!(res.into_body().unwrap(), "<home>");
+
+        let res = get(&router, "/files/docs/");
+        assert_eq!(
+            res.headers()[http::header::CONTENT_TYPE],
+            "text/html; charset=utf-8"
+        );
+        let body = res.into_body().unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains(r#"<a href="/files/docs/a%20%3Cb%3E.txt">a &lt;b&gt;.txt</a>"#));
+        assert!(body.contains(r#"<a href="/files/docs/guides/">guides/</a>"#));
+
+        let mut router = Router::new();
+        router.serve_dir("/files/*", &dir);
+        assert_eq!(
+            get(&router, "/files/site").status(),
+            http::StatusCode::FORBIDDEN
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_spa_fallback() {
+        let dir = std::env::temp_dir().join(format!("spa-fallback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "<app>").unwrap();
+
+        let mut router = Router::new();
+        router.get("/api/items", |_req, _params| Ok(http::Response::new(None)));
+        router.spa_fallback(dir.join("index.html"));
+
+        let navigate = |path: &str| {
+            let req = http::Request::builder()
+                .uri(path)
+                .header(http::header::ACCEPT, "text/html,*/*;q=0.8")
+                .body(None)
+                .unwrap();
+            router.handle(req).unwrap()
+        };
+        let res = navigate("/settings/profile");
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.into_body().unwrap(), "<app>");
+
+        let res = get(&router, "/api/missing");
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+        assert_eq!(
+            res.headers()[http::header::CONTENT_TYPE],
+            "application/json"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+//! Composable filters, an alternative to registering handlers against route patterns.
+//!
+//! A filter matches part of a request and extracts values from it. Filters are combined with
+//! [`Filter::and`] and finished with [`Filter::map`], which passes the extracted values to a
+//! handler; [`Router::filter`] then registers the result as an ordinary route:
+//!
+//! ```
+//! # use spin_sdk_router::{filter::{get, param, path, Filter}, Router};
+//! fn show_user(id: u64) -> http::Response<String> {
+//!     http::Response::new(format!("user {id}"))
+//! }
+//!
+//! let mut router = Router::new();
+//! router.filter(get().and(path("users")).and(param::<u64>()).map(show_user));
+//! ```
+//!
+//! Path filters contribute segments to the route's pattern, so matching happens in the
+//! router as usual. Parameters that fail to parse answer 404 Not Found, and bodies that fail
+//! to parse 400 Bad Request.
+use crate::compat::IntoResponse;
+use crate::services::Service;
+use crate::{Params, Rejection, Request, Response, Route, Router};
+use bytes::Bytes;
+use std::{marker::PhantomData, str::FromStr};
+
+/// The route a filter matches: its method and path segments.
+#[derive(Default)]
+pub struct Spec {
+    method: Option<http::Method>,
+    segments: Vec<String>,
+}
+
+impl Spec {
+    /// The route pattern the segments form.
+    fn pattern(&self) -> String {
+        format!("/{}", self.segments.join("/"))
+    }
+}
+
+/// A part of a request that a filter matches and extracts values from.
+pub trait Filter: Sized + 'static {
+    /// The values extracted from the request, as a tuple.
+    type Extract: Tuple;
+
+    /// Adds what the filter matches to the route spec.
+    fn spec(&self, spec: &mut Spec);
+
+    /// Extracts the filter's values from a request matching its route. `param` numbers the
+    /// route parameters in the order the filters declared them.
+    fn extract(
+        &self,
+        req: &Request,
+        params: &Params,
+        param: &mut usize,
+    ) -> Result<Self::Extract, Rejection>;
+
+    /// Combine with `other`, matching both and extracting the values of both.
+    fn and<F>(self, other: F) -> And<Self, F>
+    where
+        F: Filter,
+        Self::Extract: Combine<F::Extract>,
+    {
+        And(self, other)
+    }
+
+    /// Finish the filter with a handler receiving the extracted values.
+    fn map<H>(self, handler: H) -> Map<Self, H>
+    where
+        H: Func<Self::Extract>,
+    {
+        Map {
+            filter: self,
+            handler,
+        }
+    }
+}
+
+/// A filter matching a request with the given method.
+pub fn method(method: http::Method) -> Method {
+    Method(method)
+}
+
+/// A filter matching GET requests.
+pub fn get() -> Method {
+    method(http::Method::GET)
+}
+
+/// A filter matching POST requests.
+pub fn post() -> Method {
+    method(http::Method::POST)
+}
+
+/// A filter matching PUT requests.
+pub fn put() -> Method {
+    method(http::Method::PUT)
+}
+
+/// A filter matching DELETE requests.
+pub fn delete() -> Method {
+    method(http::Method::DELETE)
+}
+
+/// A filter matching a literal path segment.
+pub fn path(segment: &str) -> Path {
+    Path(segment.trim_matches('/').to_owned())
+}
+
+/// A filter matching any path segment and extracting it parsed as `T`.
+pub fn param<T>() -> Param<T>
+where
+    T: FromStr + 'static,
+{
+    Param(PhantomData)
+}
+
+/// A filter extracting the value of a request header, answering 400 Bad Request when the
+/// header is missing.
+pub fn header(name: &'static str) -> Header {
+    Header(name)
+}
+
+/// A filter extracting the [service](crate::services) of type `T`, answering 500 Internal
+/// Server Error when it is not registered or fails to build.
+pub fn service<T>() -> ServiceOf<T>
+where
+    T: Send + Sync + 'static,
+{
+    ServiceOf(PhantomData)
+}
+
+/// A filter extracting the request body.
+pub fn body() -> Body {
+    Body
+}
+
+/// A filter extracting the request body deserialized from JSON.
+#[cfg(feature = "json")]
+pub fn json_body<T>() -> Json<T>
+where
+    T: serde::de::DeserializeOwned + 'static,
+{
+    Json(PhantomData)
+}
+
+/// See [`method`].
+pub struct Method(http::Method);
+
+impl Filter for Method {
+    type Extract = ();
+
+    fn spec(&self, spec: &mut Spec) {
+        spec.method = Some(self.0.clone());
+    }
+
+    fn extract(&self, _: &Request, _: &Params, _: &mut usize) -> Result<(), Rejection> {
+        Ok(())
+    }
+}
+
+/// See [`path`].
+pub struct Path(String);
+
+impl Filter for Path {
+    type Extract = ();
+
+    fn spec(&self, spec: &mut Spec) {
+        spec.segments.push(self.0.clone());
+    }
+
+    fn extract(&self, _: &Request, _: &Params, _: &mut usize) -> Result<(), Rejection> {
+        Ok(())
+    }
+}
+
+/// See [`param`].
+pub struct Param<T>(PhantomData<fn() -> T>);
+
+fn param_name(index: usize) -> String {
+    format!("p{index}")
+}
+
+impl<T: FromStr + 'static> Filter for Param<T> {
+    type Extract = (T,);
+
+    fn spec(&self, spec: &mut Spec) {
+        let index = spec.segments.iter().filter(|s| s.starts_with(':')).count();
+        spec.segments.push(format!(":{}", param_name(index)));
+    }
+
+    fn extract(&self, _: &Request, params: &Params, param: &mut usize) -> Result<(T,), Rejection> {
+        let name = param_name(*param);
+        *param += 1;
+        let value = params.get(&name).unwrap_or_default();
+        let parsed = value.parse().map_err(|_| {
+            Rejection::new(
+                http::StatusCode::NOT_FOUND,
+                format!("invalid path parameter {value:?}"),
+            )
+        })?;
+        Ok((parsed,))
+    }
+}
+
+/// See [`header`].
+pub struct Header(&'static str);
+
+impl Filter for Header {
+    type Extract = (String,);
+
+    fn spec(&self, _: &mut Spec) {}
+
+    fn extract(&self, req: &Request, _: &Params, _: &mut usize) -> Result<(String,), Rejection> {
+        let value = req.headers().get(self.0).and_then(|v| v.to_str().ok());
+        match value {
+            Some(value) => Ok((value.to_owned(),)),
+            None => Err(Rejection::new(
+                http::StatusCode::BAD_REQUEST,
+                format!("missing header {}", self.0),
+            )),
+        }
+    }
+}
+
+/// See [`service`].
+pub struct ServiceOf<T>(PhantomData<fn() -> T>);
+
+impl<T: Send + Sync + 'static> Filter for ServiceOf<T> {
+    type Extract = (Service<T>,);
+
+    fn spec(&self, _: &mut Spec) {}
+
+    fn extract(
+        &self,
+        req: &Request,
+        _: &Params,
+        _: &mut usize,
+    ) -> Result<(Service<T>,), Rejection> {
+        let service = Service::from_request(req).map_err(|e| {
+            Rejection::new(http::StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}"))
+        })?;
+        Ok((service,))
+    }
+}
+
+/// See [`body`].
+pub struct Body;
+
+impl Filter for Body {
+    type Extract = (Bytes,);
+
+    fn spec(&self, _: &mut Spec) {}
+
+    fn extract(&self, req: &Request, _: &Params, _: &mut usize) -> Result<(Bytes,), Rejection> {
+        Ok((req.body().clone().unwrap_or_default(),))
+    }
+}
+
+/// See [`json_body`].
+#[cfg(feature = "json")]
+pub struct Json<T>(PhantomData<fn() -> T>);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned + 'static> Filter for Json<T> {
+    type Extract = (T,);
+
+    fn spec(&self, _: &mut Spec) {}
+
+    fn extract(&self, req: &Request, _: &Params, _: &mut usize) -> Result<(T,), Rejection> {
+        let body = req.body().as_deref().unwrap_or_default();
+        let value = serde_json::from_slice(body).map_err(|e| {
+            Rejection::new(
+                http::StatusCode::BAD_REQUEST,
+                format!("invalid JSON body: {e}"),
+            )
+        })?;
+        Ok((value,))
+    }
+}
+
+/// See [`Filter::and`].
+pub struct And<A, B>(A, B);
+
+impl<A, B> Filter for And<A, B>
+where
+    A: Filter,
+    B: Filter,
+    A::Extract: Combine<B::Extract>,
+{
+    type Extract = <A::Extract as Combine<B::Extract>>::Output;
+
+    fn spec(&self, spec: &mut Spec) {
+        self.0.spec(spec);
+        self.1.spec(spec);
+    }
+
+    fn extract(
+        &self,
+        req: &Request,
+        params: &Params,
+        param: &mut usize,
+    ) -> Result<Self::Extract, Rejection> {
+        let a = self.0.extract(req, params, param)?;
+        let b = self.1.extract(req, params, param)?;
+        Ok(a.combine(b))
+    }
+}
+
+/// A filter finished with a handler, ready to be registered with [`Router::filter`].
+pub struct Map<F, H> {
+    filter: F,
+    handler: H,
+}
+
+/// A tuple of values extracted by a filter.
+pub trait Tuple {}
+
+/// Concatenation of the tuples extracted by two filters.
+pub trait Combine<T: Tuple>: Tuple {
+    /// The concatenated tuple.
+    type Output: Tuple;
+
+    /// Concatenates the tuples.
+    fn combine(self, other: T) -> Self::Output;
+}
+
+/// A handler taking the values of a tuple as its arguments.
+pub trait Func<Args: Tuple> {
+    /// The handler's return type.
+    type Output;
+
+    /// Calls the handler with the values of `args`.
+    fn call(&self, args: Args) -> Self::Output;
+}
+
+macro_rules! tuples {
+    ($($t:ident)*) => {
+        impl<$($t,)*> Tuple for ($($t,)*) {}
+
+        impl<H, R, $($t,)*> Func<($($t,)*)> for H
+        where
+            H: Fn($($t),*) -> R,
+        {
+            type Output = R;
+
+            #[allow(non_snake_case)]
+            fn call(&self, ($($t,)*): ($($t,)*)) -> R {
+                self($($t),*)
+            }
+        }
+    };
+}
+
+tuples!();
+tuples!(A);
+tuples!(A B);
+tuples!(A B C);
+tuples!(A B C D);
+tuples!(A B C D E);
+
+macro_rules! combine {
+    ($($a:ident)* ; $($b:ident)*) => {
+        impl<$($a,)* $($b,)*> Combine<($($b,)*)> for ($($a,)*) {
+            type Output = ($($a,)* $($b,)*);
+
+            #[allow(non_snake_case, clippy::unused_unit)]
+            fn combine(self, other: ($($b,)*)) -> Self::Output {
+                let ($($a,)*) = self;
+                let ($($b,)*) = other;
+                ($($a,)* $($b,)*)
+            }
+        }
+    };
+}
+
+combine!(;);
+combine!(; B1);
+combine!(; B1 B2);
+combine!(; B1 B2 B3);
+combine!(; B1 B2 B3 B4);
+combine!(; B1 B2 B3 B4 B5);
+combine!(A1;);
+combine!(A1; B1);
+combine!(A1; B1 B2);
+combine!(A1; B1 B2 B3);
+combine!(A1; B1 B2 B3 B4);
+combine!(A1 A2;);
+combine!(A1 A2; B1);
+combine!(A1 A2; B1 B2);
+combine!(A1 A2; B1 B2 B3);
+combine!(A1 A2 A3;);
+combine!(A1 A2 A3; B1);
+combine!(A1 A2 A3; B1 B2);
+combine!(A1 A2 A3 A4;);
+combine!(A1 A2 A3 A4; B1);
+combine!(A1 A2 A3 A4 A5;);
+
+impl Router {
+    /// Register a filter finished with a handler as a route. The route's method and pattern
+    /// are those of the filter's method and path filters; without a method filter it matches
+    /// every method.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path segments do not form a valid route pattern.
+    pub fn filter<F, H>(&mut self, endpoint: Map<F, H>) -> &mut Route
+    where
+        F: Filter,
+        H: Func<F::Extract> + 'static,
+        H::Output: IntoResponse,
+    {
+        let mut spec = Spec::default();
+        endpoint.filter.spec(&mut spec);
+        let pattern = spec.pattern();
+        let handler = move |req: Request, params: Params| -> anyhow::Result<Response> {
+            match endpoint.filter.extract(&req, &params, &mut 0) {
+                Ok(args) => endpoint.handler.call(args).into_response(),
+                Err(rejection) => Ok(rejection.into_response()),
+            }
+        };
+        match spec.method {
+            Some(method) => self.add(&pattern, method, handler),
+            None => self.all(&pattern, handler),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: http::Method, path: &str, body: &'static str) -> Request {
+        http::Request::builder()
+            .method(method)
+            .uri(path)
+            .header("x-user", "ada")
+            .body(Some(Bytes::from_static(body.as_bytes())))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut router = Router::new();
+        router.filter(
+            get()
+                .and(path("users"))
+                .and(param::<u64>())
+                .and(path("posts"))
+                .and(param::<String>())
+                .map(|user: u64, post: String| http::Response::new(format!("{user}/{post}"))),
+        );
+        router.filter(
+            post()
+                .and(path("/notes/"))
+                .and(header("x-user"))
+                .and(body())
+                .map(|user: String, body: Bytes| {
+                    http::Response::new(format!("{user}: {}", String::from_utf8_lossy(&body)))
+                }),
+        );
+
+        let res = router
+            .handle(request(http::Method::GET, "/users/7/posts/hello", ""))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "7/hello");
+        let res = router
+            .handle(request(http::Method::GET, "/users/seven/posts/hello", ""))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        let res = router
+            .handle(request(http::Method::POST, "/notes", "hi"))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "ada: hi");
+        let res = router
+            .handle(request(http::Method::GET, "/notes", ""))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_body() {
+        let mut router = Router::new();
+        router.filter(
+            post()
+                .and(path("echo"))
+                .and(json_body::<serde_json::Value>())
+                .map(|value: serde_json::Value| http::Response::new(value["name"].to_string())),
+        );
+
+        let res = router
+            .handle(request(http::Method::POST, "/echo", r#"{"name":"ada"}"#))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "\"ada\"");
+        let res = router
+            .handle(request(http::Method::POST, "/echo", "{"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::BAD_REQUEST);
+    }
+}
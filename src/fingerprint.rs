@@ -0,0 +1,93 @@
+//! A stable fingerprint of the routing configuration.
+use crate::Router;
+use std::fmt::Write;
+
+/// A 64-bit FNV-1a hash. Unlike the standard library's hasher its output is specified, so it
+/// stays the same across builds and Rust versions.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+impl Router {
+    /// A stable fingerprint of the route table and routing configuration, as 16 hex digits.
+    ///
+    /// It changes whenever routes, their handlers or settings, guards, builtin endpoints or
+    /// virtual hosts change, so it can be made part of cache keys to invalidate cached
+    /// responses automatically when routing changes between deployments.
+    pub fn fingerprint(&self) -> String {
+        format!("{:016x}", fnv1a(self.canonical_config().as_bytes()))
+    }
+
+    /// Describes everything that affects routing, one item per line, in a stable order.
+    fn canonical_config(&self) -> String {
+        let mut lines: Vec<String> = self
+            .routes
+            .iter()
+            .map(|route| format!("route {}", route.describe()))
+            .collect();
+        lines.extend(
+            self.guards
+                .iter()
+                .map(|g| format!("guard {}", g.guard.name())),
+        );
+        lines.extend(self.builtins.keys().map(|path| format!("builtin {path}")));
+        lines.sort();
+        // Guard and middleware order matters, so list them again in registration order.
+        let guards: Vec<_> = self.guards.iter().map(|g| g.guard.name()).collect();
+        lines.push(format!("guard order {}", guards.join(",")));
+        lines.push(format!("middleware {}", self.middleware.len()));
+        lines.push(format!("fallback {}", self.fallback.is_some()));
+        lines.push(format!("expect {:?}", self.expect_continue));
+        lines.push(format!("audit {}", self.audit_mode));
+
+        let mut hosts: Vec<_> = self.hosts.iter().collect();
+        hosts.sort_by_key(|(host, _)| host.as_str());
+        let mut config = lines.join("\n");
+        for (host, router) in hosts {
+            let _ = write!(config, "\nhost {host} {}", router.fingerprint());
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CachePolicy, Params, Request, Response, Router};
+
+    fn a(_req: Request, _params: Params) -> anyhow::Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    fn b(_req: Request, _params: Params) -> anyhow::Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let build = |second: bool| {
+            let mut router = Router::new();
+            router.get("/a", a);
+            if second {
+                router.post("/b", b);
+            }
+            router
+        };
+        let fingerprint = build(false).fingerprint();
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(build(false).fingerprint(), fingerprint);
+        assert_ne!(build(true).fingerprint(), fingerprint);
+
+        let mut router = Router::new();
+        router.get("/a", b);
+        assert_ne!(router.fingerprint(), fingerprint);
+
+        let mut router = build(false);
+        router.get("/c", a).cache(CachePolicy::no_store());
+        let cached = router.fingerprint();
+        let mut router = build(false);
+        router.get("/c", a).cache(CachePolicy::public());
+        assert_ne!(router.fingerprint(), cached);
+    }
+}
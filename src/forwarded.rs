@@ -0,0 +1,267 @@
+//! The client address and scheme of requests relayed by trusted proxies.
+use crate::{ipfilter::Cidr, ratelimit::client_addr, Middleware, Next, Request, Response};
+use anyhow::Result;
+use std::net::IpAddr;
+
+/// The client that made a request, as resolved by [`TrustedProxies`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// The address of the client, if known.
+    pub ip: Option<IpAddr>,
+    /// The scheme the client used, e.g. `https`.
+    pub scheme: String,
+    /// The host the client asked for, if known.
+    pub host: Option<String>,
+}
+
+impl ClientInfo {
+    /// The client info inserted into the request extensions by [`TrustedProxies`].
+    pub fn of(req: &Request) -> Option<&ClientInfo> {
+        req.extensions().get()
+    }
+}
+
+/// One hop of the chain of proxies a request went through.
+#[derive(Default)]
+struct Hop {
+    /// The address the hop received the request from; `None` if unknown or obfuscated.
+    client: Option<IpAddr>,
+    proto: Option<String>,
+    host: Option<String>,
+}
+
+/// Middleware resolving the [`ClientInfo`] of each request into its extensions, following
+/// the `Forwarded` header (RFC 7239), or `X-Forwarded-For`, `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` in its absence, through the proxies it trusts:
+///
+/// ```
+/// # use spin_sdk_router::{forwarded::{ClientInfo, TrustedProxies}, Params, Request, Router};
+/// let mut router = Router::new();
+/// router.layer(TrustedProxies::new().trust("10.0.0.0/8"));
+/// router.get("/", |req: Request, _params: Params| {
+///     let client = ClientInfo::of(&req).unwrap();
+///     Ok(http::Response::new(Some(client.scheme.clone().into())))
+/// });
+/// ```
+///
+/// The headers are only believed when the peer Spin reports in `spin-client-addr` is a
+/// trusted proxy. The client is then the rightmost forwarded address that is not itself a
+/// trusted proxy, so that entries a client forged at the left of the chain are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    proxies: Vec<Cidr>,
+}
+
+impl TrustedProxies {
+    /// Trust no proxy, taking the peer as the client.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust the forwarding headers of requests relayed by proxies in the block `cidr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cidr` is not a valid CIDR block.
+    pub fn trust(mut self, cidr: &str) -> Self {
+        self.proxies.push(cidr.parse().expect("invalid CIDR block"));
+        self
+    }
+
+    fn trusted(&self, addr: IpAddr) -> bool {
+        self.proxies.iter().any(|proxy| proxy.contains(addr))
+    }
+
+    /// Resolve the client that made `req`.
+    pub fn client_info(&self, req: &Request) -> ClientInfo {
+        let header = |name| {
+            let value = req.headers().get(name)?;
+            value.to_str().ok().map(str::to_owned)
+        };
+        let mut info = ClientInfo {
+            ip: client_addr(req).and_then(|addr| addr.parse().ok()),
+            scheme: req.uri().scheme_str().unwrap_or("http").to_owned(),
+            host: header(http::header::HOST.as_str()),
+        };
+        let mut hops = forwarded_hops(req);
+        while info.ip.is_some_and(|ip| self.trusted(ip)) {
+            let Some(hop) = hops.pop() else { break };
+            info.ip = hop.client;
+            if let Some(proto) = hop.proto {
+                info.scheme = proto.to_ascii_lowercase();
+            }
+            if let Some(host) = hop.host {
+                info.host = Some(host);
+            }
+        }
+        info
+    }
+}
+
+/// The hops listed in the `Forwarded` header of the request, or else in its `X-Forwarded-*`
+/// headers, from the client to the last proxy.
+fn forwarded_hops(req: &Request) -> Vec<Hop> {
+    let values = |name| {
+        let values = req.headers().get_all(name).iter();
+        let values = values.filter_map(|value| value.to_str().ok());
+        values
+            .flat_map(|value| split_unquoted(value, ','))
+            .collect::<Vec<_>>()
+    };
+    let forwarded = values("forwarded");
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .map(|element| forwarded_element(element))
+            .collect();
+    }
+    let mut hops: Vec<Hop> = values("x-forwarded-for")
+        .iter()
+        .map(|node| Hop {
+            client: parse_node(node),
+            ..Hop::default()
+        })
+        .collect();
+    // The last proxy in the chain says how, and for what host, it was reached.
+    if let Some(last) = hops.last_mut() {
+        last.proto = values("x-forwarded-proto").pop().map(str::to_owned);
+        last.host = values("x-forwarded-host").pop().map(str::to_owned);
+    }
+    hops
+}
+
+/// Splits `s` at each `separator` outside of double quotes, trimming the parts.
+fn split_unquoted(s: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    let mut escaped = false;
+    s.split(move |c: char| {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            _ => return c == separator && !quoted,
+        }
+        false
+    })
+    .map(str::trim)
+    .filter(|part| !part.is_empty())
+}
+
+/// Parses a `Forwarded` element such as `for=192.0.2.60;proto=https;host=example.com`.
+fn forwarded_element(element: &str) -> Hop {
+    let mut hop = Hop::default();
+    for pair in split_unquoted(element, ';') {
+        let Some((name, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+            None => value.to_owned(),
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "for" => hop.client = parse_node(&value),
+            "proto" => hop.proto = Some(value),
+            "host" => hop.host = Some(value),
+            _ => {}
+        }
+    }
+    hop
+}
+
+/// Parses a node such as `192.0.2.60`, `192.0.2.60:4711` or `[2001:db8::17]:4711`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    node.parse()
+        .ok()
+        .or_else(|| node.rsplit_once(':')?.0.parse().ok())
+}
+
+impl Middleware for TrustedProxies {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        let info = self.client_info(&req);
+        req.extensions_mut().insert(info);
+        next.run(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ratelimit::CLIENT_ADDR_HEADER;
+
+    fn info(proxies: &TrustedProxies, headers: &[(&str, &str)]) -> ClientInfo {
+        let mut req = http::Request::builder()
+            .uri("/")
+            .header(http::header::HOST, "internal:3000");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        proxies.client_info(&req.body(None).unwrap())
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let proxies = TrustedProxies::new().trust("10.0.0.0/8");
+        let info = info(
+            &proxies,
+            &[
+                (CLIENT_ADDR_HEADER, "10.0.0.2:5000"),
+                (
+                    "forwarded",
+                    "for=1.2.3.4, for=\"[2001:db8::17]:4711\";proto=https;host=\"example.com\", for=10.0.0.1",
+                ),
+            ],
+        );
+        assert_eq!(info.ip, Some("2001:db8::17".parse().unwrap()));
+        assert_eq!(info.scheme, "https");
+        assert_eq!(info.host.as_deref(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_x_forwarded() {
+        let proxies = TrustedProxies::new().trust("10.0.0.0/8");
+        let headers = [
+            ("x-forwarded-for", "6.6.6.6, 1.2.3.4, 10.0.0.1"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "example.com"),
+        ];
+        let trusted = info(
+            &proxies,
+            &[&headers[..], &[(CLIENT_ADDR_HEADER, "10.0.0.2:5000")]].concat(),
+        );
+        assert_eq!(trusted.ip, Some("1.2.3.4".parse().unwrap()));
+        assert_eq!(trusted.scheme, "https");
+        assert_eq!(trusted.host.as_deref(), Some("example.com"));
+
+        // Headers from an untrusted peer are ignored.
+        let untrusted = info(
+            &proxies,
+            &[&headers[..], &[(CLIENT_ADDR_HEADER, "8.8.8.8:5000")]].concat(),
+        );
+        assert_eq!(
+            untrusted,
+            ClientInfo {
+                ip: Some("8.8.8.8".parse().unwrap()),
+                scheme: "http".to_owned(),
+                host: Some("internal:3000".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_obfuscated_client() {
+        let proxies = TrustedProxies::new().trust("10.0.0.0/8");
+        let info = info(
+            &proxies,
+            &[
+                (CLIENT_ADDR_HEADER, "10.0.0.2:5000"),
+                ("forwarded", "for=_hidden"),
+            ],
+        );
+        assert_eq!(info.ip, None);
+    }
+}
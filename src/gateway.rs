@@ -0,0 +1,193 @@
+//! Exporting a manifest as the route configuration of a gateway in front of the component.
+use crate::manifest::{Entry, Manifest};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+/// How a gateway can match the paths of a route pattern.
+enum PathMatch {
+    /// The pattern is a literal path.
+    Exact(String),
+    /// A regular expression matching the whole path.
+    Regex(String),
+}
+
+impl PathMatch {
+    fn of(pattern: &str) -> Self {
+        if !pattern.contains([':', '*']) {
+            return PathMatch::Exact(pattern.to_owned());
+        }
+        let mut regex = String::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ':' => {
+                    while chars
+                        .next_if(|c| c.is_alphanumeric() || *c == '_')
+                        .is_some()
+                    {}
+                    let mut value = "[^/]+".to_owned();
+                    if chars.next_if_eq(&'<').is_some() {
+                        let choices: String = chars.by_ref().take_while(|c| *c != '>').collect();
+                        let choices: Vec<_> = choices.split('|').map(escape).collect();
+                        value = format!("(?:{})", choices.join("|"));
+                    }
+                    if chars.next_if_eq(&'=').is_some() {
+                        // A parameter with a default may be omitted along with its slash.
+                        while chars.next_if(|c| *c != '/').is_some() {}
+                        regex.pop();
+                        regex.push_str(&format!("(?:/{value})?"));
+                    } else {
+                        regex.push_str(&value);
+                    }
+                }
+                '*' => regex.push_str(".*"),
+                c => regex.push_str(&escape(&c.to_string())),
+            }
+        }
+        PathMatch::Regex(regex)
+    }
+}
+
+/// Escapes the characters of `literal` that are special in regular expressions.
+fn escape(literal: &str) -> String {
+    let mut escaped = String::new();
+    for c in literal.chars() {
+        if !(c.is_alphanumeric() || "/-_~".contains(c)) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The routes grouped by pattern in registration order, with their methods, or `None` for
+/// patterns with a route for all methods.
+fn patterns(manifest: &Manifest) -> Vec<(&str, Option<Vec<&str>>)> {
+    let mut patterns: Vec<(&str, Option<Vec<&str>>)> = Vec::new();
+    for Entry {
+        method, pattern, ..
+    } in &manifest.routes
+    {
+        let index = match patterns.iter().position(|(p, _)| p == pattern) {
+            Some(index) => index,
+            None => {
+                patterns.push((pattern, Some(Vec::new())));
+                patterns.len() - 1
+            }
+        };
+        let methods = &mut patterns[index].1;
+        match (method, methods.as_mut()) {
+            (None, _) => *methods = None,
+            (Some(method), Some(methods)) if !methods.contains(&method.as_str()) => {
+                methods.push(method);
+            }
+            _ => {}
+        }
+    }
+    patterns
+}
+
+impl Manifest {
+    /// Generate NGINX `location` blocks proxying the component's routes to `upstream`, e.g.
+    /// `http://spin`, so that a gateway can turn away requests for other paths.
+    ///
+    /// Only paths are matched: several patterns may match the same path with different
+    /// methods, so which methods are allowed is left to the component.
+    pub fn to_nginx(&self, upstream: &str) -> String {
+        let mut config = String::new();
+        let mut locations = Vec::new();
+        for (pattern, _) in patterns(self) {
+            let location = match PathMatch::of(pattern) {
+                PathMatch::Exact(path) => format!("= {path}"),
+                PathMatch::Regex(regex) => format!("~ ^{regex}$"),
+            };
+            // Patterns differing only in parameter names match the same paths.
+            if locations.contains(&location) {
+                continue;
+            }
+            locations.push(location.clone());
+            let _ = writeln!(
+                config,
+                "location {location} {{\n    proxy_pass {upstream};\n}}"
+            );
+        }
+        config
+    }
+
+    /// Generate an Envoy route configuration, in JSON, routing the component's routes to
+    /// `cluster` by path and method so that a gateway can turn away other requests.
+    pub fn to_envoy(&self, cluster: &str) -> String {
+        let routes: Vec<Value> = patterns(self)
+            .into_iter()
+            .map(|(pattern, methods)| {
+                let mut route_match = match PathMatch::of(pattern) {
+                    PathMatch::Exact(path) => json!({ "path": path }),
+                    PathMatch::Regex(regex) => json!({ "safe_regex": { "regex": regex } }),
+                };
+                if let Some(methods) = methods {
+                    let mut methods = methods;
+                    // Like the router, answer HEAD requests with GET routes.
+                    if methods.contains(&"GET") && !methods.contains(&"HEAD") {
+                        methods.push("HEAD");
+                    }
+                    route_match["headers"] = json!([{
+                        "name": ":method",
+                        "string_match": { "safe_regex": { "regex": methods.join("|") } },
+                    }]);
+                }
+                json!({ "match": route_match, "route": { "cluster": cluster } })
+            })
+            .collect();
+        let config = json!({
+            "name": cluster,
+            "virtual_hosts": [{ "name": cluster, "domains": ["*"], "routes": routes }],
+        });
+        serde_json::to_string_pretty(&config).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        Manifest::from_json(
+            r#"{ "routes": [
+                { "method": "GET", "pattern": "/users/:id" },
+                { "method": "DELETE", "pattern": "/users/:id" },
+                { "method": "PUT", "pattern": "/users/:name" },
+                { "method": "GET", "pattern": "/health" },
+                { "pattern": "/files/:name.:ext" },
+                { "method": "GET", "pattern": "/static/*" },
+                { "method": "GET", "pattern": "/feed/:format<json|rss>=json" }
+            ] }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_nginx() {
+        assert_eq!(
+            manifest().to_nginx("http://spin"),
+            "location ~ ^/users/[^/]+$ {\n    proxy_pass http://spin;\n}\n\
+             location = /health {\n    proxy_pass http://spin;\n}\n\
+             location ~ ^/files/[^/]+\\.[^/]+$ {\n    proxy_pass http://spin;\n}\n\
+             location ~ ^/static/.*$ {\n    proxy_pass http://spin;\n}\n\
+             location ~ ^/feed(?:/(?:json|rss))?$ {\n    proxy_pass http://spin;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_envoy() {
+        let config: Value = serde_json::from_str(&manifest().to_envoy("spin")).unwrap();
+        let routes = &config["virtual_hosts"][0]["routes"];
+        assert_eq!(routes[0]["match"]["safe_regex"]["regex"], "/users/[^/]+");
+        assert_eq!(
+            routes[0]["match"]["headers"][0]["string_match"]["safe_regex"]["regex"],
+            "GET|DELETE|HEAD"
+        );
+        assert_eq!(routes[2]["match"]["path"], "/health");
+        assert!(routes[3]["match"].get("headers").is_none());
+        assert_eq!(routes[4]["route"]["cluster"], "spin");
+    }
+}
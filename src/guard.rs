@@ -0,0 +1,109 @@
+//! Guards that can reject a request before it is dispatched to a handler.
+use crate::{Request, Response};
+use std::fmt;
+
+/// A guard inspects a request before dispatch and may reject it.
+///
+/// Guards are used for policies such as authorization, rate limiting or body limits. A guard
+/// may also modify the request, e.g. to attach an authenticated principal to its extensions.
+pub trait Guard: 'static {
+    /// Checks the request, returning a [`Rejection`] if it must not proceed.
+    fn check(&self, req: &mut Request) -> Result<(), Rejection>;
+
+    /// A name identifying this guard in audit events.
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl<F> Guard for F
+where
+    F: Fn(&mut Request) -> Result<(), Rejection> + 'static,
+{
+    fn check(&self, req: &mut Request) -> Result<(), Rejection> {
+        self(req)
+    }
+}
+
+/// The outcome of a failed guard check: the response to send and a reason for logging.
+pub struct Rejection {
+    response: Box<Response>,
+    reason: String,
+}
+
+impl Rejection {
+    /// Construct a rejection responding with an empty body and the given status.
+    pub fn new(status: http::StatusCode, reason: impl Into<String>) -> Self {
+        let response = http::Response::builder().status(status).body(None).unwrap();
+        Self::with_response(response, reason)
+    }
+
+    /// Construct a rejection responding with the given response.
+    pub fn with_response(response: Response, reason: impl Into<String>) -> Self {
+        Rejection {
+            response: Box::new(response),
+            reason: reason.into(),
+        }
+    }
+
+    /// The reason the request was rejected.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// The status of the response that will be sent.
+    pub fn status(&self) -> http::StatusCode {
+        self.response.status()
+    }
+
+    /// Consume the rejection, returning the response to send.
+    pub fn into_response(self) -> Response {
+        *self.response
+    }
+}
+
+impl fmt::Debug for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Rejection")
+            .field("status", &self.status())
+            .field("reason", &self.reason)
+            .finish()
+    }
+}
+
+/// Emitted when a guard running in audit mode would have rejected a request.
+#[derive(Debug)]
+pub struct AuditEvent<'a> {
+    /// The name of the guard.
+    pub guard: &'a str,
+    /// The method of the request.
+    pub method: &'a http::Method,
+    /// The path of the request.
+    pub path: &'a str,
+    /// The status the request would have been rejected with.
+    pub status: http::StatusCode,
+    /// The reason given by the guard.
+    pub reason: &'a str,
+}
+
+impl fmt::Display for AuditEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "audit: {} {} would be rejected by {} with {}: {}",
+            self.method, self.path, self.guard, self.status, self.reason
+        )
+    }
+}
+
+pub(crate) type AuditSink = dyn Fn(&AuditEvent<'_>);
+
+pub(crate) struct GuardEntry {
+    pub(crate) guard: Box<dyn Guard>,
+    pub(crate) audit: bool,
+}
+
+/// The default audit sink, writing events to stderr where Spin captures component logs.
+pub(crate) fn log_audit_event(event: &AuditEvent<'_>) {
+    eprintln!("{event}");
+}
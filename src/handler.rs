@@ -0,0 +1,108 @@
+//! Handlers implemented by types, for handlers holding configuration, clients or caches.
+use crate::{Params, Request, Response};
+use anyhow::Result;
+
+/// A request handler. Functions and closures taking a [`Request`] and [`Params`] are
+/// handlers, and so is any type implementing this trait:
+///
+/// ```
+/// # use spin_sdk_router::{handler, Handler, Params, Request, Response, Router};
+/// struct Greeter {
+///     greeting: String,
+/// }
+///
+/// impl Handler for Greeter {
+///     fn call(&self, _req: Request, params: Params) -> anyhow::Result<Response> {
+///         let name = params.get("name").unwrap_or("world");
+///         let body = format!("{}, {name}!", self.greeting);
+///         Ok(http::Response::new(Some(body.into())))
+///     }
+/// }
+///
+/// let mut router = Router::new();
+/// router.get("/hello/:name", handler(Greeter { greeting: "Hello".to_owned() }));
+/// ```
+///
+/// The registration methods take closures so that their argument types are inferred, and
+/// take other handlers through [`handler`].
+pub trait Handler: 'static {
+    /// Handles `req`, whose route captured `params`.
+    fn call(&self, req: Request, params: Params) -> Result<Response>;
+}
+
+impl<F> Handler for F
+where
+    F: Fn(Request, Params) -> Result<Response> + 'static,
+{
+    fn call(&self, req: Request, params: Params) -> Result<Response> {
+        self(req, params)
+    }
+}
+
+impl Handler for Box<dyn Handler> {
+    fn call(&self, req: Request, params: Params) -> Result<Response> {
+        (**self).call(req, params)
+    }
+}
+
+/// `handler` as the function the registration methods of [`Router`](crate::Router) take.
+pub fn handler<H: Handler>(handler: H) -> impl Fn(Request, Params) -> Result<Response> + 'static {
+    move |req, params| handler.call(req, params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+    use std::cell::Cell;
+
+    /// Counts the requests it handles.
+    #[derive(Default)]
+    struct Counter {
+        count: Cell<u32>,
+    }
+
+    impl Handler for Counter {
+        fn call(&self, _req: Request, _params: Params) -> Result<Response> {
+            self.count.set(self.count.get() + 1);
+            Ok(http::Response::new(Some(
+                self.count.get().to_string().into(),
+            )))
+        }
+    }
+
+    fn ok(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::new(Some("ok".into())))
+    }
+
+    #[test]
+    fn test_handlers() {
+        let mut router = Router::new();
+        router.get("/count", handler(Counter::default()));
+        router.get("/hello/:name", |_req, params| {
+            let body = format!("hello {}", params.get("name").unwrap_or_default());
+            Ok(http::Response::new(Some(body.into())))
+        });
+        let boxed: Vec<(&str, Box<dyn Handler>)> = vec![
+            ("/fn", Box::new(ok)),
+            ("/struct", Box::new(Counter::default())),
+        ];
+        for (path, h) in boxed {
+            router.post(path, handler(h));
+        }
+
+        let send = |method: http::Method, uri: &str| {
+            let req = http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(None)
+                .unwrap();
+            router.handle(req).unwrap().into_body().unwrap()
+        };
+        assert_eq!(send(http::Method::GET, "/count"), "1");
+        assert_eq!(send(http::Method::GET, "/count"), "2");
+        assert_eq!(send(http::Method::GET, "/hello/ada"), "hello ada");
+        assert_eq!(send(http::Method::POST, "/fn"), "ok");
+        assert_eq!(send(http::Method::POST, "/struct"), "1");
+    }
+}
@@ -0,0 +1,108 @@
+//! Runtime introspection of the registered routes.
+use crate::sitemap::escape_xml;
+use crate::{Request, Response, Route, Router};
+use anyhow::Result;
+
+impl Router {
+    /// The registered routes, in registration order, leaving out those replaced or chained
+    /// under a [`DuplicatePolicy`](crate::DuplicatePolicy).
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        let resolved = self.duplicates();
+        self.routes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| resolved.lists(*index))
+            .map(|(_, route)| route)
+    }
+
+    /// Serve an HTML page listing the routes with their handlers and summaries at
+    /// `/__routes`, for debugging.
+    pub fn serve_routes(&mut self) {
+        self.builtins.insert(
+            "/__routes",
+            Box::new(|router: &Router, _req: Request| -> Result<Response> {
+                Ok(http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, "text/html; charset=utf-8")
+                    .body(Some(router.routes_page().into()))?)
+            }),
+        );
+    }
+
+    fn routes_page(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head><title>Routes</title></head>\n<body>\n<table>\n\
+             <tr><th>Method</th><th>Pattern</th><th>Handler</th><th>Summary</th></tr>\n",
+        );
+        for route in self.routes() {
+            let method = route.method().map_or("*", http::Method::as_str);
+            let cells = [
+                method,
+                route.pattern(),
+                route.handler_name().unwrap_or("<closure>"),
+                &route.summary().unwrap_or_default(),
+            ]
+            .map(|cell| format!("<td>{}</td>", escape_xml(cell)));
+            html.push_str(&format!("<tr>{}</tr>\n", cells.concat()));
+        }
+        html.push_str(&format!(
+            "</table>\n<p>Duplicate routes: {}</p>\n</body>\n</html>\n",
+            self.duplicate_policy()
+        ));
+        html
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Params, Request, Response, Router};
+
+    fn list_users(_req: Request, _params: Params) -> anyhow::Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    #[test]
+    fn test_route_docs() {
+        let mut router = Router::new();
+        router
+            .get("/users", list_users)
+            .doc("Lists the users.\n\nResults are paginated <50 per page>.");
+        router.serve_routes();
+
+        let route = router.routes().next().unwrap();
+        assert_eq!(route.summary().as_deref(), Some("Lists the users."));
+        assert_eq!(
+            route.description(),
+            Some("Results are paginated <50 per page>.")
+        );
+
+        let req = http::Request::builder()
+            .uri("/__routes")
+            .body(None)
+            .unwrap();
+        let body = router.handle(req).unwrap().into_body().unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("<td>GET</td><td>/users</td>"));
+        assert!(body.contains("<td>Lists the users.</td>"));
+    }
+
+    #[test]
+    fn test_macro_doc_comments() {
+        let router = crate::try_router! {
+            /// Lists the users,
+            /// newest first.
+            ///
+            /// Requires a session.
+            GET "/users" => list_users,
+            POST "/users" => list_users
+        }
+        .unwrap();
+        let routes: Vec<_> = router.routes().collect();
+        assert_eq!(
+            routes[0].summary().as_deref(),
+            Some("Lists the users, newest first.")
+        );
+        assert_eq!(routes[0].description(), Some("Requires a session."));
+        assert_eq!(routes[1].docs(), None);
+    }
+}
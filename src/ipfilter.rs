@@ -0,0 +1,223 @@
+//! Allow and deny lists of client addresses.
+use crate::{forwarded::TrustedProxies, AuditEvent, Middleware, Next, Request, Response};
+use anyhow::{anyhow, Result};
+use std::{fmt, net::IpAddr, str::FromStr};
+
+/// A block of IP addresses in CIDR notation, e.g. `10.0.0.0/8` or `2001:db8::/32`. A bare
+/// address is a block of one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Whether `addr` is in the block. IPv4-mapped IPv6 addresses match IPv4 blocks.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            addr => addr,
+        };
+        match (self.addr, addr) {
+            (IpAddr::V4(block), IpAddr::V4(addr)) => {
+                prefix_eq(&block.octets(), &addr.octets(), self.prefix)
+            }
+            (IpAddr::V6(block), IpAddr::V6(addr)) => {
+                prefix_eq(&block.octets(), &addr.octets(), self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether the first `prefix` bits of `a` and `b` are equal.
+fn prefix_eq(a: &[u8], b: &[u8], prefix: u8) -> bool {
+    let bits = a.iter().zip(b).map(|(x, y)| x ^ y);
+    bits.enumerate().all(|(i, diff)| {
+        let mask_bits = usize::from(prefix).saturating_sub(i * 8).min(8);
+        let mask = !(0xffu16 >> mask_bits) as u8;
+        diff & mask == 0
+    })
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| anyhow!("invalid address in {s}"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| anyhow!("invalid prefix length in {s}"))?,
+            None => max,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Middleware admitting clients by address, answering blocked clients with 403 Forbidden:
+///
+/// ```
+/// # use spin_sdk_router::{ipfilter::IpFilter, Router};
+/// let mut router = Router::new();
+/// router.layer(
+///     IpFilter::new()
+///         .allow("10.0.0.0/8")
+///         .deny("10.1.0.0/16")
+///         .trust_forwarded_for("10.255.0.1"),
+/// );
+/// ```
+///
+/// A client matching the deny list is blocked. When the allow list is not empty, a client
+/// must also match it. Clients whose address is unknown are blocked.
+///
+/// The address is read from the `spin-client-addr` header. Behind proxies listed with
+/// [`trust_forwarded_for`](Self::trust_forwarded_for), it is read from the forwarding
+/// headers instead, as resolved by [`TrustedProxies`].
+#[derive(Clone, Debug, Default)]
+pub struct IpFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+    proxies: TrustedProxies,
+}
+
+impl IpFilter {
+    /// A filter admitting every client with a known address.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Admit clients in the block `cidr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cidr` is not a valid CIDR block.
+    pub fn allow(mut self, cidr: &str) -> Self {
+        self.allow.push(cidr.parse().expect("invalid CIDR block"));
+        self
+    }
+
+    /// Block clients in the block `cidr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cidr` is not a valid CIDR block.
+    pub fn deny(mut self, cidr: &str) -> Self {
+        self.deny.push(cidr.parse().expect("invalid CIDR block"));
+        self
+    }
+
+    /// Trust the forwarding headers of requests relayed by proxies in the block `cidr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cidr` is not a valid CIDR block.
+    pub fn trust_forwarded_for(mut self, cidr: &str) -> Self {
+        self.proxies = self.proxies.trust(cidr);
+        self
+    }
+
+    /// Whether the client at `addr` is admitted.
+    pub fn admits(&self, addr: IpAddr) -> bool {
+        let denied = self.deny.iter().any(|cidr| cidr.contains(addr));
+        let allowed = self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(addr));
+        allowed && !denied
+    }
+}
+
+impl Middleware for IpFilter {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let ip = self.proxies.client_info(&req).ip;
+        if ip.is_some_and(|addr| self.admits(addr)) {
+            return next.run(req);
+        }
+        if next.audit_mode() {
+            let client = ip.map_or_else(|| "unknown".to_owned(), |ip| ip.to_string());
+            next.audit(&AuditEvent {
+                guard: "ip filter",
+                method: req.method(),
+                path: req.uri().path(),
+                status: http::StatusCode::FORBIDDEN,
+                reason: &format!("client address {client} is not admitted"),
+            });
+            return next.run(req);
+        }
+        Ok(http::Response::builder()
+            .status(http::StatusCode::FORBIDDEN)
+            .body(None)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ratelimit::CLIENT_ADDR_HEADER, Params, Router};
+
+    #[test]
+    fn test_cidr() {
+        let block: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(block.contains("10.20.30.40".parse().unwrap()));
+        assert!(block.contains("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!block.contains("11.0.0.0".parse().unwrap()));
+        let block: Cidr = "192.168.1.128/25".parse().unwrap();
+        assert!(block.contains("192.168.1.200".parse().unwrap()));
+        assert!(!block.contains("192.168.1.127".parse().unwrap()));
+        let block: Cidr = "2001:db8::/32".parse().unwrap();
+        assert!(block.contains("2001:db8:1::1".parse().unwrap()));
+        assert!(!block.contains("2001:db9::1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("example.com".parse::<Cidr>().is_err());
+        assert_eq!("::1".parse::<Cidr>().unwrap().to_string(), "::1/128");
+    }
+
+    #[test]
+    fn test_ip_filter() {
+        let mut router = Router::new();
+        router.get("/", |_req: Request, _params: Params| {
+            Ok(http::Response::new(None))
+        });
+        router.layer(
+            IpFilter::new()
+                .allow("10.0.0.0/8")
+                .deny("10.1.0.0/16")
+                .trust_forwarded_for("192.168.0.1"),
+        );
+        let status = |addr: Option<&str>, forwarded_for: Option<&str>| {
+            let mut req = http::Request::builder().uri("/");
+            if let Some(addr) = addr {
+                req = req.header(CLIENT_ADDR_HEADER, addr);
+            }
+            if let Some(forwarded_for) = forwarded_for {
+                req = req.header("x-forwarded-for", forwarded_for);
+            }
+            router.handle(req.body(None).unwrap()).unwrap().status()
+        };
+
+        assert_eq!(status(Some("10.0.0.1:4000"), None), 200);
+        assert_eq!(status(Some("10.1.0.1:4000"), None), 403);
+        assert_eq!(status(Some("8.8.8.8:4000"), None), 403);
+        assert_eq!(status(None, None), 403);
+        // Forwarded addresses only count when relayed by a trusted proxy.
+        assert_eq!(
+            status(Some("192.168.0.1:80"), Some("8.8.8.8, 10.0.0.1")),
+            200
+        );
+        assert_eq!(status(Some("192.168.0.1:80"), Some("10.1.0.1")), 403);
+        assert_eq!(status(Some("8.8.8.8:80"), Some("10.0.0.1")), 403);
+    }
+}
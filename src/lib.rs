@@ -2,10 +2,125 @@
 #![deny(missing_docs)]
 
 use anyhow::Result;
+use guard::{AuditSink, GuardEntry};
 use routefinder::{Captures, Router as MethodRouter};
-use std::collections::HashMap;
+use std::{cell::Cell, collections::HashMap, fmt, rc::Rc};
 
-type Handler = dyn Fn(Request, Params) -> anyhow::Result<Response>;
+pub mod accesslog;
+pub mod alias;
+#[cfg(feature = "attributes")]
+pub mod attributes;
+#[cfg(feature = "auth")]
+pub mod auth;
+mod builder;
+pub mod bundle;
+mod cache;
+mod capabilities;
+mod check;
+pub mod compat;
+pub mod compose;
+#[cfg(feature = "compression")]
+mod compression;
+pub mod defer;
+mod diagnostic;
+#[cfg(feature = "digest")]
+pub mod digest;
+mod dot;
+pub mod drain;
+mod duplicate;
+#[cfg(feature = "embed")]
+mod embed;
+mod error;
+mod files;
+pub mod filter;
+mod fingerprint;
+pub mod forwarded;
+#[cfg(feature = "manifest")]
+mod gateway;
+mod guard;
+mod handler;
+#[cfg(any(feature = "jwt", feature = "webhook"))]
+mod hmac;
+mod introspect;
+pub mod ipfilter;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod limits;
+#[cfg(feature = "manifest")]
+pub mod manifest;
+pub mod matrix;
+pub mod methodoverride;
+pub mod metrics;
+mod middleware;
+pub mod mime;
+pub mod mirror;
+mod mounts;
+pub mod negotiate;
+mod normalize;
+#[cfg(feature = "openapi")]
+mod openapi;
+pub mod outbound;
+mod pathroute;
+pub mod plugin;
+mod priority;
+pub mod problem;
+mod profile;
+pub mod range;
+pub mod ratelimit;
+pub mod recover;
+pub mod redirect;
+pub mod redirectmap;
+pub mod requestid;
+mod resource;
+mod route;
+pub mod sampling;
+pub mod services;
+pub mod sfv;
+mod sitemap;
+#[cfg(feature = "tracing")]
+mod spans;
+pub mod state;
+pub mod tracecontext;
+mod version;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+pub use builder::RouterBuilder;
+pub use cache::CachePolicy;
+pub use capabilities::{Capability, Environment};
+pub use check::{RouteIssue, RouteReport};
+#[cfg(feature = "compression")]
+pub use compression::{Compression, Decompression};
+pub use diagnostic::Diagnostic;
+pub use duplicate::DuplicatePolicy;
+pub use error::{ErrorEvent, RouteError, RouterError};
+pub use files::ServeDir;
+pub use guard::{AuditEvent, Guard, Rejection};
+pub use handler::{handler, Handler};
+pub use middleware::{Middleware, Next};
+pub use mounts::{Mounts, PathSplit, COMPONENT_ROUTE_HEADER, PATH_INFO_HEADER};
+pub use normalize::{PathPolicy, TrailingSlash};
+#[cfg(feature = "openapi")]
+pub use openapi::OpenApiBinder;
+pub use pathroute::PathRoute;
+pub use priority::Priority;
+pub use profile::ColdStart;
+pub use resource::{Resource, ResourceAction};
+pub use route::Route;
+pub use version::{VersionBy, Versioned};
+
+#[doc(hidden)]
+pub use http as __http;
+#[doc(hidden)]
+pub use spin_sdk_router_macros::pattern as __pattern;
+
+// Lets `#[route]`, which names the crate by its path by default, expand within it.
+#[cfg(test)]
+extern crate self as spin_sdk_router;
+
+type HandlerFn = dyn Fn(Request, Params) -> anyhow::Result<Response>;
+type InitHook = dyn Fn() -> anyhow::Result<()>;
+type Builtin = dyn Fn(&Router, Request) -> anyhow::Result<Response>;
 
 /// The Spin SDK response type.
 pub type Response = http::Response<Option<bytes::Bytes>>;
@@ -13,11 +128,119 @@ pub type Response = http::Response<Option<bytes::Bytes>>;
 pub type Request = http::Request<Option<bytes::Bytes>>;
 /// Route parameters extracted from a URI that match a route pattern.
 pub type Params = Captures<'static, 'static>;
+/// A single named route parameter.
+pub type Param = routefinder::Capture<'static, 'static>;
 
 /// The Spin SDK HTTP router.
 pub struct Router {
-    methods_map: HashMap<http::Method, MethodRouter<Box<Handler>>>,
-    all_methods: MethodRouter<Box<Handler>>,
+    routes: Vec<Route>,
+    methods_map: HashMap<http::Method, MethodRouter<usize>>,
+    all_methods: MethodRouter<usize>,
+    builtins: HashMap<&'static str, Box<Builtin>>,
+    guards: Vec<GuardEntry>,
+    audit_mode: bool,
+    audit_sink: Option<Box<AuditSink>>,
+    diagnostic_sink: Option<Rc<diagnostic::DiagnosticSink>>,
+    init_hooks: Vec<(Box<InitHook>, Cell<bool>)>,
+    error_hooks: Vec<Box<error::ErrorHook>>,
+    expect_continue: ExpectContinue,
+    middleware: Vec<Box<dyn Middleware>>,
+    fallback: Option<Box<HandlerFn>>,
+    not_allowed: Option<Box<HandlerFn>>,
+    hosts: HashMap<String, Router>,
+    profiler: profile::Profiler,
+    drain: drain::Drain,
+    path_policies: normalize::PathPolicies,
+    problem_details: bool,
+    duplicate_policy: DuplicatePolicy,
+    duplicates: std::cell::OnceCell<duplicate::Duplicates>,
+    chained_extensions: Vec<duplicate::CopyExtension>,
+    states: state::States,
+    services: std::sync::Arc<services::Services>,
+    aliases: alias::Aliases,
+    bundles: Vec<bundle::BundleInfo>,
+    plugins: Vec<&'static str>,
+}
+
+/// How requests carrying an `Expect: 100-continue` header are handled.
+///
+/// Spin components receive the whole request body up front and cannot send an interim
+/// `100 Continue` response, so clients waiting for one may stall.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExpectContinue {
+    /// Respond with 417 Expectation Failed so the client retries without the expectation.
+    #[default]
+    Reject,
+    /// Remove the header and dispatch the request as usual.
+    Ignore,
+}
+
+impl Router {
+    /// Returns `(method, pattern, handler name)` for every registered route, with method
+    /// specific routes first (sorted by method) followed by routes registered for all methods.
+    fn route_table(&self) -> Vec<(String, String, Option<&'static str>)> {
+        let listed = |index: &usize| self.duplicates().lists(*index);
+        let mut methods: Vec<_> = self.methods_map.iter().collect();
+        methods.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+        let method_routes = methods.into_iter().flat_map(|(method, router)| {
+            router
+                .iter()
+                .filter(move |(_, index)| listed(index))
+                .map(move |(spec, index)| {
+                    let name = self.routes[*index].handler_name();
+                    (method.to_string(), spec.to_string(), name)
+                })
+        });
+        let all_routes = self
+            .all_methods
+            .iter()
+            .filter(|(_, index)| listed(index))
+            .map(|(spec, index)| {
+                let name = self.routes[*index].handler_name();
+                ("*".to_owned(), spec.to_string(), name)
+            });
+
+        method_routes.chain(all_routes).collect()
+    }
+}
+
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let routes: Vec<_> = self
+            .route_table()
+            .into_iter()
+            .map(|(method, pattern, name)| {
+                format!("{method} {pattern} -> {}", name.unwrap_or("<closure>"))
+            })
+            .collect();
+        let mut debug = f.debug_struct("Router");
+        debug.field("routes", &routes);
+        if !self.hosts.is_empty() {
+            let mut hosts: Vec<_> = self.hosts.iter().collect();
+            hosts.sort_by_key(|(host, _)| host.as_str());
+            debug.field("hosts", &hosts);
+        }
+        debug.field("fingerprint", &self.fingerprint());
+        debug.finish()
+    }
+}
+
+impl fmt::Display for Router {
+    /// Renders the route table with one aligned `METHOD PATTERN HANDLER` row per route.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = self.route_table();
+        let method_width = table.iter().map(|(m, _, _)| m.len()).max().unwrap_or(0);
+        let pattern_width = table.iter().map(|(_, p, _)| p.len()).max().unwrap_or(0);
+        for (method, pattern, name) in table {
+            writeln!(
+                f,
+                "{method:<method_width$}  {pattern:<pattern_width$}  {}",
+                name.unwrap_or("<closure>")
+            )?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for Router {
@@ -28,89 +251,515 @@ impl Default for Router {
 
 struct RouteMatch<'a> {
     params: Captures<'static, 'static>,
-    handler: &'a Handler,
+    handler: &'a HandlerFn,
+    route: Option<&'a Route>,
+    /// The duplicate routes tried after the handler, under [`DuplicatePolicy::Chain`].
+    chain: &'a [usize],
+}
+
+impl<'a> RouteMatch<'a> {
+    fn status(handler: &'a HandlerFn) -> Self {
+        RouteMatch {
+            params: Captures::default(),
+            handler,
+            route: None,
+            chain: &[],
+        }
+    }
 }
 
 impl Router {
-    /// Dispatches a request to the appropriate handler along with the URI parameters.
+    /// Dispatches a request to the appropriate handler along with the URI parameters, then
+    /// runs the tasks [deferred](defer::defer) while handling it.
     pub fn handle(&self, request: Request) -> Result<Response> {
+        let (response, deferred) = defer::collect(|| self.dispatch_all(request));
+        deferred.reporting_to(self.diagnostic_sink.clone()).run();
+        response
+    }
+
+    /// Dispatches a request through the middleware to the router.
+    fn dispatch_all(&self, request: Request) -> Result<Response> {
+        let dispatch = |request| {
+            self.profiler.dispatch(|| {
+                let dispatch = |request| self.problems(self.dispatch(request));
+                Next::new(self, &self.middleware, &dispatch).run(request)
+            })
+        };
+        #[cfg(feature = "tracing")]
+        return spans::instrument(request, dispatch);
+        #[cfg(not(feature = "tracing"))]
+        dispatch(request)
+    }
+
+    fn dispatch(&self, mut request: Request) -> Result<Response> {
+        if !self.run_init_hooks() {
+            return service_unavailable(request, Params::default());
+        }
+        self.report_duplicates();
+        if let Some(expect) = request.headers().get(http::header::EXPECT) {
+            let is_continue = expect.as_bytes().eq_ignore_ascii_case(b"100-continue");
+            if !is_continue || self.expect_continue == ExpectContinue::Reject {
+                return expectation_failed(request, Params::default());
+            }
+            request.headers_mut().remove(http::header::EXPECT);
+        }
+        if let Some(rejection) = self.check_guards(&mut request) {
+            return Ok(rejection.into_response());
+        }
+        if let Some(host) = self.virtual_host(&request) {
+            return host.handle(request);
+        }
+        if let Some(builtin) = self.builtin(&request) {
+            return builtin(self, request);
+        }
+        if let Some(response) = self.resolve_alias(&mut request)? {
+            return Ok(response);
+        }
+        self.fold_path_case(&mut request)?;
         let method = request.method().to_owned();
-        let path = request.uri().path().to_owned();
-        let RouteMatch { params, handler } = self.find(&path, method);
-        handler(request, params)
+        let RouteMatch {
+            params,
+            handler,
+            route,
+            chain,
+        } = self.check_trailing_slash(&request, self.find(&request, method));
+        if route.is_some_and(|route| route.body_mode == limits::BodyMode::Ignored) {
+            *request.body_mut() = None;
+            request.headers_mut().remove(http::header::CONTENT_LENGTH);
+        }
+        let body_limit = route
+            .and_then(|route| route.max_body_size)
+            .or_else(|| request.extensions().get::<limits::BodyLimit>().map(|l| l.0));
+        if let Some(max) = body_limit.filter(|max| limits::body_too_large(&request, *max)) {
+            if !self.audit_mode {
+                return payload_too_large(request, params);
+            }
+            self.audit(&AuditEvent {
+                guard: "body limit",
+                method: request.method(),
+                path: request.uri().path(),
+                status: http::StatusCode::PAYLOAD_TOO_LARGE,
+                reason: &format!("the body is over the limit of {max} bytes"),
+            });
+        }
+        if let Some(response) = self.drained(route)? {
+            return Ok(response);
+        }
+        if let Some((route, slot)) = route.zip(request.extensions().get::<metrics::PatternSlot>()) {
+            slot.set(&route.pattern);
+        }
+        if let Some(remaining) = params.wildcard().filter(|_| route.is_some()) {
+            mounts::consume(&mut request, remaining);
+        }
+        self.provide_state(&mut request);
+        self.provide_services(&mut request);
+        // A copy of the request, for the handlers that handle errors.
+        let handles_errors =
+            !self.error_hooks.is_empty() || self.fallback.is_some() || self.not_allowed.is_some();
+        let copy = handles_errors.then(|| {
+            let mut copy = http::Request::new(request.body().clone());
+            *copy.method_mut() = request.method().clone();
+            *copy.uri_mut() = request.uri().clone();
+            *copy.version_mut() = request.version();
+            *copy.headers_mut() = request.headers().clone();
+            copy
+        });
+        let mut response = match self.call_chain(handler, chain, request, params) {
+            Ok(response) => response,
+            Err(error) => {
+                let copy = copy.unwrap_or_else(|| http::Request::new(None));
+                match error.downcast_ref::<RouterError>() {
+                    Some(RouterError::NotFound) => {
+                        let fallback = self.fallback.as_deref().unwrap_or(&not_found);
+                        return fallback(copy, Params::default());
+                    }
+                    Some(RouterError::MethodNotAllowed) => {
+                        let handler = self.not_allowed.as_deref().unwrap_or(&method_not_allowed);
+                        return handler(copy, Params::default());
+                    }
+                    None => {}
+                }
+                if let Some(problem) = error.downcast_ref::<problem::Problem>() {
+                    return compat::IntoResponse::into_response(problem.clone());
+                }
+                let event = ErrorEvent {
+                    error: &error,
+                    method: copy.method(),
+                    uri: copy.uri(),
+                    headers: copy.headers(),
+                    route: route.map(|route| route.pattern.as_str()),
+                };
+                for hook in &self.error_hooks {
+                    hook(&event);
+                }
+                return Err(error);
+            }
+        };
+        if let Some(route) = route {
+            self.decorate(route, &mut response)?;
+        }
+        Ok(response)
+    }
+
+    /// Adds the headers implied by the route's settings to its response.
+    fn decorate(&self, route: &Route, response: &mut Response) -> Result<()> {
+        response
+            .extensions_mut()
+            .insert(metrics::MatchedPattern(route.pattern.clone()));
+        let cacheable =
+            response.status().is_success() || response.status() == http::StatusCode::NOT_MODIFIED;
+        let headers = response.headers_mut();
+        if route.negotiates() {
+            headers.append(http::header::VARY, http::HeaderValue::from_static("accept"));
+        }
+        if self.has_device_variants(&route.pattern) {
+            let value = http::HeaderValue::from_static("sec-ch-ua-mobile, user-agent");
+            headers.append(http::header::VARY, value);
+        }
+        if let Some(policy) = &route.cache {
+            if cacheable && !headers.contains_key(http::header::CACHE_CONTROL) {
+                headers.insert(http::header::CACHE_CONTROL, policy.to_string().parse()?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether some route for `pattern` is limited to a class of device.
+    fn has_device_variants(&self, pattern: &str) -> bool {
+        self.routes
+            .iter()
+            .any(|r| r.pattern == pattern && r.device_class().is_some())
+    }
+
+    /// Runs the instance init hooks that have not yet succeeded, returning whether all of them
+    /// have now completed.
+    fn run_init_hooks(&self) -> bool {
+        for (hook, done) in &self.init_hooks {
+            if done.get() {
+                continue;
+            }
+            if let Err(e) = self.profiler.init_hook(hook) {
+                self.report(&Diagnostic {
+                    source: "instance init",
+                    message: &format!("instance init failed: {e:#}"),
+                });
+                return false;
+            }
+            done.set(true);
+        }
+        true
+    }
+
+    /// Runs the guards in registration order, returning the first enforced rejection.
+    fn check_guards(&self, request: &mut Request) -> Option<Rejection> {
+        for entry in &self.guards {
+            let rejection = match entry.guard.check(request) {
+                Ok(()) => continue,
+                Err(rejection) => rejection,
+            };
+            if !(entry.audit || self.audit_mode) {
+                return Some(rejection);
+            }
+            self.audit(&AuditEvent {
+                guard: entry.guard.name(),
+                method: request.method(),
+                path: request.uri().path(),
+                status: rejection.status(),
+                reason: rejection.reason(),
+            });
+        }
+        None
+    }
+
+    /// Reports a problem that did not fail the request to the diagnostic sink.
+    pub(crate) fn report(&self, diagnostic: &Diagnostic<'_>) {
+        diagnostic::report(self.diagnostic_sink.as_deref(), diagnostic)
+    }
+
+    /// Reports a request that would have been rejected to the audit sink.
+    pub(crate) fn audit(&self, event: &AuditEvent<'_>) {
+        match &self.audit_sink {
+            Some(sink) => sink(event),
+            None => guard::log_audit_event(event),
+        }
+    }
+
+    /// Returns the virtual host router for the request's `Host`, if one is registered.
+    ///
+    /// An exact host match takes precedence over a `*.` wildcard match.
+    fn virtual_host(&self, request: &Request) -> Option<&Router> {
+        if self.hosts.is_empty() {
+            return None;
+        }
+        let host = request
+            .headers()
+            .get(http::header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .or_else(|| request.uri().host())?;
+        let host = host.rsplit_once(':').map_or(host, |(name, _port)| name);
+        let host = host.to_ascii_lowercase();
+
+        self.hosts.get(&host).or_else(|| {
+            let (_, parent) = host.split_once('.')?;
+            self.hosts.get(&format!("*.{parent}"))
+        })
+    }
+
+    /// Returns the built-in endpoint serving this request, if any.
+    fn builtin(&self, request: &Request) -> Option<&Builtin> {
+        if !matches!(*request.method(), http::Method::GET | http::Method::HEAD) {
+            return None;
+        }
+        self.builtins.get(request.uri().path()).map(|b| &**b)
+    }
+
+    /// Returns the best match in `router` whose route conditions accept the request.
+    fn best_match<'a, 'p>(
+        &self,
+        router: &'a MethodRouter<usize>,
+        path: &'p str,
+        request: &Request,
+    ) -> Option<routefinder::Match<'a, 'p, usize>> {
+        let mut matches: Vec<_> = router
+            .match_iter(path)
+            .filter(|m| !self.duplicates().skips(*m.handler()))
+            .filter(|m| self.routes[*m.handler()].accepts(request))
+            .filter(|m| self.routes[*m.handler()].admits(&m.captures()))
+            .collect();
+        // Variants of a route constraining more query parameters take precedence, then those
+        // with other conditions, then the earliest registered.
+        let pattern = &self.routes[*matches.first()?.handler()].pattern;
+        let variants = matches
+            .iter()
+            .take_while(|m| self.routes[*m.handler()].pattern == *pattern)
+            .count();
+        matches[..variants].sort_by_key(|m| {
+            let route = &self.routes[*m.handler()];
+            std::cmp::Reverse((route.queries.len(), route.is_conditional()))
+        });
+        let mut matches = matches.into_iter();
+        let first = matches.next()?;
+        let route = &self.routes[*first.handler()];
+        if !route.negotiates() {
+            return Some(first);
+        }
+        // Variants of the same route compete on the quality the `Accept` header gives them.
+        let mut best = (route.quality(request), first);
+        for m in matches {
+            let variant = &self.routes[*m.handler()];
+            if variant.pattern != route.pattern {
+                break;
+            }
+            let quality = variant.quality(request);
+            if quality > best.0 {
+                best = (quality, m);
+            }
+        }
+        Some(best.1)
+    }
+
+    fn matched(&self, m: routefinder::Match<'_, '_, usize>) -> RouteMatch<'_> {
+        let index = *m.handler();
+        let route = &self.routes[index];
+        let mut params = m.captures().into_owned();
+        for (name, default) in &route.defaults {
+            if params.get(name).is_none() {
+                params.push(Param::new(name.clone(), default.clone()));
+            }
+        }
+        RouteMatch {
+            params,
+            handler: &*route.handler,
+            route: Some(route),
+            chain: self.duplicates().chain(index),
+        }
+    }
+
+    /// Whether a route for the path and method, meeting its conditions, satisfies `check`.
+    fn any_route(
+        &self,
+        method: &http::Method,
+        request: &Request,
+        check: impl Fn(&Route) -> bool,
+    ) -> bool {
+        let path = request.uri().path();
+        self.methods_map
+            .get(method)
+            .into_iter()
+            .chain([&self.all_methods])
+            .flat_map(|r| r.match_iter(path))
+            .filter(|m| !self.duplicates().skips(*m.handler()))
+            .filter(|m| self.routes[*m.handler()].admits(&m.captures()))
+            .map(|m| &self.routes[*m.handler()])
+            .any(|route| route.meets_conditions(request) && check(route))
     }
 
-    fn find(&self, path: &str, method: http::Method) -> RouteMatch<'_> {
+    fn find(&self, request: &Request, method: http::Method) -> RouteMatch<'_> {
+        let path = request.uri().path();
         let best_match = self
             .methods_map
             .get(&method)
-            .and_then(|r| r.best_match(path));
+            .and_then(|r| self.best_match(r, path, request));
 
         if let Some(m) = best_match {
-            let params = m.captures().into_owned();
-            let handler = m.handler();
-            return RouteMatch { handler, params };
+            return self.matched(m);
         }
 
-        let best_match = self.all_methods.best_match(path);
+        let best_match = self.best_match(&self.all_methods, path, request);
 
         match best_match {
-            Some(m) => {
-                let params = m.captures().into_owned();
-                let handler = m.handler();
-                RouteMatch { handler, params }
-            }
+            Some(m) => self.matched(m),
             None if method == http::Method::HEAD => {
                 // If it is a HTTP HEAD request then check if there is a callback in the methods map
                 // if not then fallback to the behavior of HTTP GET else proceed as usual
-                self.find(path, http::Method::GET)
+                self.find(request, http::Method::GET)
+            }
+            None if self.any_route(&method, request, |r| !r.accepts_content_type(request)) => {
+                RouteMatch::status(&unsupported_media_type)
+            }
+            None if self.any_route(&method, request, |r| r.quality(request) == 0.0) => {
+                RouteMatch::status(&not_acceptable)
             }
             None => {
                 let not_allowed = self
                     .methods_map
                     .iter()
                     .filter(|(k, _)| **k != method)
-                    .any(|(_, r)| r.best_match(path).is_some());
+                    .flat_map(|(_, r)| r.match_iter(path))
+                    .filter(|m| !self.duplicates().skips(*m.handler()))
+                    .any(|m| self.routes[*m.handler()].admits(&m.captures()));
 
                 if not_allowed {
                     // If this `path` can be handled by a callback registered with a different HTTP method
                     // should return 405 Method Not Allowed
-                    RouteMatch {
-                        handler: &method_not_allowed,
-                        params: Captures::default(),
-                    }
+                    RouteMatch::status(self.not_allowed.as_deref().unwrap_or(&method_not_allowed))
                 } else {
-                    RouteMatch {
-                        handler: &not_found,
-                        params: Captures::default(),
-                    }
+                    RouteMatch::status(self.fallback.as_deref().unwrap_or(&not_found))
                 }
             }
         }
     }
 
     /// Register a handler at the path for all methods.
-    pub fn all<F>(&mut self, path: &str, handler: F)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is not a valid route pattern; see [`Router::try_all`].
+    pub fn all<F>(&mut self, path: &str, handler: F) -> &mut Route
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_all(path, handler)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Register a handler at the path for all methods, failing if the path is not a valid
+    /// route pattern.
+    pub fn try_all<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.insert(Route::new(None, path, handler))
+    }
+
+    /// Register one handler at the path for each of `methods`, e.g. GET and POST for a form,
+    /// returning the routes registered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is not a valid route pattern; see [`Router::try_any_of`].
+    pub fn any_of<F>(&mut self, methods: &[http::Method], path: &str, handler: F) -> Vec<&mut Route>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_any_of(methods, path, handler)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Register one handler at the path for each of `methods`, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_any_of<F>(
+        &mut self,
+        methods: &[http::Method],
+        path: &str,
+        handler: F,
+    ) -> Result<Vec<&mut Route>, RouteError>
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
-        self.all_methods.add(path, Box::new(handler)).unwrap();
+        let handler = std::rc::Rc::new(handler);
+        let start = self.routes.len();
+        for method in methods {
+            self.insert(Route::shared(method.clone(), path, handler.clone()))?;
+        }
+        Ok(self.routes[start..].iter_mut().collect())
+    }
+
+    /// Indexes and stores a route built elsewhere, e.g. moved over from another router.
+    pub(crate) fn insert(&mut self, mut route: Route) -> Result<&mut Route, RouteError> {
+        let (pattern, choices) = error::split_choices(&route.pattern)?;
+        let (pattern, defaults) = error::split_defaults(&pattern)?;
+        if !choices.is_empty() || !defaults.is_empty() {
+            route.pattern = pattern;
+            route.choices = choices;
+            route.defaults = defaults;
+        }
+        // Duplicates are resolved once the route's conditions are final.
+        route.duplicate_policy.get_or_insert(self.duplicate_policy);
+        self.duplicates.take();
+        // A route with defaults also matches paths omitting the defaulted segments.
+        let segments: Vec<_> = route.pattern.split('/').collect();
+        let mut specs = vec![error::parse_pattern(&route.pattern)?];
+        for omitted in 1..=route.defaults.len() {
+            let pattern = segments[..segments.len() - omitted].join("/");
+            let pattern = if pattern.is_empty() { "/" } else { &pattern };
+            specs.push(error::parse_pattern(pattern)?);
+        }
+        let index = self.routes.len();
+        for spec in specs {
+            match &route.method {
+                Some(method) => self
+                    .methods_map
+                    .entry(method.clone())
+                    .or_default()
+                    .add(spec, index)
+                    .unwrap(),
+                None => self.all_methods.add(spec, index).unwrap(),
+            }
+        }
+        self.routes.push(route);
+        Ok(&mut self.routes[index])
     }
 
     /// Register a handler at the path for the specified HTTP method.
-    pub fn add<F>(&mut self, path: &str, method: http::Method, handler: F)
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is not a valid route pattern; see [`Router::try_add`].
+    pub fn add<F>(&mut self, path: &str, method: http::Method, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
-        self.methods_map
-            .entry(method)
-            .or_insert_with(MethodRouter::new)
-            .add(path, Box::new(handler))
-            .unwrap();
+        self.try_add(path, method, handler)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Register a handler at the path for the specified HTTP method, failing if the path is
+    /// not a valid route pattern.
+    pub fn try_add<F>(
+        &mut self,
+        path: &str,
+        method: http::Method,
+        handler: F,
+    ) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.insert(Route::new(Some(method), path, handler))
     }
 
     /// Register a handler at the path for the HTTP GET method.
-    pub fn get<F>(&mut self, path: &str, handler: F)
+    pub fn get<F>(&mut self, path: &str, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
@@ -118,7 +767,7 @@ impl Router {
     }
 
     /// Register a handler at the path for the HTTP HEAD method.
-    pub fn head<F>(&mut self, path: &str, handler: F)
+    pub fn head<F>(&mut self, path: &str, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
@@ -126,7 +775,7 @@ impl Router {
     }
 
     /// Register a handler at the path for the HTTP POST method.
-    pub fn post<F>(&mut self, path: &str, handler: F)
+    pub fn post<F>(&mut self, path: &str, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
@@ -134,7 +783,7 @@ impl Router {
     }
 
     /// Register a handler at the path for the HTTP DELETE method.
-    pub fn delete<F>(&mut self, path: &str, handler: F)
+    pub fn delete<F>(&mut self, path: &str, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
@@ -142,7 +791,7 @@ impl Router {
     }
 
     /// Register a handler at the path for the HTTP PUT method.
-    pub fn put<F>(&mut self, path: &str, handler: F)
+    pub fn put<F>(&mut self, path: &str, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
@@ -150,18 +799,269 @@ impl Router {
     }
 
     /// Register a handler at the path for the HTTP PATCH method.
-    pub fn patch<F>(&mut self, path: &str, handler: F)
+    pub fn patch<F>(&mut self, path: &str, handler: F) -> &mut Route
     where
         F: Fn(Request, Params) -> Result<Response> + 'static,
     {
         self.add(path, http::Method::PATCH, handler)
     }
 
+    /// Register a handler at the path for the HTTP OPTIONS method.
+    pub fn options<F>(&mut self, path: &str, handler: F) -> &mut Route
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::OPTIONS, handler)
+    }
+
+    /// Register a handler at the path for the HTTP TRACE method.
+    pub fn trace<F>(&mut self, path: &str, handler: F) -> &mut Route
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::TRACE, handler)
+    }
+
+    /// Register a handler at the path for the HTTP CONNECT method.
+    pub fn connect<F>(&mut self, path: &str, handler: F) -> &mut Route
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.add(path, http::Method::CONNECT, handler)
+    }
+
+    /// Register a handler at the path for the HTTP GET method, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_get<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::GET, handler)
+    }
+
+    /// Register a handler at the path for the HTTP HEAD method, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_head<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::HEAD, handler)
+    }
+
+    /// Register a handler at the path for the HTTP POST method, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_post<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::POST, handler)
+    }
+
+    /// Register a handler at the path for the HTTP DELETE method, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_delete<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::DELETE, handler)
+    }
+
+    /// Register a handler at the path for the HTTP PUT method, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_put<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::PUT, handler)
+    }
+
+    /// Register a handler at the path for the HTTP PATCH method, failing if the path is not a
+    /// valid route pattern.
+    pub fn try_patch<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::PATCH, handler)
+    }
+
+    /// Register a handler at the path for the HTTP OPTIONS method, failing if the path is
+    /// not a valid route pattern.
+    pub fn try_options<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::OPTIONS, handler)
+    }
+
+    /// Register a handler at the path for the HTTP TRACE method, failing if the path is not
+    /// a valid route pattern.
+    pub fn try_trace<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::TRACE, handler)
+    }
+
+    /// Register a handler at the path for the HTTP CONNECT method, failing if the path is
+    /// not a valid route pattern.
+    pub fn try_connect<F>(&mut self, path: &str, handler: F) -> Result<&mut Route, RouteError>
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.try_add(path, http::Method::CONNECT, handler)
+    }
+
+    /// Register a guard that is checked before every request is dispatched.
+    pub fn guard<G: Guard>(&mut self, guard: G) {
+        self.guards.push(GuardEntry {
+            guard: Box::new(guard),
+            audit: false,
+        });
+    }
+
+    /// Register a guard in audit mode: requests it rejects are reported to the audit sink
+    /// but still dispatched, so that a new policy can be rolled out safely.
+    pub fn audit_guard<G: Guard>(&mut self, guard: G) {
+        self.guards.push(GuardEntry {
+            guard: Box::new(guard),
+            audit: true,
+        });
+    }
+
+    /// Run every guard in audit mode, regardless of how it was registered, and only report
+    /// the requests that the limits, such as body size limits, rate limits and the
+    /// [`IpFilter`](ipfilter::IpFilter), would have rejected. Middleware finds out with
+    /// [`Next::audit_mode`].
+    pub fn audit_mode(&mut self, enabled: bool) {
+        self.audit_mode = enabled;
+    }
+
+    /// Set the callback receiving audit events. By default they are written to stderr.
+    pub fn on_audit<F>(&mut self, sink: F)
+    where
+        F: Fn(&AuditEvent<'_>) + 'static,
+    {
+        self.audit_sink = Some(Box::new(sink));
+    }
+
+    /// Set the callback receiving [diagnostics](Diagnostic), the problems the router and its
+    /// middleware work around rather than fail the request for, such as a failing
+    /// [deferred](defer::defer) task. By default they are written to the `log` crate when the
+    /// `log` feature is enabled, and to stderr otherwise.
+    pub fn on_diagnostic<F>(&mut self, sink: F)
+    where
+        F: Fn(&Diagnostic<'_>) + 'static,
+    {
+        self.diagnostic_sink = Some(Rc::new(sink));
+    }
+
+    /// Register a hook that runs once per Wasm instance, before the first request is
+    /// dispatched, e.g. to warm caches, load templates or verify configuration.
+    ///
+    /// While a hook fails, requests are answered with 503 Service Unavailable and the hook is
+    /// retried on the next request.
+    pub fn on_instance_init<F>(&mut self, hook: F)
+    where
+        F: Fn() -> Result<()> + 'static,
+    {
+        self.init_hooks.push((Box::new(hook), Cell::new(false)));
+    }
+
+    /// Register a hook called whenever a handler returns an error, with the error, the
+    /// request and the matched route, e.g. to report it to an error tracking service. The
+    /// error is still returned from [`Router::handle`] afterwards.
+    ///
+    /// A hook sending the report over outbound HTTP can [defer](defer::defer) it so that it
+    /// does not hold up the response.
+    pub fn on_error<F>(&mut self, hook: F)
+    where
+        F: Fn(&ErrorEvent<'_>) + 'static,
+    {
+        self.error_hooks.push(Box::new(hook));
+    }
+
+    /// Set how requests carrying an `Expect: 100-continue` header are handled. Requests with
+    /// any other expectation are always answered with 417 Expectation Failed.
+    pub fn expect_continue(&mut self, policy: ExpectContinue) {
+        self.expect_continue = policy;
+    }
+
+    /// Register middleware wrapping the dispatch of every request. Middleware runs in the
+    /// order it was registered, with the first registered being the outermost.
+    pub fn layer<M: Middleware>(&mut self, middleware: M) {
+        self.middleware.push(Box::new(middleware));
+    }
+
+    /// Answer requests that match no route with `handler` instead of an empty 404 Not Found,
+    /// e.g. to render an error page in the format the client accepts. Handlers returning
+    /// [`RouterError::NotFound`] are answered with it too.
+    pub fn fallback<F>(&mut self, handler: F)
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
+    }
+
+    /// Answer requests matching routes only for other methods with `handler` instead of an
+    /// empty 405 Method Not Allowed. Handlers returning [`RouterError::MethodNotAllowed`]
+    /// are answered with it too.
+    pub fn method_not_allowed<F>(&mut self, handler: F)
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.not_allowed = Some(Box::new(handler));
+    }
+
+    /// Cascade requests that match no route to another router (e.g. a legacy API or static
+    /// file router) instead of responding with 404 Not Found.
+    ///
+    /// Requests matching a route registered for a different method still get 405 Method Not
+    /// Allowed from this router.
+    pub fn fallback_to(&mut self, other: Router) {
+        self.fallback = Some(Box::new(move |req, _params| other.handle(req)));
+    }
+
+    /// Returns the router for requests whose `Host` header matches `host`, creating it if
+    /// needed. `host` may be an exact name such as `api.example.com` or a wildcard such as
+    /// `*.example.com` matching a single subdomain label.
+    ///
+    /// Requests for hosts without a router of their own are dispatched to this router, which
+    /// acts as the default virtual host. Middleware and guards registered on this router run
+    /// for every host.
+    pub fn host(&mut self, host: &str) -> &mut Router {
+        self.hosts.entry(host.to_ascii_lowercase()).or_default()
+    }
+
     /// Construct a new Router.
     pub fn new() -> Self {
         Router {
+            routes: Vec::new(),
             methods_map: HashMap::default(),
             all_methods: MethodRouter::new(),
+            builtins: HashMap::default(),
+            guards: Vec::new(),
+            audit_mode: false,
+            audit_sink: None,
+            diagnostic_sink: None,
+            init_hooks: Vec::new(),
+            error_hooks: Vec::new(),
+            expect_continue: ExpectContinue::default(),
+            middleware: Vec::new(),
+            fallback: None,
+            not_allowed: None,
+            hosts: HashMap::default(),
+            profiler: profile::Profiler::new(),
+            drain: drain::Drain::default(),
+            path_policies: normalize::PathPolicies::default(),
+            problem_details: false,
+            duplicate_policy: DuplicatePolicy::default(),
+            duplicates: std::cell::OnceCell::new(),
+            chained_extensions: duplicate::chained_extensions(),
+            states: state::States::default(),
+            services: Default::default(),
+            aliases: alias::Aliases::default(),
+            bundles: Vec::new(),
+            plugins: Vec::new(),
         }
     }
 }
@@ -173,6 +1073,41 @@ fn not_found(_req: Request, _params: Params) -> Result<Response> {
         .unwrap())
 }
 
+fn payload_too_large(_req: Request, _params: Params) -> Result<Response> {
+    Ok(http::Response::builder()
+        .status(http::StatusCode::PAYLOAD_TOO_LARGE)
+        .body(None)
+        .unwrap())
+}
+
+fn service_unavailable(_req: Request, _params: Params) -> Result<Response> {
+    Ok(http::Response::builder()
+        .status(http::StatusCode::SERVICE_UNAVAILABLE)
+        .body(None)
+        .unwrap())
+}
+
+fn expectation_failed(_req: Request, _params: Params) -> Result<Response> {
+    Ok(http::Response::builder()
+        .status(http::StatusCode::EXPECTATION_FAILED)
+        .body(None)
+        .unwrap())
+}
+
+fn unsupported_media_type(_req: Request, _params: Params) -> Result<Response> {
+    Ok(http::Response::builder()
+        .status(http::StatusCode::UNSUPPORTED_MEDIA_TYPE)
+        .body(None)
+        .unwrap())
+}
+
+fn not_acceptable(_req: Request, _params: Params) -> Result<Response> {
+    Ok(http::Response::builder()
+        .status(http::StatusCode::NOT_ACCEPTABLE)
+        .body(None)
+        .unwrap())
+}
+
 fn method_not_allowed(_req: Request, _params: Params) -> Result<Response> {
     Ok(http::Response::builder()
         .status(http::StatusCode::METHOD_NOT_ALLOWED)
@@ -181,37 +1116,224 @@ fn method_not_allowed(_req: Request, _params: Params) -> Result<Response> {
 }
 
 /// A macro to help with constructing a Router from a stream of tokens.
+///
+/// `///` comments before a route become its documentation, as with [`Route::doc`], and
+/// methods separated by `|` share a handler, as with [`Router::any_of`]. `ANY`, or `_`,
+/// registers a route for all methods, and `group` blocks prefix the paths of the routes in
+/// them, nesting as needed:
+///
+/// ```
+/// # use spin_sdk_router::{router, Params, Request, Response};
+/// # fn form(_req: Request, _params: Params) -> anyhow::Result<Response> {
+/// #     Ok(http::Response::new(None))
+/// # }
+/// let router = router! {
+///     /// Shows and submits the contact form.
+///     GET | POST "/contact" => form,
+///     group "/api" {
+///         GET "/users" => form,
+///         group "/admin" {
+///             DELETE "/users/:id" => form,
+///         },
+///     },
+///     ANY        "/*"       => form,
+/// };
+/// assert!(router.routes().any(|route| route.pattern() == "/api/admin/users/:id"));
+/// ```
+///
+/// Patterns are checked when the macro is expanded, so that an invalid pattern fails to
+/// compile instead of panicking when the router is built:
+///
+/// ```compile_fail
+/// # use spin_sdk_router::{router, Params, Request, Response};
+/// # fn show(_req: Request, _params: Params) -> anyhow::Result<Response> {
+/// #     Ok(http::Response::new(None))
+/// # }
+/// let router = router! {
+///     GET "/files/*/edit" => show,
+/// };
+/// ```
+///
+/// Methods are checked too, so that a method shared with `|` must be one of the standard
+/// methods in upper case:
+///
+/// ```compile_fail
+/// # use spin_sdk_router::{router, Params, Request, Response};
+/// # fn form(_req: Request, _params: Params) -> anyhow::Result<Response> {
+/// #     Ok(http::Response::new(None))
+/// # }
+/// let router = router! {
+///     get | post "/contact" => form,
+/// };
+/// ```
+///
+/// [`try_router!`] checks patterns when the router is built instead.
 #[macro_export]
 macro_rules! router {
-    ($($method:tt $path:literal => $h:expr),*) => {
-        {
-            let mut router = spin_sdk_router::Router::new();
-            $(
-                spin_sdk_router::router!(@build router $method $path => $h);
-            )*
-            router
-        }
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*]) => {};
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] #[doc = $d:literal] $($rest:tt)*) => {
+        $crate::router!(@munch $r ($($pre)*) [$($doc)* $d] $($rest)*)
     };
-    (@build $r:ident HEAD $path:literal => $h:expr) => {
-        $r.head($path, $h);
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] group $prefix:literal { $($routes:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::router!(@munch $r ($($pre)* $prefix) [] $($routes)*);
+        $crate::router!(@munch $r ($($pre)*) [] $($($rest)*)?)
     };
-    (@build $r:ident GET $path:literal => $h:expr) => {
-        $r.get($path, $h);
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] $method:tt $path:literal => $h:expr $(, $($rest:tt)*)?) => {
+        $crate::router!(@build $r $method $crate::__pattern!($($pre)* $path) => $h).doc_lines(&[$($doc),*]);
+        $crate::router!(@munch $r ($($pre)*) [] $($($rest)*)?)
     };
-    (@build $r:ident PUT $path:literal => $h:expr) => {
-        $r.put($path, $h);
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] $first:ident $(| $more:ident)+ $path:literal => $h:expr $(, $($rest:tt)*)?) => {
+        let methods = [$crate::__method!($first) $(, $crate::__method!($more))+];
+        for route in $r.any_of(&methods, $crate::__pattern!($($pre)* $path), $h) {
+            route.doc_lines(&[$($doc),*]);
+        }
+        $crate::router!(@munch $r ($($pre)*) [] $($($rest)*)?)
+    };
+    (@build $r:ident HEAD $path:expr => $h:expr) => {
+        $r.head($path, $h)
+    };
+    (@build $r:ident GET $path:expr => $h:expr) => {
+        $r.get($path, $h)
+    };
+    (@build $r:ident PUT $path:expr => $h:expr) => {
+        $r.put($path, $h)
+    };
+    (@build $r:ident POST $path:expr => $h:expr) => {
+        $r.post($path, $h)
+    };
+    (@build $r:ident PATCH $path:expr => $h:expr) => {
+        $r.patch($path, $h)
+    };
+    (@build $r:ident DELETE $path:expr => $h:expr) => {
+        $r.delete($path, $h)
+    };
+    (@build $r:ident OPTIONS $path:expr => $h:expr) => {
+        $r.options($path, $h)
+    };
+    (@build $r:ident TRACE $path:expr => $h:expr) => {
+        $r.trace($path, $h)
+    };
+    (@build $r:ident CONNECT $path:expr => $h:expr) => {
+        $r.connect($path, $h)
+    };
+    (@build $r:ident _ $path:expr => $h:expr) => {
+        $r.all($path, $h)
+    };
+    (@build $r:ident ANY $path:expr => $h:expr) => {
+        $r.all($path, $h)
+    };
+    ($($routes:tt)*) => {
+        {
+            let mut router = $crate::Router::new();
+            $crate::router!(@munch router () [] $($routes)*);
+            router
+        }
+    };
+}
+
+/// The standard method named by an identifier, for the methods [`router!`] shares a handler
+/// between, failing to compile for any other identifier.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __method {
+    (HEAD) => {
+        $crate::__http::Method::HEAD
+    };
+    (GET) => {
+        $crate::__http::Method::GET
+    };
+    (PUT) => {
+        $crate::__http::Method::PUT
+    };
+    (POST) => {
+        $crate::__http::Method::POST
+    };
+    (PATCH) => {
+        $crate::__http::Method::PATCH
+    };
+    (DELETE) => {
+        $crate::__http::Method::DELETE
+    };
+    (OPTIONS) => {
+        $crate::__http::Method::OPTIONS
+    };
+    (TRACE) => {
+        $crate::__http::Method::TRACE
+    };
+    (CONNECT) => {
+        $crate::__http::Method::CONNECT
+    };
+    ($other:ident) => {
+        compile_error!(concat!(
+            "expected a standard method in upper case, e.g. `GET`, found `",
+            stringify!($other),
+            "`"
+        ))
+    };
+}
+
+/// Like [`router!`], but registers routes with the fallible registration methods and
+/// evaluates to a `Result<Router, RouteError>` instead of panicking on an invalid pattern.
+#[macro_export]
+macro_rules! try_router {
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*]) => {};
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] #[doc = $d:literal] $($rest:tt)*) => {
+        $crate::try_router!(@munch $r ($($pre)*) [$($doc)* $d] $($rest)*)
+    };
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] group $prefix:literal { $($routes:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::try_router!(@munch $r ($($pre)* $prefix) [] $($routes)*);
+        $crate::try_router!(@munch $r ($($pre)*) [] $($($rest)*)?)
+    };
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] $method:tt $path:literal => $h:expr $(, $($rest:tt)*)?) => {
+        $crate::try_router!(@build $r $method concat!($($pre,)* $path) => $h).doc_lines(&[$($doc),*]);
+        $crate::try_router!(@munch $r ($($pre)*) [] $($($rest)*)?)
+    };
+    (@munch $r:ident ($($pre:literal)*) [$($doc:literal)*] $first:ident $(| $more:ident)+ $path:literal => $h:expr $(, $($rest:tt)*)?) => {
+        let methods = [$crate::__method!($first) $(, $crate::__method!($more))+];
+        for route in $r.try_any_of(&methods, concat!($($pre,)* $path), $h)? {
+            route.doc_lines(&[$($doc),*]);
+        }
+        $crate::try_router!(@munch $r ($($pre)*) [] $($($rest)*)?)
+    };
+    (@build $r:ident HEAD $path:expr => $h:expr) => {
+        $r.try_head($path, $h)?
+    };
+    (@build $r:ident GET $path:expr => $h:expr) => {
+        $r.try_get($path, $h)?
+    };
+    (@build $r:ident PUT $path:expr => $h:expr) => {
+        $r.try_put($path, $h)?
+    };
+    (@build $r:ident POST $path:expr => $h:expr) => {
+        $r.try_post($path, $h)?
+    };
+    (@build $r:ident PATCH $path:expr => $h:expr) => {
+        $r.try_patch($path, $h)?
+    };
+    (@build $r:ident DELETE $path:expr => $h:expr) => {
+        $r.try_delete($path, $h)?
+    };
+    (@build $r:ident OPTIONS $path:expr => $h:expr) => {
+        $r.try_options($path, $h)?
+    };
+    (@build $r:ident TRACE $path:expr => $h:expr) => {
+        $r.try_trace($path, $h)?
     };
-    (@build $r:ident POST $path:literal => $h:expr) => {
-        $r.post($path, $h);
+    (@build $r:ident CONNECT $path:expr => $h:expr) => {
+        $r.try_connect($path, $h)?
     };
-    (@build $r:ident PATCH $path:literal => $h:expr) => {
-        $r.patch($path, $h);
+    (@build $r:ident _ $path:expr => $h:expr) => {
+        $r.try_all($path, $h)?
     };
-    (@build $r:ident DELETE $path:literal => $h:expr) => {
-        $r.delete($path, $h);
+    (@build $r:ident ANY $path:expr => $h:expr) => {
+        $r.try_all($path, $h)?
     };
-    (@build $r:ident _ $path:literal => $h:expr) => {
-        $r.all($path, $h);
+    ($($routes:tt)*) => {
+        (|| -> ::std::result::Result<$crate::Router, $crate::RouteError> {
+            let mut router = $crate::Router::new();
+            $crate::try_router!(@munch router () [] $($routes)*);
+            Ok(router)
+        })()
     };
 }
 
@@ -320,6 +1442,779 @@ mod tests {
         assert_eq!(res.into_body().unwrap(), "foo".to_string());
     }
 
+    #[test]
+    fn test_param_defaults() {
+        let mut router = Router::default();
+        router.get("/feed/:format=json/:page=1", |_req, params: Params| {
+            let body = format!(
+                "{} {}",
+                params.get("format").unwrap(),
+                params.get("page").unwrap()
+            );
+            Ok(http::Response::new(Some(body.into())))
+        });
+
+        for (path, expected) in [
+            ("/feed", "json 1"),
+            ("/feed/xml", "xml 1"),
+            ("/feed/xml/3", "xml 3"),
+        ] {
+            let res = router
+                .handle(make_request(http::Method::GET, path))
+                .unwrap();
+            assert_eq!(res.into_body().unwrap(), expected);
+        }
+        let route = router.routes().next().unwrap();
+        assert_eq!(route.pattern(), "/feed/:format/:page");
+        assert_eq!(route.registered_pattern(), "/feed/:format=json/:page=1");
+
+        let err = router.try_get("/:lang=en/docs", echo_param).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "invalid route pattern `/:lang=en/docs`: only trailing parameters can have defaults"
+        );
+    }
+
+    #[test]
+    fn test_param_choices() {
+        let format = |_req, params: Params| {
+            let format = params.get("format").unwrap().to_owned();
+            Ok(http::Response::new(Some(format.into())))
+        };
+        let mut router = Router::default();
+        router.get("/export/:format<json|csv>=json", format);
+        router.get("/export/:name", |_req, _params| {
+            Ok(http::Response::new(Some("other".into())))
+        });
+        router.get("/reports/:format<pdf|html>", format);
+
+        for (path, status, expected) in [
+            ("/export", 200, "json"),
+            ("/export/csv", 200, "csv"),
+            ("/export/xml", 200, "other"),
+            ("/reports/html", 200, "html"),
+            ("/reports/HTML", 404, ""),
+        ] {
+            let res = router
+                .handle(make_request(http::Method::GET, path))
+                .unwrap();
+            assert_eq!(res.status(), status, "{path}");
+            if status == 200 {
+                assert_eq!(res.into_body().unwrap(), expected);
+            }
+        }
+        // Another method is only allowed for the choices of its route.
+        for (path, status) in [("/reports/pdf", 405), ("/reports/csv", 404)] {
+            let res = router
+                .handle(make_request(http::Method::POST, path))
+                .unwrap();
+            assert_eq!(res.status(), status, "{path}");
+        }
+        let route = router.routes().next().unwrap();
+        assert_eq!(route.pattern(), "/export/:format");
+        assert_eq!(route.registered_pattern(), "/export/:format<json|csv>=json");
+        assert_eq!(
+            route.choices().collect::<Vec<_>>(),
+            [("format", &["json".to_owned(), "csv".to_owned()][..])]
+        );
+
+        for (pattern, reason) in [
+            ("/:format<json", "unclosed `<` in parameter choices"),
+            ("/:format<json|>", "parameter choices cannot be empty"),
+            (
+                "/:format<json>.gz",
+                "parameter choices must end the segment",
+            ),
+            (
+                "/:format<json>=xml",
+                "a parameter default must be one of its choices",
+            ),
+        ] {
+            let err = router.try_get(pattern, echo_param).err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                format!("invalid route pattern `{pattern}`: {reason}")
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_dispatch() {
+        let reply =
+            |body: &'static str| move |_req, _params| Ok(http::Response::new(Some(body.into())));
+        let mut router = Router::default();
+        router.get("/search", reply("any"));
+        router.get("/search", reply("user")).query("type", "user");
+        router.get("/search", reply("repo")).query("type", "repo");
+        router
+            .get("/search", reply("starred repo"))
+            .query("type", "repo")
+            .query_present("starred");
+        router.get("/search", reply("tagged")).query("tag", "a b");
+
+        for (uri, expected) in [
+            ("/search", "any"),
+            ("/search?type=user", "user"),
+            ("/search?q=x&type=repo", "repo"),
+            ("/search?type=repo&starred", "starred repo"),
+            ("/search?type=org", "any"),
+            ("/search?tag=a+b", "tagged"),
+            ("/search?tag=a%20b", "tagged"),
+        ] {
+            let res = router.handle(make_request(http::Method::GET, uri)).unwrap();
+            assert_eq!(res.into_body().unwrap(), expected, "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_on_error() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let reports = Rc::new(RefCell::new(Vec::new()));
+        let mut router = Router::default();
+        router.get("/users/:id", |_req, _params| anyhow::bail!("database down"));
+        router.get("/ok", |_req, _params| Ok(http::Response::new(None)));
+        let sink = reports.clone();
+        router.on_error(move |event| sink.borrow_mut().push(event.to_string()));
+
+        let err = router
+            .handle(make_request(http::Method::GET, "/users/7?x=1"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "database down");
+        router
+            .handle(make_request(http::Method::GET, "/ok"))
+            .unwrap();
+        assert_eq!(
+            *reports.borrow(),
+            ["GET /users/7 (/users/:id) failed: database down"]
+        );
+    }
+
+    #[test]
+    fn test_router_errors() {
+        let mut router = Router::default();
+        router.get("/users/:id", |_req, _params| {
+            Err(RouterError::NotFound.into())
+        });
+        router.put("/users/:id", |_req, _params| {
+            Err(RouterError::MethodNotAllowed.into())
+        });
+        router.post("/users", |_req, _params| Err(RouterError::NotFound.into()));
+        router.on_error(|event| panic!("reported {}", event.error));
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/users/7"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        router.fallback(|req, _params| {
+            let body = match req.body() {
+                Some(body) => format!(
+                    "no {} for {}",
+                    req.uri().path(),
+                    String::from_utf8_lossy(body)
+                ),
+                None => format!("no {}", req.uri().path()),
+            };
+            Ok(http::Response::builder()
+                .status(http::StatusCode::NOT_FOUND)
+                .body(Some(body.into()))?)
+        });
+        router.method_not_allowed(|req, _params| {
+            let body = format!("no {} here", req.method());
+            Ok(http::Response::builder()
+                .status(http::StatusCode::METHOD_NOT_ALLOWED)
+                .body(Some(body.into()))?)
+        });
+        for (method, path, expected) in [
+            (http::Method::GET, "/users/7", "no /users/7"),
+            (http::Method::GET, "/other", "no /other"),
+            (http::Method::PUT, "/users/7", "no PUT here"),
+            (http::Method::DELETE, "/users/7", "no DELETE here"),
+        ] {
+            let res = router.handle(make_request(method, path)).unwrap();
+            assert_eq!(res.into_body().unwrap(), expected);
+        }
+
+        // The fallback sees the body of a request whose handler found nothing.
+        let req = http::Request::post("/users")
+            .body(Some("name=ann".into()))
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "no /users for name=ann");
+    }
+
+    #[test]
+    fn test_debug_route_table() {
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.post("/:x", echo_param);
+        router.all("/*", |_req, _params| Ok(http::Response::new(None)));
+
+        let rendered = format!("{router:?}");
+        assert!(rendered.contains("GET /:x -> spin_sdk_router::tests::echo_param"));
+        assert!(rendered.contains("POST /:x -> spin_sdk_router::tests::echo_param"));
+        assert!(rendered.contains("* /* -> <closure>"));
+        assert!(rendered.contains(&format!("fingerprint: \"{}\"", router.fingerprint())));
+
+        let table = router.to_string();
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("GET   /:x"));
+        assert!(lines[2].starts_with("*     /* "));
+    }
+
+    fn deny_all(_req: &mut Request) -> Result<(), Rejection> {
+        Err(Rejection::new(http::StatusCode::FORBIDDEN, "denied"))
+    }
+
+    #[test]
+    fn test_guard_rejects() {
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.guard(deny_all);
+
+        let req = make_request(http::Method::GET, "/foo");
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_audit_guard_reports_without_blocking() {
+        use std::{cell::RefCell, rc::Rc};
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let sink = events.clone();
+
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.audit_guard(deny_all);
+        router.on_audit(move |event| sink.borrow_mut().push(event.to_string()));
+
+        let req = make_request(http::Method::GET, "/foo");
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(
+            *events.borrow(),
+            ["audit: GET /foo would be rejected by spin_sdk_router::tests::deny_all with 403 Forbidden: denied"]
+        );
+    }
+
+    #[test]
+    fn test_audit_mode_overrides_enforced_guards() {
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.guard(deny_all);
+        router.audit_mode(true);
+        router.on_audit(|_| {});
+
+        let req = make_request(http::Method::GET, "/foo");
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
+
+    #[test]
+    fn test_audit_mode_reports_oversized_bodies() {
+        let mut router = Router::default();
+        router.post("/upload", |_req, _params| Ok(http::Response::new(None)));
+        router.layer(limits::RequestSizeLimit::new(4));
+        let upload = || {
+            http::Request::post("/upload")
+                .body(Some("too large".into()))
+                .unwrap()
+        };
+        let res = router.handle(upload()).unwrap();
+        assert_eq!(res.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = events.clone();
+        router.audit_mode(true);
+        router.on_audit(move |event| sink.borrow_mut().push(event.to_string()));
+        let res = router.handle(upload()).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(
+            *events.borrow(),
+            [
+                "audit: POST /upload would be rejected by body limit with 413 Payload Too Large: \
+              the body is over the limit of 4 bytes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_audit_mode_covers_limit_middleware() {
+        let mut router = Router::default();
+        router.get("/", |_req, _params| {
+            Ok(http::Response::new(Some("too large".into())))
+        });
+        router.layer(limits::ResponseSizeLimit::new(4));
+        router.layer(ratelimit::RateLimit::new(
+            0,
+            std::time::Duration::from_secs(60),
+        ));
+        router.layer(ratelimit::ConcurrencyLimit::new(0));
+        router.layer(ipfilter::IpFilter::new().deny("10.0.0.0/8"));
+        let get = || {
+            http::Request::get("/")
+                .header(ratelimit::CLIENT_ADDR_HEADER, "10.0.0.1")
+                .body(None)
+                .unwrap()
+        };
+        let res = router.handle(get()).unwrap();
+        assert_eq!(res.status(), http::StatusCode::TOO_MANY_REQUESTS);
+
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = events.clone();
+        router.audit_mode(true);
+        router.on_audit(move |event| sink.borrow_mut().push(event.guard.to_owned()));
+        let res = router.handle(get()).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.into_body().unwrap(), "too large");
+        let mut guards = events.borrow().clone();
+        guards.sort();
+        assert_eq!(
+            guards,
+            [
+                "concurrency limit",
+                "ip filter",
+                "rate limit",
+                "response size limit"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instance_init_runs_once() {
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.on_instance_init(move || {
+            counter.set(counter.get() + 1);
+            Ok(())
+        });
+
+        for _ in 0..2 {
+            let res = router
+                .handle(make_request(http::Method::GET, "/foo"))
+                .unwrap();
+            assert_eq!(res.status(), http::StatusCode::OK);
+        }
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_instance_init_failure_is_retried() {
+        use std::rc::Rc;
+
+        let ready = Rc::new(Cell::new(false));
+        let flag = ready.clone();
+
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.on_instance_init(move || match flag.get() {
+            true => Ok(()),
+            false => anyhow::bail!("config not loaded"),
+        });
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/foo"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+
+        ready.set(true);
+        let res = router
+            .handle(make_request(http::Method::GET, "/foo"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
+
+    #[test]
+    fn test_expect_continue() {
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+
+        let make_expect_request = |expect: &str| {
+            let mut req = make_request(http::Method::GET, "/foo");
+            req.headers_mut()
+                .insert(http::header::EXPECT, expect.parse().unwrap());
+            req
+        };
+
+        let res = router.handle(make_expect_request("100-continue")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::EXPECTATION_FAILED);
+
+        router.expect_continue(ExpectContinue::Ignore);
+        let res = router.handle(make_expect_request("100-Continue")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        let res = router
+            .handle(make_expect_request("something-else"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::EXPECTATION_FAILED);
+    }
+
+    #[test]
+    fn test_try_add_invalid_pattern() {
+        let mut router = Router::default();
+        let err = router.try_get("/files/*name", echo_param).err().unwrap();
+        assert!(matches!(err, RouteError::InvalidPattern { .. }));
+
+        let err = router.try_all("/*/edit", echo_param).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "invalid route pattern `/*/edit`: a wildcard must be the last segment"
+        );
+        assert!(router.route_table().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid route pattern `/:`")]
+    fn test_add_invalid_pattern_panics() {
+        Router::default().get("/:", echo_param);
+    }
+
+    #[test]
+    fn test_try_router_macro() {
+        let router = try_router! {
+            GET "/:x" => echo_param,
+            _   "/*"  => echo_param
+        };
+        assert!(router.is_ok());
+
+        let router = try_router! {
+            ANY "/*" => echo_param
+        };
+        assert_eq!(router.unwrap().routes().next().unwrap().method(), None);
+
+        let router = try_router! {
+            GET  "/:x"   => echo_param,
+            POST "/*/x"  => echo_param
+        };
+        assert!(router.is_err());
+    }
+
+    #[test]
+    fn test_any_of() {
+        let mut router = Router::default();
+        let routes = router.any_of(
+            &[http::Method::GET, http::Method::POST],
+            "/form/:x",
+            echo_param,
+        );
+        assert_eq!(routes.len(), 2);
+        for route in routes {
+            route.doc("The form.");
+        }
+        for method in [http::Method::GET, http::Method::POST] {
+            let res = router.handle(make_request(method, "/form/a")).unwrap();
+            assert_eq!(res.into_body().unwrap(), "a");
+        }
+        let res = router
+            .handle(make_request(http::Method::PUT, "/form/a"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+        assert!(router
+            .routes()
+            .all(|route| route.docs() == Some("The form.")));
+        assert!(router
+            .routes()
+            .all(|route| route.handler_name().unwrap().ends_with("echo_param")));
+    }
+
+    #[test]
+    fn test_router_macro_method_sets() {
+        let router = try_router! {
+            /// The form.
+            GET | POST "/form/:x" => echo_param,
+            DELETE "/form/:x" => echo_param
+        }
+        .unwrap();
+        for method in [http::Method::GET, http::Method::POST, http::Method::DELETE] {
+            let res = router.handle(make_request(method, "/form/a")).unwrap();
+            assert_eq!(res.into_body().unwrap(), "a");
+        }
+        assert_eq!(router.routes().count(), 3);
+        assert_eq!(router.routes().next().unwrap().docs(), Some("The form."));
+    }
+
+    #[test]
+    fn test_router_macro_groups() {
+        let router = try_router! {
+            GET "/:x" => echo_param,
+            group "/api" {
+                /// A user.
+                GET "/users/:x" => echo_param,
+                group "/v2" {
+                    GET | POST "/users/:x" => echo_param
+                }
+            },
+            DELETE "/:x" => echo_param
+        }
+        .unwrap();
+        let patterns: Vec<_> = router.routes().map(|route| route.pattern()).collect();
+        assert_eq!(
+            patterns,
+            [
+                "/:x",
+                "/api/users/:x",
+                "/api/v2/users/:x",
+                "/api/v2/users/:x",
+                "/:x"
+            ]
+        );
+        assert_eq!(router.routes().nth(1).unwrap().docs(), Some("A user."));
+        let res = router
+            .handle(make_request(http::Method::POST, "/api/v2/users/a"))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "a");
+
+        let router = try_router! {
+            group "/api" {
+                GET "/*/x" => echo_param
+            }
+        };
+        assert!(router.is_err());
+    }
+
+    #[test]
+    fn test_options_trace_connect() {
+        let router = try_router! {
+            OPTIONS "/:x" => echo_param,
+            TRACE   "/:x" => echo_param,
+            CONNECT "/:x" => echo_param
+        }
+        .unwrap();
+        for method in [
+            http::Method::OPTIONS,
+            http::Method::TRACE,
+            http::Method::CONNECT,
+        ] {
+            let res = router.handle(make_request(method, "/a")).unwrap();
+            assert_eq!(res.into_body().unwrap(), "a");
+        }
+        let res = router
+            .handle(make_request(http::Method::GET, "/a"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn test_middleware_order() {
+        fn tag(name: &'static str) -> impl Fn(Request, Next<'_>) -> Result<Response> {
+            move |req, next| {
+                let mut res = next.run(req)?;
+                res.headers_mut().append("x-tag", name.parse()?);
+                Ok(res)
+            }
+        }
+
+        let mut router = Router::default();
+        router.get("/:x", echo_param);
+        router.layer(tag("outer"));
+        router.layer(tag("inner"));
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/foo"))
+            .unwrap();
+        let tags: Vec<_> = res.headers().get_all("x-tag").iter().collect();
+        assert_eq!(tags, ["inner", "outer"]);
+        assert_eq!(res.into_body().unwrap(), "foo");
+    }
+
+    #[test]
+    fn test_fallback_to() {
+        let mut legacy = Router::default();
+        legacy.get("/legacy/:x", echo_param);
+
+        let mut router = Router::default();
+        router.get("/v2/:x", echo_param);
+        router.fallback_to(legacy);
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/v2/a"))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "a");
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/legacy/b"))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "b");
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/other"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        let res = router
+            .handle(make_request(http::Method::POST, "/v2/a"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn test_host_routing() {
+        fn respond(body: &'static str) -> impl Fn(Request, Params) -> Result<Response> {
+            move |_req, _params| Ok(http::Response::new(Some(body.into())))
+        }
+
+        let mut router = Router::default();
+        router.get("/", respond("default"));
+        router.host("api.example.com").get("/", respond("api"));
+        router.host("*.example.com").get("/", respond("tenant"));
+
+        let request = |host: &str| {
+            http::Request::builder()
+                .uri("/")
+                .header(http::header::HOST, host)
+                .body(None)
+                .unwrap()
+        };
+        for (host, expected) in [
+            ("API.example.com:8080", "api"),
+            ("acme.example.com", "tenant"),
+            ("example.com", "default"),
+            ("other.org", "default"),
+        ] {
+            let res = router.handle(request(host)).unwrap();
+            assert_eq!(res.into_body().unwrap(), expected, "{host}");
+        }
+    }
+
+    #[test]
+    fn test_route_conditions() {
+        fn respond(body: &'static str) -> impl Fn(Request, Params) -> Result<Response> {
+            move |_req, _params| Ok(http::Response::new(Some(body.into())))
+        }
+
+        let mut router = Router::default();
+        router
+            .get("/items/:id", respond("beta"))
+            .when(|req| req.headers().contains_key("x-beta"));
+        router.get("/items/:x", respond("stable"));
+        router
+            .get("/admin", respond("admin"))
+            .when(|req| req.headers().contains_key("x-admin"));
+
+        let mut req = make_request(http::Method::GET, "/items/1");
+        req.headers_mut().insert("x-beta", "1".parse().unwrap());
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "beta");
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/items/1"))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "stable");
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/admin"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_content_type_dispatch() {
+        fn respond(body: &'static str) -> impl Fn(Request, Params) -> Result<Response> {
+            move |_req, _params| Ok(http::Response::new(Some(body.into())))
+        }
+        fn post(content_type: Option<&str>) -> Request {
+            let mut req = make_request(http::Method::POST, "/upload");
+            if let Some(content_type) = content_type {
+                req.headers_mut()
+                    .insert(http::header::CONTENT_TYPE, content_type.parse().unwrap());
+            }
+            req
+        }
+
+        let mut router = Router::default();
+        router
+            .post("/upload", respond("json"))
+            .consumes("application/json");
+        router
+            .post("/upload", respond("form"))
+            .consumes("application/x-www-form-urlencoded")
+            .consumes("multipart/*");
+
+        let res = router.handle(post(Some("application/json"))).unwrap();
+        assert_eq!(res.into_body().unwrap(), "json");
+        let res = router
+            .handle(post(Some("Multipart/Form-Data; boundary=x")))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "form");
+
+        let res = router.handle(post(Some("text/plain"))).unwrap();
+        assert_eq!(res.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let res = router.handle(post(None)).unwrap();
+        assert_eq!(res.status(), http::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let res = router
+            .handle(make_request(http::Method::GET, "/upload"))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn test_accept_negotiation() {
+        fn respond(body: &'static str) -> impl Fn(Request, Params) -> Result<Response> {
+            move |_req, _params| Ok(http::Response::new(Some(body.into())))
+        }
+        fn get(accept: Option<&str>) -> Request {
+            let mut req = make_request(http::Method::GET, "/report");
+            if let Some(accept) = accept {
+                req.headers_mut()
+                    .insert(http::header::ACCEPT, accept.parse().unwrap());
+            }
+            req
+        }
+
+        let mut router = Router::default();
+        router
+            .get("/report", respond("json"))
+            .produces("application/json");
+        router.get("/report", respond("csv")).produces("text/csv");
+        router.get("/report", respond("html")).produces("text/html");
+
+        let res = router.handle(get(Some("text/html, text/*;q=0.8"))).unwrap();
+        assert_eq!(res.headers()[http::header::VARY], "accept");
+        assert_eq!(res.into_body().unwrap(), "html");
+        let res = router.handle(get(Some("text/*"))).unwrap();
+        assert_eq!(res.into_body().unwrap(), "csv");
+        let res = router.handle(get(None)).unwrap();
+        assert_eq!(res.into_body().unwrap(), "json");
+
+        let res = router.handle(get(Some("image/png"))).unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_ACCEPTABLE);
+    }
+
+    #[test]
+    fn test_device_variants() {
+        fn respond(body: &'static str) -> impl Fn(Request, Params) -> Result<Response> {
+            move |_req, _params| Ok(http::Response::new(Some(body.into())))
+        }
+
+        let mut router = Router::default();
+        router
+            .get("/", respond("mobile"))
+            .device(negotiate::DeviceClass::Mobile);
+        router.get("/", respond("desktop"));
+
+        let mut req = make_request(http::Method::GET, "/");
+        req.headers_mut()
+            .insert(negotiate::UA_MOBILE, "?1".parse().unwrap());
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "mobile");
+
+        let res = router.handle(make_request(http::Method::GET, "/")).unwrap();
+        assert_eq!(
+            res.headers()[http::header::VARY],
+            "sec-ch-ua-mobile, user-agent"
+        );
+        assert_eq!(res.into_body().unwrap(), "desktop");
+    }
+
     #[test]
     fn test_ambiguous_wildcard_vs_star() {
         fn h1(_req: Request, _params: Params) -> Result<Response> {
@@ -0,0 +1,403 @@
+//! Limits on the size of requests and responses and on the memory used by the instance.
+use crate::{AuditEvent, Diagnostic, Middleware, Next, Request, Response, Route};
+use anyhow::Result;
+
+/// What to do with a response body larger than the limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Oversize {
+    /// Replace the response with 500 Internal Server Error.
+    #[default]
+    Error,
+    /// Cut the body down to the limit, dropping the `Content-Length` and `ETag` headers that
+    /// describe the whole body. Bodies with a `Content-Encoding` cannot be cut without
+    /// corrupting them, so they are replaced with 500 Internal Server Error instead.
+    Truncate,
+}
+
+/// Middleware enforcing a maximum response body size, to catch runaway serializations
+/// before they exhaust the memory of the Wasm instance. Oversized responses are reported to
+/// the router's diagnostic sink, set with [`Router::on_diagnostic`](crate::Router::on_diagnostic).
+pub struct ResponseSizeLimit {
+    max: usize,
+    oversize: Oversize,
+}
+
+impl ResponseSizeLimit {
+    /// Allow response bodies of up to `max` bytes, answering larger ones with 500.
+    pub fn new(max: usize) -> Self {
+        ResponseSizeLimit {
+            max,
+            oversize: Oversize::default(),
+        }
+    }
+
+    /// Truncate oversized bodies instead of failing the request.
+    pub fn truncate(mut self) -> Self {
+        self.oversize = Oversize::Truncate;
+        self
+    }
+}
+
+impl Middleware for ResponseSizeLimit {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        let audit_mode = next.audit_mode();
+        let router = next.router();
+        let mut res = next.run(req)?;
+        let size = res.body().as_ref().map_or(0, |body| body.len());
+        if size <= self.max {
+            return Ok(res);
+        }
+        if audit_mode {
+            router.audit(&AuditEvent {
+                guard: "response size limit",
+                method: &method,
+                path: &path,
+                status: http::StatusCode::INTERNAL_SERVER_ERROR,
+                reason: &format!("the body is {size} bytes, over the limit of {}", self.max),
+            });
+            return Ok(res);
+        }
+        router.report(&Diagnostic {
+            source: "response size limit",
+            message: &format!(
+                "response to {method} {path} is {size} bytes, over the limit of {}",
+                self.max
+            ),
+        });
+        let encoded = res.headers().contains_key(http::header::CONTENT_ENCODING);
+        match self.oversize {
+            Oversize::Truncate if !encoded => {
+                if let Some(body) = res.body_mut() {
+                    body.truncate(self.max);
+                }
+                res.headers_mut().remove(http::header::CONTENT_LENGTH);
+                res.headers_mut().remove(http::header::ETAG);
+                Ok(res)
+            }
+            Oversize::Error | Oversize::Truncate => Ok(http::Response::builder()
+                .status(http::StatusCode::INTERNAL_SERVER_ERROR)
+                .body(None)?),
+        }
+    }
+}
+
+/// Middleware rejecting requests whose body is larger than a limit with 413 Payload Too
+/// Large before they reach the handler. Both the declared `Content-Length` and the actual
+/// body are checked.
+///
+/// The limit is enforced once the request has been matched to a route, so that routes
+/// expecting larger or smaller bodies can override it with [`Route::max_body_size`].
+pub struct RequestSizeLimit {
+    max: usize,
+}
+
+/// The request body limit set by [`RequestSizeLimit`], carried in the request extensions
+/// until the route is known.
+#[derive(Clone, Copy)]
+pub(crate) struct BodyLimit(pub(crate) usize);
+
+impl RequestSizeLimit {
+    /// Allow request bodies of up to `max` bytes.
+    pub fn new(max: usize) -> Self {
+        RequestSizeLimit { max }
+    }
+}
+
+impl Middleware for RequestSizeLimit {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        req.extensions_mut().insert(BodyLimit(self.max));
+        next.run(req)
+    }
+}
+
+/// What a route does with request bodies.
+///
+/// Spin hands the component the whole request body up front, so bodies cannot be streamed
+/// to handlers; what a route can avoid is keeping, checking and passing on a body it never
+/// reads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyMode {
+    /// Pass the body to the handler, subject to the body size limit.
+    #[default]
+    Buffered,
+    /// Drop the body before the handler runs, without checking its size, so that e.g. a
+    /// large upload sent to a route that never reads it is neither rejected nor kept.
+    Ignored,
+}
+
+impl Route {
+    /// Allow request bodies of up to `bytes` bytes for the route, instead of the limit set by
+    /// [`RequestSizeLimit`]. Larger bodies are answered with 413 Payload Too Large.
+    pub fn max_body_size(&mut self, bytes: usize) -> &mut Self {
+        self.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Set what the route does with request bodies; see [`BodyMode`].
+    pub fn body_mode(&mut self, mode: BodyMode) -> &mut Self {
+        self.body_mode = mode;
+        self
+    }
+}
+
+/// Whether the request's body, or the length it declares, exceeds `max` bytes.
+pub(crate) fn body_too_large(req: &Request, max: usize) -> bool {
+    let declared = req
+        .headers()
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .unwrap_or(0);
+    let actual = req.body().as_ref().map_or(0, |body| body.len());
+    declared > max as u64 || actual > max
+}
+
+/// The size of the instance's linear memory in bytes. Outside Wasm there is no linear
+/// memory to measure, so this is always 0 there.
+pub fn linear_memory_size() -> usize {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) * 65536
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
+
+/// Middleware checking approximate memory usage around each request, to diagnose memory
+/// growth in long-lived instances.
+///
+/// Before the handler runs, usage above the rejection threshold answers the request with
+/// 503 Service Unavailable. After it runs, crossing the warning threshold, or growing by
+/// more than the growth threshold during the request, is reported to the router's
+/// diagnostic sink, set with [`Router::on_diagnostic`](crate::Router::on_diagnostic).
+pub struct MemoryGuard {
+    warn_above: Option<usize>,
+    reject_above: Option<usize>,
+    warn_growth: Option<usize>,
+    probe: fn() -> usize,
+}
+
+impl Default for MemoryGuard {
+    fn default() -> Self {
+        MemoryGuard {
+            warn_above: None,
+            reject_above: None,
+            warn_growth: None,
+            probe: linear_memory_size,
+        }
+    }
+}
+
+impl MemoryGuard {
+    /// Construct a guard without thresholds, measuring [`linear_memory_size`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report requests after which memory usage exceeds `bytes`.
+    pub fn warn_above(mut self, bytes: usize) -> Self {
+        self.warn_above = Some(bytes);
+        self
+    }
+
+    /// Reject requests with 503 while memory usage exceeds `bytes`.
+    pub fn reject_above(mut self, bytes: usize) -> Self {
+        self.reject_above = Some(bytes);
+        self
+    }
+
+    /// Report requests during which memory usage grows by more than `bytes`.
+    pub fn warn_growth(mut self, bytes: usize) -> Self {
+        self.warn_growth = Some(bytes);
+        self
+    }
+
+    /// Measure memory usage with `probe` instead, e.g. with an allocator's statistics.
+    pub fn probe(mut self, probe: fn() -> usize) -> Self {
+        self.probe = probe;
+        self
+    }
+}
+
+impl Middleware for MemoryGuard {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let before = (self.probe)();
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        if self.reject_above.is_some_and(|max| before > max) && next.audit_mode() {
+            next.audit(&AuditEvent {
+                guard: "memory guard",
+                method: &method,
+                path: &path,
+                status: http::StatusCode::SERVICE_UNAVAILABLE,
+                reason: &format!("memory usage of {before} bytes is over the limit"),
+            });
+        } else if self.reject_above.is_some_and(|max| before > max) {
+            next.report(&Diagnostic {
+                source: "memory guard",
+                message: &format!(
+                    "rejecting {method} {path}: memory usage of {before} bytes is over the limit"
+                ),
+            });
+            return Ok(http::Response::builder()
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body(None)?);
+        }
+        let router = next.router();
+        let res = next.run(req);
+        let after = (self.probe)();
+        let growth = after.saturating_sub(before);
+        if self.warn_above.is_some_and(|max| after > max)
+            || self.warn_growth.is_some_and(|max| growth > max)
+        {
+            router.report(&Diagnostic {
+                source: "memory guard",
+                message: &format!(
+                    "{method} {path} left memory usage at {after} bytes, up {growth} bytes"
+                ),
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Router;
+
+    fn limited(limit: ResponseSizeLimit) -> Router {
+        let mut router = Router::new();
+        router.get("/small", |_req, _params| {
+            Ok(http::Response::new(Some("tiny".into())))
+        });
+        router.get("/large", |_req, _params| {
+            Ok(http::Response::builder()
+                .header(http::header::ETAG, "\"abc\"")
+                .header(http::header::CONTENT_LENGTH, "100")
+                .body(Some("a".repeat(100).into()))?)
+        });
+        router.get("/encoded", |_req, _params| {
+            Ok(http::Response::builder()
+                .header(http::header::CONTENT_ENCODING, "gzip")
+                .body(Some("a".repeat(100).into()))?)
+        });
+        router.layer(limit);
+        router
+    }
+
+    fn get(router: &Router, path: &str) -> Response {
+        let req = http::Request::builder().uri(path).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_response_size_limit() {
+        let router = limited(ResponseSizeLimit::new(10));
+        assert_eq!(get(&router, "/small").status(), http::StatusCode::OK);
+        assert_eq!(
+            get(&router, "/large").status(),
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let mut router = limited(ResponseSizeLimit::new(10).truncate());
+        let reported = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = reported.clone();
+        router.on_diagnostic(move |diagnostic| sink.borrow_mut().push(diagnostic.to_string()));
+        let res = get(&router, "/large");
+        assert!(!res.headers().contains_key(http::header::ETAG));
+        assert!(!res.headers().contains_key(http::header::CONTENT_LENGTH));
+        assert_eq!(res.into_body().unwrap(), "a".repeat(10));
+        assert_eq!(
+            get(&router, "/encoded").status(),
+            http::StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert_eq!(
+            *reported.borrow(),
+            [
+                "response size limit: response to GET /large is 100 bytes, over the limit of 10",
+                "response size limit: response to GET /encoded is 100 bytes, over the limit of 10",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_size_limit() {
+        let mut router = Router::new();
+        router.post("/small", |_req, _params| Ok(http::Response::new(None)));
+        router
+            .post("/upload", |_req, _params| Ok(http::Response::new(None)))
+            .max_body_size(100);
+        router.layer(RequestSizeLimit::new(10));
+
+        let post = |path: &str, body: &str| {
+            let req = http::Request::builder()
+                .method(http::Method::POST)
+                .uri(path)
+                .body(Some(body.to_owned().into()))
+                .unwrap();
+            router.handle(req).unwrap().status()
+        };
+        assert_eq!(post("/small", "tiny"), http::StatusCode::OK);
+        assert_eq!(
+            post("/small", &"a".repeat(11)),
+            http::StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(post("/upload", &"a".repeat(100)), http::StatusCode::OK);
+        assert_eq!(
+            post("/upload", &"a".repeat(101)),
+            http::StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri("/small")
+            .header(http::header::CONTENT_LENGTH, "1000")
+            .body(None)
+            .unwrap();
+        assert_eq!(
+            router.handle(req).unwrap().status(),
+            http::StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[test]
+    fn test_ignored_body() {
+        let mut router = Router::new();
+        router
+            .post("/ping", |req: Request, _params| {
+                let body = format!("{:?} {:?}", req.body(), req.headers().get("content-length"));
+                Ok(http::Response::new(Some(body.into())))
+            })
+            .body_mode(BodyMode::Ignored);
+        router.layer(RequestSizeLimit::new(10));
+
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri("/ping")
+            .header(http::header::CONTENT_LENGTH, "1000")
+            .body(Some("a".repeat(1000).into()))
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "None None");
+    }
+
+    #[test]
+    fn test_memory_guard() {
+        let mut router = Router::new();
+        router.get("/", |_req, _params| Ok(http::Response::new(None)));
+        router.layer(MemoryGuard::new().reject_above(1 << 20).probe(|| 2 << 20));
+        assert_eq!(
+            get(&router, "/").status(),
+            http::StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let mut router = Router::new();
+        router.get("/", |_req, _params| Ok(http::Response::new(None)));
+        router.layer(MemoryGuard::new().reject_above(4 << 20).probe(|| 2 << 20));
+        assert_eq!(get(&router, "/").status(), http::StatusCode::OK);
+    }
+}
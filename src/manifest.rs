@@ -0,0 +1,365 @@
+//! Serialized route manifests, for inspecting a component's route table without running it.
+//!
+//! A build script can construct the component's router natively and write its manifest to
+//! `OUT_DIR`, from where [`embed_manifest!`](crate::embed_manifest) embeds it in the Wasm
+//! binary:
+//!
+//! ```ignore
+//! // build.rs
+//! #[path = "src/routes.rs"]
+//! mod routes;
+//!
+//! fn main() -> anyhow::Result<()> {
+//!     Manifest::of(&routes::router()).write_to_out_dir("routes.json")?;
+//!     Ok(())
+//! }
+//!
+//! // lib.rs
+//! spin_sdk_router::embed_manifest!(concat!(env!("OUT_DIR"), "/routes.json"));
+//! ```
+//!
+//! The `spin-routes` binary, built with the `cli` feature, reads manifests from JSON files and
+//! from the custom section of Wasm binaries.
+use crate::{Params, Request, Response, RouteReport, Router};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::{fmt, path::PathBuf};
+
+/// The name of the Wasm custom section [`embed_manifest!`](crate::embed_manifest) embeds the
+/// manifest in.
+pub const SECTION: &str = "spin-routes";
+
+/// Embed a route manifest file in the Wasm binary, in the [`SECTION`] custom section where
+/// [`Manifest::from_wasm`] finds it. Outside Wasm the manifest is kept in an ordinary static.
+#[macro_export]
+macro_rules! embed_manifest {
+    ($path:expr) => {
+        const _: () = {
+            #[cfg_attr(target_arch = "wasm32", link_section = "spin-routes")]
+            #[used]
+            static MANIFEST: [u8; include_bytes!($path).len()] = *include_bytes!($path);
+        };
+    };
+}
+
+/// A route in a [`Manifest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The route's method, or `None` if it matches all methods.
+    pub method: Option<String>,
+    /// The route's pattern.
+    pub pattern: String,
+    /// The handler name, unless the handler is an anonymous closure.
+    pub handler: Option<String>,
+    /// The first paragraph of the route's documentation.
+    pub summary: Option<String>,
+    /// Whether the route only matches some requests for its pattern.
+    pub conditional: bool,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = self.method.as_deref().unwrap_or("*");
+        write!(f, "{method} {}", self.pattern)?;
+        if let Some(handler) = &self.handler {
+            write!(f, " -> {handler}")?;
+        }
+        if self.conditional {
+            write!(f, " (conditional)")?;
+        }
+        Ok(())
+    }
+}
+
+/// A difference between two manifests found by [`Manifest::diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A route only in the newer manifest.
+    Added(Entry),
+    /// A route only in the older manifest.
+    Removed(Entry),
+    /// A route for the same method and pattern whose handler or metadata changed.
+    Changed {
+        /// The route in the older manifest.
+        before: Entry,
+        /// The route in the newer manifest.
+        after: Entry,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(entry) => write!(f, "+ {entry}"),
+            Change::Removed(entry) => write!(f, "- {entry}"),
+            Change::Changed { before, after } => write!(f, "~ {before}\n  {after}"),
+        }
+    }
+}
+
+/// The route table of a router in a serializable form, in registration order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Manifest {
+    /// The routes.
+    pub routes: Vec<Entry>,
+}
+
+impl Manifest {
+    /// The manifest of a router's routes.
+    pub fn of(router: &Router) -> Self {
+        let routes = router
+            .routes()
+            .map(|route| Entry {
+                method: route.method().map(ToString::to_string),
+                pattern: route.registered_pattern(),
+                handler: route.handler_name().map(str::to_owned),
+                summary: route.summary(),
+                conditional: route.is_conditional(),
+            })
+            .collect();
+        Manifest { routes }
+    }
+
+    /// Parse a manifest serialized with [`Manifest::to_json`].
+    pub fn from_json(json: &str) -> Result<Self> {
+        let document: Value = serde_json::from_str(json).context("invalid route manifest")?;
+        let routes = document
+            .get("routes")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("route manifest has no `routes` array"))?;
+        let string = |route: &Value, key| route.get(key).and_then(Value::as_str).map(str::to_owned);
+        let routes = routes
+            .iter()
+            .map(|route| {
+                Ok(Entry {
+                    method: string(route, "method"),
+                    pattern: string(route, "pattern")
+                        .ok_or_else(|| anyhow!("route without a pattern in manifest"))?,
+                    handler: string(route, "handler"),
+                    summary: string(route, "summary"),
+                    conditional: route.get("conditional").and_then(Value::as_bool) == Some(true),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Manifest { routes })
+    }
+
+    /// Serialize the manifest to JSON.
+    pub fn to_json(&self) -> String {
+        let routes: Vec<_> = self
+            .routes
+            .iter()
+            .map(|entry| {
+                json!({
+                    "method": entry.method,
+                    "pattern": entry.pattern,
+                    "handler": entry.handler,
+                    "summary": entry.summary,
+                    "conditional": entry.conditional,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({ "routes": routes })).unwrap()
+    }
+
+    /// Write the manifest as JSON to `file_name` in the `OUT_DIR` of the build script being
+    /// run, returning the path written to.
+    pub fn write_to_out_dir(&self, file_name: &str) -> Result<PathBuf> {
+        let out_dir = std::env::var_os("OUT_DIR").context("OUT_DIR is not set")?;
+        let path = PathBuf::from(out_dir).join(file_name);
+        std::fs::write(&path, self.to_json())
+            .with_context(|| format!("cannot write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Read the manifest embedded in a Wasm binary by [`embed_manifest!`](crate::embed_manifest).
+    pub fn from_wasm(module: &[u8]) -> Result<Self> {
+        let Some(mut rest) = module.strip_prefix(b"\0asm") else {
+            bail!("not a Wasm binary");
+        };
+        rest = rest.get(4..).context("truncated Wasm binary")?;
+        while let Some((&id, tail)) = rest.split_first() {
+            let (size, tail) = leb128(tail)?;
+            let (section, tail) = tail
+                .split_at_checked(size)
+                .context("truncated Wasm section")?;
+            rest = tail;
+            if id != 0 {
+                continue;
+            }
+            let (name_len, section) = leb128(section)?;
+            let (name, contents) = section
+                .split_at_checked(name_len)
+                .context("truncated Wasm custom section")?;
+            if name == SECTION.as_bytes() {
+                return Self::from_json(std::str::from_utf8(contents)?);
+            }
+        }
+        bail!("no `{SECTION}` section in Wasm binary")
+    }
+
+    /// Check the routes as [`Router::check`] would, failing if a method or pattern is
+    /// invalid.
+    pub fn validate(&self) -> Result<RouteReport> {
+        fn placeholder(_req: Request, _params: Params) -> Result<Response> {
+            unreachable!("manifest routes are never dispatched to")
+        }
+
+        let mut router = Router::new();
+        for entry in &self.routes {
+            let route = match &entry.method {
+                Some(method) => {
+                    let method = http::Method::from_bytes(method.as_bytes())
+                        .with_context(|| format!("invalid method in {entry}"))?;
+                    router.try_add(&entry.pattern, method, placeholder)?
+                }
+                None => router.try_all(&entry.pattern, placeholder)?,
+            };
+            if entry.conditional {
+                route.when(|_| true);
+            }
+        }
+        Ok(router.check())
+    }
+
+    /// The changes from this manifest to `newer`, matching routes by method and pattern.
+    pub fn diff(&self, newer: &Manifest) -> Vec<Change> {
+        let key = |entry: &Entry| (entry.method.clone(), entry.pattern.clone());
+        let mut changes = Vec::new();
+        for before in &self.routes {
+            match newer.routes.iter().find(|after| key(after) == key(before)) {
+                None => changes.push(Change::Removed(before.clone())),
+                Some(after) if after != before => changes.push(Change::Changed {
+                    before: before.clone(),
+                    after: after.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for after in &newer.routes {
+            if !self.routes.iter().any(|before| key(before) == key(after)) {
+                changes.push(Change::Added(after.clone()));
+            }
+        }
+        changes
+    }
+}
+
+/// Reads an unsigned LEB128 integer, returning it and the remaining bytes.
+fn leb128(bytes: &[u8]) -> Result<(usize, &[u8])> {
+    let mut value = 0usize;
+    for (i, byte) in bytes.iter().enumerate().take(5) {
+        value |= usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+    bail!("invalid LEB128 integer in Wasm binary")
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.routes {
+            write!(f, "{entry}")?;
+            if let Some(summary) = &entry.summary {
+                write!(f, "  # {summary}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show_user(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::new(None))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut router = Router::new();
+        router.get("/users/:id", show_user).doc("Show a user.");
+        router.all("/*", |_req, _params| Ok(http::Response::new(None)));
+
+        let manifest = Manifest::of(&router);
+        assert_eq!(
+            manifest.to_string(),
+            "GET /users/:id -> spin_sdk_router::manifest::tests::show_user  # Show a user.\n* /*\n"
+        );
+        assert_eq!(Manifest::from_json(&manifest.to_json()).unwrap(), manifest);
+    }
+
+    crate::embed_manifest!("manifest.rs");
+
+    #[test]
+    fn test_from_wasm() {
+        let json = r#"{ "routes": [{ "method": "GET", "pattern": "/" }] }"#;
+        let mut custom = vec![SECTION.len() as u8];
+        custom.extend(SECTION.as_bytes());
+        custom.extend(json.as_bytes());
+
+        let mut module = b"\0asm\x01\0\0\0".to_vec();
+        // A type section with no types, then the custom section.
+        module.extend([1, 1, 0, 0]);
+        module.extend([custom.len() as u8]);
+        module.extend(custom);
+
+        let manifest = Manifest::from_wasm(&module).unwrap();
+        assert_eq!(manifest.routes[0].pattern, "/");
+        assert!(Manifest::from_wasm(&module[..12]).is_err());
+        assert!(Manifest::from_wasm(b"{}").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let manifest = Manifest::from_json(
+            r#"{ "routes": [
+                { "method": "GET", "pattern": "/users/:id" },
+                { "method": "GET", "pattern": "/users/:name" }
+            ] }"#,
+        )
+        .unwrap();
+        let report = manifest.validate().unwrap();
+        assert_eq!(
+            report.to_string(),
+            "GET /users/:name is unreachable, shadowed by /users/:id\n"
+        );
+
+        let invalid = Manifest::from_json(r#"{ "routes": [{ "method": "G T", "pattern": "/" }] }"#);
+        assert!(invalid.unwrap().validate().is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let entry = |pattern: &str, handler: &str| Entry {
+            method: Some("GET".to_owned()),
+            pattern: pattern.to_owned(),
+            handler: Some(handler.to_owned()),
+            summary: None,
+            conditional: false,
+        };
+        let before = Manifest {
+            routes: vec![entry("/a", "a"), entry("/b", "b")],
+        };
+        let after = Manifest {
+            routes: vec![entry("/a", "a2"), entry("/c", "c")],
+        };
+        let changes: Vec<_> = before
+            .diff(&after)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                "~ GET /a -> a\n  GET /a -> a2",
+                "- GET /b -> b",
+                "+ GET /c -> c"
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+}
@@ -0,0 +1,146 @@
+//! Matrix parameters within path segments, such as `/items;sort=price;dir=asc/42`.
+use crate::{files::percent_decode, Middleware, Next, Request, Response};
+use anyhow::Result;
+
+/// The matrix parameters of a request path, inserted into the request extensions by
+/// [`MatrixParams`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Matrix {
+    /// Each segment of the path without its parameters, with its `(name, value)` pairs.
+    segments: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Matrix {
+    /// The matrix parameters of `req`, if [`MatrixParams`] parsed them.
+    pub fn of(req: &Request) -> Option<&Matrix> {
+        req.extensions().get()
+    }
+
+    /// Parses the segments of `path`, returning the path without the parameters.
+    fn parse(path: &str) -> (String, Matrix) {
+        let mut stripped = Vec::new();
+        let mut matrix = Matrix::default();
+        for segment in path.split('/').skip(1) {
+            let mut parts = segment.split(';');
+            let name = parts.next().unwrap_or_default();
+            let params = parts
+                .filter(|part| !part.is_empty())
+                .map(|part| {
+                    let (key, value) = part.split_once('=').unwrap_or((part, ""));
+                    let decode = |s: &str| percent_decode(s).unwrap_or_else(|| s.to_owned());
+                    (decode(key), decode(value))
+                })
+                .collect();
+            stripped.push(name);
+            matrix.segments.push((name.to_owned(), params));
+        }
+        (format!("/{}", stripped.join("/")), matrix)
+    }
+
+    /// The first value of the parameter `name` of the segment `segment`, e.g.
+    /// `get("items", "sort")` is `price` for `/items;sort=price;dir=asc/42`. Parameters without
+    /// a value, such as `;expand`, have an empty one.
+    pub fn get(&self, segment: &str, name: &str) -> Option<&str> {
+        self.segments
+            .iter()
+            .filter(|(s, _)| s == segment)
+            .flat_map(|(_, params)| params)
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The `(name, value)` parameters of the segment at `index`, counting from zero after the
+    /// leading slash.
+    pub fn segment(&self, index: usize) -> impl Iterator<Item = (&str, &str)> {
+        self.segments
+            .get(index)
+            .into_iter()
+            .flat_map(|(_, params)| params)
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Whether no segment has parameters.
+    pub fn is_empty(&self) -> bool {
+        self.segments.iter().all(|(_, params)| params.is_empty())
+    }
+}
+
+/// Middleware stripping matrix parameters from request paths before routing and inserting
+/// them into the request extensions as a [`Matrix`]:
+///
+/// ```
+/// # use spin_sdk_router::{matrix::{Matrix, MatrixParams}, Params, Request, Router};
+/// let mut router = Router::new();
+/// router.layer(MatrixParams);
+/// router.get("/items/:id", |req: Request, params: Params| {
+///     // For `/items;sort=price;dir=asc/42`, `id` is `42` and `sort` is `price`.
+///     let sort = Matrix::of(&req).and_then(|m| m.get("items", "sort"));
+///     Ok(http::Response::new(sort.map(|sort| sort.to_owned().into())))
+/// });
+/// ```
+///
+/// Parameter names and values are percent-decoded. Routes then match the path without the
+/// parameters, so `/items;sort=price/42` is dispatched to `/items/:id`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MatrixParams;
+
+impl Middleware for MatrixParams {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        if req.uri().path().contains(';') {
+            let (path, matrix) = Matrix::parse(req.uri().path());
+            let target = match req.uri().query() {
+                Some(query) => format!("{path}?{query}"),
+                None => path,
+            };
+            let mut parts = req.uri().clone().into_parts();
+            parts.path_and_query = Some(target.parse()?);
+            *req.uri_mut() = http::Uri::from_parts(parts)?;
+            req.extensions_mut().insert(matrix);
+        }
+        next.run(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    #[test]
+    fn test_parse() {
+        let (path, matrix) = Matrix::parse("/items;sort=price;dir=asc/42;expand;q=a%20b");
+        assert_eq!(path, "/items/42");
+        assert_eq!(matrix.get("items", "sort"), Some("price"));
+        assert_eq!(matrix.get("items", "dir"), Some("asc"));
+        assert_eq!(matrix.get("items", "expand"), None);
+        assert_eq!(
+            matrix.segment(1).collect::<Vec<_>>(),
+            [("expand", ""), ("q", "a b")]
+        );
+        assert_eq!(matrix.segment(2).count(), 0);
+        assert!(!matrix.is_empty());
+        assert!(Matrix::parse("/items/42").1.is_empty());
+    }
+
+    #[test]
+    fn test_matrix_params() {
+        let mut router = Router::new();
+        router.layer(MatrixParams);
+        router.get("/items/:id", |req: Request, params: Params| {
+            let matrix = Matrix::of(&req).unwrap();
+            let body = format!(
+                "{} {} {}",
+                params.get("id").unwrap(),
+                matrix.get("items", "dir").unwrap(),
+                req.uri()
+            );
+            Ok(http::Response::new(Some(body.into())))
+        });
+        let req = http::Request::builder()
+            .uri("/items;sort=price;dir=asc/42?page=2")
+            .body(None)
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "42 asc /items/42?page=2");
+    }
+}
@@ -0,0 +1,140 @@
+//! Method overrides, letting HTML forms and restricted clients reach PUT, PATCH and DELETE
+//! routes with POST requests.
+use crate::{route::query_values, Middleware, Next, Request, Response};
+use anyhow::Result;
+
+/// Middleware dispatching POST requests with the method named by their
+/// `X-HTTP-Method-Override` header, and optionally by their `_method` form field:
+///
+/// ```
+/// # use spin_sdk_router::{methodoverride::MethodOverride, Params, Router};
+/// let mut router = Router::new();
+/// router.delete("/posts/:id", |_req, _params: Params| Ok(http::Response::new(None)));
+/// router.layer(MethodOverride::new().form_field());
+/// ```
+///
+/// Only PUT, PATCH and DELETE can be asked for unless [`MethodOverride::allow`] adds others.
+/// Other requests, and overrides naming a method that is not allowed, are left as they are.
+pub struct MethodOverride {
+    header: http::header::HeaderName,
+    form_field: Option<String>,
+    allowed: Vec<http::Method>,
+}
+
+impl Default for MethodOverride {
+    fn default() -> Self {
+        MethodOverride {
+            header: http::header::HeaderName::from_static("x-http-method-override"),
+            form_field: None,
+            allowed: vec![http::Method::PUT, http::Method::PATCH, http::Method::DELETE],
+        }
+    }
+}
+
+impl MethodOverride {
+    /// Honor the `X-HTTP-Method-Override` header, for PUT, PATCH and DELETE.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the method from the header `name` instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn header(mut self, name: &str) -> Self {
+        self.header = name.parse().expect("invalid header name");
+        self
+    }
+
+    /// Also honor the `_method` field of URL-encoded form bodies, as sent by HTML forms.
+    pub fn form_field(self) -> Self {
+        self.form_field_named("_method")
+    }
+
+    /// Also honor the field `name` of URL-encoded form bodies.
+    pub fn form_field_named(mut self, name: &str) -> Self {
+        self.form_field = Some(name.to_owned());
+        self
+    }
+
+    /// Let overrides ask for `method` as well.
+    pub fn allow(mut self, method: http::Method) -> Self {
+        self.allowed.push(method);
+        self
+    }
+
+    /// The method `req` asks to be dispatched with, from its header or else its form body.
+    fn requested(&self, req: &Request) -> Option<http::Method> {
+        let from_header = || {
+            req.headers()
+                .get(&self.header)?
+                .to_str()
+                .ok()
+                .map(str::to_owned)
+        };
+        let from_form = || {
+            let field = self.form_field.as_deref()?;
+            let content_type = req.headers().get(http::header::CONTENT_TYPE)?;
+            let essence = content_type.to_str().ok()?.split(';').next()?.trim();
+            if !essence.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
+                return None;
+            }
+            let body = std::str::from_utf8(req.body().as_deref()?).ok()?;
+            query_values(body, field).next()
+        };
+        let method = from_header().or_else(from_form)?;
+        let method =
+            http::Method::from_bytes(method.trim().to_ascii_uppercase().as_bytes()).ok()?;
+        self.allowed.contains(&method).then_some(method)
+    }
+}
+
+impl Middleware for MethodOverride {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        if req.method() == http::Method::POST {
+            if let Some(method) = self.requested(&req) {
+                *req.method_mut() = method;
+            }
+        }
+        next.run(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    #[test]
+    fn test_method_override() {
+        let mut router = Router::new();
+        let echo = |req: Request, _params: Params| {
+            Ok(http::Response::new(Some(req.method().to_string().into())))
+        };
+        router.post("/posts/1", echo);
+        router.delete("/posts/1", echo);
+        router.put("/posts/1", echo);
+        router.get("/posts/1", echo);
+        router.layer(MethodOverride::new().form_field());
+        let send = |method: &str, header: Option<&str>, form: Option<&str>| {
+            let mut req = http::Request::builder().method(method).uri("/posts/1");
+            if let Some(method) = header {
+                req = req.header("x-http-method-override", method);
+            }
+            if form.is_some() {
+                req = req.header(
+                    http::header::CONTENT_TYPE,
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                );
+            }
+            let req = req.body(form.map(|form| form.to_owned().into())).unwrap();
+            router.handle(req).unwrap().into_body().unwrap()
+        };
+        assert_eq!(send("POST", Some("delete"), None), "DELETE");
+        assert_eq!(send("POST", None, Some("title=x&_method=PUT")), "PUT");
+        assert_eq!(send("POST", Some("GET"), None), "POST");
+        assert_eq!(send("GET", Some("DELETE"), None), "GET");
+        assert_eq!(send("POST", None, Some("title=x")), "POST");
+    }
+}
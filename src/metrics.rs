@@ -0,0 +1,494 @@
+//! Request metrics with bounded label cardinality.
+use crate::{Middleware, Next, Request, Response, Router};
+use anyhow::Result;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The route pattern a response was produced for, stored in the response extensions by the
+/// router so that middleware can label requests by route rather than by path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedPattern(pub String);
+
+/// Where the router records the pattern a request matched, stored in the request
+/// extensions by [`Metrics`] so that a failing request can be labelled by route too.
+#[derive(Clone, Default)]
+pub(crate) struct PatternSlot(Arc<Mutex<Option<String>>>);
+
+impl PatternSlot {
+    pub(crate) fn set(&self, pattern: &str) {
+        *self.0.lock().unwrap() = Some(pattern.to_owned());
+    }
+
+    fn take(&self) -> Option<String> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// The labels requests are counted under.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Labels {
+    /// The request method.
+    pub method: String,
+    /// The route pattern, the request path, or a bucket label.
+    pub route: String,
+    /// The response status code.
+    pub status: u16,
+}
+
+/// The totals recorded for a set of labels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Totals {
+    /// The number of requests.
+    pub count: u64,
+    /// The total time spent handling the requests.
+    pub duration: Duration,
+    /// The total size of the response bodies in bytes.
+    pub bytes: u64,
+    /// The size of the largest response body in bytes.
+    pub max_bytes: u64,
+    /// The number of requests whose handler failed; these are counted under status 500.
+    pub errors: u64,
+}
+
+/// The distribution of the durations of requests to a route.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    /// The number of requests that took at most each bucket's upper bound in seconds,
+    /// ordered by bound.
+    pub buckets: Vec<(f64, u64)>,
+    /// The total time spent handling the requests.
+    pub sum: Duration,
+    /// The number of requests.
+    pub count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, bounds: &[f64], duration: Duration) {
+        if self.buckets.is_empty() {
+            self.buckets = bounds.iter().map(|bound| (*bound, 0)).collect();
+        }
+        for (bound, count) in &mut self.buckets {
+            if duration.as_secs_f64() <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += duration;
+        self.count += 1;
+    }
+}
+
+/// The default histogram buckets in seconds, those of the Prometheus client libraries.
+const DEFAULT_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Registry {
+    series: BTreeMap<Labels, Totals>,
+    histograms: BTreeMap<(String, String), Histogram>,
+    upstreams: BTreeMap<String, Totals>,
+    upstream_histograms: BTreeMap<String, Histogram>,
+}
+
+/// Middleware counting requests, their durations and response sizes by method, route and
+/// status.
+///
+/// To keep metrics storage bounded, requests are labelled by the matched route pattern
+/// rather than by path, requests matching no route share a single bucket, and the number of
+/// label sets can be capped. Clones share the same counters, so a clone kept aside can read
+/// them after the middleware has been added to a router.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Rc<RefCell<Registry>>,
+    collapse_captures: bool,
+    max_label_sets: Option<usize>,
+    unmatched: String,
+    overflow: String,
+    buckets: Vec<f64>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            registry: Rc::default(),
+            collapse_captures: true,
+            max_label_sets: None,
+            unmatched: "unmatched".to_owned(),
+            overflow: "other".to_owned(),
+            buckets: DEFAULT_BUCKETS.to_vec(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Construct metrics labelling requests by route pattern, without a cap on label sets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to label matched requests by their route pattern (the default) rather than
+    /// by their path, which creates a label set per distinct capture.
+    pub fn collapse_captures(mut self, collapse: bool) -> Self {
+        self.collapse_captures = collapse;
+        self
+    }
+
+    /// Record at most `max` label sets; requests that would add another are counted under
+    /// the overflow route label instead.
+    pub fn max_label_sets(mut self, max: usize) -> Self {
+        self.max_label_sets = Some(max);
+        self
+    }
+
+    /// The route label of requests that matched no route, `unmatched` by default.
+    pub fn unmatched_label(mut self, label: &str) -> Self {
+        self.unmatched = label.to_owned();
+        self
+    }
+
+    /// The route label of requests past the label set cap, `other` by default.
+    pub fn overflow_label(mut self, label: &str) -> Self {
+        self.overflow = label.to_owned();
+        self
+    }
+
+    /// The upper bounds in seconds of the latency histogram buckets, by default those of the
+    /// Prometheus client libraries from 5 ms to 10 s.
+    pub fn buckets(mut self, bounds: &[f64]) -> Self {
+        self.buckets = bounds.to_vec();
+        self.buckets.sort_by(f64::total_cmp);
+        self
+    }
+
+    /// The totals recorded so far, ordered by labels.
+    pub fn snapshot(&self) -> Vec<(Labels, Totals)> {
+        let registry = self.registry.borrow();
+        registry
+            .series
+            .iter()
+            .map(|(labels, totals)| (labels.clone(), *totals))
+            .collect()
+    }
+
+    /// The latency histograms recorded so far by method and route, ordered by them.
+    pub fn histograms(&self) -> Vec<(String, String, Histogram)> {
+        let registry = self.registry.borrow();
+        registry
+            .histograms
+            .iter()
+            .map(|((method, route), histogram)| (method.clone(), route.clone(), histogram.clone()))
+            .collect()
+    }
+
+    /// The metrics recorded so far in the Prometheus text exposition format:
+    /// `http_requests_total` by method, route and status, `http_request_errors_total` by
+    /// method and route, the `http_request_duration_seconds` histogram by method and route,
+    /// and the `upstream_request_duration_seconds` histogram by upstream host.
+    pub fn prometheus(&self) -> String {
+        let registry = self.registry.borrow();
+        let mut text = String::from(
+            "# HELP http_requests_total The number of requests handled.\n\
+             # TYPE http_requests_total counter\n",
+        );
+        for (labels, totals) in &registry.series {
+            text.push_str(&format!(
+                "http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}\n",
+                escape_label(&labels.method),
+                escape_label(&labels.route),
+                labels.status,
+                totals.count
+            ));
+        }
+        text.push_str(
+            "# HELP http_request_errors_total The number of requests whose handler failed.\n\
+             # TYPE http_request_errors_total counter\n",
+        );
+        let mut errors = BTreeMap::<_, u64>::new();
+        for (labels, totals) in &registry.series {
+            *errors.entry((&labels.method, &labels.route)).or_default() += totals.errors;
+        }
+        for ((method, route), count) in errors {
+            text.push_str(&format!(
+                "http_request_errors_total{{method=\"{}\",route=\"{}\"}} {count}\n",
+                escape_label(method),
+                escape_label(route)
+            ));
+        }
+        text.push_str(
+            "# HELP http_request_duration_seconds The time taken to handle requests.\n\
+             # TYPE http_request_duration_seconds histogram\n",
+        );
+        for ((method, route), histogram) in &registry.histograms {
+            let labels = format!(
+                "method=\"{}\",route=\"{}\"",
+                escape_label(method),
+                escape_label(route)
+            );
+            push_histogram(
+                &mut text,
+                "http_request_duration_seconds",
+                &labels,
+                histogram,
+            );
+        }
+        text.push_str(
+            "# HELP upstream_request_duration_seconds The time taken by calls to upstream hosts.\n\
+             # TYPE upstream_request_duration_seconds histogram\n",
+        );
+        for (upstream, histogram) in &registry.upstream_histograms {
+            let labels = format!("upstream=\"{}\"", escape_label(upstream));
+            push_histogram(
+                &mut text,
+                "upstream_request_duration_seconds",
+                &labels,
+                histogram,
+            );
+        }
+        text
+    }
+
+    /// Record a call to the upstream host `upstream` that took `duration`.
+    pub fn record_upstream(&self, upstream: &str, duration: Duration) {
+        let mut registry = self.registry.borrow_mut();
+        let totals = registry.upstreams.entry(upstream.to_owned()).or_default();
+        totals.count += 1;
+        totals.duration += duration;
+        registry
+            .upstream_histograms
+            .entry(upstream.to_owned())
+            .or_default()
+            .observe(&self.buckets, duration);
+    }
+
+    /// The totals recorded so far for calls to upstream hosts, ordered by host.
+    pub fn upstreams(&self) -> Vec<(String, Totals)> {
+        let registry = self.registry.borrow();
+        registry
+            .upstreams
+            .iter()
+            .map(|(upstream, totals)| (upstream.clone(), *totals))
+            .collect()
+    }
+
+    fn record(&self, mut labels: Labels, duration: Duration, bytes: u64, failed: bool) {
+        let mut registry = self.registry.borrow_mut();
+        let full = self
+            .max_label_sets
+            .is_some_and(|max| registry.series.len() >= max);
+        if full && !registry.series.contains_key(&labels) {
+            labels.route = self.overflow.clone();
+        }
+        registry
+            .histograms
+            .entry((labels.method.clone(), labels.route.clone()))
+            .or_default()
+            .observe(&self.buckets, duration);
+        let totals = registry.series.entry(labels).or_default();
+        totals.count += 1;
+        totals.duration += duration;
+        totals.bytes += bytes;
+        totals.max_bytes = totals.max_bytes.max(bytes);
+        totals.errors += u64::from(failed);
+    }
+}
+
+/// Appends the samples of the histogram `name` with `labels` to `text`.
+fn push_histogram(text: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    for (bound, count) in &histogram.buckets {
+        text.push_str(&format!(
+            "{name}_bucket{{{labels},le=\"{bound}\"}} {count}\n"
+        ));
+    }
+    text.push_str(&format!(
+        "{name}_bucket{{{labels},le=\"+Inf\"}} {}\n\
+         {name}_sum{{{labels}}} {}\n\
+         {name}_count{{{labels}}} {}\n",
+        histogram.count,
+        histogram.sum.as_secs_f64(),
+        histogram.count
+    ));
+}
+
+impl Middleware for Metrics {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        let method = req.method().to_string();
+        let path = req.uri().path().to_owned();
+        let slot = PatternSlot::default();
+        req.extensions_mut().insert(slot.clone());
+        let start = Instant::now();
+        let res = match next.run(req) {
+            Ok(res) => res,
+            Err(e) => {
+                let route = match slot.take() {
+                    Some(pattern) if self.collapse_captures => pattern,
+                    Some(_) => path,
+                    None => self.unmatched.clone(),
+                };
+                let labels = Labels {
+                    method,
+                    route,
+                    status: 500,
+                };
+                self.record(labels, start.elapsed(), 0, true);
+                return Err(e);
+            }
+        };
+        let route = match res.extensions().get::<MatchedPattern>() {
+            Some(MatchedPattern(pattern)) if self.collapse_captures => pattern.clone(),
+            Some(_) => path,
+            None => self.unmatched.clone(),
+        };
+        let labels = Labels {
+            method,
+            route,
+            status: res.status().as_u16(),
+        };
+        let bytes = res.body().as_ref().map_or(0, |body| body.len() as u64);
+        self.record(labels, start.elapsed(), bytes, false);
+        Ok(res)
+    }
+}
+
+/// Escapes a Prometheus label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Router {
+    /// Serve the metrics recorded by `metrics`, typically a clone of a layer of this router,
+    /// in the Prometheus text format at `/metrics`.
+    pub fn serve_metrics(&mut self, metrics: &Metrics) {
+        let metrics = metrics.clone();
+        self.builtins.insert(
+            "/metrics",
+            Box::new(move |_router: &Router, _req: Request| -> Result<Response> {
+                Ok(http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(
+                        http::header::CONTENT_TYPE,
+                        "text/plain; version=0.0.4; charset=utf-8",
+                    )
+                    .body(Some(metrics.prometheus().into()))?)
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    fn ok(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::new(Some("ok".into())))
+    }
+
+    fn get(router: &Router, path: &str) {
+        let req = http::Request::builder().uri(path).body(None).unwrap();
+        router.handle(req).unwrap();
+    }
+
+    fn routes(metrics: &Metrics) -> Vec<(String, u16, u64)> {
+        metrics
+            .snapshot()
+            .into_iter()
+            .map(|(labels, totals)| (labels.route, labels.status, totals.count))
+            .collect()
+    }
+
+    #[test]
+    fn test_labels_by_route() {
+        let metrics = Metrics::new();
+        let mut router = Router::new();
+        router.get("/users/:id", ok);
+        router.layer(metrics.clone());
+
+        get(&router, "/users/1");
+        get(&router, "/users/2");
+        get(&router, "/nope/1");
+        get(&router, "/nope/2");
+        assert_eq!(
+            routes(&metrics),
+            [
+                ("/users/:id".to_owned(), 200, 2),
+                ("unmatched".to_owned(), 404, 2)
+            ]
+        );
+        let (_, totals) = &metrics.snapshot()[0];
+        assert_eq!((totals.bytes, totals.max_bytes), (4, 2));
+    }
+
+    #[test]
+    fn test_label_set_cap() {
+        let metrics = Metrics::new().collapse_captures(false).max_label_sets(2);
+        let mut router = Router::new();
+        router.get("/users/:id", ok);
+        router.layer(metrics.clone());
+
+        for path in ["/users/1", "/users/2", "/users/3", "/users/4", "/users/1"] {
+            get(&router, path);
+        }
+        assert_eq!(
+            routes(&metrics),
+            [
+                ("/users/1".to_owned(), 200, 2),
+                ("/users/2".to_owned(), 200, 1),
+                ("other".to_owned(), 200, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prometheus() {
+        let metrics = Metrics::new().buckets(&[10.0, 0.5]);
+        let mut router = Router::new();
+        router.get("/users/:id", ok);
+        router.get("/fail", |_req, _params| anyhow::bail!("boom"));
+        router.layer(metrics.clone());
+        router.serve_metrics(&metrics);
+
+        get(&router, "/users/1");
+        get(&router, "/users/2");
+        let req = http::Request::builder().uri("/fail").body(None).unwrap();
+        assert!(router.handle(req).is_err());
+        metrics.record_upstream("api.example.com", Duration::from_secs(1));
+
+        let req = http::Request::builder().uri("/metrics").body(None).unwrap();
+        let res = router.handle(req).unwrap();
+        let text = String::from_utf8(res.into_body().unwrap().to_vec()).unwrap();
+        let samples: Vec<_> = text
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.contains("_sum"))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                r#"http_requests_total{method="GET",route="/fail",status="500"} 1"#,
+                r#"http_requests_total{method="GET",route="/users/:id",status="200"} 2"#,
+                r#"http_request_errors_total{method="GET",route="/fail"} 1"#,
+                r#"http_request_errors_total{method="GET",route="/users/:id"} 0"#,
+                r#"http_request_duration_seconds_bucket{method="GET",route="/fail",le="0.5"} 1"#,
+                r#"http_request_duration_seconds_bucket{method="GET",route="/fail",le="10"} 1"#,
+                r#"http_request_duration_seconds_bucket{method="GET",route="/fail",le="+Inf"} 1"#,
+                r#"http_request_duration_seconds_count{method="GET",route="/fail"} 1"#,
+                r#"http_request_duration_seconds_bucket{method="GET",route="/users/:id",le="0.5"} 2"#,
+                r#"http_request_duration_seconds_bucket{method="GET",route="/users/:id",le="10"} 2"#,
+                r#"http_request_duration_seconds_bucket{method="GET",route="/users/:id",le="+Inf"} 2"#,
+                r#"http_request_duration_seconds_count{method="GET",route="/users/:id"} 2"#,
+                r#"upstream_request_duration_seconds_bucket{upstream="api.example.com",le="0.5"} 0"#,
+                r#"upstream_request_duration_seconds_bucket{upstream="api.example.com",le="10"} 1"#,
+                r#"upstream_request_duration_seconds_bucket{upstream="api.example.com",le="+Inf"} 1"#,
+                r#"upstream_request_duration_seconds_count{upstream="api.example.com"} 1"#,
+            ]
+        );
+        assert_eq!(escape_label("a\"b\\"), "a\\\"b\\\\");
+    }
+}
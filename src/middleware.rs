@@ -0,0 +1,72 @@
+//! Middleware wrapping the dispatch of every request.
+use crate::{AuditEvent, Diagnostic, Request, Response, Router};
+use anyhow::Result;
+
+/// Middleware wraps the dispatch of a request, running code before the request reaches the
+/// router and after the response is produced.
+pub trait Middleware: 'static {
+    /// Handle the request, calling [`Next::run`] to continue dispatching it.
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response>;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(Request, Next<'_>) -> Result<Response> + 'static,
+{
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        self(req, next)
+    }
+}
+
+/// The remainder of the middleware chain, ending with the router itself.
+pub struct Next<'a> {
+    router: &'a Router,
+    middleware: &'a [Box<dyn Middleware>],
+    endpoint: &'a dyn Fn(Request) -> Result<Response>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(
+        router: &'a Router,
+        middleware: &'a [Box<dyn Middleware>],
+        endpoint: &'a dyn Fn(Request) -> Result<Response>,
+    ) -> Self {
+        Next {
+            router,
+            middleware,
+            endpoint,
+        }
+    }
+
+    /// Report a request the middleware would have rejected to the router's audit sink, set
+    /// with [`Router::on_audit`].
+    pub fn audit(&self, event: &AuditEvent<'_>) {
+        self.router.audit(event)
+    }
+
+    /// Whether the router runs in [audit mode](Router::audit_mode), in which middleware
+    /// enforcing a policy reports the requests it would have rejected with [`Next::audit`]
+    /// and passes them on instead.
+    pub fn audit_mode(&self) -> bool {
+        self.router.audit_mode
+    }
+
+    /// Report a problem that did not fail the request to the router's diagnostic sink, set
+    /// with [`Router::on_diagnostic`].
+    pub fn report(&self, diagnostic: &Diagnostic<'_>) {
+        self.router.report(diagnostic)
+    }
+
+    /// The router the request is dispatched by, for middleware reporting after [`Next::run`].
+    pub(crate) fn router(&self) -> &'a Router {
+        self.router
+    }
+
+    /// Pass the request on to the next middleware, or to the router if none remain.
+    pub fn run(self, req: Request) -> Result<Response> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(req, Next::new(self.router, rest, self.endpoint)),
+            None => (self.endpoint)(req),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Guessing the `Content-Type` of files from their extension.
+use std::{cell::RefCell, collections::HashMap, path::Path};
+
+thread_local! {
+    static OVERRIDES: RefCell<HashMap<String, String>> = RefCell::default();
+}
+
+/// Use `content_type` for files with the extension `extension` (without the leading dot),
+/// overriding the built-in table, e.g. for `wgsl` shaders or a site-specific `.data` format.
+pub fn override_content_type(extension: &str, content_type: &str) {
+    OVERRIDES.with(|overrides| {
+        overrides
+            .borrow_mut()
+            .insert(extension.to_ascii_lowercase(), content_type.to_owned());
+    });
+}
+
+/// The `Content-Type` for a file at `path`, from the overrides registered with
+/// [`override_content_type`] and then from the extension table of [`mime_guess`].
+///
+/// Textual types get `charset=utf-8`, and unknown extensions `application/octet-stream`.
+pub fn content_type_for_path(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    if let Some(content_type) = extension
+        .as_deref()
+        .and_then(|e| OVERRIDES.with(|o| o.borrow().get(e).cloned()))
+    {
+        return content_type;
+    }
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let textual = mime.type_() == mime_guess::mime::TEXT
+        || matches!(mime.subtype().as_str(), "javascript" | "xml");
+    if textual && mime.get_param(mime_guess::mime::CHARSET).is_none() {
+        format!("{mime}; charset=utf-8")
+    } else {
+        mime.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_type_for_path() {
+        assert_eq!(
+            content_type_for_path("a/site.CSS"),
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(content_type_for_path("logo.png"), "image/png");
+        assert_eq!(content_type_for_path("app.wasm"), "application/wasm");
+        assert_eq!(content_type_for_path("README"), "application/octet-stream");
+
+        override_content_type("wgsl", "text/wgsl");
+        assert_eq!(content_type_for_path("shader.WGSL"), "text/wgsl");
+    }
+}
@@ -0,0 +1,213 @@
+//! Mirroring request metadata to an analytics pipeline.
+use crate::{
+    defer::defer, metrics::MatchedPattern, sampling::Sampler, Middleware, Next, Request, Response,
+};
+use anyhow::{Context, Result};
+use std::{
+    rc::Rc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Middleware posting the metadata of sampled requests as JSON to an analytics endpoint,
+/// through a function sending outbound HTTP requests such as
+/// `spin_sdk::outbound_http::send_request`:
+///
+/// ```
+/// # use spin_sdk_router::{mirror::Mirror, sampling::Sampler, Request, Response, Router};
+/// # fn send_request(req: Request) -> anyhow::Result<Response> { Ok(http::Response::new(None)) }
+/// let mut router = Router::new();
+/// router.layer(
+///     Mirror::new("https://analytics.example.com/events", send_request)
+///         .sampler(Sampler::new(0.1))
+///         .header("user-agent"),
+/// );
+/// ```
+///
+/// Each event records the method, path, matched route pattern, status, duration and time of
+/// the request, e.g. `{"method":"GET","path":"/users/7","route":"/users/:id","status":200,
+/// "duration_ms":3,"timestamp":1700000000}`. Query strings and bodies are never mirrored.
+///
+/// Mirroring is best-effort: events are posted by a [deferred](defer) task once the response
+/// has been produced, and failures are reported to the router's
+/// [diagnostic sink](crate::Router::on_diagnostic) without affecting the response.
+pub struct Mirror<S> {
+    endpoint: String,
+    send: Rc<S>,
+    sampler: Sampler,
+    headers: Vec<http::header::HeaderName>,
+}
+
+impl<S> Mirror<S>
+where
+    S: Fn(Request) -> Result<Response> + 'static,
+{
+    /// Mirror every request to `endpoint`, posting with `send`.
+    pub fn new(endpoint: &str, send: S) -> Self {
+        Mirror {
+            endpoint: endpoint.to_owned(),
+            send: Rc::new(send),
+            sampler: Sampler::default(),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Only mirror the requests chosen by `sampler`.
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Include the request header `name` in the events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn header(mut self, name: &str) -> Self {
+        self.headers
+            .push(name.parse().expect("invalid header name"));
+        self
+    }
+
+    /// The JSON event describing a request and its response.
+    fn event(&self, req: &Request, res: &Response, started: Instant) -> String {
+        let route = match res.extensions().get::<MatchedPattern>() {
+            Some(MatchedPattern(pattern)) => json_string(pattern),
+            None => "null".to_owned(),
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut event = format!(
+            "{{\"method\":{},\"path\":{},\"route\":{route},\"status\":{},\"duration_ms\":{},\"timestamp\":{timestamp}",
+            json_string(req.method().as_str()),
+            json_string(req.uri().path()),
+            res.status().as_u16(),
+            started.elapsed().as_millis(),
+        );
+        if !self.headers.is_empty() {
+            let headers: Vec<_> = self
+                .headers
+                .iter()
+                .filter_map(|name| {
+                    let value = req.headers().get(name)?.to_str().ok()?;
+                    Some(format!(
+                        "{}:{}",
+                        json_string(name.as_str()),
+                        json_string(value)
+                    ))
+                })
+                .collect();
+            event.push_str(&format!(",\"headers\":{{{}}}", headers.join(",")));
+        }
+        event.push('}');
+        event
+    }
+}
+
+/// Posts `event` to `endpoint` with `send`.
+fn post<S>(endpoint: &str, send: &S, event: String) -> Result<()>
+where
+    S: Fn(Request) -> Result<Response>,
+{
+    let req = http::Request::post(endpoint)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Some(event.into()))?;
+    let res = send(req)?;
+    anyhow::ensure!(
+        res.status().is_success(),
+        "analytics endpoint answered {}",
+        res.status()
+    );
+    Ok(())
+}
+
+/// A JSON string literal holding `s`.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl<S> Middleware for Mirror<S>
+where
+    S: Fn(Request) -> Result<Response> + 'static,
+{
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let started = Instant::now();
+        let mut metadata = http::Request::new(None);
+        *metadata.method_mut() = req.method().clone();
+        *metadata.uri_mut() = req.uri().clone();
+        *metadata.headers_mut() = req.headers().clone();
+        let res = next.run(req)?;
+        if self.sampler.sample(&res) {
+            let event = self.event(&metadata, &res, started);
+            let (endpoint, send) = (self.endpoint.clone(), self.send.clone());
+            defer(move || {
+                post(&endpoint, &*send, event)
+                    .with_context(|| format!("cannot mirror request to {endpoint}"))
+            });
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a\"b\\c\n\u{1}"), "\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+
+    #[test]
+    fn test_mirror() {
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let send = {
+            let sent = sent.clone();
+            move |req: Request| -> Result<Response> {
+                sent.borrow_mut().push(req);
+                Ok(http::Response::builder().status(502).body(None)?)
+            }
+        };
+        let mut router = Router::new();
+        router.get("/users/:id", |_req: Request, _params: Params| {
+            Ok(http::Response::new(None))
+        });
+        router
+            .layer(Mirror::new("https://analytics.example.com/events", send).header("user-agent"));
+
+        let req = http::Request::builder()
+            .uri("/users/7?token=secret")
+            .header("user-agent", "curl")
+            .body(None)
+            .unwrap();
+        // A failing analytics endpoint does not affect the response.
+        assert_eq!(router.handle(req).unwrap().status(), http::StatusCode::OK);
+
+        let sent = sent.borrow();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].method(), http::Method::POST);
+        assert_eq!(sent[0].uri(), "https://analytics.example.com/events");
+        let body = std::str::from_utf8(sent[0].body().as_ref().unwrap()).unwrap();
+        assert!(body.starts_with(
+            "{\"method\":\"GET\",\"path\":\"/users/7\",\"route\":\"/users/:id\",\"status\":200,"
+        ));
+        assert!(body.ends_with(",\"headers\":{\"user-agent\":\"curl\"}}"));
+        assert!(!body.contains("secret"));
+    }
+}
@@ -0,0 +1,235 @@
+//! Dispatch among several routers mounted at different Spin component routes.
+use crate::{not_found, Params, Request, Response, Router};
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// The header Spin sets to the route of the component handling the request, e.g. `/api` for
+/// a component mounted at `/api/...`.
+pub const COMPONENT_ROUTE_HEADER: &str = "spin-component-route";
+/// The header Spin sets to the request path relative to the component route.
+pub const PATH_INFO_HEADER: &str = "spin-path-info";
+
+/// How the request path divides between the prefix consumed by the mounts and wildcard
+/// routes it went through, and the remaining path they dispatched on, inserted into the
+/// request extensions.
+///
+/// For a request to `/api/files/a/b.txt` arriving on the component route `/api` and matching
+/// the route `/files/*`, the consumed prefix is `/api/files` and the remaining path `/a/b.txt`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathSplit {
+    consumed: String,
+    remaining: String,
+}
+
+impl PathSplit {
+    /// The path split of `req`, if it was dispatched through a mount or a wildcard route.
+    pub fn of(req: &Request) -> Option<&PathSplit> {
+        req.extensions().get()
+    }
+
+    /// The prefix consumed so far, e.g. `/api/files`; empty if nothing was consumed.
+    pub fn consumed(&self) -> &str {
+        &self.consumed
+    }
+
+    /// The rest of the path, starting with a `/`.
+    pub fn remaining(&self) -> &str {
+        &self.remaining
+    }
+
+    /// The path `path`, relative to the consumed prefix, as seen by the client, e.g.
+    /// `/api/files/c.txt` for `/c.txt`.
+    pub fn join(&self, path: &str) -> String {
+        match path.trim_start_matches('/') {
+            "" if !self.consumed.is_empty() => self.consumed.clone(),
+            rest => format!("{}/{rest}", self.consumed),
+        }
+    }
+}
+
+/// Records that the request path ends with `remaining`, the part it is dispatched on from
+/// now on, appending the rest to the consumed prefix.
+pub(crate) fn consume(request: &mut Request, remaining: &str) {
+    let remaining = format!("/{}", remaining.trim_start_matches('/'));
+    let path = request.uri().path();
+    let consumed = path
+        .strip_suffix(remaining.as_str())
+        .unwrap_or(path.trim_end_matches('/'));
+    let consumed = match PathSplit::of(request) {
+        Some(outer) => format!("{}{consumed}", outer.consumed),
+        None => consumed.to_owned(),
+    };
+    request.extensions_mut().insert(PathSplit {
+        consumed,
+        remaining,
+    });
+}
+
+/// Selects a [`Router`] based on the Spin component route the request arrived on, so that a
+/// single Wasm binary can be deployed to several component routes in one application.
+///
+/// The selected router sees the request path relative to its mount point (taken from the
+/// `spin-path-info` header), so its routes do not depend on where it is mounted. The
+/// component route is recorded as the consumed prefix of the request's [`PathSplit`].
+#[derive(Default)]
+pub struct Mounts {
+    routers: HashMap<String, Router>,
+    fallback: Option<Router>,
+}
+
+impl Mounts {
+    /// Construct an empty set of mounts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve requests arriving on the given component route (e.g. `/api`) with `router`.
+    pub fn mount(&mut self, component_route: &str, router: Router) -> &mut Self {
+        let route = component_route
+            .trim_end_matches("/...")
+            .trim_end_matches('/');
+        self.routers.insert(route.to_owned(), router);
+        self
+    }
+
+    /// Serve requests that match no mounted component route with `router`.
+    pub fn fallback(&mut self, router: Router) -> &mut Self {
+        self.fallback = Some(router);
+        self
+    }
+
+    /// Dispatches a request to the router mounted at its component route.
+    pub fn handle(&self, mut request: Request) -> Result<Response> {
+        let component_route = request
+            .headers()
+            .get(COMPONENT_ROUTE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_end_matches('/'));
+
+        let Some(router) = component_route
+            .and_then(|route| self.routers.get(route))
+            .or(self.fallback.as_ref())
+        else {
+            return not_found(request, Params::default());
+        };
+
+        if component_route.is_some() {
+            if let Some(uri) = relative_uri(&request) {
+                consume(&mut request, uri.path());
+                *request.uri_mut() = uri;
+            }
+        }
+        router.handle(request)
+    }
+}
+
+/// Builds the request URI relative to the component route from the `spin-path-info` header,
+/// preserving the query string.
+fn relative_uri(request: &Request) -> Option<http::Uri> {
+    let path_info = request.headers().get(PATH_INFO_HEADER)?.to_str().ok()?;
+    let path = if path_info.is_empty() { "/" } else { path_info };
+    let path_and_query = match request.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_owned(),
+    };
+    path_and_query.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_request(component_route: Option<&str>, path_info: &str, uri: &str) -> Request {
+        let mut builder = http::Request::builder()
+            .uri(uri)
+            .header(PATH_INFO_HEADER, path_info);
+        if let Some(route) = component_route {
+            builder = builder.header(COMPONENT_ROUTE_HEADER, route);
+        }
+        builder.body(None).unwrap()
+    }
+
+    fn named(name: &'static str) -> Router {
+        let mut router = Router::new();
+        router.get("/items/:id", move |req, params| {
+            let body = format!("{name} {} {}", params.get("id").unwrap(), req.uri());
+            Ok(http::Response::builder()
+                .status(200)
+                .body(Some(body.into()))?)
+        });
+        router
+    }
+
+    #[test]
+    fn test_selects_router_by_component_route() {
+        let mut mounts = Mounts::new();
+        mounts
+            .mount("/api/...", named("api"))
+            .mount("/admin", named("admin"));
+
+        let req = make_request(Some("/admin"), "/items/2", "/admin/items/2?full=1");
+        let res = mounts.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "admin 2 /items/2?full=1");
+
+        let req = make_request(Some("/api"), "/items/1", "/api/items/1");
+        let res = mounts.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "api 1 /items/1");
+    }
+
+    #[test]
+    fn test_unknown_component_route() {
+        let mut mounts = Mounts::new();
+        mounts.mount("/api", named("api"));
+
+        let req = make_request(Some("/other"), "/items/1", "/other/items/1");
+        let res = mounts.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        mounts.fallback(named("fallback"));
+        let req = make_request(None, "/items/1", "/items/1");
+        let res = mounts.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "fallback 1 /items/1");
+    }
+
+    #[test]
+    fn test_path_split() {
+        let mut files = Router::new();
+        files.get("/files/*", |req, _params| {
+            let split = PathSplit::of(&req).unwrap();
+            let body = format!(
+                "{} {} {}",
+                split.consumed(),
+                split.remaining(),
+                split.join("/c.txt")
+            );
+            Ok(http::Response::new(Some(body.into())))
+        });
+        let mut mounts = Mounts::new();
+        mounts.mount("/api/...", files);
+
+        for (path_info, uri, expected) in [
+            (
+                "/files/a/b.txt",
+                "/api/files/a/b.txt",
+                "/api/files /a/b.txt /api/files/c.txt",
+            ),
+            ("/files/", "/api/files/", "/api/files / /api/files/c.txt"),
+        ] {
+            let req = make_request(Some("/api"), path_info, uri);
+            let res = mounts.handle(req).unwrap();
+            assert_eq!(res.into_body().unwrap(), expected);
+        }
+
+        let mut router = Router::new();
+        router.get("/static/*", |req, _params| {
+            let split = PathSplit::of(&req).unwrap();
+            let body = format!("{} {}", split.consumed(), split.remaining());
+            Ok(http::Response::new(Some(body.into())))
+        });
+        let req = http::Request::get("/static/css/site.css")
+            .body(None)
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.into_body().unwrap(), "/static /css/site.css");
+    }
+}
@@ -0,0 +1,309 @@
+//! Content negotiation on the `Accept` request header and on client hints.
+use crate::sfv::{self, BareItem};
+use crate::{Request, Response};
+use std::cell::RefCell;
+
+/// The quality the request's `Accept` header assigns to `media_type`, from 0 (not
+/// acceptable) to 1. Requests without an `Accept` header accept every media type.
+///
+/// The most specific matching media range decides the quality, so with
+/// `text/*;q=0.5, text/csv` a `text/csv` response has quality 1 and `text/html` 0.5.
+pub fn quality(request: &Request, media_type: &str) -> f32 {
+    let accept = request
+        .headers()
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect::<Vec<_>>();
+    if accept.is_empty() {
+        return 1.0;
+    }
+    let media_type = media_type.to_ascii_lowercase();
+    let (kind, _) = media_type.split_once('/').unwrap_or((&media_type, ""));
+
+    let mut best: Option<(u8, f32)> = None;
+    for range in accept.iter().flat_map(|v| v.split(',')) {
+        let mut parts = range.split(';');
+        let range = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let specificity = if range == "*/*" {
+            1
+        } else if range.strip_suffix("/*") == Some(kind) {
+            2
+        } else if range == media_type {
+            3
+        } else {
+            continue;
+        };
+        let q = parts
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .and_then(|(_, q)| q.trim().parse::<f32>().ok())
+            .map_or(1.0, |q| q.clamp(0.0, 1.0));
+        if best.is_none_or(|(s, _)| specificity > s) {
+            best = Some((specificity, q));
+        }
+    }
+    best.map_or(0.0, |(_, q)| q)
+}
+
+/// The quality the request's `Accept-Encoding` header assigns to the content coding
+/// `coding`, e.g. `br` or `gzip`, from 0 (not acceptable) to 1.
+///
+/// Requests without an `Accept-Encoding` header get 0 for every coding, since clients that
+/// omit it rarely cope with compressed responses.
+pub fn encoding_quality(request: &Request, coding: &str) -> f32 {
+    let mut best: Option<(bool, f32)> = None;
+    for value in request.headers().get_all(http::header::ACCEPT_ENCODING) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for entry in value.split(',') {
+            let mut parts = entry.split(';');
+            let name = parts.next().unwrap_or_default().trim();
+            let exact = name.eq_ignore_ascii_case(coding)
+                || (coding == "gzip" && name.eq_ignore_ascii_case("x-gzip"));
+            if !exact && name != "*" {
+                continue;
+            }
+            let q = parts
+                .filter_map(|param| param.split_once('='))
+                .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+                .and_then(|(_, q)| q.trim().parse::<f32>().ok())
+                .map_or(1.0, |q| q.clamp(0.0, 1.0));
+            if best.is_none_or(|(was_exact, _)| exact && !was_exact) {
+                best = Some((exact, q));
+            }
+        }
+    }
+    best.map_or(0.0, |(_, q)| q)
+}
+
+/// Picks the media type from `available` the request prefers, or `None` if it accepts none
+/// of them. Ties go to the earliest in `available`.
+pub fn media_type<'a>(request: &Request, available: &[&'a str]) -> Option<&'a str> {
+    let mut best = None;
+    for media_type in available {
+        let q = quality(request, media_type);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((*media_type, q));
+        }
+    }
+    best.map(|(media_type, _)| media_type)
+}
+
+/// The client hint header carrying the device pixel ratio.
+pub const DPR: &str = "sec-ch-dpr";
+/// The client hint header carrying the layout viewport width in CSS pixels.
+pub const VIEWPORT_WIDTH: &str = "sec-ch-viewport-width";
+/// The client hint header carrying the user's preferred color scheme.
+pub const PREFERS_COLOR_SCHEME: &str = "sec-ch-prefers-color-scheme";
+
+/// The client hint header telling whether the client is a mobile device.
+pub const UA_MOBILE: &str = "sec-ch-ua-mobile";
+
+/// The class of device a request comes from, as used by [`Route::device`].
+///
+/// [`Route::device`]: crate::Route::device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceClass {
+    /// A phone or other mobile device.
+    Mobile,
+    /// Any other device.
+    Desktop,
+}
+
+impl DeviceClass {
+    /// The device class of the request, from the `Sec-CH-UA-Mobile` client hint or, when
+    /// that is missing, from a `Mobi` token in the `User-Agent`.
+    pub fn of(request: &Request) -> Self {
+        let headers = request.headers();
+        let mobile = match headers.get(UA_MOBILE).and_then(|v| v.to_str().ok()) {
+            Some(hint) => hint.trim() == "?1",
+            None => headers
+                .get(http::header::USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|ua| ua.contains("Mobi")),
+        };
+        if mobile {
+            DeviceClass::Mobile
+        } else {
+            DeviceClass::Desktop
+        }
+    }
+}
+
+/// Asks the client to send the given client hints on subsequent requests by adding them to
+/// the response's `Accept-CH` header.
+pub fn accept_ch(response: &mut Response, hints: &[&str]) -> anyhow::Result<()> {
+    let value = hints.join(", ").parse()?;
+    response.headers_mut().append("accept-ch", value);
+    Ok(())
+}
+
+/// Reads client hints from a request, remembering which ones were consulted so that
+/// [`ClientHints::vary`] can mark the response as varying on them.
+///
+/// Both the `Sec-CH-` headers and the legacy unprefixed ones (e.g. `DPR`) are read.
+pub struct ClientHints<'a> {
+    request: &'a Request,
+    consulted: RefCell<Vec<&'static str>>,
+}
+
+impl<'a> ClientHints<'a> {
+    /// Reads the client hints of `request`.
+    pub fn of(request: &'a Request) -> Self {
+        ClientHints {
+            request,
+            consulted: RefCell::default(),
+        }
+    }
+
+    fn hint(&self, name: &'static str) -> Option<BareItem> {
+        let mut consulted = self.consulted.borrow_mut();
+        if !consulted.contains(&name) {
+            consulted.push(name);
+        }
+        let headers = self.request.headers();
+        let value = headers
+            .get(name)
+            .or_else(|| headers.get(name.trim_start_matches("sec-ch-")))?;
+        Some(sfv::parse_item(value.to_str().ok()?).ok()?.bare_item)
+    }
+
+    /// The device pixel ratio, e.g. `2.0` on a high density display.
+    pub fn dpr(&self) -> Option<f64> {
+        match self.hint(DPR)? {
+            BareItem::Decimal(dpr) => Some(dpr),
+            BareItem::Integer(dpr) => Some(dpr as f64),
+            _ => None,
+        }
+        .filter(|dpr| *dpr > 0.0)
+    }
+
+    /// The layout viewport width in CSS pixels.
+    pub fn viewport_width(&self) -> Option<u32> {
+        match self.hint(VIEWPORT_WIDTH)? {
+            BareItem::Integer(width) => width.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    /// The user's preferred color scheme, `light` or `dark`.
+    pub fn prefers_color_scheme(&self) -> Option<String> {
+        match self.hint(PREFERS_COLOR_SCHEME)? {
+            BareItem::String(scheme) | BareItem::Token(scheme) => Some(scheme),
+            _ => None,
+        }
+    }
+
+    /// Adds the client hints consulted so far to the response's `Vary` header, so caches
+    /// keep a variant per hint value.
+    pub fn vary(&self, response: &mut Response) -> anyhow::Result<()> {
+        let consulted = self.consulted.borrow();
+        if !consulted.is_empty() {
+            let value = consulted.join(", ").parse()?;
+            response.headers_mut().append(http::header::VARY, value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(accept: Option<&str>) -> Request {
+        let mut builder = http::Request::builder().uri("/");
+        if let Some(accept) = accept {
+            builder = builder.header(http::header::ACCEPT, accept);
+        }
+        builder.body(None).unwrap()
+    }
+
+    #[test]
+    fn test_quality() {
+        let req = request(Some("text/*;q=0.5, text/csv, */*;q=0.1, image/png;q=0"));
+        assert_eq!(quality(&req, "text/csv"), 1.0);
+        assert_eq!(quality(&req, "Text/HTML"), 0.5);
+        assert_eq!(quality(&req, "application/json"), 0.1);
+        assert_eq!(quality(&req, "image/png"), 0.0);
+        assert_eq!(quality(&request(None), "image/png"), 1.0);
+        assert_eq!(quality(&request(Some("text/html")), "text/csv"), 0.0);
+    }
+
+    #[test]
+    fn test_encoding_quality() {
+        let req = http::Request::builder()
+            .uri("/")
+            .header(
+                http::header::ACCEPT_ENCODING,
+                "gzip;q=0.5, *;q=0.1, zstd;q=0",
+            )
+            .body(None)
+            .unwrap();
+        assert_eq!(encoding_quality(&req, "gzip"), 0.5);
+        assert_eq!(encoding_quality(&req, "br"), 0.1);
+        assert_eq!(encoding_quality(&req, "zstd"), 0.0);
+        assert_eq!(encoding_quality(&request(None), "gzip"), 0.0);
+    }
+
+    #[test]
+    fn test_media_type() {
+        let available = ["application/json", "text/csv", "text/html"];
+        let req = request(Some("text/html;q=0.9, text/csv"));
+        assert_eq!(media_type(&req, &available), Some("text/csv"));
+        let req = request(Some("*/*"));
+        assert_eq!(media_type(&req, &available), Some("application/json"));
+        let req = request(Some("image/*"));
+        assert_eq!(media_type(&req, &available), None);
+    }
+
+    #[test]
+    fn test_client_hints() {
+        let req = http::Request::builder()
+            .uri("/")
+            .header("dpr", "2.5")
+            .header(VIEWPORT_WIDTH, "1280")
+            .header(PREFERS_COLOR_SCHEME, "\"dark\"")
+            .body(None)
+            .unwrap();
+        let hints = ClientHints::of(&req);
+        assert_eq!(hints.dpr(), Some(2.5));
+        assert_eq!(hints.prefers_color_scheme().as_deref(), Some("dark"));
+
+        let mut res = http::Response::new(None);
+        hints.vary(&mut res).unwrap();
+        accept_ch(&mut res, &[DPR, VIEWPORT_WIDTH]).unwrap();
+        assert_eq!(
+            res.headers()[http::header::VARY],
+            "sec-ch-dpr, sec-ch-prefers-color-scheme"
+        );
+        assert_eq!(
+            res.headers()["accept-ch"],
+            "sec-ch-dpr, sec-ch-viewport-width"
+        );
+
+        let req = request(None);
+        let hints = ClientHints::of(&req);
+        assert_eq!(hints.viewport_width(), None);
+    }
+
+    #[test]
+    fn test_device_class() {
+        let req = |name: &str, value: &str| {
+            http::Request::builder()
+                .uri("/")
+                .header(name, value)
+                .body(None)
+                .unwrap()
+        };
+        assert_eq!(DeviceClass::of(&req(UA_MOBILE, "?1")), DeviceClass::Mobile);
+        assert_eq!(DeviceClass::of(&req(UA_MOBILE, "?0")), DeviceClass::Desktop);
+        let iphone = "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) Mobile/15E148";
+        assert_eq!(
+            DeviceClass::of(&req("user-agent", iphone)),
+            DeviceClass::Mobile
+        );
+        assert_eq!(DeviceClass::of(&request(None)), DeviceClass::Desktop);
+    }
+}
@@ -0,0 +1,262 @@
+//! Trailing slash and case policies for matching request paths.
+use crate::{not_found, redirect, Params, Request, Response, RouteMatch, Router};
+use anyhow::Result;
+
+/// What to do with a request whose path matches a route except for a trailing slash, e.g.
+/// `/users/` for the route `/users` or `/about` for the route `/about/`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// Dispatch the request to the route.
+    #[default]
+    Lenient,
+    /// Treat the request as matching no route.
+    Strict,
+    /// Redirect the client to the path of the route with 308 Permanent Redirect.
+    Redirect,
+}
+
+/// How request paths are matched against route patterns.
+///
+/// A policy applies to the whole router, or to the paths under a prefix; see
+/// [`Router::path_policy`] and [`Router::group_path_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PathPolicy {
+    trailing_slash: TrailingSlash,
+    case_insensitive: bool,
+}
+
+impl PathPolicy {
+    /// Match the case of paths exactly and ignore trailing slashes, as the router does by
+    /// default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle paths that match a route except for a trailing slash as given.
+    pub fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.trailing_slash = trailing_slash;
+        self
+    }
+
+    /// Match the literal segments of route patterns regardless of case. Captured parameters
+    /// keep the case of the request.
+    pub fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+}
+
+/// The path policies of a router.
+#[derive(Default)]
+pub(crate) struct PathPolicies {
+    global: PathPolicy,
+    groups: Vec<(String, PathPolicy)>,
+}
+
+impl PathPolicies {
+    /// The policy for `path`: that of the longest group prefix containing it regardless of
+    /// case, or else the global one.
+    fn policy(&self, path: &str) -> PathPolicy {
+        self.groups
+            .iter()
+            .filter(|(prefix, _)| {
+                let (head, rest) = path.split_at(prefix.len().min(path.len()));
+                head.eq_ignore_ascii_case(prefix) && (rest.is_empty() || rest.starts_with('/'))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.global, |(_, policy)| *policy)
+    }
+}
+
+/// Rewrites the literal segments of `path` to the case of those of `pattern`, if the path
+/// matches the pattern regardless of case. Trailing slashes are left as they are.
+fn fold_case(path: &str, pattern: &str) -> Option<String> {
+    let trimmed = path.trim_end_matches('/');
+    let mut segments = trimmed.split('/');
+    let mut folded = Vec::new();
+    for literal in pattern.trim_end_matches('/').split('/') {
+        if literal.starts_with('*') {
+            folded.extend(segments.by_ref());
+            break;
+        }
+        let segment = segments.next()?;
+        if literal.contains(':') {
+            folded.push(segment);
+        } else if literal.eq_ignore_ascii_case(segment) {
+            folded.push(literal);
+        } else {
+            return None;
+        }
+    }
+    if segments.next().is_some() {
+        return None;
+    }
+    Some(folded.join("/") + &path[trimmed.len()..])
+}
+
+/// Redirects the client to the request path with its trailing slash added or removed.
+fn toggle_trailing_slash(req: Request, _params: Params) -> Result<Response> {
+    let path = req.uri().path();
+    let mut target = match path.trim_end_matches('/') {
+        trimmed if trimmed.len() < path.len() => trimmed.to_owned(),
+        _ => format!("{path}/"),
+    };
+    if let Some(query) = req.uri().query() {
+        target = format!("{target}?{query}");
+    }
+    redirect::permanent(&target)
+}
+
+impl Router {
+    /// Match request paths with `policy`, unless a [group policy](Router::group_path_policy)
+    /// applies.
+    pub fn path_policy(&mut self, policy: PathPolicy) {
+        self.path_policies.global = policy;
+    }
+
+    /// Match request paths under `prefix`, e.g. `/api`, with `policy` instead. The policy of
+    /// the longest matching prefix applies, so that an API can match strictly while the
+    /// website next to it is lenient.
+    pub fn group_path_policy(&mut self, prefix: &str, policy: PathPolicy) {
+        let prefix = prefix.trim_end_matches('/').to_owned();
+        self.path_policies.groups.retain(|(p, _)| *p != prefix);
+        self.path_policies.groups.push((prefix, policy));
+    }
+
+    /// Rewrites the path of a request matching no route to the case of a route it matches
+    /// regardless of case, if the path policy allows it.
+    pub(crate) fn fold_path_case(&self, request: &mut Request) -> Result<()> {
+        let path = request.uri().path();
+        if !self.path_policies.policy(path).case_insensitive || self.routes_path(request, path) {
+            return Ok(());
+        }
+        let Some(folded) = self
+            .routes
+            .iter()
+            .filter_map(|route| fold_case(path, &route.pattern))
+            .find(|folded| self.routes_path(request, folded))
+        else {
+            return Ok(());
+        };
+        let target = match request.uri().query() {
+            Some(query) => format!("{folded}?{query}"),
+            None => folded,
+        };
+        let mut parts = request.uri().clone().into_parts();
+        parts.path_and_query = Some(target.parse()?);
+        *request.uri_mut() = http::Uri::from_parts(parts)?;
+        Ok(())
+    }
+
+    /// Applies the trailing slash policy to the route matched for the request.
+    pub(crate) fn check_trailing_slash<'a>(
+        &'a self,
+        request: &Request,
+        m: RouteMatch<'a>,
+    ) -> RouteMatch<'a> {
+        let path = request.uri().path();
+        let Some(route) = m.route else {
+            return m;
+        };
+        let slashed = |p: &str| p.len() > 1 && p.ends_with('/');
+        if path == "/" || route.pattern.contains('*') || slashed(path) == slashed(&route.pattern) {
+            return m;
+        }
+        match self.path_policies.policy(path).trailing_slash {
+            TrailingSlash::Lenient => m,
+            TrailingSlash::Strict => {
+                RouteMatch::status(self.fallback.as_deref().unwrap_or(&not_found))
+            }
+            TrailingSlash::Redirect => RouteMatch::status(&toggle_trailing_slash),
+        }
+    }
+
+    /// Whether a route matches the request at `path`.
+    fn routes_path(&self, request: &Request, path: &str) -> bool {
+        let method = request.method().clone();
+        let matches = |method: &http::Method| {
+            let router = self.methods_map.get(method).into_iter();
+            router
+                .chain([&self.all_methods])
+                .flat_map(|r| r.match_iter(path))
+                .filter(|m| !self.duplicates().skips(*m.handler()))
+                .any(|m| {
+                    let route = &self.routes[*m.handler()];
+                    route.meets_conditions(request) && route.admits(&m.captures())
+                })
+        };
+        matches(&method) || (method == http::Method::HEAD && matches(&http::Method::GET))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+
+    fn echo(req: Request, params: Params) -> Result<Response> {
+        let id = params.get("id").unwrap_or_default();
+        let body = format!("{} {id}", req.uri().path());
+        Ok(http::Response::new(Some(body.into())))
+    }
+
+    fn get(router: &Router, uri: &str) -> Response {
+        let req = http::Request::builder().uri(uri).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_fold_case() {
+        assert_eq!(
+            fold_case("/API/Users/AbC", "/api/users/:id").as_deref(),
+            Some("/api/users/AbC")
+        );
+        assert_eq!(
+            fold_case("/Files/A/B", "/files/*").as_deref(),
+            Some("/files/A/B")
+        );
+        assert_eq!(fold_case("/api/users", "/api/users/:id"), None);
+        assert_eq!(fold_case("/api/users/1/x", "/api/users/:id"), None);
+    }
+
+    #[test]
+    fn test_group_policies() {
+        let mut router = Router::new();
+        router.get("/api/users/:id", echo);
+        router.get("/docs/Guide", echo);
+        router.get("/about/", echo);
+        router.path_policy(PathPolicy::new().case_insensitive());
+        router.group_path_policy(
+            "/api",
+            PathPolicy::new().trailing_slash(TrailingSlash::Strict),
+        );
+        router.group_path_policy(
+            "/docs",
+            PathPolicy::new().trailing_slash(TrailingSlash::Redirect),
+        );
+
+        // The API is strict.
+        assert_eq!(get(&router, "/api/users/7").status(), 200);
+        assert_eq!(get(&router, "/api/users/7/").status(), 404);
+        assert_eq!(get(&router, "/API/users/7").status(), 404);
+
+        // The website is lenient.
+        let res = get(&router, "/About");
+        assert_eq!(res.into_body().unwrap(), "/about ");
+
+        // The docs redirect to the canonical path, but are case sensitive.
+        let res = get(&router, "/docs/Guide/?v=2");
+        assert_eq!(res.status(), http::StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[http::header::LOCATION], "/docs/Guide?v=2");
+        assert_eq!(get(&router, "/docs/guide").status(), 404);
+    }
+
+    #[test]
+    fn test_case_insensitive_keeps_params() {
+        let mut router = Router::new();
+        router.get("/users/:id", echo);
+        router.path_policy(PathPolicy::new().case_insensitive());
+        let res = get(&router, "/USERS/AbC");
+        assert_eq!(res.into_body().unwrap(), "/users/AbC AbC");
+    }
+}
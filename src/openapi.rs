@@ -0,0 +1,523 @@
+//! OpenAPI 3.1 document generation from the registered routes.
+use crate::{Params, Request, Response, Route, RouteError, Router};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+impl Route {
+    /// Annotate the route with an OpenAPI operation object.
+    ///
+    /// The fields of `operation` are merged over the operation generated from the route
+    /// pattern. Path parameters declared here replace the generated ones of the same name.
+    pub fn operation(&mut self, operation: Value) -> &mut Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    fn to_operation(&self, params: &[String]) -> Value {
+        let mut parameters: Vec<Value> = params
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        let mut operation = Map::new();
+        if let Some(name) = self.handler_name() {
+            operation.insert("x-handler".into(), name.into());
+        }
+        if let Some(summary) = self.summary() {
+            operation.insert("summary".into(), summary.into());
+        }
+        if let Some(description) = self.description() {
+            operation.insert("description".into(), description.into());
+        }
+
+        if let Some(Value::Object(annotation)) = &self.operation {
+            for (key, value) in annotation {
+                match (key.as_str(), value) {
+                    ("parameters", Value::Array(declared)) => {
+                        parameters.retain(|generated| {
+                            !declared.iter().any(|d| {
+                                d["name"] == generated["name"] && d["in"] == generated["in"]
+                            })
+                        });
+                        parameters.extend(declared.iter().cloned());
+                    }
+                    _ => {
+                        operation.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        if !parameters.is_empty() {
+            operation.insert("parameters".into(), parameters.into());
+        }
+        operation
+            .entry("responses")
+            .or_insert_with(|| json!({ "default": { "description": "Default response" } }));
+        Value::Object(operation)
+    }
+}
+
+/// Converts a route pattern into an OpenAPI path template along with its parameter names.
+///
+/// `/users/:id/*` becomes `/users/{id}/{wildcard}`.
+fn path_template(pattern: &str) -> (String, Vec<String>) {
+    let mut template = String::new();
+    let mut params = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ':' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| *c != '/' && *c != '.') {
+                    name.push(c);
+                }
+                template.push_str(&format!("{{{name}}}"));
+                params.push(name);
+            }
+            '*' => {
+                template.push_str("{wildcard}");
+                params.push("wildcard".to_owned());
+            }
+            c => template.push(c),
+        }
+    }
+    if !template.starts_with('/') {
+        template.insert(0, '/');
+    }
+    (template, params)
+}
+
+/// Converts an OpenAPI path template into a route pattern.
+///
+/// `/users/{id}/{wildcard}` becomes `/users/:id/*`, the inverse of [`path_template`].
+fn route_pattern(template: &str) -> String {
+    let pattern = template.replace('{', ":").replace('}', "");
+    match pattern.strip_suffix(":wildcard") {
+        Some(prefix) if prefix.ends_with('/') => format!("{prefix}*"),
+        _ => pattern,
+    }
+}
+
+type Binding = Box<
+    dyn for<'a> FnOnce(&'a mut Router, &str, http::Method) -> Result<&'a mut Route, RouteError>,
+>;
+
+/// Builds a [`Router`] from an OpenAPI document by binding each operation to a handler by its
+/// `operationId`.
+///
+/// ```ignore
+/// let router = OpenApiBinder::from_json(include_str!("openapi.json"))?
+///     .bind("getUser", api::get_user)
+///     .bind("deleteUser", api::delete_user)
+///     .build()?;
+/// ```
+pub struct OpenApiBinder {
+    document: Value,
+    bindings: HashMap<String, Binding>,
+}
+
+impl OpenApiBinder {
+    /// Parse an OpenAPI document in JSON format.
+    pub fn from_json(document: &str) -> Result<Self> {
+        let document = serde_json::from_str(document).context("invalid OpenAPI document")?;
+        Ok(Self::new(document))
+    }
+
+    /// Construct a binder for an already parsed OpenAPI document.
+    pub fn new(document: Value) -> Self {
+        OpenApiBinder {
+            document,
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Bind the operation with the given `operationId` to a handler.
+    pub fn bind<F>(mut self, operation_id: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.bindings.insert(
+            operation_id.to_owned(),
+            Box::new(move |router: &mut Router, path: &str, method| {
+                router.try_add(path, method, handler)
+            }),
+        );
+        self
+    }
+
+    /// Build the router, failing if any operation is left unbound, a binding does not
+    /// correspond to an operation in the document, or an operation's path is not a valid
+    /// route pattern, with the [`RouteError`].
+    ///
+    /// Each route is annotated with its operation so that [`Router::openapi`] reproduces it.
+    pub fn build(mut self) -> Result<Router> {
+        let mut router = Router::new();
+        let mut unbound = Vec::new();
+        for (template, method, id, operation) in self.operations()? {
+            match self.bindings.remove(&id) {
+                Some(bind) => {
+                    bind(&mut router, &route_pattern(&template), method)?.operation(operation);
+                }
+                None => unbound.push(id),
+            }
+        }
+
+        if !unbound.is_empty() {
+            unbound.sort();
+            bail!("unbound OpenAPI operations: {}", unbound.join(", "));
+        }
+        if !self.bindings.is_empty() {
+            let mut unknown: Vec<_> = self.bindings.into_keys().collect();
+            unknown.sort();
+            bail!("no OpenAPI operations named: {}", unknown.join(", "));
+        }
+        Ok(router)
+    }
+
+    /// Generate Rust source with a handler stub for each operation, answering 501 Not
+    /// Implemented, and a `router()` function registering them, as a starting point for
+    /// implementing an API designed up front.
+    ///
+    /// Stubs are named after the snake-cased `operationId` and documented with the
+    /// operation's summary. The source is typically written from a build script and included
+    /// in the crate:
+    ///
+    /// ```ignore
+    /// // build.rs
+    /// let document = std::fs::read_to_string("openapi.json")?;
+    /// let source = OpenApiBinder::from_json(&document)?.codegen()?;
+    /// std::fs::write(Path::new(&std::env::var("OUT_DIR")?).join("api.rs"), source)?;
+    ///
+    /// // lib.rs
+    /// mod api {
+    ///     include!(concat!(env!("OUT_DIR"), "/api.rs"));
+    /// }
+    /// ```
+    ///
+    /// The generated code refers to the `spin_sdk_router`, `http` and `anyhow` crates.
+    pub fn codegen(&self) -> Result<String> {
+        let mut stubs = String::new();
+        let mut routes = String::new();
+        let mut names = HashMap::new();
+        for (template, method, id, operation) in self.operations()? {
+            let name = snake_case(&id);
+            if let Some(other) = names.insert(name.clone(), id.clone()) {
+                bail!("operations {other} and {id} both generate a handler named `{name}`");
+            }
+            stubs.push_str(&format!("/// `{method} {template}`"));
+            if let Some(summary) = operation.get("summary").and_then(Value::as_str) {
+                stubs.push_str(&format!(
+                    ": {}",
+                    summary.split_whitespace().collect::<Vec<_>>().join(" ")
+                ));
+            }
+            stubs.push_str(&format!(
+                "\npub fn {name}(\n    _req: spin_sdk_router::Request,\n    _params: spin_sdk_router::Params,\n) -> anyhow::Result<spin_sdk_router::Response> {{\n    Ok(http::Response::builder()\n        .status(http::StatusCode::NOT_IMPLEMENTED)\n        .body(None)?)\n}}\n\n"
+            ));
+            routes.push_str(&format!(
+                "    router.add({:?}, http::Method::{method}, {name});\n",
+                route_pattern(&template)
+            ));
+        }
+        Ok(format!(
+            "// Generated by spin-sdk-router from an OpenAPI document.\n\n{stubs}/// Assemble the router for the API.\npub fn router() -> spin_sdk_router::Router {{\n    let mut router = spin_sdk_router::Router::new();\n{routes}    router\n}}\n"
+        ))
+    }
+
+    /// The operations in the document with their path template, method and `operationId`.
+    fn operations(&self) -> Result<Vec<(String, http::Method, String, Value)>> {
+        let paths = self
+            .document
+            .get("paths")
+            .and_then(Value::as_object)
+            .ok_or_else(|| anyhow!("OpenAPI document has no `paths` object"))?;
+
+        let mut operations = Vec::new();
+        for (template, item) in paths {
+            for (key, operation) in item.as_object().into_iter().flatten() {
+                if !METHODS.contains(&key.as_str()) {
+                    continue;
+                }
+                let method = http::Method::from_bytes(key.to_ascii_uppercase().as_bytes())?;
+                let Some(id) = operation.get("operationId").and_then(Value::as_str) else {
+                    bail!("operation {method} {template} has no operationId");
+                };
+                operations.push((template.clone(), method, id.to_owned(), operation.clone()));
+            }
+        }
+        Ok(operations)
+    }
+}
+
+const KEYWORDS: [&str; 38] = [
+    "abstract", "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+    "enum", "extern", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod",
+    "move", "mut", "pub", "ref", "return", "self", "static", "struct", "super", "trait", "try",
+    "type", "unsafe", "use", "where", "while",
+];
+
+/// Converts an `operationId` such as `getUserById` into a snake-cased Rust identifier.
+fn snake_case(id: &str) -> String {
+    let mut name = String::new();
+    let mut prev_lower = false;
+    for c in id.chars() {
+        if c.is_ascii_uppercase() {
+            if prev_lower {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+            prev_lower = false;
+        } else if c.is_ascii_alphanumeric() {
+            name.push(c);
+            prev_lower = true;
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+            prev_lower = false;
+        }
+    }
+    let name = name.trim_end_matches('_');
+    match name.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() && !KEYWORDS.contains(&name) => name.to_owned(),
+        Some(c) if c.is_ascii_alphabetic() => format!("{name}_"),
+        _ => format!("op_{name}"),
+    }
+}
+
+impl Router {
+    /// Build an OpenAPI 3.1 document describing the registered routes.
+    ///
+    /// Each operation records the name of its handler, when known, in the `x-handler`
+    /// extension field.
+    ///
+    /// Routes registered for all methods with [`Router::all`] have no corresponding operation
+    /// and are left out of the document.
+    pub fn openapi(&self, title: &str, version: &str) -> Value {
+        let mut paths = Map::new();
+        for route in self.routes() {
+            let Some(method) = route.method() else {
+                continue;
+            };
+            let (template, params) = path_template(route.pattern());
+            let item = paths
+                .entry(template)
+                .or_insert_with(|| Value::Object(Map::new()));
+            item[method.as_str().to_ascii_lowercase()] = route.to_operation(&params);
+        }
+
+        json!({
+            "openapi": "3.1.0",
+            "info": { "title": title, "version": version },
+            "paths": paths,
+        })
+    }
+
+    /// Serve the OpenAPI document for this router at `/openapi.json`.
+    pub fn serve_openapi(&mut self, title: &str, version: &str) {
+        let (title, version) = (title.to_owned(), version.to_owned());
+        self.builtins.insert(
+            "/openapi.json",
+            Box::new(move |router: &Router, _req: Request| -> Result<Response> {
+                let spec = router.openapi(&title, &version);
+                Ok(http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(Some(serde_json::to_vec(&spec)?.into()))?)
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show_user(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::builder().status(200).body(None)?)
+    }
+
+    #[test]
+    fn test_path_template() {
+        assert_eq!(
+            path_template("/users/:id/files/:name.:ext"),
+            (
+                "/users/{id}/files/{name}.{ext}".to_owned(),
+                vec!["id".to_owned(), "name".to_owned(), "ext".to_owned()]
+            )
+        );
+        assert_eq!(
+            path_template("/static/*"),
+            ("/static/{wildcard}".to_owned(), vec!["wildcard".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_route_pattern() {
+        assert_eq!(route_pattern("/users/{id}"), "/users/:id");
+        assert_eq!(route_pattern("/files/{name}.{ext}"), "/files/:name.:ext");
+        assert_eq!(route_pattern("/static/{wildcard}"), "/static/*");
+    }
+
+    const USERS: &str = r#"{
+        "openapi": "3.1.0",
+        "info": { "title": "Users", "version": "1.0.0" },
+        "paths": {
+            "/users/{id}": {
+                "parameters": [],
+                "get": { "operationId": "getUser" },
+                "delete": { "operationId": "deleteUser" }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn test_binder_builds_router() {
+        let router = OpenApiBinder::from_json(USERS)
+            .unwrap()
+            .bind("getUser", show_user)
+            .bind("deleteUser", show_user)
+            .build()
+            .unwrap();
+
+        let req = http::Request::builder()
+            .method(http::Method::DELETE)
+            .uri("/users/1")
+            .body(None)
+            .unwrap();
+        assert_eq!(router.handle(req).unwrap().status(), http::StatusCode::OK);
+
+        let spec = router.openapi("Users", "1.0.0");
+        assert_eq!(
+            spec["paths"]["/users/{id}"]["get"]["operationId"],
+            "getUser"
+        );
+    }
+
+    #[test]
+    fn test_binder_rejects_unbound_operations() {
+        let err = OpenApiBinder::from_json(USERS)
+            .unwrap()
+            .bind("getUser", show_user)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "unbound OpenAPI operations: deleteUser");
+
+        let err = OpenApiBinder::from_json(USERS)
+            .unwrap()
+            .bind("getUser", show_user)
+            .bind("deleteUser", show_user)
+            .bind("createUser", show_user)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "no OpenAPI operations named: createUser");
+    }
+
+    #[test]
+    fn test_binder_rejects_invalid_paths() {
+        let document = json!({
+            "paths": {
+                "/files/{}": { "get": { "operationId": "getFile" } }
+            }
+        });
+        let err = OpenApiBinder::new(document)
+            .bind("getFile", show_user)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.downcast_ref::<RouteError>(),
+            Some(&RouteError::InvalidPattern {
+                pattern: "/files/:".to_owned(),
+                reason: "params must be named".to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(snake_case("getUserById"), "get_user_by_id");
+        assert_eq!(snake_case("list-users"), "list_users");
+        assert_eq!(snake_case("HTTPStatus"), "httpstatus");
+        assert_eq!(snake_case("2fa.verify"), "op_2fa_verify");
+        assert_eq!(snake_case("move"), "move_");
+    }
+
+    #[test]
+    fn test_codegen() {
+        let source = OpenApiBinder::from_json(USERS).unwrap().codegen().unwrap();
+        assert!(source.contains("pub fn get_user(\n    _req: spin_sdk_router::Request,"));
+        assert!(source.contains("pub fn delete_user("));
+        assert!(source.contains("http::StatusCode::NOT_IMPLEMENTED"));
+        assert!(source.contains("    router.add(\"/users/:id\", http::Method::GET, get_user);\n"));
+        assert!(
+            source.contains("    router.add(\"/users/:id\", http::Method::DELETE, delete_user);\n")
+        );
+
+        let clash = r#"{ "paths": { "/a": {
+            "get": { "operationId": "getA" },
+            "put": { "operationId": "get_a" }
+        } } }"#;
+        let err = OpenApiBinder::from_json(clash)
+            .unwrap()
+            .codegen()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "operations getA and get_a both generate a handler named `get_a`"
+        );
+    }
+
+    #[test]
+    fn test_openapi_document() {
+        let mut router = Router::default();
+        router.get("/users/:id", show_user).operation(json!({
+            "operationId": "getUser",
+            "parameters": [{ "name": "verbose", "in": "query", "schema": { "type": "boolean" } }],
+            "responses": { "200": { "description": "The user" } },
+        }));
+        router.delete("/users/:id", show_user);
+        router.all("/*", show_user);
+
+        let spec = router.openapi("Users", "1.0.0");
+        assert_eq!(spec["openapi"], "3.1.0");
+        assert_eq!(spec["paths"].as_object().unwrap().len(), 1);
+
+        let get = &spec["paths"]["/users/{id}"]["get"];
+        assert_eq!(get["operationId"], "getUser");
+        assert_eq!(get["parameters"].as_array().unwrap().len(), 2);
+        assert_eq!(get["responses"]["200"]["description"], "The user");
+
+        let delete = &spec["paths"]["/users/{id}"]["delete"];
+        assert_eq!(delete["parameters"][0]["name"], "id");
+        assert!(delete["responses"]["default"].is_object());
+    }
+
+    #[test]
+    fn test_serve_openapi() {
+        let mut router = Router::default();
+        router.get("/users/:id", show_user);
+        router.serve_openapi("Users", "1.0.0");
+
+        let req = http::Request::builder()
+            .uri("/openapi.json")
+            .body(None)
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        let spec: Value = serde_json::from_slice(&res.into_body().unwrap()).unwrap();
+        assert_eq!(spec["info"]["title"], "Users");
+    }
+}
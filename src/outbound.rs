@@ -0,0 +1,238 @@
+//! Outbound HTTP requests correlated with the inbound request being handled.
+use crate::metrics::Metrics;
+use crate::ratelimit::{InFlightStore, MemoryStore};
+use crate::tracecontext::TraceContext;
+use crate::{Request, Response};
+use anyhow::Result;
+use std::{collections::HashMap, rc::Rc, time::Instant};
+
+/// The headers copied from the inbound request to outbound requests: the request ID and the
+/// W3C trace context.
+pub const PROPAGATED_HEADERS: &[&str] = &["x-request-id", "traceparent", "tracestate"];
+
+/// A wrapper around a function sending outbound HTTP requests, such as
+/// `spin_sdk::outbound_http::send_request`, that attaches the correlation headers of the
+/// inbound request and records the latency of each upstream host.
+///
+/// ```
+/// # use spin_sdk_router::{outbound::Client, metrics::Metrics, Params, Request, Response};
+/// # fn send_request(req: Request) -> anyhow::Result<Response> { Ok(http::Response::new(None)) }
+/// # let metrics = Metrics::new();
+/// let handler = move |req: Request, _params: Params| -> anyhow::Result<Response> {
+///     let client = Client::new(send_request)
+///         .propagate_from(&req)
+///         .metrics(metrics.clone());
+///     let upstream = http::Request::get("https://api.example.com/items").body(None)?;
+///     client.send(upstream)
+/// };
+/// ```
+pub struct Client<S> {
+    send: S,
+    context: http::HeaderMap,
+    metrics: Option<Metrics>,
+    bulkhead: Option<Bulkhead>,
+}
+
+/// Per-upstream limits on concurrent outbound requests, so that one slow backend cannot tie
+/// up every instance of the component.
+///
+/// Once an upstream's budget is exhausted, further requests to it are shed immediately with
+/// 503 Service Unavailable instead of being sent. There is deliberately no queue of waiting
+/// requests: outbound calls block the instance making them, so a queued request could only
+/// wait by polling the counters, holding its own instance for as long as the upstream is slow,
+/// which is the exhaustion the bulkhead exists to prevent. Clients that want to wait should
+/// retry the 503 with backoff. Clones share the same budgets; to hold them across Spin
+/// instances, use an [`InFlightStore`] backed by shared state.
+#[derive(Clone)]
+pub struct Bulkhead {
+    store: Rc<dyn InFlightStore>,
+    limits: HashMap<String, u64>,
+    default: Option<u64>,
+}
+
+impl Bulkhead {
+    /// Allow `max` concurrent requests to each upstream host without a limit of its own,
+    /// counting in memory.
+    pub fn new(max: u64) -> Self {
+        Bulkhead {
+            store: Rc::new(MemoryStore::new()),
+            limits: HashMap::new(),
+            default: Some(max),
+        }
+    }
+
+    /// Only limit the upstream hosts given a limit with [`Bulkhead::upstream`].
+    pub fn unlimited() -> Self {
+        Bulkhead {
+            default: None,
+            ..Bulkhead::new(0)
+        }
+    }
+
+    /// Allow `max` concurrent requests to the upstream host `host`.
+    pub fn upstream(mut self, host: &str, max: u64) -> Self {
+        self.limits.insert(host.to_ascii_lowercase(), max);
+        self
+    }
+
+    /// Keep the in-flight counters in the given store.
+    pub fn store<T: InFlightStore>(mut self, store: T) -> Self {
+        self.store = Rc::new(store);
+        self
+    }
+
+    fn limit(&self, host: &str) -> Option<u64> {
+        self.limits.get(host).copied().or(self.default)
+    }
+}
+
+impl<S> Client<S>
+where
+    S: Fn(Request) -> Result<Response>,
+{
+    /// Wrap `send`, which performs the outbound request.
+    pub fn new(send: S) -> Self {
+        Client {
+            send,
+            context: http::HeaderMap::new(),
+            metrics: None,
+            bulkhead: None,
+        }
+    }
+
+    /// Copy the [`PROPAGATED_HEADERS`] of `inbound` onto every outbound request. If `inbound`
+    /// has a [`TraceContext`], outbound requests carry it instead of the inbound trace headers.
+    pub fn propagate_from(mut self, inbound: &Request) -> Self {
+        for name in PROPAGATED_HEADERS {
+            for value in inbound.headers().get_all(*name) {
+                self.context.append(*name, value.clone());
+            }
+        }
+        if let Some(context) = TraceContext::of(inbound) {
+            let mut carrier = http::Request::new(None);
+            if context.inject(&mut carrier).is_ok() {
+                self.context.remove("tracestate");
+                self.context.extend(carrier.headers_mut().drain());
+            }
+        }
+        self
+    }
+
+    /// Record the latency of outbound requests by upstream host into `metrics`.
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Limit concurrent requests per upstream host with `bulkhead`.
+    pub fn bulkhead(mut self, bulkhead: Bulkhead) -> Self {
+        self.bulkhead = Some(bulkhead);
+        self
+    }
+
+    /// Sends `request`, adding correlation headers it does not already carry. Requests to an
+    /// upstream whose bulkhead is full are answered with 503 without being sent.
+    pub fn send(&self, request: Request) -> Result<Response> {
+        let upstream = request
+            .uri()
+            .host()
+            .unwrap_or("unknown")
+            .to_ascii_lowercase();
+        let Some((bulkhead, max)) = self
+            .bulkhead
+            .as_ref()
+            .and_then(|b| Some((b, b.limit(&upstream)?)))
+        else {
+            return self.send_correlated(request, &upstream);
+        };
+        let in_flight = bulkhead.store.acquire(&upstream)?;
+        let res = if in_flight <= max {
+            self.send_correlated(request, &upstream)
+        } else {
+            Ok(http::Response::builder()
+                .status(http::StatusCode::SERVICE_UNAVAILABLE)
+                .body(None)?)
+        };
+        bulkhead.store.release(&upstream)?;
+        res
+    }
+
+    fn send_correlated(&self, mut request: Request, upstream: &str) -> Result<Response> {
+        for name in self.context.keys() {
+            if !request.headers().contains_key(name) {
+                for value in self.context.get_all(name) {
+                    request.headers_mut().append(name, value.clone());
+                }
+            }
+        }
+        let start = Instant::now();
+        let res = (self.send)(request);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_upstream(upstream, start.elapsed());
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ratelimit::SharedStore;
+
+    #[test]
+    fn test_propagates_context_and_records_latency() {
+        let inbound = http::Request::builder()
+            .uri("/")
+            .header("x-request-id", "abc")
+            .header(
+                "traceparent",
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            )
+            .header("authorization", "secret")
+            .body(None)
+            .unwrap();
+        let metrics = Metrics::new();
+        let client = Client::new(|req: Request| {
+            assert_eq!(req.headers()["x-request-id"], "abc");
+            assert_eq!(req.headers()["traceparent"].len(), 55);
+            assert!(!req.headers().contains_key("authorization"));
+            Ok(http::Response::new(None))
+        })
+        .propagate_from(&inbound)
+        .metrics(metrics.clone());
+
+        for _ in 0..2 {
+            let req = http::Request::builder()
+                .uri("https://api.example.com/items")
+                .body(None)
+                .unwrap();
+            client.send(req).unwrap();
+        }
+        let upstreams = metrics.upstreams();
+        assert_eq!(upstreams.len(), 1);
+        assert_eq!(upstreams[0].0, "api.example.com");
+        assert_eq!(upstreams[0].1.count, 2);
+    }
+
+    #[test]
+    fn test_bulkhead_sheds_exhausted_upstream() {
+        let store = SharedStore::default();
+        let bulkhead = Bulkhead::unlimited()
+            .upstream("slow.example.com", 1)
+            .store(store.clone());
+        let client = Client::new(|_req: Request| Ok(http::Response::new(None))).bulkhead(bulkhead);
+        let send = |uri: &str| {
+            let req = http::Request::builder().uri(uri).body(None).unwrap();
+            client.send(req).unwrap().status()
+        };
+
+        assert_eq!(send("https://slow.example.com/"), http::StatusCode::OK);
+        // Another instance is waiting on the slow upstream.
+        store.acquire("slow.example.com").unwrap();
+        assert_eq!(
+            send("https://slow.example.com/"),
+            http::StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(send("https://fast.example.com/"), http::StatusCode::OK);
+    }
+}
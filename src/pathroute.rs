@@ -0,0 +1,174 @@
+//! Registration of the handlers of one path for several methods, writing the path once.
+use crate::{Params, Request, Response, Route, Router};
+use anyhow::Result;
+
+/// The routes of one path, returned by [`Router::route`]:
+///
+/// ```
+/// # use spin_sdk_router::{Params, Request, Response, Router};
+/// # fn handler(_req: Request, _params: Params) -> anyhow::Result<Response> {
+/// #     Ok(http::Response::new(None))
+/// # }
+/// # let (show, update, remove) = (handler, handler, handler);
+/// let mut router = Router::new();
+/// router
+///     .route("/items/:id")
+///     .get(show)
+///     .put(update)
+///     .delete(remove);
+/// ```
+///
+/// Each method panics if the path is not a valid route pattern, as [`Router::add`] does.
+pub struct PathRoute<'r> {
+    router: &'r mut Router,
+    path: String,
+}
+
+impl Router {
+    /// The routes of `path`, to register its handlers for each method in one chain.
+    pub fn route(&mut self, path: &str) -> PathRoute<'_> {
+        PathRoute {
+            router: self,
+            path: path.to_owned(),
+        }
+    }
+}
+
+impl PathRoute<'_> {
+    /// Register `handler` for `method`.
+    pub fn method<F>(self, method: http::Method, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.router.add(&self.path, method, handler);
+        self
+    }
+
+    /// Register `handler` for `method`, and attach metadata to its route with `configure`.
+    pub fn method_with<F, C>(self, method: http::Method, handler: F, configure: C) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+        C: FnOnce(&mut Route),
+    {
+        configure(self.router.add(&self.path, method, handler));
+        self
+    }
+
+    /// Register `handler` for all methods.
+    pub fn all<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.router.all(&self.path, handler);
+        self
+    }
+
+    /// Register `handler` for GET.
+    pub fn get<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::GET, handler)
+    }
+
+    /// Register `handler` for HEAD.
+    pub fn head<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::HEAD, handler)
+    }
+
+    /// Register `handler` for POST.
+    pub fn post<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::POST, handler)
+    }
+
+    /// Register `handler` for PUT.
+    pub fn put<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::PUT, handler)
+    }
+
+    /// Register `handler` for PATCH.
+    pub fn patch<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::PATCH, handler)
+    }
+
+    /// Register `handler` for DELETE.
+    pub fn delete<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::DELETE, handler)
+    }
+
+    /// Register `handler` for OPTIONS.
+    pub fn options<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::OPTIONS, handler)
+    }
+
+    /// Register `handler` for TRACE.
+    pub fn trace<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::TRACE, handler)
+    }
+
+    /// Register `handler` for CONNECT.
+    pub fn connect<F>(self, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        self.method(http::Method::CONNECT, handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn echo(req: Request, params: Params) -> Result<Response> {
+        let body = format!("{} {}", req.method(), params.get("id").unwrap_or_default());
+        Ok(http::Response::new(Some(body.into())))
+    }
+
+    #[test]
+    fn test_path_route() {
+        let mut router = Router::new();
+        router.route("/items/:id").get(echo).put(echo).method_with(
+            http::Method::DELETE,
+            echo,
+            |route| {
+                route.doc("Removes an item.");
+            },
+        );
+        for method in [http::Method::GET, http::Method::PUT, http::Method::DELETE] {
+            let req = http::Request::builder()
+                .method(method.clone())
+                .uri("/items/7")
+                .body(None)
+                .unwrap();
+            let res = router.handle(req).unwrap();
+            assert_eq!(res.into_body().unwrap(), format!("{method} 7"));
+        }
+        let req = http::Request::post("/items/7").body(None).unwrap();
+        assert_eq!(router.handle(req).unwrap().status(), 405);
+        assert_eq!(
+            router.routes().nth(2).unwrap().docs(),
+            Some("Removes an item.")
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Extension points for crates building on this one, e.g. `spin-sdk-router-sessions`.
+//!
+//! An extension crate implements one or more of the traits re-exported here, whose
+//! signatures only change in a new major version of this crate:
+//!
+//! | Trait | Registered with |
+//! |---|---|
+//! | [`Middleware`] | [`Router::layer`] |
+//! | [`Guard`] | [`Router::guard`], [`Router::audit_guard`] |
+//! | [`IntoResponse`] | returned by [`compat`](crate::compat) handlers |
+//! | [`FromRequest`] | taken by [`compat`](crate::compat) handlers |
+//! | [`DrainStore`] | [`Router::drain_store`] |
+//! | [`RateLimitStore`] | [`RateLimit::store`](crate::ratelimit::RateLimit::store) |
+//! | [`InFlightStore`] | [`ConcurrencyLimit::store`](crate::ratelimit::ConcurrencyLimit::store) |
+//!
+//! and bundles what it registers as a [`Plugin`], which applications install with
+//! [`Router::plugin`].
+use crate::Router;
+
+pub use crate::compat::{FromRequest, IntoResponse};
+pub use crate::drain::DrainStore;
+pub use crate::ratelimit::{InFlightStore, Store as RateLimitStore};
+pub use crate::{Guard, Middleware, Next, Rejection};
+
+/// A set of middleware, guards, routes and settings installed on a router in one call:
+///
+/// ```
+/// # use spin_sdk_router::{plugin::Plugin, Router};
+/// /// Adds the `X-Powered-By` header to every response.
+/// pub struct PoweredBy;
+///
+/// impl Plugin for PoweredBy {
+///     fn name(&self) -> &'static str {
+///         "powered-by"
+///     }
+///
+///     fn register(&self, router: &mut Router) {
+///         router.layer(|req, next: spin_sdk_router::Next<'_>| {
+///             let mut res = next.run(req)?;
+///             res.headers_mut().insert("x-powered-by", "spin".parse()?);
+///             Ok(res)
+///         });
+///     }
+/// }
+///
+/// let mut router = Router::new();
+/// router.plugin(PoweredBy);
+/// ```
+pub trait Plugin: 'static {
+    /// The name identifying the plugin, conventionally that of its crate.
+    fn name(&self) -> &'static str;
+
+    /// Installs the plugin on `router`.
+    fn register(&self, router: &mut Router);
+}
+
+impl Router {
+    /// Install `plugin`, unless a plugin of the same name is installed already.
+    pub fn plugin<P: Plugin>(&mut self, plugin: P) {
+        if self.plugins.contains(&plugin.name()) {
+            return;
+        }
+        self.plugins.push(plugin.name());
+        plugin.register(self);
+    }
+
+    /// The names of the plugins installed, in the order they were installed.
+    pub fn plugins(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.plugins.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Request};
+
+    struct Health;
+
+    impl Plugin for Health {
+        fn name(&self) -> &'static str {
+            "health"
+        }
+
+        fn register(&self, router: &mut Router) {
+            router.get("/health", |_req: Request, _params: Params| {
+                Ok(http::Response::new(Some("ok".into())))
+            });
+        }
+    }
+
+    #[test]
+    fn test_plugins() {
+        let mut router = Router::new();
+        router.on_duplicate(crate::DuplicatePolicy::Error);
+        router.plugin(Health);
+        router.plugin(Health);
+        assert_eq!(router.plugins().collect::<Vec<_>>(), ["health"]);
+        let req = http::Request::get("/health").body(None).unwrap();
+        assert_eq!(router.handle(req).unwrap().into_body().unwrap(), "ok");
+    }
+}
@@ -0,0 +1,130 @@
+//! The `Priority` request header of extensible HTTP prioritization ([RFC 9218]).
+//!
+//! [RFC 9218]: https://www.rfc-editor.org/rfc/rfc9218
+use crate::sfv::{self, BareItem, Item, ListEntry};
+use crate::Request;
+use std::fmt;
+
+/// The priority a client assigned to a request, parsed from its `Priority` header.
+///
+/// Handlers and middleware can use it to skip expensive optional work, such as analytics or
+/// prefetch generation, for background fetches:
+///
+/// ```
+/// # use spin_sdk_router::{Params, Priority, Request, Response};
+/// fn handler(req: Request, _params: Params) -> anyhow::Result<Response> {
+///     if !Priority::of(&req).is_background() {
+///         // record analytics...
+///     }
+///     Ok(http::Response::new(None))
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Priority {
+    /// The urgency, from 0 (most urgent) to 7 (least urgent).
+    pub urgency: u8,
+    /// Whether the response can be processed incrementally.
+    pub incremental: bool,
+}
+
+impl Priority {
+    /// The urgency of requests that do not specify one.
+    pub const DEFAULT_URGENCY: u8 = 3;
+    /// The urgency clients use for background fetches.
+    pub const BACKGROUND_URGENCY: u8 = 7;
+
+    /// The priority of the request, falling back to the defaults for missing or invalid
+    /// parameters as the RFC requires.
+    pub fn of(request: &Request) -> Self {
+        let mut priority = Self::default();
+        let dictionary = request
+            .headers()
+            .get_all("priority")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let Ok(dictionary) = sfv::parse_dictionary(&dictionary) else {
+            return priority;
+        };
+        // Later members override earlier ones when the header is repeated.
+        for (key, entry) in dictionary {
+            let ListEntry::Item(Item { bare_item, .. }) = entry else {
+                continue;
+            };
+            match (key.as_str(), bare_item) {
+                ("u", BareItem::Integer(u)) if (0..=7).contains(&u) => priority.urgency = u as u8,
+                ("i", BareItem::Boolean(i)) => priority.incremental = i,
+                _ => {}
+            }
+        }
+        priority
+    }
+
+    /// Whether the request is a low-priority background fetch.
+    pub fn is_background(&self) -> bool {
+        self.urgency >= Self::BACKGROUND_URGENCY
+    }
+
+    /// Whether the request is less urgent than requests without a priority.
+    pub fn is_low(&self) -> bool {
+        self.urgency > Self::DEFAULT_URGENCY
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority {
+            urgency: Self::DEFAULT_URGENCY,
+            incremental: false,
+        }
+    }
+}
+
+/// Renders the priority as a `Priority` header value, omitting default parameters.
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut dictionary = sfv::Dictionary::new();
+        if self.urgency != Self::DEFAULT_URGENCY {
+            let urgency = BareItem::Integer(self.urgency.into());
+            dictionary.push(("u".to_owned(), ListEntry::Item(Item::new(urgency))));
+        }
+        if self.incremental {
+            let incremental = BareItem::Boolean(true);
+            dictionary.push(("i".to_owned(), ListEntry::Item(Item::new(incremental))));
+        }
+        f.write_str(&sfv::serialize_dictionary(&dictionary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(priority: &[&str]) -> Request {
+        let mut builder = http::Request::builder().uri("/");
+        for value in priority {
+            builder = builder.header("priority", *value);
+        }
+        builder.body(None).unwrap()
+    }
+
+    #[test]
+    fn test_priority() {
+        assert_eq!(Priority::of(&request(&[])), Priority::default());
+
+        let priority = Priority::of(&request(&["u=7, i"]));
+        assert_eq!(priority.urgency, 7);
+        assert!(priority.incremental);
+        assert!(priority.is_background());
+        assert_eq!(priority.to_string(), "u=7, i");
+
+        let priority = Priority::of(&request(&["u=5", "u=1, x=?1"]));
+        assert_eq!(priority.urgency, 1);
+        assert!(!priority.is_low());
+
+        assert_eq!(Priority::of(&request(&["u=9"])).urgency, 3);
+        assert_eq!(Priority::of(&request(&["u=(1 2)"])).urgency, 3);
+        assert_eq!(Priority::default().to_string(), "");
+    }
+}
@@ -0,0 +1,240 @@
+//! Problem Details for HTTP APIs (RFC 7807) as error responses.
+use crate::{compat::IntoResponse, mirror::json_string, Response, Router};
+use anyhow::Result;
+use std::fmt;
+
+/// The media type of problem details.
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// A problem details object, answered as `application/problem+json`:
+///
+/// ```
+/// # use spin_sdk_router::{compat, problem::Problem, Params, Request, Router};
+/// let mut router = Router::new();
+/// router.post("/transfers", compat::handler(|_req: Request, _params: Params| {
+///     Problem::new(http::StatusCode::FORBIDDEN)
+///         .type_uri("https://example.com/probs/out-of-credit")
+///         .title("You do not have enough credit.")
+///         .detail("Your current balance is 30, but that costs 50.")
+///         .member("balance", "30")
+/// }));
+/// ```
+///
+/// A problem is also an error, so handlers can return it with `?` or `Err(problem.into())`
+/// and the router answers with it, without reporting it to [`Router::on_error`] hooks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    status: http::StatusCode,
+    type_uri: Option<String>,
+    title: Option<String>,
+    detail: Option<String>,
+    instance: Option<String>,
+    members: Vec<(String, String)>,
+}
+
+impl Problem {
+    /// A problem of type `about:blank` with the given status, titled by its reason phrase.
+    pub fn new(status: http::StatusCode) -> Self {
+        Problem {
+            status,
+            type_uri: None,
+            title: None,
+            detail: None,
+            instance: None,
+            members: Vec::new(),
+        }
+    }
+
+    /// Identify the problem type with `uri`.
+    pub fn type_uri(mut self, uri: &str) -> Self {
+        self.type_uri = Some(uri.to_owned());
+        self
+    }
+
+    /// Summarize the problem type with `title` instead of the status reason phrase.
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_owned());
+        self
+    }
+
+    /// Explain this occurrence of the problem.
+    pub fn detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_owned());
+        self
+    }
+
+    /// Identify this occurrence of the problem with `uri`.
+    pub fn instance(mut self, uri: &str) -> Self {
+        self.instance = Some(uri.to_owned());
+        self
+    }
+
+    /// Add the extension member `name` with the string `value`.
+    pub fn member(mut self, name: &str, value: &str) -> Self {
+        self.members.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// The status of the problem.
+    pub fn status(&self) -> http::StatusCode {
+        self.status
+    }
+
+    /// The problem as a JSON object.
+    pub fn to_json(&self) -> String {
+        let title = self
+            .title
+            .as_deref()
+            .or(self.status.canonical_reason())
+            .unwrap_or_default();
+        let mut members = vec![
+            ("type", self.type_uri.as_deref().unwrap_or("about:blank")),
+            ("title", title),
+        ];
+        members.extend(self.detail.as_deref().map(|detail| ("detail", detail)));
+        members.extend(
+            self.instance
+                .as_deref()
+                .map(|instance| ("instance", instance)),
+        );
+        let mut json = format!("{{\"status\":{}", self.status.as_u16());
+        for (name, value) in members
+            .into_iter()
+            .chain(self.members.iter().map(|(n, v)| (n.as_str(), v.as_str())))
+        {
+            json.push_str(&format!(",{}:{}", json_string(name), json_string(value)));
+        }
+        json.push('}');
+        json
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.title, &self.detail) {
+            (_, Some(detail)) => f.write_str(detail),
+            (Some(title), None) => f.write_str(title),
+            (None, None) => write!(f, "{}", self.status),
+        }
+    }
+}
+
+impl std::error::Error for Problem {}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Result<Response> {
+        Ok(http::Response::builder()
+            .status(self.status)
+            .header(http::header::CONTENT_TYPE, PROBLEM_JSON)
+            .body(Some(self.to_json().into()))?)
+    }
+}
+
+impl Router {
+    /// Answer with problem details: responses with an error status and no body, such as the
+    /// built-in 404 and 405 responses, get a problem details body, and handler errors are
+    /// answered with a 500 problem instead of being returned from [`Router::handle`]. The
+    /// error itself is not disclosed to the client; see [`Router::on_error`] to report it.
+    pub fn problem_details(&mut self) {
+        self.problem_details = true;
+    }
+
+    /// Applies [`Router::problem_details`] to the result of dispatching a request.
+    pub(crate) fn problems(&self, result: Result<Response>) -> Result<Response> {
+        if !self.problem_details {
+            return result;
+        }
+        let Ok(response) = result else {
+            return Problem::new(http::StatusCode::INTERNAL_SERVER_ERROR).into_response();
+        };
+        let status = response.status();
+        let empty = response.body().as_ref().is_none_or(|body| body.is_empty());
+        if !(status.is_client_error() || status.is_server_error()) || !empty {
+            return Ok(response);
+        }
+        let (mut parts, _) = response.into_parts();
+        let problem = Problem::new(status);
+        parts.headers.insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static(PROBLEM_JSON),
+        );
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+        Ok(Response::from_parts(parts, Some(problem.to_json().into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Request};
+
+    #[test]
+    fn test_problem_json() {
+        let problem = Problem::new(http::StatusCode::FORBIDDEN)
+            .type_uri("https://example.com/probs/out-of-credit")
+            .title("You do not have enough credit.")
+            .detail("Balance is \"30\".")
+            .instance("/account/12345/msgs/abc")
+            .member("balance", "30");
+        assert_eq!(
+            problem.to_json(),
+            "{\"status\":403,\"type\":\"https://example.com/probs/out-of-credit\",\
+             \"title\":\"You do not have enough credit.\",\"detail\":\"Balance is \\\"30\\\".\",\
+             \"instance\":\"/account/12345/msgs/abc\",\"balance\":\"30\"}"
+        );
+        let res = problem.into_response().unwrap();
+        assert_eq!(res.status(), http::StatusCode::FORBIDDEN);
+        assert_eq!(res.headers()[http::header::CONTENT_TYPE], PROBLEM_JSON);
+    }
+
+    #[test]
+    fn test_problem_details_mode() {
+        let mut router = Router::new();
+        router.get("/fail", |_req, _params| anyhow::bail!("secret"));
+        router.get("/gone", |_req: Request, _params: Params| {
+            Err(Problem::new(http::StatusCode::GONE)
+                .detail("deleted")
+                .into())
+        });
+        router.get("/teapot", |_req, _params| {
+            Ok(http::Response::builder()
+                .status(418)
+                .body(Some("short and stout".into()))?)
+        });
+        router.problem_details();
+
+        let get = |path: &str| {
+            let req = http::Request::builder().uri(path).body(None).unwrap();
+            let res = router.handle(req).unwrap();
+            let body = res.body().clone().unwrap_or_default();
+            (
+                res.status().as_u16(),
+                String::from_utf8(body.to_vec()).unwrap(),
+            )
+        };
+        assert_eq!(
+            get("/nope"),
+            (
+                404,
+                "{\"status\":404,\"type\":\"about:blank\",\"title\":\"Not Found\"}".to_owned()
+            )
+        );
+        assert_eq!(
+            get("/fail"),
+            (
+                500,
+                "{\"status\":500,\"type\":\"about:blank\",\"title\":\"Internal Server Error\"}"
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            get("/gone"),
+            (
+                410,
+                "{\"status\":410,\"type\":\"about:blank\",\"title\":\"Gone\",\"detail\":\"deleted\"}"
+                    .to_owned()
+            )
+        );
+        assert_eq!(get("/teapot"), (418, "short and stout".to_owned()));
+    }
+}
@@ -0,0 +1,129 @@
+//! Cold-start profiling of router construction, the first dispatch and instance init hooks.
+use crate::{Request, Response, Router};
+use anyhow::Result;
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Timings of the start of a Wasm instance, to guide cold-start optimization; see
+/// [`Router::cold_start`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColdStart {
+    /// The time from constructing the router to it handling its first request, which covers
+    /// registering routes and any other setup in between.
+    pub construction: Option<Duration>,
+    /// The time taken to handle the first request, including init hooks and middleware.
+    pub first_dispatch: Option<Duration>,
+    /// The time taken by each run of an instance init hook, in the order they ran.
+    pub init_hooks: Vec<Duration>,
+}
+
+impl fmt::Display for ColdStart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pending = |d: Option<Duration>| d.map_or("pending".to_owned(), |d| format!("{d:?}"));
+        writeln!(f, "construction: {}", pending(self.construction))?;
+        writeln!(f, "first dispatch: {}", pending(self.first_dispatch))?;
+        for (i, hook) in self.init_hooks.iter().enumerate() {
+            writeln!(f, "init hook {i}: {hook:?}")?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) struct Profiler {
+    created: Instant,
+    construction: Cell<Option<Duration>>,
+    first_dispatch: Cell<Option<Duration>>,
+    init_hooks: RefCell<Vec<Duration>>,
+}
+
+impl Profiler {
+    pub(crate) fn new() -> Self {
+        Profiler {
+            created: Instant::now(),
+            construction: Cell::new(None),
+            first_dispatch: Cell::new(None),
+            init_hooks: RefCell::default(),
+        }
+    }
+
+    /// Runs `handle`, timing it if it is the first request.
+    pub(crate) fn dispatch<T>(&self, handle: impl FnOnce() -> T) -> T {
+        if self.construction.get().is_some() {
+            return handle();
+        }
+        self.construction.set(Some(self.created.elapsed()));
+        let start = Instant::now();
+        let result = handle();
+        self.first_dispatch.set(Some(start.elapsed()));
+        result
+    }
+
+    /// Runs an init hook, recording how long it took.
+    pub(crate) fn init_hook<T>(&self, hook: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = hook();
+        self.init_hooks.borrow_mut().push(start.elapsed());
+        result
+    }
+}
+
+impl Router {
+    /// Timings of the router's construction, first dispatch and instance init hooks.
+    pub fn cold_start(&self) -> ColdStart {
+        ColdStart {
+            construction: self.profiler.construction.get(),
+            first_dispatch: self.profiler.first_dispatch.get(),
+            init_hooks: self.profiler.init_hooks.borrow().clone(),
+        }
+    }
+
+    /// Serve the [`Router::cold_start`] timings as plain text at `/__cold_start`.
+    pub fn serve_cold_start(&mut self) {
+        self.builtins.insert(
+            "/__cold_start",
+            Box::new(|router: &Router, _req: Request| -> Result<Response> {
+                Ok(http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+                    .body(Some(router.cold_start().to_string().into()))?)
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(router: &Router, path: &str) -> Response {
+        let req = http::Request::builder().uri(path).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_cold_start() {
+        let mut router = Router::new();
+        router.on_instance_init(|| Ok(()));
+        router.get("/", |_req, _params| Ok(http::Response::new(None)));
+        router.serve_cold_start();
+        assert_eq!(router.cold_start(), ColdStart::default());
+
+        get(&router, "/");
+        let cold_start = router.cold_start();
+        assert!(cold_start.construction.is_some());
+        assert!(cold_start.first_dispatch.is_some());
+        assert_eq!(cold_start.init_hooks.len(), 1);
+
+        get(&router, "/");
+        assert_eq!(router.cold_start(), cold_start);
+
+        let res = get(&router, "/__cold_start");
+        let body = res.into_body().unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.starts_with("construction: "));
+        assert!(body.contains("init hook 0: "));
+    }
+}
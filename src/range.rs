@@ -0,0 +1,160 @@
+//! Serving parts of a response for `Range` requests.
+use crate::{Request, Response};
+use anyhow::Result;
+
+/// Applies the request's `Range` header to a complete 200 OK response, so that e.g. media
+/// players can seek within a file.
+///
+/// A satisfiable single byte range gives 206 Partial Content with a `Content-Range` header,
+/// and an unsatisfiable one 416 Range Not Satisfiable. Requests without a range, with
+/// several ranges, or whose `If-Range` no longer matches the response's `ETag` or
+/// `Last-Modified` get the complete response. The response always advertises
+/// `Accept-Ranges: bytes`.
+pub fn ranged_response(req: &Request, mut res: Response) -> Result<Response> {
+    if res.status() != http::StatusCode::OK {
+        return Ok(res);
+    }
+    res.headers_mut()
+        .insert(http::header::ACCEPT_RANGES, "bytes".parse()?);
+    if !matches!(*req.method(), http::Method::GET | http::Method::HEAD) {
+        return Ok(res);
+    }
+    let Some(range) = req
+        .headers()
+        .get(http::header::RANGE)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Ok(res);
+    };
+    if !if_range_matches(req, &res) {
+        return Ok(res);
+    }
+    let len = res.body().as_ref().map_or(0, |body| body.len() as u64);
+    let (start, end) = match parse_range(range, len) {
+        Some(Ok(range)) => range,
+        Some(Err(())) => {
+            return Ok(http::Response::builder()
+                .status(http::StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(http::header::CONTENT_RANGE, format!("bytes */{len}"))
+                .body(None)?)
+        }
+        None => return Ok(res),
+    };
+    *res.status_mut() = http::StatusCode::PARTIAL_CONTENT;
+    let headers = res.headers_mut();
+    headers.insert(
+        http::header::CONTENT_RANGE,
+        format!("bytes {start}-{end}/{len}").parse()?,
+    );
+    headers.remove(http::header::CONTENT_LENGTH);
+    if let Some(body) = res.body_mut() {
+        *body = body.slice(start as usize..=end as usize);
+    }
+    Ok(res)
+}
+
+/// Whether the validator in `If-Range`, if any, still matches the response.
+fn if_range_matches(req: &Request, res: &Response) -> bool {
+    let Some(if_range) = req.headers().get(http::header::IF_RANGE) else {
+        return true;
+    };
+    let validator = if if_range.as_bytes().starts_with(b"\"") {
+        // Only a strong entity tag can be used, and it must compare equal.
+        res.headers().get(http::header::ETAG)
+    } else {
+        res.headers().get(http::header::LAST_MODIFIED)
+    };
+    validator == Some(if_range)
+}
+
+/// Parses a single byte range against a body of `len` bytes into inclusive bounds.
+///
+/// Returns `None` for ranges that should be ignored (other units, several ranges or invalid
+/// syntax) and `Some(Err(()))` for ranges that cannot be satisfied.
+fn parse_range(range: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    let bounds = if first.is_empty() {
+        let suffix: u64 = last.parse().ok()?;
+        if suffix == 0 {
+            return Some(Err(()));
+        }
+        (len.saturating_sub(suffix), len.checked_sub(1))
+    } else {
+        let first: u64 = first.parse().ok()?;
+        let last = match last {
+            "" => len.checked_sub(1),
+            last => Some(last.parse::<u64>().ok()?.min(len.saturating_sub(1))),
+        };
+        (first, last)
+    };
+    match bounds {
+        (first, Some(last)) if first <= last && first < len => Some(Ok((first, last))),
+        (first, _) if first >= len || len == 0 => Some(Err(())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranged(range: &str) -> Response {
+        let req = http::Request::builder()
+            .uri("/")
+            .header(http::header::RANGE, range)
+            .body(None)
+            .unwrap();
+        let res = http::Response::new(Some("0123456789".into()));
+        ranged_response(&req, res).unwrap()
+    }
+
+    fn content_range(res: &Response) -> &str {
+        res.headers()[http::header::CONTENT_RANGE].to_str().unwrap()
+    }
+
+    #[test]
+    fn test_ranges() {
+        let res = ranged("bytes=2-4");
+        assert_eq!(res.status(), http::StatusCode::PARTIAL_CONTENT);
+        assert_eq!(content_range(&res), "bytes 2-4/10");
+        assert_eq!(res.into_body().unwrap(), "234");
+
+        assert_eq!(ranged("bytes=7-").into_body().unwrap(), "789");
+        assert_eq!(ranged("bytes=-2").into_body().unwrap(), "89");
+        assert_eq!(ranged("bytes=8-100").into_body().unwrap(), "89");
+
+        let res = ranged("bytes=10-");
+        assert_eq!(res.status(), http::StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(content_range(&res), "bytes */10");
+
+        let res = ranged("bytes=0-1,4-5");
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.headers()[http::header::ACCEPT_RANGES], "bytes");
+        assert_eq!(ranged("items=0-1").status(), http::StatusCode::OK);
+    }
+
+    #[test]
+    fn test_if_range() {
+        let req = http::Request::builder()
+            .uri("/")
+            .header(http::header::RANGE, "bytes=0-0")
+            .header(http::header::IF_RANGE, "\"v1\"")
+            .body(None)
+            .unwrap();
+        let res = |etag: &str| {
+            http::Response::builder()
+                .header(http::header::ETAG, etag)
+                .body(Some("abc".into()))
+                .unwrap()
+        };
+        let partial = ranged_response(&req, res("\"v1\"")).unwrap();
+        assert_eq!(partial.status(), http::StatusCode::PARTIAL_CONTENT);
+        let full = ranged_response(&req, res("\"v2\"")).unwrap();
+        assert_eq!(full.status(), http::StatusCode::OK);
+    }
+}
@@ -0,0 +1,436 @@
+//! Fixed-window rate limiting with standard `RateLimit` response headers, and limits on the
+//! number of requests each client may have in flight at once.
+use crate::{AuditEvent, Middleware, Next, Rejection, Request, Response};
+use anyhow::Result;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The header Spin sets to the address of the client, e.g. `127.0.0.1:53152`.
+pub const CLIENT_ADDR_HEADER: &str = "spin-client-addr";
+
+/// Storage for rate limit counters.
+///
+/// Each Spin instance may only serve a single request, so limits that must hold across
+/// requests need a store backed by shared state such as a key-value store.
+pub trait Store: 'static {
+    /// Counts a hit for `key` in the window starting at `window_start` (seconds since the
+    /// Unix epoch), returning the number of hits in that window including this one.
+    fn hit(&self, key: &str, window_start: u64, window: Duration) -> Result<u64>;
+}
+
+/// Storage for the number of requests each client has in flight.
+///
+/// To hold the cap across Spin instances serving requests concurrently, the counters must
+/// live in shared state such as a key-value store.
+pub trait InFlightStore: 'static {
+    /// Counts a request for `key` as started, returning the number of requests in flight for
+    /// `key` including this one.
+    fn acquire(&self, key: &str) -> Result<u64>;
+
+    /// Counts a request for `key` as finished.
+    fn release(&self, key: &str) -> Result<()>;
+}
+
+/// A [`Store`] and [`InFlightStore`] keeping counters in the memory of the current instance.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    counters: RefCell<HashMap<String, (u64, u64)>>,
+    in_flight: RefCell<HashMap<String, u64>>,
+}
+
+impl MemoryStore {
+    /// Construct an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn hit(&self, key: &str, window_start: u64, _window: Duration) -> Result<u64> {
+        let mut counters = self.counters.borrow_mut();
+        let (start, count) = counters.entry(key.to_owned()).or_insert((window_start, 0));
+        if *start != window_start {
+            *start = window_start;
+            *count = 0;
+        }
+        *count += 1;
+        Ok(*count)
+    }
+}
+
+impl InFlightStore for MemoryStore {
+    fn acquire(&self, key: &str) -> Result<u64> {
+        let mut in_flight = self.in_flight.borrow_mut();
+        let count = in_flight.entry(key.to_owned()).or_default();
+        *count += 1;
+        Ok(*count)
+    }
+
+    fn release(&self, key: &str) -> Result<()> {
+        let mut in_flight = self.in_flight.borrow_mut();
+        if let Some(count) = in_flight.get_mut(key) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which rate limit headers are added to responses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Headers {
+    /// The IETF draft `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers.
+    #[default]
+    Standard,
+    /// The legacy `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset` headers.
+    Legacy,
+    /// Both the standard and the legacy headers.
+    Both,
+    /// No headers, apart from `Retry-After` on limited responses.
+    None,
+}
+
+type KeyFn = dyn Fn(&Request) -> Option<String>;
+
+/// Middleware limiting each client to a number of requests per fixed window, answering
+/// with 429 Too Many Requests once the limit is exceeded.
+pub struct RateLimit {
+    store: Box<dyn Store>,
+    limit: u64,
+    window: Duration,
+    key: Box<KeyFn>,
+    headers: Headers,
+    expose_policy: bool,
+    audit: bool,
+    now: fn() -> SystemTime,
+}
+
+impl RateLimit {
+    /// Allow `limit` requests per `window` for each client address, counting in memory.
+    pub fn new(limit: u64, window: Duration) -> Self {
+        RateLimit {
+            store: Box::new(MemoryStore::new()),
+            limit,
+            window,
+            key: Box::new(client_addr),
+            headers: Headers::default(),
+            expose_policy: false,
+            audit: false,
+            now: SystemTime::now,
+        }
+    }
+
+    /// Keep the counters in the given store.
+    pub fn store<S: Store>(mut self, store: S) -> Self {
+        self.store = Box::new(store);
+        self
+    }
+
+    /// Derive the key requests are counted under, e.g. from an API key header. Requests for
+    /// which `key` returns `None` are not limited.
+    pub fn key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+
+    /// Choose which rate limit headers are added to responses.
+    pub fn headers(mut self, headers: Headers) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Also describe the quota policy in a `RateLimit-Policy` header, e.g. `100;w=60`.
+    pub fn expose_policy(mut self, expose: bool) -> Self {
+        self.expose_policy = expose;
+        self
+    }
+
+    /// Only report requests that exceed the limit to the router's audit sink, without
+    /// rejecting them.
+    pub fn audit(mut self) -> Self {
+        self.audit = true;
+        self
+    }
+
+    fn set_headers(&self, res: &mut Response, remaining: u64, reset: u64) -> Result<()> {
+        let headers = res.headers_mut();
+        let values = [
+            ("limit", self.limit.to_string()),
+            ("remaining", remaining.to_string()),
+            ("reset", reset.to_string()),
+        ];
+        for (name, value) in values {
+            if matches!(self.headers, Headers::Standard | Headers::Both) {
+                headers.insert(
+                    format!("ratelimit-{name}").parse::<http::HeaderName>()?,
+                    value.parse()?,
+                );
+            }
+            if matches!(self.headers, Headers::Legacy | Headers::Both) {
+                headers.insert(
+                    format!("x-ratelimit-{name}").parse::<http::HeaderName>()?,
+                    value.parse()?,
+                );
+            }
+        }
+        if self.expose_policy {
+            let policy = format!("{};w={}", self.limit, self.window.as_secs());
+            headers.insert("ratelimit-policy", policy.parse()?);
+        }
+        Ok(())
+    }
+}
+
+/// Keys requests by the client address Spin provides, without the port.
+pub(crate) fn client_addr(req: &Request) -> Option<String> {
+    let addr = req.headers().get(CLIENT_ADDR_HEADER)?.to_str().ok()?;
+    let host = match addr.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => addr,
+    };
+    Some(
+        host.trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_owned(),
+    )
+}
+
+impl Middleware for RateLimit {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let Some(key) = (self.key)(&req) else {
+            return next.run(req);
+        };
+        let window = self.window.as_secs().max(1);
+        let now = (self.now)().duration_since(UNIX_EPOCH)?.as_secs();
+        let window_start = now - now % window;
+        let reset = window_start + window - now;
+
+        let count = self.store.hit(&key, window_start, self.window)?;
+        let remaining = self.limit.saturating_sub(count);
+
+        let mut res = if count <= self.limit {
+            next.run(req)?
+        } else {
+            let rejection = Rejection::new(
+                http::StatusCode::TOO_MANY_REQUESTS,
+                format!("{key} exceeded {} requests per {window}s", self.limit),
+            );
+            if self.audit || next.audit_mode() {
+                next.audit(&AuditEvent {
+                    guard: "rate limit",
+                    method: req.method(),
+                    path: req.uri().path(),
+                    status: rejection.status(),
+                    reason: rejection.reason(),
+                });
+                next.run(req)?
+            } else {
+                let mut res = rejection.into_response();
+                res.headers_mut()
+                    .insert(http::header::RETRY_AFTER, reset.into());
+                res
+            }
+        };
+        self.set_headers(&mut res, remaining, reset)?;
+        Ok(res)
+    }
+}
+
+/// Middleware capping the number of requests each client may have in flight at once,
+/// answering with 429 Too Many Requests while the cap is reached.
+///
+/// Apply it to expensive routes by layering it on the router that serves them, with an
+/// [`InFlightStore`] shared between instances.
+pub struct ConcurrencyLimit {
+    store: Box<dyn InFlightStore>,
+    max: u64,
+    key: Box<KeyFn>,
+}
+
+impl ConcurrencyLimit {
+    /// Allow `max` requests in flight for each client address, counting in memory.
+    pub fn new(max: u64) -> Self {
+        ConcurrencyLimit {
+            store: Box::new(MemoryStore::new()),
+            max,
+            key: Box::new(client_addr),
+        }
+    }
+
+    /// Keep the counters in the given store.
+    pub fn store<S: InFlightStore>(mut self, store: S) -> Self {
+        self.store = Box::new(store);
+        self
+    }
+
+    /// Derive the key requests are counted under, e.g. from an API key header. Requests for
+    /// which `key` returns `None` are not limited.
+    pub fn key<F>(mut self, key: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + 'static,
+    {
+        self.key = Box::new(key);
+        self
+    }
+}
+
+impl Middleware for ConcurrencyLimit {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let Some(key) = (self.key)(&req) else {
+            return next.run(req);
+        };
+        let count = self.store.acquire(&key)?;
+        let res = if count <= self.max {
+            next.run(req)
+        } else {
+            let rejection = Rejection::new(
+                http::StatusCode::TOO_MANY_REQUESTS,
+                format!("{key} has {} requests in flight", self.max),
+            );
+            if next.audit_mode() {
+                next.audit(&AuditEvent {
+                    guard: "concurrency limit",
+                    method: req.method(),
+                    path: req.uri().path(),
+                    status: rejection.status(),
+                    reason: rejection.reason(),
+                });
+                next.run(req)
+            } else {
+                Ok(rejection.into_response())
+            }
+        };
+        self.store.release(&key)?;
+        res
+    }
+}
+
+/// An [`InFlightStore`] whose clones share one [`MemoryStore`], standing in for state shared
+/// between Spin instances in tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct SharedStore(pub(crate) std::rc::Rc<MemoryStore>);
+
+#[cfg(test)]
+impl InFlightStore for SharedStore {
+    fn acquire(&self, key: &str) -> Result<u64> {
+        self.0.acquire(key)
+    }
+
+    fn release(&self, key: &str) -> Result<()> {
+        self.0.release(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    fn ok(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::builder().status(200).body(None)?)
+    }
+
+    fn request(addr: &str) -> Request {
+        http::Request::builder()
+            .uri("/")
+            .header(CLIENT_ADDR_HEADER, addr)
+            .body(None)
+            .unwrap()
+    }
+
+    fn router(limit: RateLimit) -> Router {
+        let mut router = Router::new();
+        router.get("/", ok);
+        router.layer(RateLimit {
+            now: || UNIX_EPOCH + Duration::from_secs(1_000_015),
+            ..limit
+        });
+        router
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let router = router(RateLimit::new(2, Duration::from_secs(60)).expose_policy(true));
+
+        let res = router.handle(request("10.0.0.1:5000")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.headers()["ratelimit-limit"], "2");
+        assert_eq!(res.headers()["ratelimit-remaining"], "1");
+        assert_eq!(res.headers()["ratelimit-reset"], "5");
+        assert_eq!(res.headers()["ratelimit-policy"], "2;w=60");
+
+        router.handle(request("10.0.0.1:5001")).unwrap();
+        let res = router.handle(request("10.0.0.1:5002")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["ratelimit-remaining"], "0");
+        assert_eq!(res.headers()[http::header::RETRY_AFTER], "5");
+
+        let res = router.handle(request("[::1]:5000")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+    }
+
+    #[test]
+    fn test_rate_limit_legacy_headers_and_audit() {
+        let limit = RateLimit::new(1, Duration::from_secs(60))
+            .headers(Headers::Legacy)
+            .audit();
+        let mut router = router(limit);
+        let events = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = events.clone();
+        router.on_audit(move |event| sink.borrow_mut().push(event.to_string()));
+
+        router.handle(request("10.0.0.1")).unwrap();
+        let res = router.handle(request("10.0.0.1")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(
+            *events.borrow(),
+            [
+                "audit: GET / would be rejected by rate limit with 429 Too Many Requests: \
+              10.0.0.1 exceeded 1 requests per 60s"
+            ]
+        );
+        assert_eq!(res.headers()["x-ratelimit-remaining"], "0");
+        assert!(!res.headers().contains_key("ratelimit-remaining"));
+    }
+
+    #[test]
+    fn test_concurrency_limit() {
+        let store = SharedStore::default();
+        let mut router = Router::new();
+        router.get("/", ok);
+        router.layer(ConcurrencyLimit::new(1).store(store.clone()).key(|req| {
+            req.headers()
+                .get("x-api-key")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+        }));
+        let request = |key: &str| {
+            http::Request::builder()
+                .uri("/")
+                .header("x-api-key", key)
+                .body(None)
+                .unwrap()
+        };
+
+        // Another instance is still serving a request for this key.
+        store.acquire("alpha").unwrap();
+        let res = router.handle(request("alpha")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::TOO_MANY_REQUESTS);
+        let res = router.handle(request("beta")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        store.release("alpha").unwrap();
+        let res = router.handle(request("alpha")).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert!(store.0.in_flight.borrow().is_empty());
+    }
+}
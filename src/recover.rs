@@ -0,0 +1,129 @@
+//! Recovery from panicking handlers.
+use crate::{
+    requestid::{random_id, RequestId},
+    Diagnostic, Middleware, Next, Request, Response,
+};
+use anyhow::Result;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Middleware answering requests whose handler panicked with 500 Internal Server Error,
+/// reporting the panic to the router's diagnostic sink, set with
+/// [`Router::on_diagnostic`](crate::Router::on_diagnostic):
+///
+/// ```
+/// # use spin_sdk_router::{recover::CatchPanic, requestid::SetRequestId, Router};
+/// let mut router = Router::new();
+/// router.layer(SetRequestId::new());
+/// router.layer(CatchPanic::new().correlation_id());
+/// ```
+///
+/// Panics can only be caught in builds that unwind; Wasm targets abort on panic unless
+/// built with `-C panic=unwind`, in which case this layer does nothing. State shared with
+/// the panicking handler, such as counters behind a `RefCell`, may be left inconsistent.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CatchPanic {
+    correlation_id: bool,
+}
+
+impl CatchPanic {
+    /// Answer panics with an empty 500 response.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include an ID in the 500 response and the report, so that a user's report can be
+    /// matched with the logs. The ID is the request's [`RequestId`] if it has one, and is
+    /// generated otherwise.
+    pub fn correlation_id(mut self) -> Self {
+        self.correlation_id = true;
+        self
+    }
+}
+
+/// The message of a panic payload.
+fn message(payload: &(dyn std::any::Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("<unknown>", |m| m.as_str()),
+    }
+}
+
+impl Middleware for CatchPanic {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let method = req.method().clone();
+        let path = req.uri().path().to_owned();
+        let id = self
+            .correlation_id
+            .then(|| RequestId::of(&req).map_or_else(random_id, str::to_owned));
+        let router = next.router();
+        let payload = match catch_unwind(AssertUnwindSafe(|| next.run(req))) {
+            Ok(result) => return result,
+            Err(payload) => payload,
+        };
+        let message = message(&*payload);
+        let report = |message: &str| {
+            router.report(&Diagnostic {
+                source: "catch panic",
+                message,
+            })
+        };
+        let mut res = http::Response::builder().status(http::StatusCode::INTERNAL_SERVER_ERROR);
+        let body = match &id {
+            Some(id) => {
+                report(&format!(
+                    "handler for {method} {path} panicked ({id}): {message}"
+                ));
+                res = res.header("x-correlation-id", id);
+                Some(format!("internal error, reference {id}").into())
+            }
+            None => {
+                report(&format!("handler for {method} {path} panicked: {message}"));
+                None
+            }
+        };
+        Ok(res.body(body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{requestid::SetRequestId, Params, Router};
+
+    fn panicking(layer: CatchPanic) -> Router {
+        let mut router = Router::new();
+        router.get("/ok", |_req, _params| Ok(http::Response::new(None)));
+        router.get("/panic", |_req: Request, _params: Params| panic!("boom"));
+        router.layer(SetRequestId::new().generator(|| "abc".to_owned()));
+        router.layer(layer);
+        router
+    }
+
+    fn get(router: &Router, path: &str) -> Response {
+        let req = http::Request::builder().uri(path).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        let mut router = panicking(CatchPanic::new());
+        let reported = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = reported.clone();
+        router.on_diagnostic(move |diagnostic| sink.borrow_mut().push(diagnostic.to_string()));
+        assert_eq!(get(&router, "/ok").status(), http::StatusCode::OK);
+        let res = get(&router, "/panic");
+        assert_eq!(res.status(), http::StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(res.body(), &None);
+        assert_eq!(
+            *reported.borrow(),
+            ["catch panic: handler for GET /panic panicked: boom"]
+        );
+
+        let router = panicking(CatchPanic::new().correlation_id());
+        let res = get(&router, "/panic");
+        assert_eq!(res.headers()["x-correlation-id"], "abc");
+        assert_eq!(res.into_body().unwrap(), "internal error, reference abc");
+    }
+}
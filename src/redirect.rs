@@ -0,0 +1,54 @@
+//! Redirect responses.
+//!
+//! ```
+//! # use spin_sdk_router::{redirect, Params, Request, Router};
+//! let mut router = Router::new();
+//! router.get("/old", |_req: Request, _params: Params| redirect::permanent("/new"));
+//! router.post("/orders", |_req: Request, _params: Params| redirect::see_other("/orders/42"));
+//! ```
+use crate::Response;
+use anyhow::Result;
+
+/// A response redirecting the client to `uri` with the redirect status `status`.
+///
+/// Fails if `uri` is not a valid header value, e.g. because it contains a newline.
+pub fn to(status: http::StatusCode, uri: &str) -> Result<Response> {
+    Ok(http::Response::builder()
+        .status(status)
+        .header(http::header::LOCATION, http::HeaderValue::from_str(uri)?)
+        .body(None)?)
+}
+
+/// 307 Temporary Redirect to `uri`, repeating the request with the same method and body.
+pub fn temporary(uri: &str) -> Result<Response> {
+    to(http::StatusCode::TEMPORARY_REDIRECT, uri)
+}
+
+/// 308 Permanent Redirect to `uri`, repeating the request with the same method and body.
+pub fn permanent(uri: &str) -> Result<Response> {
+    to(http::StatusCode::PERMANENT_REDIRECT, uri)
+}
+
+/// 303 See Other, sending the client to GET `uri`, e.g. after a form submission.
+pub fn see_other(uri: &str) -> Result<Response> {
+    to(http::StatusCode::SEE_OTHER, uri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirects() {
+        for (res, status) in [
+            (temporary("/a"), 307),
+            (permanent("/a"), 308),
+            (see_other("/a"), 303),
+        ] {
+            let res = res.unwrap();
+            assert_eq!(res.status(), status);
+            assert_eq!(res.headers()[http::header::LOCATION], "/a");
+        }
+        assert!(temporary("/a\nSet-Cookie: x=1").is_err());
+    }
+}
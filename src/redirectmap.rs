@@ -0,0 +1,297 @@
+//! A table of redirects kept as configuration, e.g. for legacy and marketing URLs.
+use crate::{
+    alias::{parse_source, substitute},
+    redirect, Middleware, Next, Request, Response,
+};
+use anyhow::{bail, Context, Result};
+use routefinder::Router as MethodRouter;
+
+/// An entry of a [`RedirectMap`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirect {
+    /// The path pattern of the source, e.g. `/blog/:slug` or `/promo/*`.
+    pub from: String,
+    /// The target, a path pattern using the parameters of the source or an absolute URL.
+    pub to: String,
+    /// The redirect status.
+    pub status: http::StatusCode,
+}
+
+/// Middleware redirecting the requests whose path matches a source of the table to its
+/// target, before they are routed.
+///
+/// The table can be built in code, or loaded from text such as the value of a Spin variable,
+/// with one redirect per line and an optional status:
+///
+/// ```
+/// # use spin_sdk_router::{redirectmap::RedirectMap, Router};
+/// let redirects = RedirectMap::parse(
+///     "# Moved in the 2024 redesign
+///      /blog/:slug      /articles/:slug
+///      /summer-sale     https://shop.example.com/sale  302
+///      /docs/v1/*       /docs/*",
+/// )?;
+/// let mut router = Router::new();
+/// router.layer(redirects);
+/// # anyhow::Ok(())
+/// ```
+///
+/// Redirects are permanent (308) unless given another status, and keep the query of the
+/// request. The most specific source matching a path applies.
+#[derive(Default)]
+pub struct RedirectMap {
+    router: MethodRouter<usize>,
+    redirects: Vec<Redirect>,
+}
+
+impl RedirectMap {
+    /// An empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redirect `from` to `to` with 308 Permanent Redirect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the redirect is invalid; see [`RedirectMap::try_insert`].
+    pub fn redirect(self, from: &str, to: &str) -> Self {
+        self.redirect_with(from, to, http::StatusCode::PERMANENT_REDIRECT)
+    }
+
+    /// Redirect `from` to `to` with `status`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the redirect is invalid; see [`RedirectMap::try_insert`].
+    pub fn redirect_with(mut self, from: &str, to: &str, status: http::StatusCode) -> Self {
+        let redirect = Redirect {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            status,
+        };
+        self.try_insert(redirect)
+            .unwrap_or_else(|e| panic!("{e:#}"));
+        self
+    }
+
+    /// Adds a redirect, failing if its source is not a valid route pattern, if its target
+    /// has a parameter the source does not capture, or if its status is not a redirect.
+    pub fn try_insert(&mut self, redirect: Redirect) -> Result<()> {
+        if !redirect.status.is_redirection() {
+            bail!("{} is not a redirect status", redirect.status);
+        }
+        // Absolute URLs are taken as they are.
+        let spec = match redirect.to.starts_with('/') {
+            true => parse_source(&redirect.from, &redirect.to)?,
+            false => crate::error::parse_pattern(&redirect.from)?,
+        };
+        self.router.add(spec, self.redirects.len()).unwrap();
+        self.redirects.push(redirect);
+        Ok(())
+    }
+
+    /// Parses a table with one redirect per line, as `from to [status]`, ignoring blank
+    /// lines and those starting with `#`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut map = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let redirect = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [from, to] => Ok((from, to, None)),
+                [from, to, status] => Ok((from, to, Some(status))),
+                _ => Err(anyhow::anyhow!("expected `from to [status]`")),
+            }
+            .and_then(|(from, to, status)| {
+                let status = match status {
+                    Some(status) => status.parse()?,
+                    None => http::StatusCode::PERMANENT_REDIRECT,
+                };
+                map.try_insert(Redirect {
+                    from: from.to_owned(),
+                    to: to.to_owned(),
+                    status,
+                })
+            });
+            redirect.with_context(|| format!("invalid redirect on line {}", number + 1))?;
+        }
+        Ok(map)
+    }
+
+    /// Parses a JSON object mapping sources to targets, or to objects with a `to` target
+    /// and a numeric `status`:
+    /// `{"/old": "/new", "/sale": {"to": "/offers", "status": 302}}`.
+    #[cfg(feature = "json")]
+    pub fn from_json(text: &str) -> Result<Self> {
+        let table: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(text).context("invalid redirect table")?;
+        let mut map = Self::new();
+        for (from, target) in table {
+            let (to, status) = match &target {
+                serde_json::Value::String(to) => (Some(to.as_str()), None),
+                _ => (
+                    target.get("to").and_then(serde_json::Value::as_str),
+                    target.get("status").map(|s| s.as_u64()),
+                ),
+            };
+            map.insert_entry(&from, to, status)?;
+        }
+        Ok(map)
+    }
+
+    /// Parses a TOML table mapping sources to targets, or to inline tables with a `to`
+    /// target and a `status`:
+    ///
+    /// ```toml
+    /// "/old" = "/new"
+    /// "/sale" = { to = "/offers", status = 302 }
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let table: toml::Table = text.parse().context("invalid redirect table")?;
+        let mut map = Self::new();
+        for (from, target) in table {
+            let (to, status) = match &target {
+                toml::Value::String(to) => (Some(to.as_str()), None),
+                _ => (
+                    target.get("to").and_then(toml::Value::as_str),
+                    target
+                        .get("status")
+                        .map(|s| s.as_integer().and_then(|s| u64::try_from(s).ok())),
+                ),
+            };
+            map.insert_entry(&from, to, status)?;
+        }
+        Ok(map)
+    }
+
+    /// Adds an entry of a JSON or TOML table, whose status is `None` when absent and
+    /// `Some(None)` when not a number.
+    #[cfg(any(feature = "json", feature = "toml"))]
+    fn insert_entry(
+        &mut self,
+        from: &str,
+        to: Option<&str>,
+        status: Option<Option<u64>>,
+    ) -> Result<()> {
+        let status = match status {
+            None => Some(http::StatusCode::PERMANENT_REDIRECT),
+            Some(status) => status
+                .and_then(|s| u16::try_from(s).ok())
+                .and_then(|s| http::StatusCode::from_u16(s).ok()),
+        };
+        let (Some(to), Some(status)) = (to, status) else {
+            bail!("invalid redirect for `{from}`: expected a target and a status");
+        };
+        let redirect = Redirect {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            status,
+        };
+        self.try_insert(redirect)
+            .with_context(|| format!("invalid redirect for `{from}`"))
+    }
+
+    /// The redirects of the table, in the order they were added.
+    pub fn redirects(&self) -> &[Redirect] {
+        &self.redirects
+    }
+}
+
+impl Middleware for RedirectMap {
+    fn handle(&self, req: Request, next: Next<'_>) -> Result<Response> {
+        let Some(m) = self.router.best_match(req.uri().path()) else {
+            return next.run(req);
+        };
+        let redirect = &self.redirects[*m.handler()];
+        let mut target = substitute(&redirect.to, &m.captures());
+        if let Some(query) = req.uri().query() {
+            let separator = if target.contains('?') { '&' } else { '?' };
+            target = format!("{target}{separator}{query}");
+        }
+        redirect::to(redirect.status, &target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    fn get(router: &Router, uri: &str) -> Response {
+        let req = http::Request::get(uri).body(None).unwrap();
+        router.handle(req).unwrap()
+    }
+
+    #[test]
+    fn test_redirect_map() {
+        let map = RedirectMap::parse(
+            "# legacy
+             /blog/:slug  /articles/:slug
+
+             /sale        https://shop.example.com/sale?ref=web  302
+             /docs/v1/*   /docs/*
+             /docs/v1/faq /faq",
+        )
+        .unwrap();
+        assert_eq!(map.redirects().len(), 4);
+        let mut router = Router::new();
+        router.get("/articles/:slug", |_req, _params: Params| {
+            Ok(http::Response::new(None))
+        });
+        router.layer(map);
+
+        let res = get(&router, "/blog/hello?utm=x");
+        assert_eq!(res.status(), 308);
+        assert_eq!(
+            res.headers()[http::header::LOCATION],
+            "/articles/hello?utm=x"
+        );
+        let res = get(&router, "/sale?utm=x");
+        assert_eq!(res.status(), 302);
+        assert_eq!(
+            res.headers()[http::header::LOCATION],
+            "https://shop.example.com/sale?ref=web&utm=x"
+        );
+        let res = get(&router, "/docs/v1/guide/setup");
+        assert_eq!(res.headers()[http::header::LOCATION], "/docs/guide/setup");
+        let res = get(&router, "/docs/v1/faq");
+        assert_eq!(res.headers()[http::header::LOCATION], "/faq");
+        assert_eq!(get(&router, "/articles/hello").status(), 200);
+
+        let err = RedirectMap::parse("/a /b\n/c /d 200").err().unwrap();
+        assert_eq!(
+            format!("{err:#}"),
+            "invalid redirect on line 2: 200 OK is not a redirect status"
+        );
+        assert!(RedirectMap::parse("/a /b/:id").is_err());
+        assert!(RedirectMap::parse("/a").is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_json() {
+        let map = RedirectMap::from_json(
+            r#"{"/old": "/new", "/sale": {"to": "/offers", "status": 302}}"#,
+        )
+        .unwrap();
+        let statuses: Vec<_> = map.redirects().iter().map(|r| r.status.as_u16()).collect();
+        assert_eq!(statuses, [308, 302]);
+        assert!(RedirectMap::from_json(r#"{"/old": {"status": 301}}"#).is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml() {
+        let map = RedirectMap::from_toml(
+            "\"/old\" = \"/new\"\n\"/sale\" = { to = \"/offers\", status = 302 }",
+        )
+        .unwrap();
+        assert_eq!(map.redirects()[1].to, "/offers");
+        assert_eq!(map.redirects()[1].status, 302);
+        assert!(RedirectMap::from_toml("\"/old\" = { to = \"/new\", status = \"x\" }").is_err());
+    }
+}
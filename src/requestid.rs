@@ -0,0 +1,179 @@
+//! Request IDs correlating the logs of a request across services.
+use crate::{Middleware, Next, Request, Response};
+use anyhow::Result;
+use std::{
+    cell::Cell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The ID of the request being handled, inserted into the request extensions by
+/// [`SetRequestId`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    /// The ID of `req`, if [`SetRequestId`] assigned one.
+    pub fn of(req: &Request) -> Option<&str> {
+        req.extensions().get().map(|RequestId(id)| id.as_str())
+    }
+}
+
+/// Middleware giving every request an ID, taken from its `X-Request-Id` header when it
+/// carries a valid one and generated otherwise:
+///
+/// ```
+/// # use spin_sdk_router::{requestid::{RequestId, SetRequestId}, Params, Request, Router};
+/// let mut router = Router::new();
+/// router.layer(SetRequestId::new());
+/// router.get("/", |req: Request, _params: Params| {
+///     eprintln!("[{}] hello", RequestId::of(&req).unwrap_or("-"));
+///     Ok(http::Response::new(None))
+/// });
+/// ```
+///
+/// The ID is inserted into the request extensions as a [`RequestId`], set on the request
+/// header so that [`outbound::Client`](crate::outbound::Client) propagates it, and added to
+/// the response. Incoming IDs longer than 128 characters or containing characters other than
+/// visible ASCII are replaced.
+pub struct SetRequestId {
+    header: http::header::HeaderName,
+    generate: Box<dyn Fn() -> String>,
+}
+
+impl Default for SetRequestId {
+    fn default() -> Self {
+        SetRequestId {
+            header: http::header::HeaderName::from_static("x-request-id"),
+            generate: Box::new(random_id),
+        }
+    }
+}
+
+impl SetRequestId {
+    /// Read and write IDs in the `X-Request-Id` header, generating random UUIDs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read and write IDs in the header `name` instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn header(mut self, name: &str) -> Self {
+        self.header = name.parse().expect("invalid header name");
+        self
+    }
+
+    /// Generate IDs with `generate` instead.
+    pub fn generator<F>(mut self, generate: F) -> Self
+    where
+        F: Fn() -> String + 'static,
+    {
+        self.generate = Box::new(generate);
+        self
+    }
+
+    fn incoming<'a>(&self, req: &'a Request) -> Option<&'a str> {
+        let id = req.headers().get(&self.header)?.to_str().ok()?;
+        let valid = !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic());
+        valid.then_some(id)
+    }
+}
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+            | 1,
+    );
+}
+
+/// The next number of an xorshift generator, for IDs that only need to be unique.
+pub(crate) fn next_random() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
+}
+
+/// A random (version 4) UUID.
+pub(crate) fn random_id() -> String {
+    let (high, low) = (next_random(), next_random());
+    let high = (high & !0xf000) | 0x4000;
+    let low = (low & !(0b11 << 62)) | (0b10 << 62);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+impl Middleware for SetRequestId {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        let id = match self.incoming(&req) {
+            Some(id) => id.to_owned(),
+            None => (self.generate)(),
+        };
+        let value = http::HeaderValue::from_str(&id)?;
+        req.headers_mut().insert(&self.header, value.clone());
+        req.extensions_mut().insert(RequestId(id));
+        let mut res = next.run(req)?;
+        res.headers_mut().insert(&self.header, value);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    fn router(layer: SetRequestId) -> Router {
+        let mut router = Router::new();
+        router.get("/", |req: Request, _params: Params| {
+            let id = RequestId::of(&req).unwrap().to_owned();
+            Ok(http::Response::new(Some(id.into())))
+        });
+        router.layer(layer);
+        router
+    }
+
+    #[test]
+    fn test_request_id() {
+        let router = router(SetRequestId::new().generator(|| "generated".to_owned()));
+        let send = |id: Option<&str>| {
+            let mut req = http::Request::builder().uri("/");
+            if let Some(id) = id {
+                req = req.header("x-request-id", id);
+            }
+            router.handle(req.body(None).unwrap()).unwrap()
+        };
+
+        let res = send(Some("abc-123"));
+        assert_eq!(res.headers()["x-request-id"], "abc-123");
+        assert_eq!(res.into_body().unwrap(), "abc-123");
+        for id in [None, Some("has space"), Some(&*"x".repeat(129))] {
+            let res = send(id);
+            assert_eq!(res.headers()["x-request-id"], "generated");
+            assert_eq!(res.into_body().unwrap(), "generated");
+        }
+    }
+
+    #[test]
+    fn test_random_id() {
+        let (a, b) = (random_id(), random_id());
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 36);
+        assert_eq!(&a[14..15], "4");
+        assert!(matches!(&a[19..20], "8" | "9" | "a" | "b"));
+    }
+}
@@ -0,0 +1,225 @@
+//! REST resources whose conventional routes are registered together.
+use crate::{error, Params, Request, Response, RouteError, Router, RouterError};
+use anyhow::Result;
+use std::rc::Rc;
+
+/// A collection of REST resources, e.g. users, handled by one type and registered with
+/// [`Router::resource`]:
+///
+/// | Route | Action |
+/// |---|---|
+/// | `GET /users` | [`index`](Resource::index) |
+/// | `POST /users` | [`create`](Resource::create) |
+/// | `GET /users/:id` | [`show`](Resource::show) |
+/// | `PUT /users/:id`, `PATCH /users/:id` | [`update`](Resource::update) |
+/// | `DELETE /users/:id` | [`delete`](Resource::delete) |
+///
+/// Only the routes of the [actions](Resource::actions) a resource implements are registered,
+/// so that requests for the others are answered with 405 Method Not Allowed and the
+/// unimplemented methods are not advertised.
+///
+/// ```
+/// # use spin_sdk_router::{Params, Request, Resource, ResourceAction, Response, Router};
+/// struct Users;
+///
+/// impl Resource for Users {
+///     fn actions(&self) -> &[ResourceAction] {
+///         &[ResourceAction::Index, ResourceAction::Show]
+///     }
+///
+///     fn index(&self, _req: Request, _params: Params) -> anyhow::Result<Response> {
+///         Ok(http::Response::new(Some("[]".into())))
+///     }
+///
+///     fn show(&self, _req: Request, params: Params) -> anyhow::Result<Response> {
+///         let id = params.get("id").unwrap_or_default().to_owned();
+///         Ok(http::Response::new(Some(id.into())))
+///     }
+/// }
+///
+/// let mut router = Router::new();
+/// router.resource("/users", Users);
+/// ```
+pub trait Resource: 'static {
+    /// The actions the resource implements, whose routes are registered.
+    fn actions(&self) -> &[ResourceAction];
+
+    /// Lists the resources.
+    fn index(&self, _req: Request, _params: Params) -> Result<Response> {
+        Err(RouterError::MethodNotAllowed.into())
+    }
+
+    /// Creates a resource.
+    fn create(&self, _req: Request, _params: Params) -> Result<Response> {
+        Err(RouterError::MethodNotAllowed.into())
+    }
+
+    /// Shows the resource identified by the `id` parameter.
+    fn show(&self, _req: Request, _params: Params) -> Result<Response> {
+        Err(RouterError::MethodNotAllowed.into())
+    }
+
+    /// Replaces or modifies the resource identified by the `id` parameter.
+    fn update(&self, _req: Request, _params: Params) -> Result<Response> {
+        Err(RouterError::MethodNotAllowed.into())
+    }
+
+    /// Deletes the resource identified by the `id` parameter.
+    fn delete(&self, _req: Request, _params: Params) -> Result<Response> {
+        Err(RouterError::MethodNotAllowed.into())
+    }
+}
+
+/// An action of a [`Resource`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResourceAction {
+    /// [`Resource::index`].
+    Index,
+    /// [`Resource::create`].
+    Create,
+    /// [`Resource::show`].
+    Show,
+    /// [`Resource::update`].
+    Update,
+    /// [`Resource::delete`].
+    Delete,
+}
+
+type ActionFn<R> = fn(&R, Request, Params) -> Result<Response>;
+
+impl Router {
+    /// Register the conventional routes of `resource` under `path`, e.g. `/users`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is not a valid route pattern; see [`Router::try_resource`].
+    pub fn resource<R: Resource>(&mut self, path: &str, resource: R) {
+        self.try_resource(path, resource)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Register the conventional routes of `resource` under `path`, failing without
+    /// registering any of them if the path is not a valid route pattern.
+    pub fn try_resource<R: Resource>(&mut self, path: &str, resource: R) -> Result<(), RouteError> {
+        let collection = path.trim_end_matches('/');
+        let member = format!("{collection}/:id");
+        let collection = if collection.is_empty() {
+            "/"
+        } else {
+            collection
+        };
+        error::check_pattern(collection)?;
+        error::check_pattern(&member)?;
+        let resource = Rc::new(resource);
+        let routes: [(ResourceAction, &str, http::Method, ActionFn<R>); 6] = [
+            (
+                ResourceAction::Index,
+                collection,
+                http::Method::GET,
+                R::index,
+            ),
+            (
+                ResourceAction::Create,
+                collection,
+                http::Method::POST,
+                R::create,
+            ),
+            (ResourceAction::Show, &member, http::Method::GET, R::show),
+            (
+                ResourceAction::Update,
+                &member,
+                http::Method::PUT,
+                R::update,
+            ),
+            (
+                ResourceAction::Update,
+                &member,
+                http::Method::PATCH,
+                R::update,
+            ),
+            (
+                ResourceAction::Delete,
+                &member,
+                http::Method::DELETE,
+                R::delete,
+            ),
+        ];
+        let actions = resource.actions().to_vec();
+        for (_, path, method, action) in routes.into_iter().filter(|r| actions.contains(&r.0)) {
+            let resource = resource.clone();
+            self.try_add(path, method, move |req, params| {
+                action(&resource, req, params)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Users {
+        names: Vec<&'static str>,
+    }
+
+    impl Resource for Users {
+        fn actions(&self) -> &[ResourceAction] {
+            &[
+                ResourceAction::Index,
+                ResourceAction::Show,
+                ResourceAction::Update,
+            ]
+        }
+
+        fn index(&self, _req: Request, _params: Params) -> Result<Response> {
+            Ok(http::Response::new(Some(self.names.join(",").into())))
+        }
+
+        fn show(&self, _req: Request, params: Params) -> Result<Response> {
+            let id: usize = params.get("id").unwrap_or_default().parse()?;
+            match self.names.get(id) {
+                Some(name) => Ok(http::Response::new(Some(name.to_string().into()))),
+                None => Err(RouterError::NotFound.into()),
+            }
+        }
+
+        fn update(&self, req: Request, _params: Params) -> Result<Response> {
+            Ok(http::Response::new(Some(req.method().to_string().into())))
+        }
+    }
+
+    #[test]
+    fn test_resource() {
+        let mut router = Router::new();
+        router.resource(
+            "/users/",
+            Users {
+                names: vec!["ada", "grace"],
+            },
+        );
+        let send = |method: http::Method, uri: &str| {
+            let req = http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(None)
+                .unwrap();
+            router.handle(req).unwrap()
+        };
+        let body = |res: Response| res.into_body().unwrap();
+
+        assert_eq!(body(send(http::Method::GET, "/users")), "ada,grace");
+        assert_eq!(body(send(http::Method::GET, "/users/1")), "grace");
+        assert_eq!(send(http::Method::GET, "/users/2").status(), 404);
+        assert_eq!(body(send(http::Method::PATCH, "/users/1")), "PATCH");
+        assert_eq!(body(send(http::Method::PUT, "/users/1")), "PUT");
+        assert_eq!(send(http::Method::POST, "/users").status(), 405);
+        assert_eq!(send(http::Method::DELETE, "/users/1").status(), 405);
+        assert_eq!(router.routes().count(), 4);
+
+        let mut router = Router::new();
+        let err = router.try_resource("/files/*", Users { names: vec![] });
+        assert!(err.is_err());
+        assert_eq!(router.routes().count(), 0);
+    }
+}
@@ -0,0 +1,395 @@
+//! Registered routes and the metadata attached to them.
+use crate::limits::BodyMode;
+use crate::negotiate::{self, DeviceClass};
+use crate::{files::percent_decode, CachePolicy, Capability, HandlerFn, Params, Request, Response};
+use anyhow::Result;
+use std::rc::Rc;
+
+type Condition = dyn Fn(&Request) -> bool;
+
+/// A route registered on a [`Router`](crate::Router).
+///
+/// The registration methods return a mutable reference to the route so that metadata can be
+/// attached to it after it has been added.
+pub struct Route {
+    pub(crate) method: Option<http::Method>,
+    pub(crate) pattern: String,
+    pub(crate) defaults: Vec<(String, String)>,
+    pub(crate) choices: Vec<crate::error::Choices>,
+    pub(crate) handler: Box<HandlerFn>,
+    handler_name: &'static str,
+    conditions: Vec<Box<Condition>>,
+    pub(crate) queries: Vec<(String, Option<String>)>,
+    content_types: Vec<String>,
+    produces: Vec<String>,
+    device: Option<DeviceClass>,
+    pub(crate) cache: Option<CachePolicy>,
+    pub(crate) max_body_size: Option<usize>,
+    pub(crate) body_mode: BodyMode,
+    pub(crate) capabilities: Vec<Capability>,
+    pub(crate) serve_while_draining: bool,
+    docs: Option<String>,
+    pub(crate) sitemap_params: Option<Box<dyn Fn() -> Vec<Params>>>,
+    /// The policy in force when the route was registered.
+    pub(crate) duplicate_policy: Option<crate::DuplicatePolicy>,
+    #[cfg(feature = "openapi")]
+    pub(crate) operation: Option<serde_json::Value>,
+}
+
+impl Route {
+    pub(crate) fn new<F>(method: Option<http::Method>, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        Route {
+            method,
+            pattern: pattern.to_owned(),
+            defaults: Vec::new(),
+            choices: Vec::new(),
+            handler: Box::new(handler),
+            handler_name: std::any::type_name::<F>(),
+            conditions: Vec::new(),
+            queries: Vec::new(),
+            content_types: Vec::new(),
+            produces: Vec::new(),
+            device: None,
+            cache: None,
+            max_body_size: None,
+            body_mode: BodyMode::default(),
+            capabilities: Vec::new(),
+            serve_while_draining: false,
+            docs: None,
+            sitemap_params: None,
+            duplicate_policy: None,
+            #[cfg(feature = "openapi")]
+            operation: None,
+        }
+    }
+
+    /// A route for `method` sharing `handler` with the routes for other methods.
+    pub(crate) fn shared<F>(method: http::Method, pattern: &str, handler: Rc<F>) -> Self
+    where
+        F: Fn(Request, Params) -> Result<Response> + 'static,
+    {
+        let mut route = Route::new(Some(method), pattern, move |req, params| {
+            handler(req, params)
+        });
+        route.handler_name = std::any::type_name::<F>();
+        route
+    }
+
+    /// The route with the handler name `name`, for handlers known by their path.
+    #[cfg(feature = "attributes")]
+    pub(crate) fn with_handler_name(mut self, name: &'static str) -> Self {
+        self.handler_name = name;
+        self
+    }
+
+    /// The HTTP method of the route, or `None` if it was registered for all methods.
+    pub fn method(&self) -> Option<&http::Method> {
+        self.method.as_ref()
+    }
+
+    /// The pattern the route was registered with, without parameter defaults.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// The defaults of the route's trailing parameters, as `(name, default)` pairs; a route
+    /// registered as `/feed/:format=json` also matches `/feed`, with `format` set to `json`.
+    pub fn defaults(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.defaults.iter().map(|(n, d)| (n.as_str(), d.as_str()))
+    }
+
+    /// The values the route's constrained parameters may take, as `(name, choices)` pairs; a
+    /// route registered as `/export/:format<json|csv>` only matches `/export/json` and
+    /// `/export/csv`.
+    ///
+    /// Handlers can rely on the parameter being one of the choices, so parsing it into an
+    /// enum cannot fail for the values the route matches:
+    ///
+    /// ```
+    /// # use spin_sdk_router::{Params, Request, Router};
+    /// enum Format {
+    ///     Json,
+    ///     Csv,
+    /// }
+    ///
+    /// impl std::str::FromStr for Format {
+    ///     type Err = ();
+    ///
+    ///     fn from_str(s: &str) -> Result<Self, ()> {
+    ///         match s {
+    ///             "json" => Ok(Format::Json),
+    ///             "csv" => Ok(Format::Csv),
+    ///             _ => Err(()),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let mut router = Router::new();
+    /// router.get("/export/:format<json|csv>", |_req: Request, params: Params| {
+    ///     let body = match params.get("format").unwrap().parse() {
+    ///         Ok(Format::Json) => "[]",
+    ///         Ok(Format::Csv) | Err(()) => "",
+    ///     };
+    ///     Ok(http::Response::new(Some(body.into())))
+    /// });
+    /// ```
+    pub fn choices(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.choices.iter().map(|(n, c)| (n.as_str(), c.as_slice()))
+    }
+
+    /// Whether the captured parameters are among the choices of the route's constrained
+    /// parameters.
+    pub(crate) fn admits(&self, captures: &routefinder::Captures<'_, '_>) -> bool {
+        self.choices.iter().all(|(name, choices)| {
+            captures
+                .get(name)
+                .is_none_or(|value| choices.iter().any(|choice| choice == value))
+        })
+    }
+
+    /// The pattern the route was registered with, including parameter choices and defaults.
+    #[cfg(any(test, feature = "manifest"))]
+    pub(crate) fn registered_pattern(&self) -> String {
+        let mut pattern = self.pattern.clone();
+        let annotations = self
+            .choices
+            .iter()
+            .map(|(name, choices)| (name, format!("<{}>", choices.join("|"))))
+            .chain(
+                self.defaults
+                    .iter()
+                    .map(|(name, default)| (name, format!("={default}"))),
+            );
+        for (name, annotation) in annotations {
+            let segment = format!(":{name}");
+            if let Some(at) = pattern.rfind(&segment) {
+                let end = pattern[at..].find('/').map_or(pattern.len(), |i| at + i);
+                pattern.insert_str(end, &annotation);
+            }
+        }
+        pattern
+    }
+
+    /// Only match the route when `condition` holds for the request, e.g. when a header is
+    /// present or a feature flag is enabled. When a condition fails the router carries on
+    /// with the next best matching route.
+    pub fn when<F>(&mut self, condition: F) -> &mut Self
+    where
+        F: Fn(&Request) -> bool + 'static,
+    {
+        self.conditions.push(Box::new(condition));
+        self
+    }
+
+    /// Only match requests whose query string sets the parameter `name` to `value`, so that
+    /// e.g. `/search?type=user` and `/search?type=repo` can be dispatched to different
+    /// handlers. Calling it again adds further constraints.
+    ///
+    /// Among routes for the same method and pattern, those constraining the most query
+    /// parameters are tried first, then those with other conditions, and then those
+    /// registered first.
+    pub fn query(&mut self, name: &str, value: &str) -> &mut Self {
+        self.queries.push((name.to_owned(), Some(value.to_owned())));
+        self
+    }
+
+    /// Only match requests whose query string has the parameter `name`, with any value.
+    pub fn query_present(&mut self, name: &str) -> &mut Self {
+        self.queries.push((name.to_owned(), None));
+        self
+    }
+
+    /// Only match requests whose `Content-Type` is `media_type`, e.g. `application/json` or
+    /// `multipart/*`. Calling it again adds further accepted media types.
+    ///
+    /// Several handlers can be registered for the same method and pattern with different
+    /// media types; a request matching none of them is answered with 415 Unsupported Media
+    /// Type.
+    pub fn consumes(&mut self, media_type: &str) -> &mut Self {
+        self.content_types.push(media_type.to_ascii_lowercase());
+        self
+    }
+
+    /// Declare that the route responds with `media_type`, e.g. `text/csv`. Calling it again
+    /// adds further media types.
+    ///
+    /// Several handlers can be registered for the same method and pattern with different
+    /// media types, and the one the request's `Accept` header prefers is chosen. Responses
+    /// from such routes carry `Vary: Accept`, and a request accepting none of them is
+    /// answered with 406 Not Acceptable.
+    pub fn produces(&mut self, media_type: &str) -> &mut Self {
+        self.produces.push(media_type.to_owned());
+        self
+    }
+
+    /// Only match requests from the given class of device, so that e.g. a mobile variant of
+    /// a page takes precedence over the route serving everyone else. Responses for the
+    /// route's pattern then vary on `Sec-CH-UA-Mobile` and `User-Agent`.
+    pub fn device(&mut self, class: DeviceClass) -> &mut Self {
+        self.device = Some(class);
+        self
+    }
+
+    /// The class of device the route is limited to.
+    pub(crate) fn device_class(&self) -> Option<DeviceClass> {
+        self.device
+    }
+
+    /// Whether the route only matches some requests for its pattern.
+    pub(crate) fn is_conditional(&self) -> bool {
+        !self.conditions.is_empty()
+            || !self.queries.is_empty()
+            || !self.content_types.is_empty()
+            || self.negotiates()
+            || self.device.is_some()
+    }
+
+    /// Whether the route is chosen by negotiation on the `Accept` header.
+    pub(crate) fn negotiates(&self) -> bool {
+        !self.produces.is_empty()
+    }
+
+    /// Whether the route accepts the request.
+    pub(crate) fn accepts(&self, request: &Request) -> bool {
+        self.meets_conditions(request)
+            && self
+                .device
+                .is_none_or(|class| DeviceClass::of(request) == class)
+            && self.accepts_content_type(request)
+            && self.quality(request) > 0.0
+    }
+
+    /// The quality the request's `Accept` header assigns to the route's best media type.
+    pub(crate) fn quality(&self, request: &Request) -> f32 {
+        self.produces
+            .iter()
+            .map(|media_type| negotiate::quality(request, media_type))
+            .fold(if self.negotiates() { 0.0 } else { 1.0 }, f32::max)
+    }
+
+    /// Whether all the route's conditions accept the request.
+    pub(crate) fn meets_conditions(&self, request: &Request) -> bool {
+        self.conditions.iter().all(|condition| condition(request))
+            && self.queries.iter().all(|(name, value)| {
+                let query = request.uri().query().unwrap_or_default();
+                let mut values = query_values(query, name);
+                match value {
+                    Some(value) => values.any(|v| v == *value),
+                    None => values.next().is_some(),
+                }
+            })
+    }
+
+    /// Whether the route consumes the request's `Content-Type`.
+    pub(crate) fn accepts_content_type(&self, request: &Request) -> bool {
+        if self.content_types.is_empty() {
+            return true;
+        }
+        let Some(content_type) = request
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return false;
+        };
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.content_types
+            .iter()
+            .any(|media_type| match media_type.strip_suffix("/*") {
+                Some(kind) => essence.split('/').next() == Some(kind),
+                None => *media_type == essence,
+            })
+    }
+
+    /// Describes the route and everything that affects how it matches and responds.
+    pub(crate) fn describe(&self) -> String {
+        let method = self.method.as_ref().map_or("*", http::Method::as_str);
+        format!(
+            "{method} {} -> {} conditions={} consumes={:?} produces={:?} device={:?} cache={:?} max_body={:?} requires={:?} serve_while_draining={} defaults={:?} choices={:?} queries={:?} body={:?}",
+            self.pattern,
+            self.handler_name,
+            self.conditions.len(),
+            self.content_types,
+            self.produces,
+            self.device,
+            self.cache.as_ref().map(ToString::to_string),
+            self.max_body_size,
+            self.capabilities,
+            self.serve_while_draining,
+            self.defaults,
+            self.choices,
+            self.queries,
+            self.body_mode,
+        )
+    }
+
+    /// Document the route. The first paragraph is its summary and the rest its description,
+    /// as in a Rust doc comment; both appear in [`Router::routes`](crate::Router::routes),
+    /// the OpenAPI document and the `/__routes` page.
+    pub fn doc(&mut self, docs: &str) -> &mut Self {
+        self.docs = Some(docs.trim().to_owned());
+        self
+    }
+
+    /// Document the route with the lines of a `///` comment, as the [`router!`](crate::router)
+    /// macro does.
+    #[doc(hidden)]
+    pub fn doc_lines(&mut self, lines: &[&str]) -> &mut Self {
+        if !lines.is_empty() {
+            let lines: Vec<_> = lines
+                .iter()
+                .map(|line| line.strip_prefix(' ').unwrap_or(line))
+                .collect();
+            self.doc(&lines.join("\n"));
+        }
+        self
+    }
+
+    /// The documentation of the route.
+    pub fn docs(&self) -> Option<&str> {
+        self.docs.as_deref()
+    }
+
+    /// The first paragraph of the route's documentation, on a single line.
+    pub fn summary(&self) -> Option<String> {
+        let docs = self.docs.as_deref()?;
+        let paragraph = docs.split("\n\n").next().unwrap_or_default();
+        Some(paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// The route's documentation after its summary.
+    pub fn description(&self) -> Option<&str> {
+        let (_, rest) = self.docs.as_deref()?.split_once("\n\n")?;
+        Some(rest.trim()).filter(|rest| !rest.is_empty())
+    }
+
+    /// The handler name, unless the handler is an anonymous closure.
+    pub fn handler_name(&self) -> Option<&'static str> {
+        if self.handler_name.contains("{{closure}}") {
+            None
+        } else {
+            Some(self.handler_name)
+        }
+    }
+}
+
+/// The decoded values of the parameter `name` in the query string (or form body) `query`.
+pub(crate) fn query_values<'a>(query: &'a str, name: &'a str) -> impl Iterator<Item = String> + 'a {
+    let decode = |s: &str| {
+        let s = s.replace('+', " ");
+        percent_decode(&s).unwrap_or(s)
+    };
+    query
+        .split('&')
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .filter(move |(key, _)| decode(key) == name)
+        .map(move |(_, value)| decode(value))
+}
@@ -0,0 +1,130 @@
+//! Sampling decisions for observability layers such as logging and tracing.
+use crate::metrics::MatchedPattern;
+use crate::Response;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    ops::RangeInclusive,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Decides which requests an observability layer records, to bound its overhead in
+/// high-traffic apps, e.g. 1% of successful responses but every server error:
+///
+/// ```
+/// # use spin_sdk_router::sampling::Sampler;
+/// let sampler = Sampler::new(0.01)
+///     .status(500..=599, 1.0)
+///     .route("/checkout", 1.0);
+/// ```
+///
+/// Rates range from 0 (never) to 1 (always). A rate for the matched route pattern takes
+/// precedence over the first matching status rate, which takes precedence over the default.
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    default: f64,
+    statuses: Vec<(RangeInclusive<u16>, f64)>,
+    routes: HashMap<String, f64>,
+    state: Cell<u64>,
+}
+
+impl Default for Sampler {
+    /// A sampler recording every request.
+    fn default() -> Self {
+        Sampler::new(1.0)
+    }
+}
+
+impl Sampler {
+    /// Sample requests at `rate` unless a more specific rate applies.
+    pub fn new(rate: f64) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Sampler {
+            default: rate,
+            statuses: Vec::new(),
+            routes: HashMap::new(),
+            state: Cell::new(seed | 1),
+        }
+    }
+
+    /// Sample responses with a status in `statuses` at `rate`.
+    pub fn status(mut self, statuses: RangeInclusive<u16>, rate: f64) -> Self {
+        self.statuses.push((statuses, rate));
+        self
+    }
+
+    /// Sample responses from the route registered with `pattern` at `rate`.
+    pub fn route(mut self, pattern: &str, rate: f64) -> Self {
+        self.routes.insert(pattern.to_owned(), rate);
+        self
+    }
+
+    /// The rate that applies to the response.
+    pub fn rate(&self, response: &Response) -> f64 {
+        let status = response.status().as_u16();
+        response
+            .extensions()
+            .get::<MatchedPattern>()
+            .and_then(|MatchedPattern(pattern)| self.routes.get(pattern))
+            .or_else(|| {
+                self.statuses
+                    .iter()
+                    .find(|(statuses, _)| statuses.contains(&status))
+                    .map(|(_, rate)| rate)
+            })
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Whether to record the request that produced `response`.
+    pub fn sample(&self, response: &Response) -> bool {
+        let rate = self.rate(response);
+        rate >= 1.0 || (rate > 0.0 && self.next_f64() < rate)
+    }
+
+    /// A pseudo-random number in `[0, 1)` from an xorshift generator; sampling needs no
+    /// cryptographic quality.
+    fn next_f64(&self) -> f64 {
+        let mut x = self.state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, pattern: Option<&str>) -> Response {
+        let mut res = http::Response::builder().status(status).body(None).unwrap();
+        if let Some(pattern) = pattern {
+            res.extensions_mut()
+                .insert(MatchedPattern(pattern.to_owned()));
+        }
+        res
+    }
+
+    #[test]
+    fn test_rates() {
+        let sampler = Sampler::new(0.0)
+            .status(500..=599, 1.0)
+            .route("/checkout", 0.5);
+        assert!(!sampler.sample(&response(200, Some("/"))));
+        assert!(sampler.sample(&response(503, Some("/"))));
+        assert_eq!(sampler.rate(&response(503, Some("/checkout"))), 0.5);
+        assert!(Sampler::default().sample(&response(200, None)));
+    }
+
+    #[test]
+    fn test_fractional_rate() {
+        let sampler = Sampler::new(0.25);
+        let res = response(200, None);
+        let sampled = (0..10_000).filter(|_| sampler.sample(&res)).count();
+        assert!((2_000..3_000).contains(&sampled), "{sampled}");
+    }
+}
@@ -0,0 +1,211 @@
+//! A container of services built lazily once per instance, such as template engines,
+//! outbound clients and repositories.
+use crate::{Request, Router};
+use anyhow::{bail, Result};
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+type Instance = Arc<dyn Any + Send + Sync>;
+type Factory = dyn Fn(&Services) -> Result<Instance> + Send + Sync;
+
+/// The services registered on a router, each built the first time it is asked for and then
+/// kept for the life of the Wasm instance.
+#[derive(Default)]
+pub struct Services {
+    factories: HashMap<TypeId, (&'static str, Box<Factory>)>,
+    built: Mutex<HashMap<TypeId, Instance>>,
+    building: Mutex<Vec<TypeId>>,
+}
+
+impl Services {
+    /// The service of type `T`, building it if needed.
+    ///
+    /// Fails if no service of type `T` is registered, if its factory fails, in which case it
+    /// is retried the next time, or if it depends on itself.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Result<Service<T>> {
+        let id = TypeId::of::<T>();
+        if let Some(instance) = self.built.lock().unwrap().get(&id) {
+            return Ok(Service(instance.clone().downcast().unwrap()));
+        }
+        let Some((name, factory)) = self.factories.get(&id) else {
+            bail!("no service of type {} is registered", type_name::<T>());
+        };
+        {
+            let mut building = self.building.lock().unwrap();
+            if building.contains(&id) {
+                bail!("service {name} depends on itself");
+            }
+            building.push(id);
+        }
+        // The lock is not held while building, as factories get their own dependencies.
+        let built = factory(self);
+        self.building.lock().unwrap().retain(|b| *b != id);
+        let instance = self
+            .built
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert(built?)
+            .clone();
+        Ok(Service(instance.downcast().unwrap()))
+    }
+}
+
+/// A handle to a service of type `T`:
+///
+/// ```
+/// # use spin_sdk_router::{services::{Service, Services}, Params, Request, Router};
+/// struct Config { greeting: String }
+/// struct Greeter { config: Service<Config> }
+///
+/// let mut router = Router::new();
+/// router.service(|_services| Ok(Config { greeting: "hello".to_owned() }));
+/// router.service(|services: &Services| Ok(Greeter { config: services.get()? }));
+/// router.get("/", |req: Request, _params: Params| {
+///     let greeter = Service::<Greeter>::from_request(&req)?;
+///     Ok(http::Response::new(Some(greeter.config.greeting.clone().into())))
+/// });
+/// ```
+///
+/// [`filter::service`](crate::filter::service) extracts services for filter handlers.
+pub struct Service<T>(Arc<T>);
+
+impl<T> Clone for Service<T> {
+    fn clone(&self) -> Self {
+        Service(self.0.clone())
+    }
+}
+
+impl<T> Deref for Service<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// The services of the router handling a request, carried in its extensions.
+#[derive(Clone)]
+pub(crate) struct Container(Arc<Services>);
+
+impl<T: Send + Sync + 'static> Service<T> {
+    /// The service of type `T` of the router handling `req`, building it if needed.
+    pub fn from_request(req: &Request) -> Result<Self> {
+        match req.extensions().get::<Container>() {
+            Some(Container(services)) => services.get(),
+            None => bail!("no service of type {} is registered", type_name::<T>()),
+        }
+    }
+}
+
+impl Router {
+    /// Register the service of type `T`, built by `factory` the first time a handler or
+    /// another service asks for it, e.g. with [`Service::from_request`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if called while the router is handling a request.
+    pub fn service<T, F>(&mut self, factory: F)
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Services) -> Result<T> + Send + Sync + 'static,
+    {
+        let services =
+            Arc::get_mut(&mut self.services).expect("services registered while handling a request");
+        let factory =
+            move |services: &Services| -> Result<Instance> { Ok(Arc::new(factory(services)?)) };
+        services
+            .factories
+            .insert(TypeId::of::<T>(), (type_name::<T>(), Box::new(factory)));
+        services.built.get_mut().unwrap().remove(&TypeId::of::<T>());
+    }
+
+    /// The services registered on the router.
+    pub fn services(&self) -> &Services {
+        &self.services
+    }
+
+    /// Inserts the router's services into the request's extensions.
+    pub(crate) fn provide_services(&self, request: &mut Request) {
+        if !self.services.factories.is_empty() {
+            request
+                .extensions_mut()
+                .insert(Container(self.services.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    struct Templates {
+        page: &'static str,
+    }
+    struct Renderer {
+        templates: Service<Templates>,
+    }
+    struct Flaky;
+    struct Cyclic;
+
+    #[test]
+    fn test_services() {
+        let mut router = Router::new();
+        router.service(|_services| {
+            BUILDS.fetch_add(1, Ordering::Relaxed);
+            Ok(Templates {
+                page: "<h1>{}</h1>",
+            })
+        });
+        router.service(|services: &Services| {
+            Ok(Renderer {
+                templates: services.get()?,
+            })
+        });
+        router.get("/", |req: Request, _params: Params| {
+            let renderer = Service::<Renderer>::from_request(&req)?;
+            Ok(http::Response::new(Some(
+                renderer.templates.page.replace("{}", "hi").into(),
+            )))
+        });
+
+        for _ in 0..2 {
+            let req = http::Request::get("/").body(None).unwrap();
+            let res = router.handle(req).unwrap();
+            assert_eq!(res.into_body().unwrap(), "<h1>hi</h1>");
+        }
+        assert_eq!(BUILDS.load(Ordering::Relaxed), 1);
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        router.service(
+            move |_services| match counter.fetch_add(1, Ordering::Relaxed) {
+                0 => bail!("not yet"),
+                _ => Ok(Flaky),
+            },
+        );
+        assert!(router.services().get::<Flaky>().is_err());
+        assert!(router.services().get::<Flaky>().is_ok());
+        assert!(router.services().get::<Flaky>().is_ok());
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+
+        router.service(|services: &Services| {
+            services.get::<Cyclic>()?;
+            Ok(Cyclic)
+        });
+        let err = router.services().get::<Cyclic>().err().unwrap();
+        assert!(
+            err.to_string().ends_with("Cyclic depends on itself"),
+            "{err}"
+        );
+        assert!(router.services().get::<String>().is_err());
+    }
+}
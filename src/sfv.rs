@@ -0,0 +1,620 @@
+//! Parsing and serialization of structured field values ([RFC 8941]), the format used by
+//! modern headers such as `Priority`, `Signature-Input` and `RateLimit`.
+//!
+//! [RFC 8941]: https://www.rfc-editor.org/rfc/rfc8941
+use std::fmt::{self, Write};
+
+/// A bare item: the value of an [`Item`] without its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BareItem {
+    /// An integer, e.g. `42`.
+    Integer(i64),
+    /// A decimal with at most three fractional digits, e.g. `4.5`.
+    Decimal(f64),
+    /// A string, e.g. `"hello"`.
+    String(String),
+    /// A token, e.g. `gzip`.
+    Token(String),
+    /// A byte sequence, e.g. `:aGVsbG8=:`.
+    ByteSequence(Vec<u8>),
+    /// A boolean, e.g. `?1`.
+    Boolean(bool),
+}
+
+/// Parameters attached to an item or inner list, in order.
+pub type Parameters = Vec<(String, BareItem)>;
+
+/// A bare item with its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Item {
+    /// The value of the item.
+    pub bare_item: BareItem,
+    /// The parameters of the item.
+    pub params: Parameters,
+}
+
+impl Item {
+    /// Construct an item without parameters.
+    pub fn new(bare_item: BareItem) -> Self {
+        Item {
+            bare_item,
+            params: Parameters::new(),
+        }
+    }
+
+    /// Looks up a parameter by key.
+    pub fn param(&self, key: &str) -> Option<&BareItem> {
+        find(&self.params, key)
+    }
+}
+
+/// A parenthesized list of items with its parameters, e.g. `(a b);q=1`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InnerList {
+    /// The items of the inner list.
+    pub items: Vec<Item>,
+    /// The parameters of the inner list.
+    pub params: Parameters,
+}
+
+/// A member of a [`List`] or [`Dictionary`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListEntry {
+    /// A single item.
+    Item(Item),
+    /// An inner list.
+    InnerList(InnerList),
+}
+
+/// A list structured field.
+pub type List = Vec<ListEntry>;
+/// A dictionary structured field, with keys in order.
+pub type Dictionary = Vec<(String, ListEntry)>;
+
+/// Looks up a value by key in a [`Dictionary`] or [`Parameters`].
+pub fn find<'a, T>(members: &'a [(String, T)], key: &str) -> Option<&'a T> {
+    members.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// An error parsing a structured field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    position: usize,
+    message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid structured field at {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse an item structured field, e.g. `u=3`'s value `3` or `"text";lang=en`.
+pub fn parse_item(input: &str) -> Result<Item, ParseError> {
+    Parser::new(input).top_level(Parser::item)
+}
+
+/// Parse a list structured field, e.g. `gzip, br;q=0.5, (a b)`.
+pub fn parse_list(input: &str) -> Result<List, ParseError> {
+    Parser::new(input).top_level(Parser::list)
+}
+
+/// Parse a dictionary structured field, e.g. `u=3, i`.
+pub fn parse_dictionary(input: &str) -> Result<Dictionary, ParseError> {
+    Parser::new(input).top_level(Parser::dictionary)
+}
+
+/// Serialize an item structured field.
+pub fn serialize_item(item: &Item) -> String {
+    let mut out = String::new();
+    write_item(&mut out, item);
+    out
+}
+
+/// Serialize a list structured field.
+pub fn serialize_list(list: &List) -> String {
+    let mut out = String::new();
+    for (i, entry) in list.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_entry(&mut out, entry);
+    }
+    out
+}
+
+/// Serialize a dictionary structured field.
+pub fn serialize_dictionary(dictionary: &Dictionary) -> String {
+    let mut out = String::new();
+    for (i, (key, entry)) in dictionary.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(key);
+        match entry {
+            ListEntry::Item(Item {
+                bare_item: BareItem::Boolean(true),
+                params,
+            }) => write_params(&mut out, params),
+            entry => {
+                out.push('=');
+                write_entry(&mut out, entry);
+            }
+        }
+    }
+    out
+}
+
+fn write_entry(out: &mut String, entry: &ListEntry) {
+    match entry {
+        ListEntry::Item(item) => write_item(out, item),
+        ListEntry::InnerList(inner) => {
+            out.push('(');
+            for (i, item) in inner.items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_item(out, item);
+            }
+            out.push(')');
+            write_params(out, &inner.params);
+        }
+    }
+}
+
+fn write_item(out: &mut String, item: &Item) {
+    write_bare_item(out, &item.bare_item);
+    write_params(out, &item.params);
+}
+
+fn write_params(out: &mut String, params: &Parameters) {
+    for (key, value) in params {
+        out.push(';');
+        out.push_str(key);
+        if *value != BareItem::Boolean(true) {
+            out.push('=');
+            write_bare_item(out, value);
+        }
+    }
+}
+
+fn write_bare_item(out: &mut String, item: &BareItem) {
+    match item {
+        BareItem::Integer(i) => write!(out, "{i}").unwrap(),
+        BareItem::Decimal(d) => {
+            let rounded = format!("{:.3}", d);
+            let trimmed = rounded.trim_end_matches('0');
+            out.push_str(trimmed);
+            if trimmed.ends_with('.') {
+                out.push('0');
+            }
+        }
+        BareItem::String(s) => {
+            out.push('"');
+            for c in s.chars() {
+                if c == '"' || c == '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push('"');
+        }
+        BareItem::Token(t) => out.push_str(t),
+        BareItem::ByteSequence(bytes) => {
+            out.push(':');
+            out.push_str(&base64_encode(bytes));
+            out.push(':');
+        }
+        BareItem::Boolean(b) => out.push_str(if *b { "?1" } else { "?0" }),
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input: input.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn error<T>(&self, message: &'static str) -> Result<T, ParseError> {
+        Err(ParseError {
+            position: self.position,
+            message,
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn skip_sp(&mut self) {
+        while self.peek() == Some(b' ') {
+            self.position += 1;
+        }
+    }
+
+    fn skip_ows(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.position += 1;
+        }
+    }
+
+    fn top_level<T>(
+        mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        if !self.input.is_ascii() {
+            return self.error("non-ASCII input");
+        }
+        self.skip_sp();
+        let value = parse(&mut self)?;
+        self.skip_sp();
+        if self.peek().is_some() {
+            return self.error("unexpected trailing characters");
+        }
+        Ok(value)
+    }
+
+    /// Parses comma separated members until the end of input.
+    fn members(
+        &mut self,
+        mut member: impl FnMut(&mut Self) -> Result<(), ParseError>,
+    ) -> Result<(), ParseError> {
+        while self.peek().is_some() {
+            member(self)?;
+            self.skip_ows();
+            match self.next() {
+                None => return Ok(()),
+                Some(b',') => {}
+                Some(_) => return self.error("expected a comma"),
+            }
+            self.skip_ows();
+            if self.peek().is_none() {
+                return self.error("trailing comma");
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&mut self) -> Result<List, ParseError> {
+        let mut list = List::new();
+        self.members(|p| {
+            list.push(p.entry()?);
+            Ok(())
+        })?;
+        Ok(list)
+    }
+
+    fn dictionary(&mut self) -> Result<Dictionary, ParseError> {
+        let mut dictionary = Dictionary::new();
+        self.members(|p| {
+            let key = p.key()?;
+            let entry = if p.peek() == Some(b'=') {
+                p.position += 1;
+                p.entry()?
+            } else {
+                ListEntry::Item(Item {
+                    bare_item: BareItem::Boolean(true),
+                    params: p.params()?,
+                })
+            };
+            insert(&mut dictionary, key, entry);
+            Ok(())
+        })?;
+        Ok(dictionary)
+    }
+
+    fn entry(&mut self) -> Result<ListEntry, ParseError> {
+        if self.peek() == Some(b'(') {
+            self.inner_list().map(ListEntry::InnerList)
+        } else {
+            self.item().map(ListEntry::Item)
+        }
+    }
+
+    fn inner_list(&mut self) -> Result<InnerList, ParseError> {
+        self.position += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_sp();
+            match self.peek() {
+                Some(b')') => {
+                    self.position += 1;
+                    let params = self.params()?;
+                    return Ok(InnerList { items, params });
+                }
+                Some(_) => {
+                    items.push(self.item()?);
+                    if !matches!(self.peek(), Some(b' ' | b')')) {
+                        return self.error("expected a space or `)` in inner list");
+                    }
+                }
+                None => return self.error("unterminated inner list"),
+            }
+        }
+    }
+
+    fn item(&mut self) -> Result<Item, ParseError> {
+        let bare_item = self.bare_item()?;
+        let params = self.params()?;
+        Ok(Item { bare_item, params })
+    }
+
+    fn params(&mut self) -> Result<Parameters, ParseError> {
+        let mut params = Parameters::new();
+        while self.peek() == Some(b';') {
+            self.position += 1;
+            self.skip_sp();
+            let key = self.key()?;
+            let value = if self.peek() == Some(b'=') {
+                self.position += 1;
+                self.bare_item()?
+            } else {
+                BareItem::Boolean(true)
+            };
+            insert(&mut params, key, value);
+        }
+        Ok(params)
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(c) if c.is_ascii_lowercase() || c == b'*' => {}
+            _ => return self.error("expected a key"),
+        }
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_lowercase() || c.is_ascii_digit() || b"_-.*".contains(&c))
+        {
+            self.position += 1;
+        }
+        Ok(self.slice(start))
+    }
+
+    fn slice(&self, start: usize) -> String {
+        String::from_utf8_lossy(&self.input[start..self.position]).into_owned()
+    }
+
+    fn bare_item(&mut self) -> Result<BareItem, ParseError> {
+        match self.peek() {
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(b'"') => self.string(),
+            Some(b':') => self.byte_sequence(),
+            Some(b'?') => self.boolean(),
+            Some(c) if c.is_ascii_alphabetic() || c == b'*' => self.token(),
+            _ => self.error("expected an item"),
+        }
+    }
+
+    fn number(&mut self) -> Result<BareItem, ParseError> {
+        let start = self.position;
+        let negative = self.peek() == Some(b'-');
+        if negative {
+            self.position += 1;
+        }
+        if !matches!(self.peek(), Some(c) if c.is_ascii_digit()) {
+            return self.error("expected a digit");
+        }
+        let digits_start = self.position;
+        let mut decimal_point = None;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() {
+                self.position += 1;
+            } else if c == b'.' && decimal_point.is_none() {
+                if self.position - digits_start > 12 {
+                    return self.error("decimal has too many integer digits");
+                }
+                decimal_point = Some(self.position);
+                self.position += 1;
+            } else {
+                break;
+            }
+            let len = self.position - digits_start;
+            if (decimal_point.is_none() && len > 15) || len > 16 {
+                return self.error("number has too many digits");
+            }
+        }
+
+        let text = self.slice(start);
+        match decimal_point {
+            None => Ok(BareItem::Integer(text.parse().unwrap())),
+            Some(point) => {
+                let fraction = self.position - point - 1;
+                if fraction == 0 || fraction > 3 {
+                    return self.error("decimal must have one to three fractional digits");
+                }
+                Ok(BareItem::Decimal(text.parse().unwrap()))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<BareItem, ParseError> {
+        self.position += 1;
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                Some(b'\\') => match self.next() {
+                    Some(c @ (b'"' | b'\\')) => s.push(c as char),
+                    _ => return self.error("invalid escape in string"),
+                },
+                Some(b'"') => return Ok(BareItem::String(s)),
+                Some(c @ 0x20..=0x7e) => s.push(c as char),
+                Some(_) => return self.error("invalid character in string"),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn token(&mut self) -> Result<BareItem, ParseError> {
+        let start = self.position;
+        self.position += 1;
+        while matches!(self.peek(), Some(c) if is_tchar(c) || c == b':' || c == b'/') {
+            self.position += 1;
+        }
+        Ok(BareItem::Token(self.slice(start)))
+    }
+
+    fn byte_sequence(&mut self) -> Result<BareItem, ParseError> {
+        self.position += 1;
+        let start = self.position;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphanumeric() || b"+/=".contains(&c)) {
+            self.position += 1;
+        }
+        let encoded = self.slice(start);
+        if self.next() != Some(b':') {
+            return self.error("unterminated byte sequence");
+        }
+        match base64_decode(&encoded) {
+            Some(bytes) => Ok(BareItem::ByteSequence(bytes)),
+            None => self.error("invalid base64 in byte sequence"),
+        }
+    }
+
+    fn boolean(&mut self) -> Result<BareItem, ParseError> {
+        self.position += 1;
+        match self.next() {
+            Some(b'1') => Ok(BareItem::Boolean(true)),
+            Some(b'0') => Ok(BareItem::Boolean(false)),
+            _ => self.error("expected `?0` or `?1`"),
+        }
+    }
+}
+
+/// Inserts a member, overwriting the value of an existing member with the same key.
+fn insert<T>(members: &mut Vec<(String, T)>, key: String, value: T) {
+    match members.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, existing)) => *existing = value,
+        None => members.push((key, value)),
+    }
+}
+
+fn is_tchar(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c)
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=');
+    let mut out = Vec::with_capacity(encoded.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in encoded.bytes() {
+        let value = BASE64.iter().position(|b| *b == c)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((n >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_item() {
+        let item = parse_item(r#" "hi \"there\"";lang=en;draft "#).unwrap();
+        assert_eq!(item.bare_item, BareItem::String(r#"hi "there""#.to_owned()));
+        assert_eq!(item.param("lang"), Some(&BareItem::Token("en".to_owned())));
+        assert_eq!(item.param("draft"), Some(&BareItem::Boolean(true)));
+
+        assert_eq!(parse_item("-12").unwrap().bare_item, BareItem::Integer(-12));
+        assert_eq!(parse_item("4.5").unwrap().bare_item, BareItem::Decimal(4.5));
+        assert_eq!(
+            parse_item("?0").unwrap().bare_item,
+            BareItem::Boolean(false)
+        );
+        assert_eq!(
+            parse_item(":aGVsbG8=:").unwrap().bare_item,
+            BareItem::ByteSequence(b"hello".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_item("").is_err());
+        for input in [
+            "1.2345",
+            "1234567890123456",
+            "\"unterminated",
+            "a, ",
+            "a b",
+            "?2",
+            ":a#:",
+            "é",
+        ] {
+            assert!(parse_list(input).is_err(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let list = parse_list("gzip, br;q=0.5, (a b);x").unwrap();
+        assert_eq!(list.len(), 3);
+        let ListEntry::InnerList(inner) = &list[2] else {
+            panic!("expected an inner list");
+        };
+        assert_eq!(inner.items.len(), 2);
+        assert_eq!(find(&inner.params, "x"), Some(&BareItem::Boolean(true)));
+        assert_eq!(serialize_list(&list), "gzip, br;q=0.5, (a b);x");
+    }
+
+    #[test]
+    fn test_parse_dictionary() {
+        let dict = parse_dictionary("u=3, i, u=1").unwrap();
+        assert_eq!(dict.len(), 2);
+        assert_eq!(
+            find(&dict, "u"),
+            Some(&ListEntry::Item(Item::new(BareItem::Integer(1))))
+        );
+        assert_eq!(serialize_dictionary(&dict), "u=1, i");
+    }
+
+    #[test]
+    fn test_serialize_item() {
+        let mut item = Item::new(BareItem::Decimal(1.0));
+        item.params
+            .push(("a".to_owned(), BareItem::Decimal(0.12345)));
+        item.params
+            .push(("b".to_owned(), BareItem::ByteSequence(b"hi".to_vec())));
+        assert_eq!(serialize_item(&item), "1.0;a=0.123;b=:aGk=:");
+    }
+}
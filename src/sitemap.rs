@@ -0,0 +1,218 @@
+//! Sitemap generation from the registered GET routes.
+use crate::files::percent_encode;
+use crate::{Param, Params, Request, Response, Route, Router};
+use anyhow::Result;
+use routefinder::{RouteSpec, Segment};
+
+impl Route {
+    /// Supply the parameter values with which this route appears in the sitemap.
+    ///
+    /// Routes with parameters are only listed in the sitemap when their values can be
+    /// enumerated; each set of params returned by `params` produces one URL.
+    pub fn sitemap<F>(&mut self, params: F) -> &mut Self
+    where
+        F: Fn() -> Vec<Params> + 'static,
+    {
+        self.sitemap_params = Some(Box::new(params));
+        self
+    }
+
+    /// The paths with which this route is listed in the sitemap.
+    ///
+    /// Defaulted parameters may be left out of the supplied params, and trailing ones holding
+    /// their default are omitted from the path, so `/feed/:format=json` is listed as `/feed`.
+    fn sitemap_paths(&self) -> Vec<String> {
+        if self.method() != Some(&http::Method::GET) {
+            return Vec::new();
+        }
+        let Ok(spec) = self.pattern.parse::<RouteSpec>() else {
+            return Vec::new();
+        };
+        let names: Vec<&str> = spec
+            .segments()
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Param(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let listed = match &self.sitemap_params {
+            Some(params) => params(),
+            None if !self.pattern.contains('*')
+                && names.iter().all(|name| self.default(name).is_some()) =>
+            {
+                vec![Params::new()]
+            }
+            None => Vec::new(),
+        };
+        listed
+            .iter()
+            .filter_map(|params| self.sitemap_path(&spec, &names, params))
+            .collect()
+    }
+
+    /// The path of this route with `params` substituted, each value percent-encoded.
+    fn sitemap_path(&self, spec: &RouteSpec, names: &[&str], params: &Params) -> Option<String> {
+        let value = |name: &str| params.get(name).or_else(|| self.default(name));
+        let mut captures = Params::new();
+        for name in names {
+            let encoded = percent_encode(value(name)?).replace('/', "%2F");
+            captures.push(Param::new(name.to_string(), encoded));
+        }
+        if let Some(wildcard) = params.wildcard() {
+            captures.set_wildcard(percent_encode(wildcard));
+        }
+        let path = spec.template(&captures)?.to_string();
+
+        let omitted = names
+            .iter()
+            .rev()
+            .take_while(|name| self.default(name).is_some_and(|d| value(name) == Some(d)))
+            .count();
+        let mut segments: Vec<_> = path.split('/').collect();
+        segments.truncate(segments.len() - omitted);
+        match segments.join("/") {
+            path if path.is_empty() => Some("/".to_owned()),
+            path => Some(path),
+        }
+    }
+
+    fn default(&self, name: &str) -> Option<&str> {
+        let default = self.defaults.iter().find(|(param, _)| param == name);
+        default.map(|(_, value)| value.as_str())
+    }
+}
+
+pub(crate) fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Router {
+    /// Render a sitemap listing the GET routes under `base_url`.
+    ///
+    /// Routes without parameters are always listed; routes with parameters are listed once
+    /// for each set of values supplied with [`Route::sitemap`].
+    pub fn sitemap(&self, base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        let mut paths: Vec<_> = self.routes().flat_map(Route::sitemap_paths).collect();
+        paths.sort();
+        paths.dedup();
+
+        let mut xml = String::from(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#,
+            "\n"
+        ));
+        for path in paths {
+            let loc = escape_xml(&format!("{base_url}{path}"));
+            xml.push_str(&format!("  <url><loc>{loc}</loc></url>\n"));
+        }
+        xml.push_str("</urlset>\n");
+        xml
+    }
+
+    /// Serve the sitemap for this router at `/sitemap.xml`.
+    pub fn serve_sitemap(&mut self, base_url: &str) {
+        let base_url = base_url.to_owned();
+        self.builtins.insert(
+            "/sitemap.xml",
+            Box::new(move |router: &Router, _req: Request| -> Result<Response> {
+                Ok(http::Response::builder()
+                    .status(http::StatusCode::OK)
+                    .header(http::header::CONTENT_TYPE, "application/xml")
+                    .body(Some(router.sitemap(&base_url).into()))?)
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(_req: Request, _params: Params) -> Result<Response> {
+        Ok(http::Response::builder().status(200).body(None)?)
+    }
+
+    #[test]
+    fn test_sitemap() {
+        let mut router = Router::default();
+        router.get("/", page);
+        router.get("/about", page);
+        router.post("/contact", page);
+        router.get("/users/:id", page);
+        router.get("/posts/:slug", page).sitemap(|| {
+            ["hello", "tom&jerry"]
+                .into_iter()
+                .map(|slug| Params::from_iter([Param::new("slug", slug.to_owned())]))
+                .collect()
+        });
+
+        let xml = router.sitemap("https://example.com/");
+        let locs: Vec<_> = xml.lines().filter(|l| l.contains("<loc>")).collect();
+        assert_eq!(
+            locs,
+            [
+                "  <url><loc>https://example.com/</loc></url>",
+                "  <url><loc>https://example.com/about</loc></url>",
+                "  <url><loc>https://example.com/posts/hello</loc></url>",
+                "  <url><loc>https://example.com/posts/tom%26jerry</loc></url>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sitemap_encodes_and_omits_defaults() {
+        let mut router = Router::default();
+        router.get("/feed/:format=json", page);
+        router.get("/docs/:section/:page=index", page).sitemap(|| {
+            [("getting started", "index"), ("a/b", "faq?")]
+                .into_iter()
+                .map(|(section, page)| {
+                    Params::from_iter([
+                        Param::new("section", section.to_owned()),
+                        Param::new("page", page.to_owned()),
+                    ])
+                })
+                .collect()
+        });
+
+        let xml = router.sitemap("https://example.com");
+        let locs: Vec<_> = xml.lines().filter(|l| l.contains("<loc>")).collect();
+        assert_eq!(
+            locs,
+            [
+                "  <url><loc>https://example.com/docs/a%2Fb/faq%3F</loc></url>",
+                "  <url><loc>https://example.com/docs/getting%20started</loc></url>",
+                "  <url><loc>https://example.com/feed</loc></url>",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serve_sitemap() {
+        let mut router = Router::default();
+        router.get("/about", page);
+        router.serve_sitemap("https://example.com");
+
+        let req = http::Request::builder()
+            .uri("/sitemap.xml")
+            .body(None)
+            .unwrap();
+        let res = router.handle(req).unwrap();
+        assert_eq!(res.status(), http::StatusCode::OK);
+        assert_eq!(res.headers()[http::header::CONTENT_TYPE], "application/xml");
+    }
+}
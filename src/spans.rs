@@ -0,0 +1,127 @@
+//! `tracing` spans around the dispatch of each request.
+use crate::{metrics::MatchedPattern, Request, Response};
+use anyhow::Result;
+use std::time::Instant;
+use tracing::field::Empty;
+
+/// Runs `dispatch` within a `request` span recording the method, the matched route pattern,
+/// the response status and the latency, and records handler errors as events.
+pub(crate) fn instrument<F>(request: Request, dispatch: F) -> Result<Response>
+where
+    F: FnOnce(Request) -> Result<Response>,
+{
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        route = Empty,
+        status = Empty,
+        latency_ms = Empty,
+    );
+    let _entered = span.enter();
+    let started = Instant::now();
+    let result = dispatch(request);
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+    match &result {
+        Ok(response) => {
+            if let Some(MatchedPattern(pattern)) = response.extensions().get() {
+                span.record("route", pattern.as_str());
+            }
+            span.record("status", response.status().as_u16());
+        }
+        Err(e) => tracing::error!(error = %format!("{e:#}"), "handler failed"),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Params, Router};
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Mutex,
+        },
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    /// A subscriber recording the fields of spans and events as `name=value` strings.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        records: Arc<Mutex<Vec<String>>>,
+        next_id: Arc<AtomicU64>,
+    }
+
+    struct Fields<'a>(&'a mut Vec<String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={value:?}", field.name()));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push(format!("{}={value}", field.name()));
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            span.record(&mut Fields(&mut self.records.lock().unwrap()));
+            span::Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+        }
+
+        fn record(&self, _span: &span::Id, values: &span::Record<'_>) {
+            values.record(&mut Fields(&mut self.records.lock().unwrap()));
+        }
+
+        fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut Fields(&mut self.records.lock().unwrap()));
+        }
+
+        fn enter(&self, _span: &span::Id) {}
+
+        fn exit(&self, _span: &span::Id) {}
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut router = Router::new();
+        router.get("/users/:id", |_req, _params: Params| {
+            Ok(http::Response::new(None))
+        });
+        router.get("/fail", |_req, _params: Params| anyhow::bail!("boom"));
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let req = http::Request::get("/users/7").body(None).unwrap();
+            router.handle(req).unwrap();
+            let req = http::Request::get("/fail").body(None).unwrap();
+            assert!(router.handle(req).is_err());
+        });
+
+        let records = recorder.records.lock().unwrap();
+        let records: Vec<_> = records
+            .iter()
+            .filter(|r| !r.starts_with("latency_ms="))
+            .collect();
+        assert_eq!(
+            records,
+            [
+                "method=GET",
+                "route=/users/:id",
+                "status=200",
+                "method=GET",
+                "message=handler failed",
+                "error=boom",
+            ]
+        );
+    }
+}
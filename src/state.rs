@@ -0,0 +1,170 @@
+//! Application state shared with handlers, registered per type and per route group.
+use crate::{Request, Router};
+use anyhow::{anyhow, Result};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    sync::Arc,
+};
+
+type TypeMap = HashMap<TypeId, Arc<dyn Any + Send + Sync>>;
+
+/// The state values available to a request, inserted into its extensions by the router.
+#[derive(Clone, Default)]
+pub(crate) struct StateMap(TypeMap);
+
+/// A state value of type `T`, shared by the handlers of the routes it was registered for:
+///
+/// ```
+/// # use spin_sdk_router::{state::State, Params, Request, Router};
+/// struct Db { url: String }
+/// struct Mailer { from: String }
+///
+/// let mut router = Router::new();
+/// router.group_state("/api", Db { url: "postgres://db".to_owned() });
+/// router.group_state("/mail", Mailer { from: "noreply@example.com".to_owned() });
+/// router.get("/api/users", |req: Request, _params: Params| {
+///     let db = State::<Db>::from_request(&req)?;
+///     Ok(http::Response::new(Some(db.url.clone().into())))
+/// });
+/// ```
+pub struct State<T>(Arc<T>);
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        State(self.0.clone())
+    }
+}
+
+impl<T> Deref for State<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Send + Sync + 'static> State<T> {
+    /// The state of type `T` available to `req`, if any.
+    pub fn of(req: &Request) -> Option<Self> {
+        let StateMap(map) = req.extensions().get()?;
+        let value = map.get(&TypeId::of::<T>())?.clone();
+        value.downcast().ok().map(State)
+    }
+
+    /// The state of type `T` available to `req`, failing if none was registered for the
+    /// route.
+    pub fn from_request(req: &Request) -> Result<Self> {
+        Self::of(req).ok_or_else(|| {
+            anyhow!(
+                "no state of type {} for {}",
+                std::any::type_name::<T>(),
+                req.uri().path()
+            )
+        })
+    }
+}
+
+/// The state registered on a router.
+#[derive(Default)]
+pub(crate) struct States {
+    global: TypeMap,
+    groups: Vec<(String, TypeMap)>,
+}
+
+impl States {
+    /// The state available to requests for `path`: for each type, the value of the longest
+    /// group prefix containing the path, or else the global one.
+    fn resolve(&self, path: &str) -> StateMap {
+        let mut groups: Vec<_> = self
+            .groups
+            .iter()
+            .filter(|(prefix, _)| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+            .collect();
+        groups.sort_by_key(|(prefix, _)| prefix.len());
+        let mut map = self.global.clone();
+        for (_, group) in groups {
+            map.extend(group.iter().map(|(k, v)| (*k, v.clone())));
+        }
+        StateMap(map)
+    }
+}
+
+impl Router {
+    /// Make `value` available to every handler as a [`State<T>`], unless a group registers a
+    /// value of the same type for its routes.
+    pub fn state<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.states
+            .global
+            .insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Make `value` available as a [`State<T>`] to the handlers of routes under `prefix`,
+    /// e.g. `/api`. Groups can register different types of state, so that each only gets
+    /// what it needs; for a given type, the longest matching prefix wins.
+    pub fn group_state<T: Send + Sync + 'static>(&mut self, prefix: &str, value: T) {
+        let prefix = prefix.trim_end_matches('/');
+        let value: Arc<dyn Any + Send + Sync> = Arc::new(value);
+        match self.states.groups.iter_mut().find(|(p, _)| p == prefix) {
+            Some((_, map)) => {
+                map.insert(TypeId::of::<T>(), value);
+            }
+            None => {
+                let map = HashMap::from([(TypeId::of::<T>(), value)]);
+                self.states.groups.push((prefix.to_owned(), map));
+            }
+        }
+    }
+
+    /// Inserts the state available to the request into its extensions.
+    pub(crate) fn provide_state(&self, request: &mut Request) {
+        if self.states.global.is_empty() && self.states.groups.is_empty() {
+            return;
+        }
+        let states = self.states.resolve(request.uri().path());
+        request.extensions_mut().insert(states);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Response};
+
+    struct Db(&'static str);
+    struct Mailer(&'static str);
+
+    fn describe(req: Request, _params: Params) -> Result<Response> {
+        let db = State::<Db>::of(&req).map_or("-", |db| (*db).0);
+        let mailer = State::<Mailer>::of(&req).map_or("-", |mailer| (*mailer).0);
+        Ok(http::Response::new(Some(format!("{db} {mailer}").into())))
+    }
+
+    #[test]
+    fn test_group_state() {
+        let mut router = Router::new();
+        router.get("/*", describe);
+        router.state(Db("main"));
+        router.group_state("/api/", Db("api"));
+        router.group_state("/api/mail", Mailer("smtp"));
+
+        for (path, expected) in [
+            ("/", "main -"),
+            ("/api/users", "api -"),
+            ("/api/mail/send", "api smtp"),
+            ("/apis", "main -"),
+        ] {
+            let req = http::Request::get(path).body(None).unwrap();
+            let res = router.handle(req).unwrap();
+            assert_eq!(res.into_body().unwrap(), expected, "{path}");
+        }
+
+        let req = http::Request::get("/").body(None).unwrap();
+        let err = State::<Mailer>::from_request(&req).err().unwrap();
+        assert!(err.to_string().starts_with("no state of type"));
+    }
+}
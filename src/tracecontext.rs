@@ -0,0 +1,232 @@
+//! W3C Trace Context propagation (`traceparent` and `tracestate`).
+use crate::{requestid::next_random, Middleware, Next, Request, Response};
+use anyhow::Result;
+
+/// The trace context of a request, inserted into the request extensions by
+/// [`ExtractTraceContext`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The ID of the whole trace, as 32 lowercase hex digits.
+    pub trace_id: String,
+    /// The ID of the caller's span, as 16 lowercase hex digits, if the request continued a
+    /// trace.
+    pub parent_id: Option<String>,
+    /// The ID of the span of this request, as 16 lowercase hex digits; outbound requests
+    /// carry it as their parent ID.
+    pub span_id: String,
+    /// Whether the caller may have recorded the trace.
+    pub sampled: bool,
+    /// The vendor-specific `tracestate` entries, passed on unchanged.
+    pub state: Option<String>,
+}
+
+impl TraceContext {
+    /// The trace context of `req`, if [`ExtractTraceContext`] ran.
+    pub fn of(req: &Request) -> Option<&TraceContext> {
+        req.extensions().get()
+    }
+
+    /// Continues the trace of a `traceparent` header value, such as
+    /// `00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01`, with a new span. Returns
+    /// `None` if the value is invalid.
+    pub fn parse(traceparent: &str, tracestate: Option<&str>) -> Option<Self> {
+        let mut fields = traceparent.trim().splitn(5, '-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let parent_id = fields.next()?;
+        let flags = fields.next()?;
+        // Later versions may append fields, which this version ignores.
+        if version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        let hex = |s: &str, len| {
+            s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        let zero = |s: &str| s.bytes().all(|b| b == b'0');
+        if !hex(version, 2) || !hex(trace_id, 32) || zero(trace_id) {
+            return None;
+        }
+        if !hex(parent_id, 16) || zero(parent_id) || !hex(flags, 2) {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id: trace_id.to_owned(),
+            parent_id: Some(parent_id.to_owned()),
+            span_id: span_id(),
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+            state: tracestate.map(str::to_owned).filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// Starts a new, sampled trace.
+    pub fn root() -> Self {
+        TraceContext {
+            trace_id: format!("{:016x}{:016x}", next_random(), next_random()),
+            parent_id: None,
+            span_id: span_id(),
+            sampled: true,
+            state: None,
+        }
+    }
+
+    /// The `traceparent` header value of requests made while handling this one.
+    pub fn traceparent(&self) -> String {
+        let flags = if self.sampled { "01" } else { "00" };
+        format!("00-{}-{}-{flags}", self.trace_id, self.span_id)
+    }
+
+    /// Sets the `traceparent` and `tracestate` headers of the outbound request `req`, so that
+    /// its handling joins this trace.
+    pub fn inject(&self, req: &mut Request) -> Result<()> {
+        let headers = req.headers_mut();
+        headers.insert("traceparent", self.traceparent().parse()?);
+        match &self.state {
+            Some(state) => headers.insert("tracestate", state.parse()?),
+            None => headers.remove("tracestate"),
+        };
+        Ok(())
+    }
+}
+
+/// A random span ID, which cannot be all zeros.
+fn span_id() -> String {
+    format!("{:016x}", next_random().max(1))
+}
+
+/// Middleware inserting the [`TraceContext`] of each request into its extensions, continuing
+/// the trace of its `traceparent` header or starting a new one:
+///
+/// ```
+/// # use spin_sdk_router::{tracecontext::{ExtractTraceContext, TraceContext}, Params, Request, Router};
+/// # fn send_request(req: Request) -> anyhow::Result<spin_sdk_router::Response> { Ok(http::Response::new(None)) }
+/// let mut router = Router::new();
+/// router.layer(ExtractTraceContext::new());
+/// router.get("/", |req: Request, _params: Params| {
+///     let mut upstream = http::Request::get("https://api.example.com/items").body(None)?;
+///     if let Some(context) = TraceContext::of(&req) {
+///         context.inject(&mut upstream)?;
+///     }
+///     send_request(upstream)
+/// });
+/// ```
+///
+/// [`outbound::Client::propagate_from`](crate::outbound::Client::propagate_from) injects the
+/// context into outbound requests as well.
+#[derive(Clone, Copy, Debug)]
+pub struct ExtractTraceContext {
+    start_traces: bool,
+}
+
+impl Default for ExtractTraceContext {
+    fn default() -> Self {
+        ExtractTraceContext { start_traces: true }
+    }
+}
+
+impl ExtractTraceContext {
+    /// Continue incoming traces, and start a new one for requests without a valid
+    /// `traceparent`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only continue incoming traces, leaving requests without a valid `traceparent`
+    /// without a context.
+    pub fn continue_only(mut self) -> Self {
+        self.start_traces = false;
+        self
+    }
+}
+
+impl Middleware for ExtractTraceContext {
+    fn handle(&self, mut req: Request, next: Next<'_>) -> Result<Response> {
+        let header = |name| req.headers().get(name).and_then(|v| v.to_str().ok());
+        let tracestate: Vec<_> = req
+            .headers()
+            .get_all("tracestate")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect();
+        let tracestate = (!tracestate.is_empty()).then(|| tracestate.join(","));
+        let context = header("traceparent")
+            .and_then(|traceparent| TraceContext::parse(traceparent, tracestate.as_deref()));
+        match context {
+            Some(context) => {
+                req.extensions_mut().insert(context);
+            }
+            None if self.start_traces => {
+                req.extensions_mut().insert(TraceContext::root());
+            }
+            None => {}
+        }
+        next.run(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Router};
+
+    const PARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    #[test]
+    fn test_parse() {
+        let context = TraceContext::parse(PARENT, Some("congo=t61rcWkgMzE")).unwrap();
+        assert_eq!(context.trace_id, "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(context.parent_id.as_deref(), Some("b7ad6b7169203331"));
+        assert!(context.sampled);
+        assert_ne!(context.span_id, "b7ad6b7169203331");
+        let traceparent = context.traceparent();
+        assert!(traceparent.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+        assert!(traceparent.ends_with("-01"));
+        assert_eq!(traceparent.len(), 55);
+
+        let future = "cc-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-extra";
+        assert!(!TraceContext::parse(future, None).unwrap().sampled);
+        for invalid in [
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+        ] {
+            assert_eq!(TraceContext::parse(invalid, None), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_extract_and_inject() {
+        let mut router = Router::new();
+        router.layer(ExtractTraceContext::new());
+        router.get("/", |req: Request, _params: Params| {
+            let mut upstream = http::Request::new(None);
+            TraceContext::of(&req).unwrap().inject(&mut upstream)?;
+            let mut res = http::Response::new(None);
+            *res.headers_mut() = upstream.headers().clone();
+            Ok(res)
+        });
+        let send = |traceparent: Option<&str>| {
+            let mut req = http::Request::builder().uri("/");
+            if let Some(traceparent) = traceparent {
+                req = req
+                    .header("traceparent", traceparent)
+                    .header("tracestate", "a=1")
+                    .header("tracestate", "b=2");
+            }
+            router.handle(req.body(None).unwrap()).unwrap()
+        };
+
+        let res = send(Some(PARENT));
+        let traceparent = res.headers()["traceparent"].to_str().unwrap();
+        assert_eq!(&traceparent[..36], &PARENT[..36]);
+        assert_ne!(traceparent, PARENT);
+        assert_eq!(res.headers()["tracestate"], "a=1,b=2");
+
+        let res = send(None);
+        let traceparent = res.headers()["traceparent"].to_str().unwrap();
+        assert_ne!(&traceparent[..36], &PARENT[..36]);
+        assert!(!res.headers().contains_key("tracestate"));
+    }
+}
@@ -0,0 +1,255 @@
+//! Routing requests to different versions of an API.
+use crate::{Request, RouteError, Router};
+use std::{cell::Cell, rc::Rc};
+
+/// How the API version of a request is determined.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionBy {
+    /// A path prefix such as `/v2/users`.
+    Path,
+    /// A request header such as `Api-Version: 2` (a leading `v` is allowed).
+    Header(String),
+    /// A `version` parameter of the requested media type, e.g.
+    /// `Accept: application/json; version=2`.
+    Accept,
+}
+
+/// Registers the routes of several versions of an API on a [`Router`]; see
+/// [`Router::versioned`].
+pub struct Versioned<'r> {
+    router: &'r mut Router,
+    by: VersionBy,
+    default: Rc<Cell<Option<u32>>>,
+    /// The indices of the routes registered for each version.
+    versions: Vec<(u32, Vec<usize>)>,
+}
+
+impl Router {
+    /// Register versions of an API, selected by a path prefix such as `/v1` unless
+    /// configured otherwise with [`Versioned::by`].
+    ///
+    /// ```
+    /// # use spin_sdk_router::{Router, VersionBy};
+    /// # fn users_v1(_: spin_sdk_router::Request, _: spin_sdk_router::Params) -> anyhow::Result<spin_sdk_router::Response> { todo!() }
+    /// # fn users_v2(_: spin_sdk_router::Request, _: spin_sdk_router::Params) -> anyhow::Result<spin_sdk_router::Response> { todo!() }
+    /// let mut router = Router::new();
+    /// router
+    ///     .versioned()
+    ///     .by(VersionBy::Header("api-version".into()))
+    ///     .default_version(2)
+    ///     .v(1, |r| {
+    ///         r.get("/users", users_v1);
+    ///     })
+    ///     .v(2, |r| {
+    ///         r.get("/users", users_v2);
+    ///     })
+    ///     .deprecate(1, Some("Sat, 01 Nov 2025 00:00:00 GMT"));
+    /// ```
+    pub fn versioned(&mut self) -> Versioned<'_> {
+        Versioned {
+            router: self,
+            by: VersionBy::Path,
+            default: Rc::default(),
+            versions: Vec::new(),
+        }
+    }
+}
+
+impl Versioned<'_> {
+    /// Choose how the version of a request is determined. Applies to versions registered
+    /// afterwards.
+    pub fn by(mut self, by: VersionBy) -> Self {
+        self.by = by;
+        self
+    }
+
+    /// Serve requests that do not ask for a version with `version`. Path prefixed versions
+    /// always need their prefix.
+    pub fn default_version(self, version: u32) -> Self {
+        self.default.set(Some(version));
+        self
+    }
+
+    /// Register the routes of `version`, added by `routes` to a fresh router. Only the routes
+    /// are taken over, not middleware, guards or other settings of that router.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a route pattern is invalid once prefixed with the version; see
+    /// [`Versioned::try_v`].
+    pub fn v<F>(self, version: u32, routes: F) -> Self
+    where
+        F: FnOnce(&mut Router),
+    {
+        self.try_v(version, routes)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Register the routes of `version` like [`Versioned::v`], failing if a route pattern is
+    /// invalid once prefixed with the version.
+    pub fn try_v<F>(mut self, version: u32, routes: F) -> Result<Self, RouteError>
+    where
+        F: FnOnce(&mut Router),
+    {
+        let mut versioned = Router::new();
+        routes(&mut versioned);
+
+        let mut indices = Vec::new();
+        for mut route in versioned.routes {
+            let default = self.default.clone();
+            match self.by.clone() {
+                VersionBy::Path => route.pattern = format!("/v{version}{}", route.pattern),
+                VersionBy::Header(name) => {
+                    route.when(move |req| {
+                        let requested = req
+                            .headers()
+                            .get(&name)
+                            .and_then(|v| v.to_str().ok())
+                            .map(parse_version);
+                        selects(requested, version, &default)
+                    });
+                }
+                VersionBy::Accept => {
+                    route.when(move |req| selects(accept_version(req), version, &default));
+                }
+            }
+            indices.push(self.router.routes.len());
+            self.router.insert(route)?;
+        }
+        self.versions.push((version, indices));
+        Ok(self)
+    }
+
+    /// Mark `version` as deprecated: its responses carry a `Deprecation: true` header, and a
+    /// `Sunset` header with the given HTTP date if there is one.
+    pub fn deprecate(self, version: u32, sunset: Option<&str>) -> Self {
+        let sunset = sunset.map(str::to_owned);
+        for (_, indices) in self.versions.iter().filter(|(v, _)| *v == version) {
+            for index in indices {
+                let route = &mut self.router.routes[*index];
+                let handler = std::mem::replace(&mut route.handler, Box::new(crate::not_found));
+                let sunset = sunset.clone();
+                route.handler = Box::new(move |req, params| {
+                    let mut res = handler(req, params)?;
+                    let headers = res.headers_mut();
+                    headers.insert("deprecation", http::HeaderValue::from_static("true"));
+                    if let Some(sunset) = &sunset {
+                        headers.insert("sunset", sunset.parse()?);
+                    }
+                    Ok(res)
+                });
+            }
+        }
+        self
+    }
+}
+
+/// Whether a request asking for `requested` is served by `version`.
+fn selects(requested: Option<Option<u32>>, version: u32, default: &Cell<Option<u32>>) -> bool {
+    match requested {
+        Some(requested) => requested == Some(version),
+        None => default.get() == Some(version),
+    }
+}
+
+fn parse_version(value: &str) -> Option<u32> {
+    let value = value.trim();
+    value.strip_prefix(['v', 'V']).unwrap_or(value).parse().ok()
+}
+
+/// The version asked for by a `version` parameter in the `Accept` header, if any.
+fn accept_version(req: &Request) -> Option<Option<u32>> {
+    req.headers()
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split([',', ';']))
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("version"))
+        .map(|(_, value)| parse_version(value.trim_matches('"')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Response};
+
+    fn respond(body: &'static str) -> impl Fn(Request, Params) -> anyhow::Result<Response> {
+        move |_req, _params| Ok(http::Response::new(Some(body.into())))
+    }
+
+    fn request(uri: &str, header: Option<(&str, &str)>) -> Request {
+        let mut builder = http::Request::builder().uri(uri);
+        if let Some((name, value)) = header {
+            builder = builder.header(name, value);
+        }
+        builder.body(None).unwrap()
+    }
+
+    #[test]
+    fn test_path_versions() {
+        let mut router = Router::new();
+        router
+            .versioned()
+            .v(1, |r| {
+                r.get("/users", respond("v1"));
+            })
+            .try_v(2, |r| {
+                r.get("/users", respond("v2"));
+            })
+            .unwrap()
+            .deprecate(1, Some("Sat, 01 Nov 2025 00:00:00 GMT"));
+
+        let res = router.handle(request("/v1/users", None)).unwrap();
+        assert_eq!(res.headers()["deprecation"], "true");
+        assert_eq!(res.headers()["sunset"], "Sat, 01 Nov 2025 00:00:00 GMT");
+        assert_eq!(res.into_body().unwrap(), "v1");
+
+        let res = router.handle(request("/v2/users", None)).unwrap();
+        assert!(!res.headers().contains_key("deprecation"));
+        assert_eq!(res.into_body().unwrap(), "v2");
+
+        let res = router.handle(request("/users", None)).unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_header_and_accept_versions() {
+        let mut router = Router::new();
+        router
+            .versioned()
+            .by(VersionBy::Header("api-version".into()))
+            .v(1, |r| {
+                r.get("/users", respond("v1"));
+            })
+            .v(2, |r| {
+                r.get("/users", respond("v2"));
+            })
+            .default_version(2);
+
+        let res = router
+            .handle(request("/users", Some(("api-version", "v1"))))
+            .unwrap();
+        assert_eq!(res.into_body().unwrap(), "v1");
+        let res = router.handle(request("/users", None)).unwrap();
+        assert_eq!(res.into_body().unwrap(), "v2");
+        let res = router
+            .handle(request("/users", Some(("api-version", "3"))))
+            .unwrap();
+        assert_eq!(res.status(), http::StatusCode::NOT_FOUND);
+
+        let mut router = Router::new();
+        router
+            .versioned()
+            .by(VersionBy::Accept)
+            .v(1, |r| {
+                r.get("/users", respond("v1"));
+            })
+            .v(2, |r| {
+                r.get("/users", respond("v2"));
+            });
+        let accept = ("accept", "application/json; version=2");
+        let res = router.handle(request("/users", Some(accept))).unwrap();
+        assert_eq!(res.into_body().unwrap(), "v2");
+    }
+}
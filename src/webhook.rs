@@ -0,0 +1,216 @@
+//! Verification of signed webhook deliveries.
+use crate::{
+    hmac::{constant_time_eq, hmac_sha256},
+    Guard, Rejection, Request,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+enum Scheme {
+    /// A hex digest of the body, optionally prefixed with `sha256=`, in the given header.
+    Hmac(http::header::HeaderName),
+    /// `Stripe-Signature: t=<timestamp>,v1=<hex digest of "<timestamp>.<body>">,...`.
+    Stripe,
+}
+
+/// A guard verifying the HMAC-SHA256 signature of a webhook delivery against its raw body
+/// before the request reaches the handler:
+///
+/// ```
+/// # use spin_sdk_router::{webhook::WebhookSignature, Router};
+/// let mut router = Router::new();
+/// router.guard(WebhookSignature::github(b"webhook secret"));
+/// ```
+///
+/// Deliveries with a missing or mismatched signature are rejected with 401 Unauthorized.
+pub struct WebhookSignature {
+    scheme: Scheme,
+    secret: Vec<u8>,
+    tolerance: Duration,
+    now: fn() -> SystemTime,
+}
+
+impl WebhookSignature {
+    /// Verify GitHub deliveries, signed in the `X-Hub-Signature-256` header.
+    pub fn github(secret: &[u8]) -> Self {
+        Self::hmac_sha256("x-hub-signature-256", secret)
+    }
+
+    /// Verify Stripe events, signed in the `Stripe-Signature` header. Events whose timestamp
+    /// is more than five minutes away from now are rejected to prevent replays; see
+    /// [`tolerance`](Self::tolerance).
+    pub fn stripe(secret: &[u8]) -> Self {
+        Self::new(Scheme::Stripe, secret)
+    }
+
+    /// Verify deliveries carrying the hex HMAC-SHA256 digest of the body in the header
+    /// `name`, optionally prefixed with `sha256=`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid header name.
+    pub fn hmac_sha256(name: &str, secret: &[u8]) -> Self {
+        let name = name.parse().expect("invalid header name");
+        Self::new(Scheme::Hmac(name), secret)
+    }
+
+    fn new(scheme: Scheme, secret: &[u8]) -> Self {
+        WebhookSignature {
+            scheme,
+            secret: secret.to_vec(),
+            tolerance: Duration::from_secs(300),
+            now: SystemTime::now,
+        }
+    }
+
+    /// Accept signatures whose timestamp is at most `tolerance` away from now, for schemes
+    /// that sign a timestamp.
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    fn signs(&self, message: &[u8], signature: &str) -> bool {
+        decode_hex(signature.trim()).is_some_and(|signature| {
+            constant_time_eq(&hmac_sha256(&self.secret, message), &signature)
+        })
+    }
+
+    fn verify(&self, req: &Request) -> Result<(), &'static str> {
+        let body = req.body().as_deref().unwrap_or_default();
+        let header = |name: &str| {
+            let value = req.headers().get(name).ok_or("missing webhook signature")?;
+            value.to_str().map_err(|_| "malformed webhook signature")
+        };
+        match &self.scheme {
+            Scheme::Hmac(name) => {
+                let value = header(name.as_str())?;
+                let signature = value.strip_prefix("sha256=").unwrap_or(value);
+                if !self.signs(body, signature) {
+                    return Err("invalid webhook signature");
+                }
+            }
+            Scheme::Stripe => {
+                let value = header("stripe-signature")?;
+                let fields = value
+                    .split(',')
+                    .filter_map(|field| field.trim().split_once('='));
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for (key, value) in fields {
+                    match key {
+                        "t" => timestamp = value.parse::<u64>().ok(),
+                        "v1" => signatures.push(value),
+                        _ => {}
+                    }
+                }
+                let timestamp = timestamp.ok_or("malformed webhook signature")?;
+                let now = (self.now)()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                if now.abs_diff(timestamp) > self.tolerance.as_secs() {
+                    return Err("webhook signature timestamp outside the tolerance");
+                }
+                let message = [format!("{timestamp}.").as_bytes(), body].concat();
+                if !signatures.iter().any(|s| self.signs(&message, s)) {
+                    return Err("invalid webhook signature");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl Guard for WebhookSignature {
+    fn check(&self, req: &mut Request) -> Result<(), Rejection> {
+        self.verify(req)
+            .map_err(|reason| Rejection::new(http::StatusCode::UNAUTHORIZED, reason))
+    }
+
+    fn name(&self) -> &str {
+        "webhook_signature"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Params, Response, Router};
+
+    fn guarded(guard: WebhookSignature) -> Router {
+        let mut router = Router::new();
+        router.post("/hook", |_req: Request, _params: Params| {
+            Ok(http::Response::new(None))
+        });
+        router.guard(guard);
+        router
+    }
+
+    fn deliver(router: &Router, header: Option<(&str, &str)>, body: &str) -> Response {
+        let mut req = http::Request::builder().method("POST").uri("/hook");
+        if let Some((name, value)) = header {
+            req = req.header(name, value);
+        }
+        let body = bytes::Bytes::copy_from_slice(body.as_bytes());
+        router.handle(req.body(Some(body)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_github() {
+        let router = guarded(WebhookSignature::github(b"It's a Secret to Everybody"));
+        // From GitHub's documentation on validating webhook deliveries.
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        let res = deliver(
+            &router,
+            Some(("x-hub-signature-256", signature)),
+            "Hello, World!",
+        );
+        assert_eq!(res.status(), http::StatusCode::OK);
+
+        for (header, body) in [
+            (Some(("x-hub-signature-256", signature)), "Hello, World?"),
+            (Some(("x-hub-signature-256", "sha256=zz")), "Hello, World!"),
+            (None, "Hello, World!"),
+        ] {
+            let res = deliver(&router, header, body);
+            assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[test]
+    fn test_stripe() {
+        let mut guard = WebhookSignature::stripe(b"whsec_test");
+        guard.now = || UNIX_EPOCH + Duration::from_secs(1_700_000_100);
+        let router = guarded(guard);
+
+        let valid = format!(
+            "t=1700000000,v1=0000,v1={}",
+            hex(&hmac_sha256(b"whsec_test", b"1700000000.{\"id\":1}"))
+        );
+        let res = deliver(&router, Some(("stripe-signature", &valid)), "{\"id\":1}");
+        assert_eq!(res.status(), http::StatusCode::OK);
+        let res = deliver(&router, Some(("stripe-signature", &valid)), "{\"id\":2}");
+        assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+
+        let stale = format!(
+            "t=1699999000,v1={}",
+            hex(&hmac_sha256(b"whsec_test", b"1699999000.{\"id\":1}"))
+        );
+        let res = deliver(&router, Some(("stripe-signature", &stale)), "{\"id\":1}");
+        assert_eq!(res.status(), http::StatusCode::UNAUTHORIZED);
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}